@@ -16,8 +16,9 @@ use lru_cache::LruCache;
 use routing::{Authority, FullId, XorName};
 use rust_sodium::crypto::{box_, sign};
 use safe_core::client::{
-    setup_routing, spawn_routing_thread, ClientInner, IMMUT_DATA_CACHE_SIZE, REQUEST_TIMEOUT_SECS,
+    setup_chunk_cache, setup_routing, spawn_routing_thread, ClientInner, IMMUT_DATA_CACHE_SIZE,
 };
+use safe_core::config_handler::RequestPolicy;
 use safe_core::crypto::{shared_box, shared_secretbox, shared_sign};
 use safe_core::ipc::BootstrapConfig;
 use safe_core::{Client, ClientKeys, NetworkTx};
@@ -25,7 +26,6 @@ use std::cell::RefCell;
 use std::collections::HashMap;
 use std::fmt;
 use std::rc::Rc;
-use std::time::Duration;
 use tiny_keccak::sha3_256;
 use tokio_core::reactor::Handle;
 use {AppContext, AppMsgTx};
@@ -34,6 +34,8 @@ use {AppContext, AppMsgTx};
 pub struct AppClient {
     inner: Rc<RefCell<ClientInner<AppClient, AppContext>>>,
     app_inner: Rc<RefCell<AppInner>>,
+    // Policy of the requests made through this handle, overriding the ones of the client.
+    request_policy: Option<RequestPolicy>,
 }
 
 impl AppClient {
@@ -49,7 +51,7 @@ impl AppClient {
         trace!("Creating unregistered client.");
 
         let (routing, routing_rx) = setup_routing(None, config.clone())?;
-        let joiner = spawn_routing_thread(routing_rx, core_tx.clone(), net_tx.clone());
+        let joiner = spawn_routing_thread(routing_rx, core_tx.clone());
 
         Ok(Self {
             inner: Rc::new(RefCell::new(ClientInner::new(
@@ -57,12 +59,12 @@ impl AppClient {
                 routing,
                 HashMap::with_capacity(10),
                 LruCache::new(IMMUT_DATA_CACHE_SIZE),
-                Duration::from_secs(REQUEST_TIMEOUT_SECS),
                 joiner,
                 core_tx,
                 net_tx,
             ))),
             app_inner: Rc::new(RefCell::new(AppInner::new(None, None, None, config))),
+            request_policy: None,
         })
     }
 
@@ -127,18 +129,17 @@ impl AppClient {
         let (mut routing, routing_rx) =
             setup_routing(Some(keys.clone().into()), Some(config.clone()))?;
         routing = routing_wrapper_fn(routing);
-        let joiner = spawn_routing_thread(routing_rx, core_tx.clone(), net_tx.clone());
+        let joiner = spawn_routing_thread(routing_rx, core_tx.clone());
 
         let digest = sha3_256(&owner.0);
         let cm_addr = Authority::ClientManager(XorName(digest));
 
-        Ok(Self {
+        let client = Self {
             inner: Rc::new(RefCell::new(ClientInner::new(
                 el_handle,
                 routing,
                 HashMap::with_capacity(10),
                 LruCache::new(IMMUT_DATA_CACHE_SIZE),
-                Duration::from_secs(REQUEST_TIMEOUT_SECS),
                 joiner,
                 core_tx,
                 net_tx,
@@ -149,7 +150,11 @@ impl AppClient {
                 Some(cm_addr),
                 Some(config),
             ))),
-        })
+            request_policy: None,
+        };
+
+        setup_chunk_cache(&client);
+        Ok(client)
     }
 }
 
@@ -204,6 +209,17 @@ impl Client for AppClient {
         let app_inner = self.app_inner.borrow();
         app_inner.owner_key
     }
+
+    fn with_request_policy(&self, policy: RequestPolicy) -> Self {
+        AppClient {
+            request_policy: Some(policy),
+            ..self.clone()
+        }
+    }
+
+    fn request_policy_override(&self) -> Option<RequestPolicy> {
+        self.request_policy
+    }
 }
 
 impl Clone for AppClient {
@@ -211,6 +227,7 @@ impl Clone for AppClient {
         AppClient {
             inner: Rc::clone(&self.inner),
             app_inner: Rc::clone(&self.app_inner),
+            request_policy: self.request_policy,
         }
     }
 }
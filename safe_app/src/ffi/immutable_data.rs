@@ -8,6 +8,7 @@
 // Software.
 
 use cipher_opt::CipherOpt;
+use client::AppClient;
 use errors::AppError;
 use ffi::object_cache::{CipherOptHandle, SelfEncryptorReaderHandle, SelfEncryptorWriterHandle};
 use ffi_utils::{catch_unwind_cb, vec_clone_from_raw_parts, FfiResult, OpaqueCtx, FFI_RESULT_OK};
@@ -15,8 +16,11 @@ use futures::Future;
 use maidsafe_utilities::serialisation::{deserialise, serialise};
 use routing::XorName;
 use safe_core::ffi::arrays::XorNameArray;
-use safe_core::{immutable_data, Client, FutureExt, SelfEncryptionStorage};
-use self_encryption::{SelfEncryptor, SequentialEncryptor};
+use safe_core::{
+    immutable_data, Client, DownloadTracker, FutureExt, SelfEncryptionStorage, UploadPipeline,
+};
+use self_encryption::{DataMap, SelfEncryptor, SequentialEncryptor};
+use std::ops::Deref;
 use std::os::raw::c_void;
 use App;
 
@@ -25,6 +29,47 @@ pub type SEWriterHandle = SelfEncryptorWriterHandle;
 /// Handle of a Self Encryptor Reader object.
 pub type SEReaderHandle = SelfEncryptorReaderHandle;
 
+/// Self Encryptor Writer, along with the pipeline it uploads the chunks through.
+pub struct SelfEncryptorWriter {
+    self_encryptor: SequentialEncryptor<SelfEncryptionStorage<AppClient>>,
+    pipeline: UploadPipeline,
+}
+
+impl Deref for SelfEncryptorWriter {
+    type Target = SequentialEncryptor<SelfEncryptionStorage<AppClient>>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.self_encryptor
+    }
+}
+
+/// Self Encryptor Reader, along with the tracker of the chunks it fetches.
+pub struct SelfEncryptorReader {
+    self_encryptor: SelfEncryptor<SelfEncryptionStorage<AppClient>>,
+    downloads: DownloadTracker,
+}
+
+impl SelfEncryptorReader {
+    fn new(storage: SelfEncryptionStorage<AppClient>, data_map: DataMap) -> Result<Self, AppError> {
+        let downloads = storage.download_tracker().clone();
+        downloads.set_data_map(&data_map);
+        let self_encryptor = SelfEncryptor::new(storage, data_map)?;
+
+        Ok(SelfEncryptorReader {
+            self_encryptor,
+            downloads,
+        })
+    }
+}
+
+impl Deref for SelfEncryptorReader {
+    type Target = SelfEncryptor<SelfEncryptionStorage<AppClient>>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.self_encryptor
+    }
+}
+
 /// Get a Self Encryptor.
 #[no_mangle]
 pub unsafe extern "C" fn idata_new_self_encryptor(
@@ -37,11 +82,16 @@ pub unsafe extern "C" fn idata_new_self_encryptor(
     catch_unwind_cb(user_data, o_cb, || {
         (*app).send(move |client, context| {
             let se_storage = SelfEncryptionStorage::new(client.clone());
+            let pipeline = se_storage.pipeline().clone();
             let context = context.clone();
 
             let fut = SequentialEncryptor::new(se_storage, None)
                 .map_err(AppError::from)
-                .map(move |se| {
+                .map(move |self_encryptor| {
+                    let se = SelfEncryptorWriter {
+                        self_encryptor,
+                        pipeline,
+                    };
                     let handle = context.object_cache().insert_se_writer(se);
                     o_cb(user_data.0, FFI_RESULT_OK, handle);
                 }).map_err(move |e| {
@@ -110,13 +160,18 @@ pub unsafe extern "C" fn idata_close_self_encryptor(
             let client3 = client.clone();
             let context2 = context.clone();
 
-            let se_writer = try_cb!(
+            let SelfEncryptorWriter {
+                self_encryptor,
+                pipeline,
+            } = try_cb!(
                 context.object_cache().remove_se_writer(se_h),
                 user_data,
                 o_cb
             );
+            let pipeline = pipeline.untracked();
+            let pipeline2 = pipeline.clone();
 
-            se_writer
+            self_encryptor
                 .close()
                 .map_err(AppError::from)
                 .and_then(move |(data_map, _)| {
@@ -128,14 +183,18 @@ pub unsafe extern "C" fn idata_close_self_encryptor(
 
                     Ok(enc_data_map)
                 }).and_then(move |enc_data_map| {
-                    immutable_data::create(&client2, &enc_data_map, None).map_err(AppError::from)
+                    immutable_data::create_with_pipeline(&client2, &enc_data_map, None, &pipeline)
+                        .map_err(AppError::from)
                 }).and_then(move |data| {
                     let name = *data.name();
 
-                    client3
-                        .put_idata(data)
+                    pipeline2
+                        .put_idata(&client3, data)
                         .map_err(AppError::from)
-                        .map(move |_| name)
+                        .map(move |_| {
+                            pipeline2.finish();
+                            name
+                        })
                 }).then(move |result| {
                     match result {
                         Ok(name) => o_cb(user_data.0, FFI_RESULT_OK, &name.0),
@@ -150,6 +209,46 @@ pub unsafe extern "C" fn idata_close_self_encryptor(
     });
 }
 
+/// Set the function called with the progress of uploading the chunks of the data written to
+/// the Self Encryptor Writer, every time a chunk has been uploaded. `bytes_done` is the size of
+/// the chunks uploaded so far, and `bytes_total` is `size`, the size the data will have once
+/// written. Small data is kept in its data map instead of in chunks, so it's reported as done
+/// once the writer is closed. Returning `false` from `o_progress_cb` cancels the remaining
+/// uploads, making the writes in progress and `idata_close_self_encryptor` fail.
+/// `progress_user_data` is passed to `o_progress_cb`, and must stay valid until the writer is
+/// closed or freed.
+#[no_mangle]
+pub unsafe extern "C" fn idata_set_writer_progress_callback(
+    app: *const App,
+    se_h: SEWriterHandle,
+    size: u64,
+    progress_user_data: *mut c_void,
+    o_progress_cb: extern "C" fn(user_data: *mut c_void, bytes_done: u64, bytes_total: u64) -> bool,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(user_data: *mut c_void, result: *const FfiResult),
+) {
+    let progress_user_data = OpaqueCtx(progress_user_data);
+    let user_data = OpaqueCtx(user_data);
+
+    catch_unwind_cb(user_data, o_cb, || {
+        (*app).send(move |_, context| {
+            let se = try_cb!(context.object_cache().get_se_writer(se_h), user_data, o_cb);
+
+            se.pipeline.set_size(size);
+            se.pipeline.set_progress_callback(move |progress| {
+                o_progress_cb(
+                    progress_user_data.0,
+                    progress.bytes_done,
+                    progress.bytes_total,
+                )
+            });
+
+            o_cb(user_data.0, FFI_RESULT_OK);
+            None
+        })
+    });
+}
+
 /// Fetch Self Encryptor.
 #[no_mangle]
 pub unsafe extern "C" fn idata_fetch_self_encryptor(
@@ -177,7 +276,7 @@ pub unsafe extern "C" fn idata_fetch_self_encryptor(
                     Ok(data_map)
                 }).and_then(move |data_map| {
                     let se_storage = SelfEncryptionStorage::new(client3);
-                    SelfEncryptor::new(se_storage, data_map).map_err(AppError::from)
+                    SelfEncryptorReader::new(se_storage, data_map)
                 }).map(move |se_reader| {
                     let handle = context3.object_cache().insert_se_reader(se_reader);
                     o_cb(user_data.0, FFI_RESULT_OK, handle);
@@ -289,6 +388,43 @@ pub unsafe extern "C" fn idata_read_from_self_encryptor(
     });
 }
 
+/// Set the function called with the progress of fetching the chunks of the data read from the
+/// Self Encryptor Reader, every time a new chunk has been fetched. `bytes_done` is the size of
+/// the data of the chunks fetched so far, and `bytes_total` the size of the data. Small data is
+/// kept in its data map, so `o_progress_cb` is called right away with all of it done.
+/// Returning `false` from `o_progress_cb` cancels fetching further chunks, making the reads in
+/// progress fail. `progress_user_data` is passed to `o_progress_cb`, and must stay valid until
+/// the reader is freed.
+#[no_mangle]
+pub unsafe extern "C" fn idata_set_reader_progress_callback(
+    app: *const App,
+    se_h: SEReaderHandle,
+    progress_user_data: *mut c_void,
+    o_progress_cb: extern "C" fn(user_data: *mut c_void, bytes_done: u64, bytes_total: u64) -> bool,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(user_data: *mut c_void, result: *const FfiResult),
+) {
+    let progress_user_data = OpaqueCtx(progress_user_data);
+    let user_data = OpaqueCtx(user_data);
+
+    catch_unwind_cb(user_data, o_cb, || {
+        (*app).send(move |_, context| {
+            let se = try_cb!(context.object_cache().get_se_reader(se_h), user_data, o_cb);
+
+            se.downloads.set_progress_callback(move |progress| {
+                o_progress_cb(
+                    progress_user_data.0,
+                    progress.bytes_done,
+                    progress.bytes_total,
+                )
+            });
+
+            o_cb(user_data.0, FFI_RESULT_OK);
+            None
+        })
+    });
+}
+
 /// Free Self Encryptor Writer handle.
 #[no_mangle]
 pub unsafe extern "C" fn idata_self_encryptor_writer_free(
@@ -431,4 +567,87 @@ mod tests {
             unwrap!(call_0(|ud, cb| cipher_opt_free(&app, cipher_opt_h, ud, cb)));
         }
     }
+
+    // Test that the progress of uploading the chunks written to a self encryptor is reported
+    // in bytes, with the total known up front, and so is the progress of fetching them back.
+    #[test]
+    fn progress() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        const SIZE: u64 = 3 * 1024 * 1024;
+
+        extern "C" fn progress_cb(user_data: *mut c_void, done: u64, total: u64) -> bool {
+            assert_eq!(total, SIZE);
+            assert!(done <= total);
+
+            let bytes_done = user_data as *const AtomicUsize;
+            unsafe { (*bytes_done).store(done as usize, Ordering::SeqCst) };
+            true
+        }
+
+        let app = create_app();
+        let plain_text = unwrap!(utils::generate_random_vector::<u8>(SIZE as usize));
+        let bytes_done = AtomicUsize::new(0);
+        let bytes_fetched = AtomicUsize::new(0);
+
+        unsafe {
+            let cipher_opt_h = unwrap!(call_1(|ud, cb| cipher_opt_new_plaintext(&app, ud, cb)));
+            let se_writer_h = unwrap!(call_1(|ud, cb| idata_new_self_encryptor(&app, ud, cb)));
+
+            unwrap!(call_0(|ud, cb| idata_set_writer_progress_callback(
+                &app,
+                se_writer_h,
+                plain_text.len() as u64,
+                &bytes_done as *const AtomicUsize as *mut c_void,
+                progress_cb,
+                ud,
+                cb,
+            )));
+            unwrap!(call_0(|ud, cb| idata_write_to_self_encryptor(
+                &app,
+                se_writer_h,
+                plain_text.as_ptr(),
+                plain_text.len(),
+                ud,
+                cb,
+            )));
+            let name: XorNameArray = unwrap!(call_1(|ud, cb| idata_close_self_encryptor(
+                &app,
+                se_writer_h,
+                cipher_opt_h,
+                ud,
+                cb
+            )));
+            assert_eq!(bytes_done.load(Ordering::SeqCst), SIZE as usize);
+
+            let se_reader_h = unwrap!(call_1(|ud, cb| idata_fetch_self_encryptor(
+                &app, &name, ud, cb
+            )));
+            unwrap!(call_0(|ud, cb| idata_set_reader_progress_callback(
+                &app,
+                se_reader_h,
+                &bytes_fetched as *const AtomicUsize as *mut c_void,
+                progress_cb,
+                ud,
+                cb,
+            )));
+
+            let size = plain_text.len() as u64;
+            for _ in 0..2 {
+                let read = unwrap!(call_vec_u8(|ud, cb| {
+                    idata_read_from_self_encryptor(&app, se_reader_h, 0, size, ud, cb)
+                }));
+                assert_eq!(read, plain_text);
+            }
+            assert_eq!(bytes_fetched.load(Ordering::SeqCst), SIZE as usize);
+
+            unwrap!(call_0(|ud, cb| idata_self_encryptor_reader_free(
+                &app,
+                se_reader_h,
+                ud,
+                cb
+            )));
+            unwrap!(call_0(|ud, cb| cipher_opt_free(&app, cipher_opt_h, ud, cb)));
+        }
+    }
 }
@@ -0,0 +1,199 @@
+// Copyright 2018 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under the MIT license <LICENSE-MIT
+// http://opensource.org/licenses/MIT> or the Modified BSD license <LICENSE-BSD
+// https://opensource.org/licenses/BSD-3-Clause>, at your option. This file may not be copied,
+// modified, or distributed except according to those terms. Please review the Licences for the
+// specific language governing permissions and limitations relating to use of the SAFE Network
+// Software.
+
+//! FFI for inboxes anyone can send messages to, but only their owner can read.
+
+use errors::AppError;
+use ffi::helper::send;
+use ffi_utils::{catch_unwind_cb, vec_clone_from_raw_parts, FfiResult, OpaqueCtx, FFI_RESULT_OK};
+use futures::Future;
+use inbox;
+use routing::XorName;
+use safe_core::ffi::arrays::XorNameArray;
+use safe_core::ffi::ipc::resp::MDataEntry;
+use safe_core::FutureExt;
+use std::os::raw::c_void;
+use App;
+
+/// Create a new inbox for the app. Anyone can send messages to it, but only this
+/// app can read and delete them.
+#[no_mangle]
+pub unsafe extern "C" fn inbox_create(
+    app: *const App,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(
+        user_data: *mut c_void,
+        result: *const FfiResult,
+        name: *const XorNameArray,
+    ),
+) {
+    catch_unwind_cb(user_data, o_cb, || {
+        let user_data = OpaqueCtx(user_data);
+
+        (*app).send(move |client, _| {
+            inbox::create(client)
+                .map(move |name| {
+                    o_cb(user_data.0, FFI_RESULT_OK, &name.0);
+                }).map_err(move |err| {
+                    call_result_cb!(Err::<(), _>(err), user_data, o_cb);
+                }).into_box()
+                .into()
+        })
+    })
+}
+
+/// Send a message to the inbox with the given name. The message is sealed to the
+/// encryption key of the app owning the inbox. Returns the key of the message.
+#[no_mangle]
+pub unsafe extern "C" fn inbox_send(
+    app: *const App,
+    name: *const XorNameArray,
+    content: *const u8,
+    content_len: usize,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(
+        user_data: *mut c_void,
+        result: *const FfiResult,
+        key: *const u8,
+        key_len: usize,
+    ),
+) {
+    catch_unwind_cb(user_data, o_cb, || {
+        let user_data = OpaqueCtx(user_data);
+        let name = XorName(*name);
+        let content = vec_clone_from_raw_parts(content, content_len);
+
+        (*app).send(move |client, _| {
+            inbox::send(client, name, &content)
+                .map(move |key| {
+                    o_cb(user_data.0, FFI_RESULT_OK, key.as_ptr(), key.len());
+                }).map_err(move |err| {
+                    call_result_cb!(Err::<(), _>(err), user_data, o_cb);
+                }).into_box()
+                .into()
+        })
+    })
+}
+
+/// List the messages in an inbox of this app, decrypted.
+#[no_mangle]
+pub unsafe extern "C" fn inbox_list(
+    app: *const App,
+    name: *const XorNameArray,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(
+        user_data: *mut c_void,
+        result: *const FfiResult,
+        messages: *const MDataEntry,
+        messages_len: usize,
+    ),
+) {
+    catch_unwind_cb(user_data, o_cb, || {
+        let user_data = OpaqueCtx(user_data);
+        let name = XorName(*name);
+
+        (*app).send(move |client, _| {
+            inbox::list(client, name)
+                .map(move |messages| {
+                    let ffi_messages: Vec<_> =
+                        messages.iter().map(|entry| entry.as_repr_c()).collect();
+                    o_cb(
+                        user_data.0,
+                        FFI_RESULT_OK,
+                        ffi_messages.as_ptr(),
+                        ffi_messages.len(),
+                    );
+                }).map_err(move |err| {
+                    call_result_cb!(Err::<(), _>(err), user_data, o_cb);
+                }).into_box()
+                .into()
+        })
+    })
+}
+
+/// Delete a message from an inbox of this app.
+#[no_mangle]
+pub unsafe extern "C" fn inbox_delete(
+    app: *const App,
+    name: *const XorNameArray,
+    key: *const u8,
+    key_len: usize,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(user_data: *mut c_void, result: *const FfiResult),
+) {
+    catch_unwind_cb(user_data, o_cb, || -> Result<_, AppError> {
+        let name = XorName(*name);
+        let key = vec_clone_from_raw_parts(key, key_len);
+
+        send(app, user_data, o_cb, move |client, _| {
+            inbox::delete(client, name, key)
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use errors::ERR_ACCESS_DENIED;
+    use ffi_utils::test_utils::{call_0, call_1, call_vec, call_vec_u8};
+    use safe_core::ipc::resp::MDataEntry as NativeMDataEntry;
+    use test_utils::create_app;
+
+    // Test creating an inbox, sending messages to it from another app, then listing
+    // and deleting them.
+    #[test]
+    fn create_send_list_and_delete() {
+        let owner = create_app();
+        let sender = create_app();
+
+        unsafe {
+            let name: XorNameArray;
+            name = unwrap!(call_1(|ud, cb| inbox_create(&owner, ud, cb)));
+
+            let content = b"hello";
+            let key = unwrap!(call_vec_u8(|ud, cb| inbox_send(
+                &sender,
+                &name,
+                content.as_ptr(),
+                content.len(),
+                ud,
+                cb,
+            )));
+
+            // Only the owner can read the messages.
+            let messages: Vec<NativeMDataEntry> =
+                unwrap!(call_vec(|ud, cb| inbox_list(&sender, &name, ud, cb)));
+            assert!(messages.is_empty());
+
+            let messages: Vec<NativeMDataEntry> =
+                unwrap!(call_vec(|ud, cb| inbox_list(&owner, &name, ud, cb)));
+            assert_eq!(messages.len(), 1);
+            assert_eq!(messages[0].key.0, key);
+            assert_eq!(messages[0].value.content, content.to_vec());
+
+            // Only the owner can delete them.
+            let res =
+                call_0(|ud, cb| inbox_delete(&sender, &name, key.as_ptr(), key.len(), ud, cb));
+            assert_eq!(res, Err(ERR_ACCESS_DENIED));
+
+            unwrap!(call_0(|ud, cb| inbox_delete(
+                &owner,
+                &name,
+                key.as_ptr(),
+                key.len(),
+                ud,
+                cb,
+            )));
+
+            let messages: Vec<NativeMDataEntry> =
+                unwrap!(call_vec(|ud, cb| inbox_list(&owner, &name, ud, cb)));
+            assert!(messages.is_empty());
+        }
+    }
+}
@@ -14,14 +14,39 @@ use ffi_utils::{
     catch_unwind_cb, from_c_str, vec_clone_from_raw_parts, FfiResult, ReprC, FFI_RESULT_OK,
 };
 use maidsafe_utilities::serialisation::serialise;
-use safe_core::ffi::ipc::req::{AuthReq, ContainersReq, ShareMDataReq};
-use safe_core::ffi::ipc::resp::AuthGranted;
+use rust_sodium::crypto::{box_, sign};
+use safe_core::ffi::arrays::SignPublicKey;
+use safe_core::ffi::ipc::req::{AuthReq, BatchReq, ContainersReq, ShareMDataReq, ShareOfferReq};
+use safe_core::ffi::ipc::resp::{AuthGranted, ShareOfferGranted};
 use safe_core::ipc::{
-    self, AuthReq as NativeAuthReq, ContainersReq as NativeContainersReq, IpcError, IpcMsg, IpcReq,
-    IpcResp, ShareMDataReq as NativeShareMDataReq,
+    self, AuthReq as NativeAuthReq, BatchItem, BatchReq as NativeBatchReq,
+    ContainersReq as NativeContainersReq, IpcError, IpcMsg, IpcReq, IpcResp,
+    ShareMDataReq as NativeShareMDataReq, ShareOfferReq as NativeShareOfferReq,
 };
+use std::collections::HashMap;
 use std::ffi::CString;
 use std::os::raw::{c_char, c_void};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+// Time the ephemeral key of a request is kept for, waiting for the response.
+const RESP_KEY_TTL_SECS: u64 = 60 * 60;
+
+lazy_static! {
+    // Ephemeral keys of the pending requests, by request ID. The keys are only kept
+    // in memory, so a response to a request made before the app restarted can't be
+    // opened.
+    static ref RESP_KEYS: Mutex<HashMap<u32, RespKeys>> = Mutex::new(HashMap::new());
+}
+
+// Keys to open and verify the response to a request with.
+struct RespKeys {
+    resp_pk: box_::PublicKey,
+    resp_sk: box_::SecretKey,
+    // Key the response has to be signed with, if the app knows its authenticator.
+    auth_key: Option<sign::PublicKey>,
+    created: Instant,
+}
 
 /// Encode `AuthReq`.
 #[no_mangle]
@@ -45,6 +70,40 @@ pub unsafe extern "C" fn encode_auth_req(
     })
 }
 
+/// Encode `AuthReq`, asking for the response to be sealed to a key only this app
+/// knows, so that other apps catching the response can't read the granted keys.
+/// Only authenticators supporting IPC version 2 can read the request. The response
+/// has to be decoded within an hour and before the app restarts.
+///
+/// The response is signed by the authenticator. `auth_key` is the key it has to be
+/// signed with, i.e. the `owner_key` of the keys granted at the first authorisation of
+/// the app, which the app should keep. It can be null before the first authorisation:
+/// the response is then only checked to be signed with the owner key it grants.
+#[no_mangle]
+pub unsafe extern "C" fn encode_sealed_auth_req(
+    req: *const AuthReq,
+    auth_key: *const SignPublicKey,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(
+        user_data: *mut c_void,
+        result: *const FfiResult,
+        req_id: u32,
+        encoded: *const c_char,
+    ),
+) {
+    catch_unwind_cb(user_data, o_cb, || -> Result<_, AppError> {
+        let req_id = ipc::gen_req_id();
+        let mut req = NativeAuthReq::clone_from_repr_c(req)?;
+        let resp_keys = add_resp_key(&mut req, auth_key);
+
+        let encoded = encode_ipc(req_id, IpcReq::Auth(req))?;
+        insert_resp_keys(req_id, resp_keys);
+
+        o_cb(user_data, FFI_RESULT_OK, req_id, encoded.as_ptr());
+        Ok(())
+    })
+}
+
 /// Encode `AuthReq` using base64 encoding.
 #[allow(dead_code)]
 #[cfg(any(test, feature = "testing"))]
@@ -135,6 +194,100 @@ pub unsafe extern "C" fn encode_share_mdata_req(
     })
 }
 
+/// Encode `ShareOfferReq`.
+#[no_mangle]
+pub unsafe extern "C" fn encode_share_offer_req(
+    req: *const ShareOfferReq,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(
+        user_data: *mut c_void,
+        result: *const FfiResult,
+        req_id: u32,
+        encoded: *const c_char,
+    ),
+) {
+    catch_unwind_cb(user_data, o_cb, || -> Result<_, AppError> {
+        let req_id = ipc::gen_req_id();
+        let req = NativeShareOfferReq::clone_from_repr_c(req)?;
+
+        let encoded = encode_ipc(req_id, IpcReq::ShareOffer(req))?;
+        o_cb(user_data, FFI_RESULT_OK, req_id, encoded.as_ptr());
+        Ok(())
+    })
+}
+
+/// Encode `BatchReq`. The response to the batch is sealed to a key of the
+/// authorisation request in the batch, if any, and checked to be signed with
+/// `auth_key`, like with `encode_sealed_auth_req`.
+#[no_mangle]
+pub unsafe extern "C" fn encode_batch_req(
+    req: *const BatchReq,
+    auth_key: *const SignPublicKey,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(
+        user_data: *mut c_void,
+        result: *const FfiResult,
+        req_id: u32,
+        encoded: *const c_char,
+    ),
+) {
+    catch_unwind_cb(user_data, o_cb, || -> Result<_, AppError> {
+        let req_id = ipc::gen_req_id();
+        let mut req = NativeBatchReq::clone_from_repr_c(req)?;
+
+        let mut resp_keys = None;
+        for item in &mut req.items {
+            if let BatchItem::Auth(ref mut auth_req) = *item {
+                resp_keys = Some(add_resp_key(auth_req, auth_key));
+            }
+        }
+
+        let encoded = encode_ipc(req_id, IpcReq::Batch(req))?;
+        if let Some(resp_keys) = resp_keys {
+            insert_resp_keys(req_id, resp_keys);
+        }
+
+        o_cb(user_data, FFI_RESULT_OK, req_id, encoded.as_ptr());
+        Ok(())
+    })
+}
+
+// Asks for the response to be sealed to a key only this app knows, and to be signed
+// with `auth_key`, if not null.
+unsafe fn add_resp_key(req: &mut NativeAuthReq, auth_key: *const SignPublicKey) -> RespKeys {
+    let (resp_pk, resp_sk) = box_::gen_keypair();
+    req.resp_key = Some(resp_pk);
+
+    RespKeys {
+        resp_pk,
+        resp_sk,
+        auth_key: if auth_key.is_null() {
+            None
+        } else {
+            Some(sign::PublicKey(*auth_key))
+        },
+        created: Instant::now(),
+    }
+}
+
+// Keeps the keys of a request until the response arrives, forgetting the expired keys
+// of requests which never got one.
+fn insert_resp_keys(req_id: u32, keys: RespKeys) {
+    let ttl = Duration::from_secs(RESP_KEY_TTL_SECS);
+    let mut resp_keys = unwrap!(RESP_KEYS.lock());
+    resp_keys.retain(|_, keys| keys.created.elapsed() < ttl);
+    let _ = resp_keys.insert(req_id, keys);
+}
+
+// Takes the keys of a request, unless they've expired.
+fn take_resp_keys(req_id: u32) -> Option<RespKeys> {
+    let ttl = Duration::from_secs(RESP_KEY_TTL_SECS);
+    match unwrap!(RESP_KEYS.lock()).remove(&req_id) {
+        Some(keys) if keys.created.elapsed() < ttl => Some(keys),
+        _ => None,
+    }
+}
+
 fn encode_ipc(req_id: u32, req: IpcReq) -> Result<CString, AppError> {
     let encoded = ipc::encode_msg(&IpcMsg::Req { req_id, req })?;
     Ok(CString::new(encoded)?)
@@ -147,6 +300,10 @@ fn encode_ipc_64(req_id: u32, req: IpcReq) -> Result<CString, AppError> {
 }
 
 /// Decode IPC message.
+///
+/// The responses to a batch of requests are reported by the callbacks of their
+/// types, in order, with the request ID of the batch. Responses to share offers are
+/// only decoded by `decode_ipc_msg_v4`.
 #[no_mangle]
 pub unsafe extern "C" fn decode_ipc_msg(
     msg: *const c_char,
@@ -174,6 +331,53 @@ pub unsafe extern "C" fn decode_ipc_msg(
             o_unregistered,
             o_containers,
             o_share_mdata,
+            None,
+            o_revoked,
+            o_err,
+        )?;
+
+        Ok(())
+    })
+}
+
+/// Decode IPC message, including responses to share offers added by IPC version 4.
+///
+/// The responses to a batch of requests are reported by the callbacks of their
+/// types, in order, with the request ID of the batch. The acceptance passed to
+/// `o_share_offer` has to be delivered to the app which made the offer.
+#[no_mangle]
+pub unsafe extern "C" fn decode_ipc_msg_v4(
+    msg: *const c_char,
+    user_data: *mut c_void,
+    o_auth: extern "C" fn(user_data: *mut c_void, req_id: u32, auth_granted: *const AuthGranted),
+    o_unregistered: extern "C" fn(
+        user_data: *mut c_void,
+        req_id: u32,
+        serialised_cfg: *const u8,
+        serialised_cfg_len: usize,
+    ),
+    o_containers: extern "C" fn(user_data: *mut c_void, req_id: u32),
+    o_share_mdata: extern "C" fn(user_data: *mut c_void, req_id: u32),
+    o_share_offer: extern "C" fn(
+        user_data: *mut c_void,
+        req_id: u32,
+        granted: *const ShareOfferGranted,
+    ),
+    o_revoked: extern "C" fn(user_data: *mut c_void),
+    o_err: extern "C" fn(user_data: *mut c_void, result: *const FfiResult, req_id: u32),
+) {
+    catch_unwind_cb(user_data, o_err, || -> Result<_, AppError> {
+        let msg = from_c_str(msg)?;
+        let msg = ipc::decode_msg(&msg)?;
+
+        decode_ipc_msg_impl(
+            msg,
+            user_data,
+            o_auth,
+            o_unregistered,
+            o_containers,
+            o_share_mdata,
+            Some(o_share_offer),
             o_revoked,
             o_err,
         )?;
@@ -211,6 +415,7 @@ unsafe extern "C" fn decode_ipc_msg_64(
             o_unregistered,
             o_containers,
             o_share_mdata,
+            None,
             o_revoked,
             o_err,
         )?;
@@ -231,10 +436,13 @@ fn decode_ipc_msg_impl(
     ),
     o_containers: extern "C" fn(user_data: *mut c_void, req_id: u32),
     o_share_mdata: extern "C" fn(user_data: *mut c_void, req_id: u32),
+    o_share_offer: Option<
+        extern "C" fn(user_data: *mut c_void, req_id: u32, granted: *const ShareOfferGranted),
+    >,
     o_revoked: extern "C" fn(user_data: *mut c_void),
     o_err: extern "C" fn(user_data: *mut c_void, result: *const FfiResult, req_id: u32),
 ) -> Result<(), AppError> {
-    match msg {
+    match open_sealed_resp(msg)? {
         IpcMsg::Resp {
             resp: IpcResp::Auth(res),
             req_id,
@@ -316,6 +524,57 @@ fn decode_ipc_msg_impl(
                 o_err(user_data, &res, req_id);
             }
         },
+        IpcMsg::Resp {
+            resp: IpcResp::ShareOffer(res),
+            req_id,
+        } => match (res, o_share_offer) {
+            (_, None) => {
+                return Err(IpcError::InvalidMsg.into());
+            }
+            (Ok(granted), Some(o_share_offer)) => {
+                let granted = granted.into_repr_c();
+                o_share_offer(user_data, req_id, &granted);
+            }
+            (Err(err), Some(_)) => {
+                let e = AppError::from(err);
+                let (error_code, description) = ffi_error!(e);
+                let res = FfiResult {
+                    error_code,
+                    description: description.as_ptr(),
+                };
+                o_err(user_data, &res, req_id);
+            }
+        },
+        IpcMsg::Resp {
+            resp: IpcResp::Batch(res),
+            req_id,
+        } => match res {
+            Ok(resps) => {
+                // Each response is reported by the callback of its type, in order.
+                for resp in resps {
+                    decode_ipc_msg_impl(
+                        IpcMsg::Resp { req_id, resp },
+                        user_data,
+                        o_auth,
+                        o_unregistered,
+                        o_containers,
+                        o_share_mdata,
+                        o_share_offer,
+                        o_revoked,
+                        o_err,
+                    )?;
+                }
+            }
+            Err(err) => {
+                let e = AppError::from(err);
+                let (error_code, description) = ffi_error!(e);
+                let res = FfiResult {
+                    error_code,
+                    description: description.as_ptr(),
+                };
+                o_err(user_data, &res, req_id);
+            }
+        },
         IpcMsg::Revoked { .. } => o_revoked(user_data),
         _ => {
             return Err(IpcError::InvalidMsg.into());
@@ -325,6 +584,57 @@ fn decode_ipc_msg_impl(
     Ok(())
 }
 
+// Opens a response sealed to the ephemeral key of a request encoded with
+// `encode_sealed_auth_req` or `encode_batch_req` and checks who signed it. Only
+// authorisations are sealed. The key is forgotten once a response to the request
+// arrives.
+fn open_sealed_resp(msg: IpcMsg) -> Result<IpcMsg, AppError> {
+    match msg {
+        IpcMsg::SealedResp { req_id, payload } => {
+            let keys = take_resp_keys(req_id).ok_or(IpcError::InvalidSealedResp)?;
+            let (resp, signer) = ipc::open_resp(req_id, &payload, &keys.resp_pk, &keys.resp_sk)?;
+
+            match resp {
+                IpcResp::Auth(_) | IpcResp::Batch(_) => (),
+                _ => return Err(IpcError::InvalidSealedResp.into()),
+            }
+
+            // Without the key of its authenticator, the app can only check the response
+            // is signed with the owner key it grants.
+            let signed_by_auth = match keys.auth_key {
+                Some(auth_key) => signer == auth_key,
+                None => granted_owner_keys(&resp).iter().all(|key| *key == signer),
+            };
+            if !signed_by_auth {
+                return Err(IpcError::InvalidSealedResp.into());
+            }
+
+            Ok(IpcMsg::Resp { req_id, resp })
+        }
+        IpcMsg::Resp {
+            resp: IpcResp::Auth(_),
+            req_id,
+        }
+        | IpcMsg::Resp {
+            resp: IpcResp::Batch(_),
+            req_id,
+        } => {
+            let _ = take_resp_keys(req_id);
+            Ok(msg)
+        }
+        msg => Ok(msg),
+    }
+}
+
+// Owner keys of the keys granted by the response.
+fn granted_owner_keys(resp: &IpcResp) -> Vec<sign::PublicKey> {
+    match *resp {
+        IpcResp::Auth(Ok(ref auth_granted)) => vec![auth_granted.app_keys.owner_key],
+        IpcResp::Batch(Ok(ref resps)) => resps.iter().flat_map(granted_owner_keys).collect(),
+        _ => Vec::new(),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -333,8 +643,9 @@ mod tests {
     use rand;
     use routing::{Action, PermissionSet};
     use rust_sodium::crypto::secretbox;
-    use safe_authenticator::ffi::ipc::encode_auth_resp;
+    use safe_authenticator::ffi::ipc::{encode_auth_resp, IPC_ENCODING_BASE32};
     use safe_authenticator::test_utils as auth_utils;
+    use safe_authenticator::Authenticator;
     use safe_core::crypto::{shared_box, shared_secretbox, shared_sign};
     use safe_core::ffi::ipc::resp::AuthGranted as FfiAuthGranted;
     use safe_core::ipc::{
@@ -345,6 +656,7 @@ mod tests {
     use std::collections::HashMap;
     use std::ffi::CString;
     use std::os::raw::c_void;
+    use std::ptr;
     use test_utils;
 
     // Test that encoding and decoding base64 is no longer backwards compatible, as expected.
@@ -388,8 +700,13 @@ mod tests {
             unwrap!(call_1(|ud, cb| {
                 let auth_req = unwrap!(auth_req.into_repr_c());
                 encode_auth_resp(
-                    &auth, &auth_req, req_id, true, // is_granted
-                    ud, cb,
+                    &auth,
+                    &auth_req,
+                    req_id,
+                    IPC_ENCODING_BASE32,
+                    true, // is_granted
+                    ud,
+                    cb,
                 )
             }))
         };
@@ -505,6 +822,72 @@ mod tests {
 
         assert_eq!(decoded_req_id, req_id);
         assert_eq!(decoded_req, req);
+
+        // A sealed request carries the response key in addition.
+        let (req_id, encoded): (u32, String) = unsafe {
+            unwrap!(call_2(|ud, cb| encode_sealed_auth_req(
+                &req_c,
+                ptr::null(),
+                ud,
+                cb
+            )))
+        };
+
+        let (decoded_req_id, decoded_req) = match unwrap!(ipc::decode_msg(&encoded)) {
+            IpcMsg::Req {
+                req_id,
+                req: IpcReq::Auth(req),
+            } => (req_id, req),
+            x => panic!("Unexpected {:?}", x),
+        };
+
+        assert_eq!(decoded_req_id, req_id);
+        assert!(decoded_req.resp_key.is_some());
+        assert_eq!(
+            NativeAuthReq {
+                resp_key: None,
+                ..decoded_req
+            },
+            req
+        );
+    }
+
+    // Test that the response to an auth request encoded with `encode_sealed_auth_req` is
+    // sealed and signed by the authenticator, that it can only be decoded once and only
+    // if it's signed with the key of the authenticator the app expects.
+    #[test]
+    fn decode_ipc_msg_with_sealed_auth_granted() {
+        let auth = auth_utils::create_account_and_login();
+
+        // Without the key of the authenticator, the response has to be signed with the
+        // owner key it grants.
+        let (req_id, encoded_auth_resp) = sealed_auth_resp(&auth, ptr::null());
+
+        let context = decode_auth_resp(&encoded_auth_resp);
+        assert!(!context.unexpected_cb);
+        assert_eq!(context.req_id, req_id);
+        let owner_key = unwrap!(context.auth_granted).app_keys.owner_key;
+
+        // The key of the request is gone, so the response can't be opened again.
+        let context = decode_auth_resp(&encoded_auth_resp);
+        assert!(context.unexpected_cb);
+        assert!(context.auth_granted.is_none());
+
+        // With the owner key granted at the first authorisation as the key of the
+        // authenticator.
+        let (_, encoded_auth_resp) = sealed_auth_resp(&auth, &owner_key.0);
+
+        let context = decode_auth_resp(&encoded_auth_resp);
+        assert!(!context.unexpected_cb);
+        assert!(context.auth_granted.is_some());
+
+        // With the key of another authenticator.
+        let (other_key, _) = sign::gen_keypair();
+        let (_, encoded_auth_resp) = sealed_auth_resp(&auth, &other_key.0);
+
+        let context = decode_auth_resp(&encoded_auth_resp);
+        assert!(context.unexpected_cb);
+        assert!(context.auth_granted.is_none());
     }
 
     // Test encoding and decoding containers requests.
@@ -962,6 +1345,114 @@ mod tests {
         }
     }
 
+    // Encodes a random auth request with `encode_sealed_auth_req` and has the
+    // authenticator grant it. Returns the request ID and the encoded response.
+    fn sealed_auth_resp(auth: &Authenticator, auth_key: *const SignPublicKey) -> (u32, CString) {
+        let ffi_auth_req = unwrap!(test_utils::create_random_auth_req().into_repr_c());
+
+        let (req_id, encoded): (u32, String) = unsafe {
+            unwrap!(call_2(|ud, cb| encode_sealed_auth_req(
+                &ffi_auth_req,
+                auth_key,
+                ud,
+                cb
+            )))
+        };
+
+        let auth_req = match unwrap!(auth_utils::auth_decode_ipc_msg_helper(auth, &encoded)) {
+            (
+                IpcMsg::Req {
+                    req: IpcReq::Auth(auth_req),
+                    ..
+                },
+                None,
+            ) => auth_req,
+            x => panic!("Unexpected {:?}", x),
+        };
+
+        let encoded_auth_resp: String = unsafe {
+            unwrap!(call_1(|ud, cb| {
+                let auth_req = unwrap!(auth_req.into_repr_c());
+                encode_auth_resp(
+                    auth,
+                    &auth_req,
+                    req_id,
+                    IPC_ENCODING_BASE32,
+                    true, // is_granted
+                    ud,
+                    cb,
+                )
+            }))
+        };
+        match unwrap!(ipc::decode_msg(&encoded_auth_resp)) {
+            IpcMsg::SealedResp { req_id: id, .. } => assert_eq!(id, req_id),
+            x => panic!("Unexpected {:?}", x),
+        }
+
+        (req_id, unwrap!(CString::new(encoded_auth_resp)))
+    }
+
+    // Decodes an auth response with `decode_ipc_msg`.
+    fn decode_auth_resp(encoded_auth_resp: &CString) -> Context {
+        extern "C" fn auth_cb(ctx: *mut c_void, req_id: u32, auth_granted: *const FfiAuthGranted) {
+            unsafe {
+                let auth_granted = unwrap!(AuthGranted::clone_from_repr_c(auth_granted));
+
+                let ctx = ctx as *mut Context;
+                (*ctx).req_id = req_id;
+                (*ctx).auth_granted = Some(auth_granted);
+            }
+        }
+
+        extern "C" fn req_id_cb(ctx: *mut c_void, _req_id: u32) {
+            unsafe {
+                let ctx = ctx as *mut Context;
+                (*ctx).unexpected_cb = true;
+            }
+        }
+
+        extern "C" fn revoked_cb(ctx: *mut c_void) {
+            unsafe {
+                let ctx = ctx as *mut Context;
+                (*ctx).unexpected_cb = true;
+            }
+        }
+
+        extern "C" fn unregistered_cb(
+            ctx: *mut c_void,
+            _req_id: u32,
+            _bootstrap_cfg: *const u8,
+            _bootstrap_cfg_len: usize,
+        ) {
+            unsafe {
+                let ctx = ctx as *mut Context;
+                (*ctx).unexpected_cb = true;
+            }
+        }
+
+        let mut context = Context {
+            unexpected_cb: false,
+            req_id: 0,
+            auth_granted: None,
+        };
+
+        let context_ptr: *mut Context = &mut context;
+        unsafe {
+            decode_ipc_msg(
+                encoded_auth_resp.as_ptr(),
+                context_ptr as *mut c_void,
+                auth_cb,
+                unregistered_cb,
+                req_id_cb,
+                req_id_cb,
+                revoked_cb,
+                err_cb,
+            );
+        }
+
+        context
+    }
+
     struct Context {
         unexpected_cb: bool,
         req_id: u32,
@@ -19,6 +19,8 @@ pub mod cipher_opt;
 pub mod crypto;
 /// Low level manipulation of `ImmutableData`.
 pub mod immutable_data;
+/// Inbox messaging.
+pub mod inbox;
 /// IPC utilities.
 pub mod ipc;
 /// Logging operations.
@@ -31,6 +33,8 @@ pub mod mutable_data;
 pub mod nfs;
 /// `ObjectCache` handles.
 pub mod object_cache;
+/// Registry of public names.
+pub mod public_id;
 /// Testing utilities.
 #[cfg(any(test, feature = "testing"))]
 pub mod test_utils;
@@ -51,6 +55,7 @@ use safe_core::ipc::{AuthGranted as NativeAuthGranted, BootstrapConfig};
 use safe_core::{self, Client, FutureExt};
 use std::ffi::{CStr, CString, OsStr};
 use std::os::raw::{c_char, c_void};
+use std::path::PathBuf;
 use std::slice;
 
 /// Create unregistered app.
@@ -109,6 +114,10 @@ pub unsafe extern "C" fn app_registered(
 }
 
 /// Try to restore a failed connection with the network.
+///
+/// Breaking change: the connection which is replaced isn't reported as lost, so
+/// `o_disconnect_notifier_cb` is no longer called when reconnecting while the app is still
+/// connected.
 #[no_mangle]
 pub unsafe extern "C" fn app_reconnect(
     app: *mut App,
@@ -129,6 +138,90 @@ pub unsafe extern "C" fn app_reconnect(
     })
 }
 
+/// Set the callback called with the code of every `NetworkEvent` of the connection, and the
+/// number of the attempt when reconnecting, replacing the previous one. Unlike
+/// `o_disconnect_notifier_cb`, it's also called when the app is connecting, connected,
+/// reconnecting or gave up connecting. `observer_user_data` is passed to it, and must stay
+/// valid until the app is freed.
+#[no_mangle]
+pub unsafe extern "C" fn app_set_network_observer(
+    app: *const App,
+    observer_user_data: *mut c_void,
+    o_observer_cb: extern "C" fn(user_data: *mut c_void, event: i32, attempt: u32),
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(user_data: *mut c_void, result: *const FfiResult),
+) {
+    catch_unwind_cb(user_data, o_cb, || -> Result<_, AppError> {
+        let observer_user_data = OpaqueCtx(observer_user_data);
+        (*app).set_network_observer(move |event| {
+            let attempt = event.attempt();
+            o_observer_cb(observer_user_data.0, event.into(), attempt)
+        });
+        o_cb(user_data, FFI_RESULT_OK);
+        Ok(())
+    })
+}
+
+/// Enable the offline queue of the app, persisted to the directory at `path`. Mutations made
+/// while the app is disconnected are then queued and reported as successful, and they're
+/// replayed as they are once it's connected again. `o_conflict_cb` is called with the error
+/// of every queued mutation which fails then. `conflict_user_data` is passed to it, and must
+/// stay valid until the app is freed.
+#[no_mangle]
+pub unsafe extern "C" fn app_enable_offline_queue(
+    app: *mut App,
+    path: *const c_char,
+    conflict_user_data: *mut c_void,
+    o_conflict_cb: extern "C" fn(user_data: *mut c_void, result: *const FfiResult),
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(user_data: *mut c_void, result: *const FfiResult),
+) {
+    catch_unwind_cb(user_data, o_cb, || -> Result<_, AppError> {
+        let path = PathBuf::from(from_c_str(path)?);
+        let conflict_user_data = OpaqueCtx(conflict_user_data);
+        let user_data = OpaqueCtx(user_data);
+
+        (*app).send(move |client, _| {
+            let res = client.enable_offline_queue(&path, move |mutation, error| {
+                debug!("Queued mutation {:?} failed: {:?}", mutation, error);
+                let error = AppError::from(error);
+                let (error_code, description) = ffi_error!(error);
+                let res = FfiResult {
+                    error_code,
+                    description: description.as_ptr(),
+                };
+                o_conflict_cb(conflict_user_data.0, &res);
+            });
+            try_cb!(res.map_err(AppError::from), user_data.0, o_cb);
+            o_cb(user_data.0, FFI_RESULT_OK);
+            None
+        })
+    })
+}
+
+/// Replay the mutations in the offline queue of the app, and return how many have been
+/// removed from it. This is done automatically once the app is connected again.
+#[no_mangle]
+pub unsafe extern "C" fn app_replay_offline_queue(
+    app: *mut App,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(user_data: *mut c_void, result: *const FfiResult, replayed: usize),
+) {
+    catch_unwind_cb(user_data, o_cb, || -> Result<_, AppError> {
+        let user_data = OpaqueCtx(user_data);
+        (*app).send(move |client, _| {
+            client
+                .replay_offline_queue()
+                .map(move |replayed| {
+                    o_cb(user_data.0, FFI_RESULT_OK, replayed);
+                }).map_err(move |e| {
+                    call_result_cb!(Err::<(), _>(AppError::from(e)), user_data, o_cb);
+                }).into_box()
+                .into()
+        })
+    })
+}
+
 /// Get the account usage statistics (mutations done and mutations available).
 #[no_mangle]
 pub unsafe extern "C" fn app_account_info(
@@ -206,6 +299,47 @@ pub unsafe extern "C" fn app_free(app: *mut App) {
     let _ = Box::from_raw(app);
 }
 
+/// Create a cancellable operation of the app. The returned handle is used in place of `app`
+/// for the calls which belong to the operation. Once `app_operation_cancel` is called with it,
+/// their pending network requests are abandoned and they fail with `ERR_OPERATION_CANCELLED`.
+/// The handle must be released with `app_operation_free`, and can't be used once `app` is freed.
+#[no_mangle]
+pub unsafe extern "C" fn app_operation_new(
+    app: *const App,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(user_data: *mut c_void, result: *const FfiResult, operation: *mut App),
+) {
+    catch_unwind_cb(user_data, o_cb, || -> Result<_, AppError> {
+        let operation = (*app).operation();
+        o_cb(user_data, FFI_RESULT_OK, Box::into_raw(Box::new(operation)));
+        Ok(())
+    })
+}
+
+/// Cancel the operation. This can be called from any thread.
+#[no_mangle]
+pub unsafe extern "C" fn app_operation_cancel(
+    operation: *const App,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(user_data: *mut c_void, result: *const FfiResult),
+) {
+    catch_unwind_cb(user_data, o_cb, || -> Result<_, AppError> {
+        let token = (*operation)
+            .operation_token()
+            .ok_or(AppError::OperationForbidden)?;
+        token.cancel();
+        o_cb(user_data, FFI_RESULT_OK);
+        Ok(())
+    })
+}
+
+/// Discard and clean up the operation handle. This doesn't cancel the operation.
+/// Using `operation` after a call to this function is undefined behaviour.
+#[no_mangle]
+pub unsafe extern "C" fn app_operation_free(operation: *mut App) {
+    let _ = Box::from_raw(operation);
+}
+
 /// Resets the object cache. Removes all objects currently in the object cache
 /// and invalidates all existing object handles.
 #[no_mangle]
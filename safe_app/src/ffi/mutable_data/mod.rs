@@ -12,6 +12,7 @@ pub mod entry_actions;
 mod helper;
 pub mod metadata;
 pub mod permissions;
+pub mod share_offer;
 #[cfg(test)]
 mod tests;
 
@@ -0,0 +1,150 @@
+// Copyright 2018 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under the MIT license <LICENSE-MIT
+// http://opensource.org/licenses/MIT> or the Modified BSD license <LICENSE-BSD
+// https://opensource.org/licenses/BSD-3-Clause>, at your option. This file may not be copied,
+// modified, or distributed except according to those terms. Please review the Licences for the
+// specific language governing permissions and limitations relating to use of the SAFE Network
+// Software.
+
+//! FFI for offers of access to mutable data made to other users.
+
+use errors::AppError;
+use ffi::helper::send;
+use ffi::object_cache::EncryptPubKeyHandle;
+use ffi_utils::{
+    catch_unwind_cb, vec_clone_from_raw_parts, FfiResult, OpaqueCtx, ReprC, FFI_RESULT_OK,
+};
+use futures::{stream, Future, Stream};
+use routing::{PermissionSet as NativePermissionSet, User};
+use rust_sodium::crypto::box_;
+use safe_core::ffi::ipc::req::PermissionSet;
+use safe_core::ffi::MDataInfo;
+use safe_core::ipc::req::permission_set_clone_from_repr_c;
+use safe_core::ipc::{self, ShareAcceptance, ShareOffer};
+use safe_core::{Client, FutureExt, MDataInfo as NativeMDataInfo};
+use std::os::raw::c_void;
+use {App, AppClient, AppFuture};
+
+/// Create an offer of access to the mutable data for the app owning the given
+/// public encryption key, e.g. the key published under the public ID of another
+/// user. The offer carries the encryption keys of the data, is signed by this app
+/// and sealed to the key of the recipient.
+///
+/// The recipient accepts the offer for that app through their authenticator and
+/// sends the acceptance back, to be applied with `mdata_share_offer_accept`.
+#[no_mangle]
+pub unsafe extern "C" fn mdata_share_offer_new(
+    app: *const App,
+    info: *const MDataInfo,
+    permission_set: *const PermissionSet,
+    recipient_h: EncryptPubKeyHandle,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(
+        user_data: *mut c_void,
+        result: *const FfiResult,
+        offer: *const u8,
+        offer_len: usize,
+    ),
+) {
+    catch_unwind_cb(user_data, o_cb, || {
+        let user_data = OpaqueCtx(user_data);
+        let info = NativeMDataInfo::clone_from_repr_c(info)?;
+        let perms = permission_set_clone_from_repr_c(*permission_set)?;
+
+        (*app).send(move |client, context| {
+            let recipient = *try_cb!(
+                context.object_cache().get_encrypt_key(recipient_h),
+                user_data,
+                o_cb
+            );
+            let offer = try_cb!(
+                seal_offer(client, info, perms, &recipient),
+                user_data,
+                o_cb
+            );
+
+            o_cb(user_data.0, FFI_RESULT_OK, offer.as_ptr(), offer.len());
+            None
+        })
+    })
+}
+
+/// Grant the permissions of an offer made with `mdata_share_offer_new` to the
+/// apps listed in its acceptance. Only the acceptance of an offer made by this
+/// app, with unchanged terms, is valid. The app needs to be allowed to manage
+/// the permissions of the mutable data.
+#[no_mangle]
+pub unsafe extern "C" fn mdata_share_offer_accept(
+    app: *const App,
+    acceptance: *const u8,
+    acceptance_len: usize,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(user_data: *mut c_void, result: *const FfiResult),
+) {
+    catch_unwind_cb(user_data, o_cb, || {
+        let acceptance = vec_clone_from_raw_parts(acceptance, acceptance_len);
+
+        send(app, user_data, o_cb, move |client, _| {
+            grant_offered_perms(client, &acceptance)
+        })
+    })
+}
+
+fn seal_offer(
+    client: &AppClient,
+    mdata: NativeMDataInfo,
+    perms: NativePermissionSet,
+    recipient: &box_::PublicKey,
+) -> Result<Vec<u8>, AppError> {
+    let (sign_pk, sign_sk) = client
+        .signing_keypair()
+        .ok_or(AppError::UnregisteredClientAccess)?;
+    let reply_key = client
+        .public_encryption_key()
+        .ok_or(AppError::UnregisteredClientAccess)?;
+
+    let offer = ShareOffer {
+        mdata,
+        perms,
+        sign_pk,
+        reply_key,
+    };
+
+    Ok(ipc::seal_share_offer(&offer, &sign_sk, recipient)?)
+}
+
+// Grants the offered permissions to each of the apps which accepted the offer.
+fn grant_offered_perms(client: &AppClient, acceptance: &[u8]) -> Box<AppFuture<()>> {
+    let sign_pk = fry!(
+        client
+            .public_signing_key()
+            .ok_or(AppError::UnregisteredClientAccess)
+    );
+    let (reply_pk, reply_sk) = fry!(
+        client
+            .encryption_keypair()
+            .ok_or(AppError::UnregisteredClientAccess)
+    );
+    let ShareAcceptance {
+        name,
+        type_tag,
+        perms,
+        keys,
+    } = fry!(ipc::open_share_acceptance(
+        acceptance, &sign_pk, &reply_pk, &reply_sk
+    ));
+
+    let c2 = client.clone();
+
+    client
+        .get_mdata_version(name, type_tag)
+        .and_then(move |version| {
+            stream::iter_ok(keys).fold(version, move |version, key| {
+                c2.set_mdata_user_permissions(name, type_tag, User::Key(key), perms, version + 1)
+                    .map(move |()| version + 1)
+            })
+        }).map(|_| ())
+        .map_err(AppError::from)
+        .into_box()
+}
@@ -8,24 +8,36 @@
 // Software.
 
 use errors::{ERR_ACCESS_DENIED, ERR_INVALID_SUCCESSOR, ERR_NO_SUCH_ENTRY, ERR_NO_SUCH_KEY};
+use ffi::crypto::{app_pub_sign_key, enc_pub_key_new, sign_pub_key_new};
+use ffi::ipc::{decode_ipc_msg_v4, encode_share_offer_req};
 use ffi::mdata_info::*;
 use ffi::mutable_data::entries::*;
 use ffi::mutable_data::entry_actions::*;
 use ffi::mutable_data::permissions::*;
+use ffi::mutable_data::share_offer::{mdata_share_offer_accept, mdata_share_offer_new};
 use ffi::mutable_data::*;
-use ffi::object_cache::MDataPermissionsHandle;
+use ffi::object_cache::{EncryptPubKeyHandle, MDataPermissionsHandle, SignPubKeyHandle};
 use ffi_utils::test_utils::{
-    call_0, call_1, call_vec, call_vec_u8, send_via_user_data, sender_as_user_data,
+    call_0, call_1, call_2, call_vec, call_vec_u8, send_via_user_data, sender_as_user_data,
 };
 use ffi_utils::{vec_clone_from_raw_parts, FfiResult};
 use permissions::UserPermissionSet;
 use routing::{Action, PermissionSet as NativePermissionSet};
+use safe_authenticator::ffi::ipc::{encode_share_offer_resp, IPC_ENCODING_BASE32};
+use safe_authenticator::test_utils as authenticator;
 use safe_core::ffi::ipc::req::PermissionSet as FfiPermissionSet;
-use safe_core::ipc::req::{permission_set_clone_from_repr_c, permission_set_into_repr_c};
+use safe_core::ffi::ipc::resp::{
+    AuthGranted as FfiAuthGranted, ShareOfferGranted as FfiShareOfferGranted,
+};
+use safe_core::ipc::req::{
+    permission_set_clone_from_repr_c, permission_set_into_repr_c, ShareOfferReq,
+};
 use safe_core::ipc::resp::{MDataKey, MDataValue};
+use safe_core::ipc::{IpcMsg, IpcReq};
 use safe_core::MDataInfo as NativeMDataInfo;
+use std::ffi::CString;
 use std::sync::mpsc;
-use test_utils::create_app;
+use test_utils::{create_app, create_random_auth_req};
 
 // The usual test to insert, update, delete and list all permissions from the FFI point of view.
 #[test]
@@ -602,3 +614,205 @@ fn entries_crud_ffi() {
         }
     }
 }
+
+// Test offering access to a mutable data to another user, granting the offer in the
+// recipient's authenticator and applying the acceptance, all through the FFI.
+#[test]
+fn share_offer_ffi() {
+    let app = create_app();
+
+    // The recipient user and the app the offer is going to be accepted for.
+    let recipient = authenticator::create_account_and_login();
+    let recipient_auth_req = create_random_auth_req();
+    let recipient_app = unwrap!(authenticator::register_app(&recipient, &recipient_auth_req));
+
+    // Create a private mutable data the app can manage the permissions of.
+    let perms_h: MDataPermissionsHandle =
+        unsafe { unwrap!(call_1(|ud, cb| mdata_permissions_new(&app, ud, cb))) };
+    let app_key_h: SignPubKeyHandle =
+        unsafe { unwrap!(call_1(|ud, cb| app_pub_sign_key(&app, ud, cb))) };
+    let app_perms = NativePermissionSet::new()
+        .allow(Action::Insert)
+        .allow(Action::ManagePermissions);
+
+    unsafe {
+        unwrap!(call_0(|ud, cb| mdata_permissions_insert(
+            &app,
+            perms_h,
+            app_key_h,
+            &permission_set_into_repr_c(app_perms),
+            ud,
+            cb,
+        )))
+    }
+
+    let md_info: NativeMDataInfo =
+        unsafe { unwrap!(call_1(|ud, cb| mdata_info_random_private(10_000, ud, cb))) };
+    let md_info = md_info.into_repr_c();
+
+    unsafe {
+        unwrap!(call_0(|ud, cb| mdata_put(
+            &app,
+            &md_info,
+            perms_h,
+            ENTRIES_EMPTY,
+            ud,
+            cb
+        )))
+    };
+
+    // Offer the permission to insert to the recipient's app.
+    let offered_perms = NativePermissionSet::new().allow(Action::Insert);
+    let recipient_key_h: EncryptPubKeyHandle = unsafe {
+        unwrap!(call_1(|ud, cb| enc_pub_key_new(
+            &app,
+            &recipient_app.app_keys.enc_pk.0,
+            ud,
+            cb
+        )))
+    };
+
+    let offer: Vec<u8> = unsafe {
+        unwrap!(call_vec_u8(|ud, cb| mdata_share_offer_new(
+            &app,
+            &md_info,
+            &permission_set_into_repr_c(offered_perms),
+            recipient_key_h,
+            ud,
+            cb,
+        )))
+    };
+
+    // The recipient grants the offer.
+    let req = ShareOfferReq {
+        app: recipient_auth_req.app.clone(),
+        offer,
+    };
+    let (req_id, encoded_req): (u32, String) = unsafe {
+        let req = unwrap!(req.into_repr_c());
+        unwrap!(call_2(|ud, cb| encode_share_offer_req(&req, ud, cb)))
+    };
+
+    let req = match unwrap!(authenticator::auth_decode_ipc_msg_helper(
+        &recipient,
+        &encoded_req
+    )) {
+        (
+            IpcMsg::Req {
+                req: IpcReq::ShareOffer(req),
+                req_id: decoded_req_id,
+            },
+            _,
+        ) => {
+            assert_eq!(decoded_req_id, req_id);
+            req
+        }
+        x => panic!("Unexpected {:?}", x),
+    };
+
+    let encoded_resp: String = unsafe {
+        let req = unwrap!(req.into_repr_c());
+        unwrap!(call_1(|ud, cb| encode_share_offer_resp(
+            &recipient,
+            &req,
+            req_id,
+            IPC_ENCODING_BASE32,
+            true,
+            ud,
+            cb
+        )))
+    };
+    let encoded_resp = unwrap!(CString::new(encoded_resp));
+
+    let acceptance = {
+        let (tx, rx) = mpsc::channel::<Result<Vec<u8>, i32>>();
+        let mut ud = Default::default();
+
+        unsafe {
+            decode_ipc_msg_v4(
+                encoded_resp.as_ptr(),
+                sender_as_user_data(&tx, &mut ud),
+                auth_cb,
+                unregistered_cb,
+                req_cb,
+                req_cb,
+                share_offer_cb,
+                revoked_cb,
+                err_cb,
+            );
+        }
+
+        unwrap!(unwrap!(rx.recv()))
+    };
+
+    // The app applies the acceptance.
+    unsafe {
+        unwrap!(call_0(|ud, cb| mdata_share_offer_accept(
+            &app,
+            acceptance.as_ptr(),
+            acceptance.len(),
+            ud,
+            cb,
+        )))
+    }
+
+    // The recipient's app has been granted the offered permissions.
+    let recipient_app_key_h: SignPubKeyHandle = unsafe {
+        unwrap!(call_1(|ud, cb| sign_pub_key_new(
+            &app,
+            &recipient_app.app_keys.sign_pk.0,
+            ud,
+            cb
+        )))
+    };
+    let perm_set: FfiPermissionSet = unsafe {
+        unwrap!(call_1(|ud, cb| mdata_list_user_permissions(
+            &app,
+            &md_info,
+            recipient_app_key_h,
+            ud,
+            cb
+        )))
+    };
+    assert_eq!(
+        unwrap!(permission_set_clone_from_repr_c(perm_set)),
+        offered_perms
+    );
+
+    extern "C" fn auth_cb(user_data: *mut c_void, _req_id: u32, _granted: *const FfiAuthGranted) {
+        unsafe { send_via_user_data::<Result<Vec<u8>, i32>>(user_data, Err(-1)) }
+    }
+
+    extern "C" fn unregistered_cb(
+        user_data: *mut c_void,
+        _req_id: u32,
+        _bootstrap_cfg: *const u8,
+        _bootstrap_cfg_len: usize,
+    ) {
+        unsafe { send_via_user_data::<Result<Vec<u8>, i32>>(user_data, Err(-1)) }
+    }
+
+    extern "C" fn req_cb(user_data: *mut c_void, _req_id: u32) {
+        unsafe { send_via_user_data::<Result<Vec<u8>, i32>>(user_data, Err(-1)) }
+    }
+
+    extern "C" fn share_offer_cb(
+        user_data: *mut c_void,
+        _req_id: u32,
+        granted: *const FfiShareOfferGranted,
+    ) {
+        unsafe {
+            let acceptance =
+                vec_clone_from_raw_parts((*granted).acceptance, (*granted).acceptance_len);
+            send_via_user_data::<Result<Vec<u8>, i32>>(user_data, Ok(acceptance))
+        }
+    }
+
+    extern "C" fn revoked_cb(user_data: *mut c_void) {
+        unsafe { send_via_user_data::<Result<Vec<u8>, i32>>(user_data, Err(-1)) }
+    }
+
+    extern "C" fn err_cb(user_data: *mut c_void, res: *const FfiResult, _req_id: u32) {
+        unsafe { send_via_user_data::<Result<Vec<u8>, i32>>(user_data, Err((*res).error_code)) }
+    }
+}
@@ -17,13 +17,14 @@ use ffi_utils::{
 };
 use futures::future::{self, Either};
 use futures::Future;
-use safe_core::ffi::nfs::File;
+use safe_core::ffi::nfs::{FetchedFile, File};
 use safe_core::ffi::MDataInfo;
 use safe_core::nfs::file_helper::{self, Version};
 use safe_core::nfs::File as NativeFile;
 use safe_core::nfs::{Mode, Reader, Writer};
 use safe_core::{FutureExt, MDataInfo as NativeMDataInfo};
 use std::os::raw::{c_char, c_void};
+use std::{ptr, slice};
 use App;
 
 /// Holds context for file operations, depending on the mode.
@@ -79,6 +80,64 @@ pub unsafe extern "C" fn dir_fetch_file(
     })
 }
 
+/// Retrieve many files with the given names, and their versions, from the directory at once.
+/// The files are passed to the callback in the same order as the names, with a null `file`
+/// for the names there's no file with. `file_names` may be null if `file_names_len` is 0.
+#[no_mangle]
+pub unsafe extern "C" fn dir_fetch_files(
+    app: *const App,
+    parent_info: *const MDataInfo,
+    file_names: *const *const c_char,
+    file_names_len: usize,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(
+        user_data: *mut c_void,
+        result: *const FfiResult,
+        files: *const FetchedFile,
+        files_len: usize,
+    ),
+) {
+    catch_unwind_cb(user_data, o_cb, || {
+        let parent_info = NativeMDataInfo::clone_from_repr_c(parent_info)?;
+        let file_names = if file_names_len == 0 {
+            Vec::new()
+        } else {
+            slice::from_raw_parts(file_names, file_names_len)
+                .iter()
+                .map(|name| from_c_str(*name))
+                .collect::<Result<Vec<_>, _>>()?
+        };
+        let user_data = OpaqueCtx(user_data);
+
+        (*app).send(move |client, _| {
+            file_helper::fetch_many(client.clone(), parent_info, &file_names)
+                .map(move |files| {
+                    let ffi_files: Vec<_> = files
+                        .into_iter()
+                        .map(|file| file.map(|(version, file)| (version, file.into_repr_c())))
+                        .collect();
+                    let fetched: Vec<_> = ffi_files
+                        .iter()
+                        .map(|file| match *file {
+                            Some((version, ref ffi_file)) => FetchedFile {
+                                file: ffi_file,
+                                version,
+                            },
+                            None => FetchedFile {
+                                file: ptr::null(),
+                                version: 0,
+                            },
+                        }).collect();
+                    o_cb(user_data.0, FFI_RESULT_OK, fetched.as_ptr(), fetched.len())
+                }).map_err(AppError::from)
+                .map_err(move |err| {
+                    call_result_cb!(Err::<(), _>(err), user_data, o_cb);
+                }).into_box()
+                .into()
+        })
+    })
+}
+
 /// Insert the file into the parent directory.
 #[no_mangle]
 pub unsafe extern "C" fn dir_insert_file(
@@ -361,3 +420,93 @@ pub unsafe extern "C" fn file_close(
         })
     })
 }
+
+/// Set the function called with the progress of fetching the chunks of the file, every time
+/// a new chunk has been fetched.
+///
+/// `bytes_done` is the size of the content of the chunks fetched so far, and `bytes_total`
+/// the size of the file. Small files are kept along with the location of their content, so
+/// `o_progress_cb` is called right away with all of it done. Returning `false` from
+/// `o_progress_cb` cancels fetching further chunks, making the reads in progress fail.
+/// `progress_user_data` is passed to `o_progress_cb`, and must stay valid until the file is
+/// closed. Fails with `InvalidFileMode` if the file isn't open for reading.
+#[no_mangle]
+pub unsafe extern "C" fn file_set_read_progress_callback(
+    app: *const App,
+    file_h: FileContextHandle,
+    progress_user_data: *mut c_void,
+    o_progress_cb: extern "C" fn(user_data: *mut c_void, bytes_done: u64, bytes_total: u64) -> bool,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(user_data: *mut c_void, result: *const FfiResult),
+) {
+    catch_unwind_cb(user_data, o_cb, || {
+        let progress_user_data = OpaqueCtx(progress_user_data);
+        let user_data = OpaqueCtx(user_data);
+
+        (*app).send(move |_client, context| {
+            let file_ctx = try_cb!(context.object_cache().get_file(file_h), user_data, o_cb);
+
+            if let Some(ref reader) = file_ctx.reader {
+                reader
+                    .download_tracker()
+                    .set_progress_callback(move |progress| {
+                        o_progress_cb(
+                            progress_user_data.0,
+                            progress.bytes_done,
+                            progress.bytes_total,
+                        )
+                    });
+                o_cb(user_data.0, FFI_RESULT_OK);
+            } else {
+                call_result_cb!(Err::<(), _>(AppError::InvalidFileMode), user_data, o_cb);
+            }
+            None
+        })
+    })
+}
+
+/// Set the function called with the progress of uploading the chunks of the file, every time
+/// a chunk has been uploaded.
+///
+/// `bytes_done` is the size of the chunks uploaded so far, and `bytes_total` is `size`, the
+/// size the file will have once written. When appending, the existing content which isn't
+/// uploaded again is reported as done once the file is closed, as is the content of small
+/// files, which is kept along with its location instead of in chunks. Returning `false` from
+/// `o_progress_cb` cancels the remaining uploads, making the writes in progress and
+/// `file_close` fail. `progress_user_data` is passed to `o_progress_cb`, and must stay valid
+/// until the file is closed. Fails with `InvalidFileMode` if the file isn't open for writing.
+#[no_mangle]
+pub unsafe extern "C" fn file_set_write_progress_callback(
+    app: *const App,
+    file_h: FileContextHandle,
+    size: u64,
+    progress_user_data: *mut c_void,
+    o_progress_cb: extern "C" fn(user_data: *mut c_void, bytes_done: u64, bytes_total: u64) -> bool,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(user_data: *mut c_void, result: *const FfiResult),
+) {
+    catch_unwind_cb(user_data, o_cb, || {
+        let progress_user_data = OpaqueCtx(progress_user_data);
+        let user_data = OpaqueCtx(user_data);
+
+        (*app).send(move |_client, context| {
+            let file_ctx = try_cb!(context.object_cache().get_file(file_h), user_data, o_cb);
+
+            if let Some(ref writer) = file_ctx.writer {
+                let pipeline = writer.upload_pipeline();
+                pipeline.set_size(size);
+                pipeline.set_progress_callback(move |progress| {
+                    o_progress_cb(
+                        progress_user_data.0,
+                        progress.bytes_done,
+                        progress.bytes_total,
+                    )
+                });
+                o_cb(user_data.0, FFI_RESULT_OK);
+            } else {
+                call_result_cb!(Err::<(), _>(AppError::InvalidFileMode), user_data, o_cb);
+            }
+            None
+        })
+    })
+}
@@ -0,0 +1,292 @@
+// Copyright 2018 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under the MIT license <LICENSE-MIT
+// http://opensource.org/licenses/MIT> or the Modified BSD license <LICENSE-BSD
+// https://opensource.org/licenses/BSD-3-Clause>, at your option. This file may not be copied,
+// modified, or distributed except according to those terms. Please review the Licences for the
+// specific language governing permissions and limitations relating to use of the SAFE Network
+// Software.
+
+//! FFI for the registry of public names.
+
+use errors::AppError;
+use ffi::helper::send;
+use ffi::object_cache::{EncryptPubKeyHandle, SignPubKeyHandle};
+use ffi_utils::{catch_unwind_cb, from_c_str, FfiResult, OpaqueCtx, ReprC, FFI_RESULT_OK};
+use futures::Future;
+use routing::ClientError;
+use safe_core::ffi::MDataInfo;
+use safe_core::public_id::{self, PublicId};
+use safe_core::{Client, CoreError, FutureExt, MDataInfo as NativeMDataInfo};
+use std::os::raw::{c_char, c_void};
+use {App, AppClient, AppContext, AppFuture};
+
+/// Name of the container the public names registered by the user are recorded in.
+pub const PUBLIC_NAMES_CONTAINER: &str = "_publicNames";
+
+/// Register a public name and publish the public keys of this app under it. Share
+/// offers sealed to the published encryption key have to be accepted for this app.
+/// The app needs to have been granted access to the `_publicNames` container.
+#[no_mangle]
+pub unsafe extern "C" fn public_id_register(
+    app: *const App,
+    name: *const c_char,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(user_data: *mut c_void, result: *const FfiResult),
+) {
+    catch_unwind_cb(user_data, o_cb, || {
+        let name = from_c_str(name)?;
+
+        send(app, user_data, o_cb, move |client, context| {
+            let id = PublicId {
+                sign_pk: fry!(
+                    client
+                        .public_signing_key()
+                        .ok_or(AppError::UnregisteredClientAccess)
+                ),
+                enc_pk: fry!(
+                    client
+                        .public_encryption_key()
+                        .ok_or(AppError::UnregisteredClientAccess)
+                ),
+                services: Default::default(),
+            };
+            let c2 = client.clone();
+
+            public_names(client, context)
+                .and_then(move |public_names| {
+                    public_id::register(&c2, &public_names, &name, &id).map_err(AppError::from)
+                }).into_box()
+        })
+    })
+}
+
+/// Resolve a public name to the keys published under it.
+#[no_mangle]
+pub unsafe extern "C" fn public_id_resolve(
+    app: *const App,
+    name: *const c_char,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(
+        user_data: *mut c_void,
+        result: *const FfiResult,
+        sign_pk_h: SignPubKeyHandle,
+        enc_pk_h: EncryptPubKeyHandle,
+    ),
+) {
+    catch_unwind_cb(user_data, o_cb, || {
+        let user_data = OpaqueCtx(user_data);
+        let name = from_c_str(name)?;
+
+        (*app).send(move |client, context| {
+            let context = context.clone();
+
+            public_id::resolve(client, &name)
+                .map(move |id| {
+                    let sign_pk_h = context.object_cache().insert_pub_sign_key(id.sign_pk);
+                    let enc_pk_h = context.object_cache().insert_encrypt_key(id.enc_pk);
+                    o_cb(user_data.0, FFI_RESULT_OK, sign_pk_h, enc_pk_h);
+                }).map_err(move |err| {
+                    call_result_cb!(Err::<(), _>(AppError::from(err)), user_data, o_cb);
+                }).into_box()
+                .into()
+        })
+    })
+}
+
+/// Resolve a service published under a public name to the `MDataInfo` of the
+/// public data backing it.
+#[no_mangle]
+pub unsafe extern "C" fn public_id_get_service(
+    app: *const App,
+    name: *const c_char,
+    service: *const c_char,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(
+        user_data: *mut c_void,
+        result: *const FfiResult,
+        mdata_info: *const MDataInfo,
+    ),
+) {
+    catch_unwind_cb(user_data, o_cb, || {
+        let user_data = OpaqueCtx(user_data);
+        let name = from_c_str(name)?;
+        let service = from_c_str(service)?;
+
+        (*app).send(move |client, _| {
+            public_id::resolve(client, &name)
+                .map_err(AppError::from)
+                .and_then(move |mut id| {
+                    let info = id
+                        .services
+                        .remove(&service)
+                        .ok_or(ClientError::NoSuchEntry)
+                        .map_err(CoreError::from)?
+                        .into_repr_c();
+                    o_cb(user_data.0, FFI_RESULT_OK, &info);
+                    Ok(())
+                }).map_err(move |err: AppError| {
+                    call_result_cb!(Err::<(), _>(err), user_data, o_cb);
+                }).into_box()
+                .into()
+        })
+    })
+}
+
+/// Publish a service under a public name registered by the user, replacing any
+/// service with the same name. Service names must not start with an underscore and
+/// the data backing the service must be public.
+#[no_mangle]
+pub unsafe extern "C" fn public_id_add_service(
+    app: *const App,
+    name: *const c_char,
+    service: *const c_char,
+    mdata_info: *const MDataInfo,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(user_data: *mut c_void, result: *const FfiResult),
+) {
+    catch_unwind_cb(user_data, o_cb, || {
+        let name = from_c_str(name)?;
+        let service = from_c_str(service)?;
+        let info = NativeMDataInfo::clone_from_repr_c(mdata_info)?;
+
+        send(app, user_data, o_cb, move |client, _| {
+            public_id::add_service(client, &name, &service, &info)
+        })
+    })
+}
+
+/// Remove a service from a public name registered by the user.
+#[no_mangle]
+pub unsafe extern "C" fn public_id_remove_service(
+    app: *const App,
+    name: *const c_char,
+    service: *const c_char,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(user_data: *mut c_void, result: *const FfiResult),
+) {
+    catch_unwind_cb(user_data, o_cb, || {
+        let name = from_c_str(name)?;
+        let service = from_c_str(service)?;
+
+        send(app, user_data, o_cb, move |client, _| {
+            public_id::remove_service(client, &name, &service)
+        })
+    })
+}
+
+// Fetches the info of the `_publicNames` container from the access container.
+fn public_names(client: &AppClient, context: &AppContext) -> Box<AppFuture<NativeMDataInfo>> {
+    context
+        .get_access_info(client)
+        .and_then(|mut containers| {
+            containers
+                .remove(PUBLIC_NAMES_CONTAINER)
+                .map(|(info, _)| info)
+                .ok_or_else(|| AppError::NoSuchContainer(PUBLIC_NAMES_CONTAINER.to_string()))
+        }).into_box()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use errors::ERR_NO_SUCH_ENTRY;
+    use ffi_utils::test_utils::{call_0, call_1, call_2};
+    use safe_core::ipc::req::Permission;
+    use safe_core::{utils, Client, DIR_TAG};
+    use std::collections::HashMap;
+    use std::ffi::CString;
+    use test_utils::{create_app_by_req, create_auth_req_with_access, run};
+
+    // Test registering a public name, publishing a service under it and resolving it.
+    #[test]
+    fn register_and_resolve() {
+        let mut container_permissions = HashMap::new();
+        let _ = container_permissions.insert(
+            PUBLIC_NAMES_CONTAINER.to_string(),
+            btree_set![Permission::Read, Permission::Insert],
+        );
+        let app = unwrap!(create_app_by_req(&create_auth_req_with_access(
+            container_permissions
+        )));
+
+        let name = unwrap!(CString::new(unwrap!(utils::generate_random_string(10))));
+        let service = unwrap!(CString::new("www"));
+        let www = unwrap!(NativeMDataInfo::random_public(DIR_TAG));
+        let ffi_www = www.clone().into_repr_c();
+
+        unsafe {
+            unwrap!(call_0(|ud, cb| public_id_register(
+                &app,
+                name.as_ptr(),
+                ud,
+                cb
+            )));
+
+            // The name can't be registered twice.
+            let res = call_0(|ud, cb| public_id_register(&app, name.as_ptr(), ud, cb));
+            assert!(res.is_err());
+
+            // Encrypted data can't back a service.
+            let private = unwrap!(NativeMDataInfo::random_private(DIR_TAG)).into_repr_c();
+            let res = call_0(|ud, cb| {
+                public_id_add_service(&app, name.as_ptr(), service.as_ptr(), &private, ud, cb)
+            });
+            assert!(res.is_err());
+
+            unwrap!(call_0(|ud, cb| public_id_add_service(
+                &app,
+                name.as_ptr(),
+                service.as_ptr(),
+                &ffi_www,
+                ud,
+                cb
+            )));
+        }
+
+        let (sign_pk_h, enc_pk_h): (SignPubKeyHandle, EncryptPubKeyHandle) = unsafe {
+            unwrap!(call_2(|ud, cb| public_id_resolve(
+                &app,
+                name.as_ptr(),
+                ud,
+                cb
+            )))
+        };
+        run(&app, move |client, context| {
+            let sign_pk = *unwrap!(context.object_cache().get_pub_sign_key(sign_pk_h));
+            let enc_pk = *unwrap!(context.object_cache().get_encrypt_key(enc_pk_h));
+            assert_eq!(sign_pk, unwrap!(client.public_signing_key()));
+            assert_eq!(enc_pk, unwrap!(client.public_encryption_key()));
+            Ok(())
+        });
+
+        let info: NativeMDataInfo = unsafe {
+            unwrap!(call_1(|ud, cb| public_id_get_service(
+                &app,
+                name.as_ptr(),
+                service.as_ptr(),
+                ud,
+                cb
+            )))
+        };
+        assert_eq!(info, www);
+
+        unsafe {
+            unwrap!(call_0(|ud, cb| public_id_remove_service(
+                &app,
+                name.as_ptr(),
+                service.as_ptr(),
+                ud,
+                cb
+            )));
+
+            let res: Result<NativeMDataInfo, _> = call_1(|ud, cb| {
+                public_id_get_service(&app, name.as_ptr(), service.as_ptr(), ud, cb)
+            });
+            match res {
+                Err(ERR_NO_SUCH_ENTRY) => (),
+                x => panic!("Unexpected {:?}", x),
+            }
+        }
+    }
+}
@@ -99,6 +99,8 @@ mod tests {
             app: rand_app(),
             app_container: false,
             containers,
+            manifest: None,
+            resp_key: None,
         };
         let auth_req = unwrap!(auth_req.into_repr_c());
 
@@ -14,7 +14,7 @@ use ffi::app_is_mock;
 use ffi::ipc::decode_ipc_msg;
 use ffi_utils::test_utils::call_1;
 use routing::ImmutableData;
-use safe_authenticator::ffi::ipc::encode_auth_resp;
+use safe_authenticator::ffi::ipc::{encode_auth_resp, IPC_ENCODING_BASE32};
 use safe_authenticator::test_utils;
 use safe_core::ffi::ipc::resp::AuthGranted as FfiAuthGranted;
 use safe_core::ffi::AccountInfo;
@@ -87,6 +87,154 @@ fn account_info() {
     unsafe { app_free(app) };
 }
 
+// Test that cancelling an operation fails its pending requests with `ERR_OPERATION_CANCELLED`,
+// while the calls made through the app handle itself aren't affected.
+#[cfg(feature = "use-mock-routing")]
+#[test]
+fn cancel_operation() {
+    use errors::ERR_OPERATION_CANCELLED;
+    use ffi_utils::test_utils::call_0;
+    use std::thread;
+    use std::time::Duration;
+
+    let app = create_app();
+    let app = Box::into_raw(Box::new(app));
+
+    // Keep the requests pending until they're cancelled.
+    unsafe {
+        unwrap!((*app).send(|client, _| {
+            client.set_simulate_timeout(true);
+            None
+        }));
+    }
+
+    let operation: *mut App = unsafe { unwrap!(call_1(|ud, cb| app_operation_new(app, ud, cb))) };
+
+    let operation_addr = operation as usize;
+    let canceller = thread::spawn(move || {
+        thread::sleep(Duration::from_millis(500));
+        let operation = operation_addr as *const App;
+        unsafe { unwrap!(call_0(|ud, cb| app_operation_cancel(operation, ud, cb))) };
+    });
+
+    let stats: Result<AccountInfo, i32> =
+        unsafe { call_1(|ud, cb| app_account_info(operation, ud, cb)) };
+    match stats {
+        Err(ERR_OPERATION_CANCELLED) => (),
+        Err(_) => panic!("Unexpected error"),
+        Ok(_) => panic!("Unexpected success"),
+    }
+    unwrap!(canceller.join());
+
+    // The calls made through the operation afterwards fail straight away.
+    let stats: Result<AccountInfo, i32> =
+        unsafe { call_1(|ud, cb| app_account_info(operation, ud, cb)) };
+    match stats {
+        Err(ERR_OPERATION_CANCELLED) => (),
+        Err(_) => panic!("Unexpected error"),
+        Ok(_) => panic!("Unexpected success"),
+    }
+
+    // The app handle isn't an operation, so it can't be cancelled.
+    let res = unsafe { call_0(|ud, cb| app_operation_cancel(app, ud, cb)) };
+    assert!(res.is_err());
+
+    unsafe {
+        unwrap!((*app).send(|client, _| {
+            client.set_simulate_timeout(false);
+            None
+        }));
+    }
+
+    let stats: AccountInfo = unsafe { unwrap!(call_1(|ud, cb| app_account_info(app, ud, cb))) };
+    assert!(stats.mutations_available > 0);
+
+    unsafe {
+        app_operation_free(operation);
+        app_free(app);
+    }
+}
+
+// Test queueing a mutation while disconnected and replaying it once reconnected, with its
+// failure reported to the conflict callback.
+#[cfg(feature = "use-mock-routing")]
+#[test]
+fn offline_queue() {
+    use errors::ERR_NO_SUCH_DATA;
+    use ffi_utils::test_utils::call_0;
+    use rand;
+    use routing::EntryActions;
+    use safe_core::client::ConnectionState;
+    use safe_core::utils;
+    use std::env;
+    use std::ffi::CString;
+    use std::fs;
+    use std::os::raw::c_void;
+    use std::sync::mpsc::{self, Receiver, Sender};
+    use std::thread;
+    use std::time::Duration;
+    use test_utils::run;
+
+    let app = create_app();
+    let app = Box::into_raw(Box::new(app));
+
+    let path = env::temp_dir().join(unwrap!(utils::generate_readable_string(10)));
+    let path_c = unwrap!(CString::new(unwrap!(path.to_str())));
+
+    let (tx, rx): (Sender<i32>, Receiver<i32>) = mpsc::channel();
+    let tx_ptr: *const _ = &tx;
+
+    unsafe {
+        unwrap!(call_0(|ud, cb| app_enable_offline_queue(
+            app,
+            path_c.as_ptr(),
+            tx_ptr as *mut c_void,
+            conflict_cb,
+            ud,
+            cb,
+        )));
+    }
+
+    run(unsafe { &*app }, |client, _| {
+        client.simulate_network_disconnect();
+        Ok(())
+    });
+    while run(unsafe { &*app }, |client, _| Ok(client.connection_state()))
+        != ConnectionState::Disconnected
+    {
+        thread::sleep(Duration::from_millis(50));
+    }
+
+    // The app is disconnected, so the mutation is queued and reported as successful.
+    run(unsafe { &*app }, |client, _| {
+        let actions = EntryActions::new().ins(vec![0], vec![1], 0).into();
+        client
+            .mutate_mdata_entries(rand::random(), 10_000, actions)
+            .map_err(AppError::from)
+    });
+
+    // It can't be replayed while the app is still disconnected.
+    let res: Result<usize, i32> = unsafe { call_1(|ud, cb| app_replay_offline_queue(app, ud, cb)) };
+    assert!(res.is_err());
+
+    // It's replayed once the app is reconnected, and fails as the data doesn't exist.
+    unsafe { unwrap!(call_0(|ud, cb| app_reconnect(app, ud, cb))) };
+    assert_eq!(
+        unwrap!(rx.recv_timeout(Duration::from_secs(15))),
+        ERR_NO_SUCH_DATA
+    );
+
+    unsafe { app_free(app) };
+    unwrap!(fs::remove_dir_all(&path));
+
+    extern "C" fn conflict_cb(user_data: *mut c_void, result: *const FfiResult) {
+        unsafe {
+            let tx = user_data as *const Sender<i32>;
+            unwrap!((*tx).send((*result).error_code));
+        }
+    }
+}
+
 // Test disconnection and reconnection with apps.
 #[cfg(all(test, feature = "use-mock-routing"))]
 #[test]
@@ -139,12 +287,10 @@ fn network_status_callback() {
             _ => panic!("Disconnect callback was called"),
         }
 
-        // The reconnection should be fine if we're already connected.
+        // The reconnection should be fine if we're already connected, and the disconnection of
+        // the replaced routing isn't reported.
         unsafe { unwrap!(call_0(|ud, cb| app_reconnect(app, ud, cb))) };
 
-        // disconnect_cb should be called.
-        unwrap!(rx.recv_timeout(Duration::from_secs(15)));
-
         // This should time out.
         let result = rx.recv_timeout(Duration::from_secs(1));
         match result {
@@ -162,6 +308,64 @@ fn network_status_callback() {
     }
 }
 
+// Test that the network observer is told about every change of the connection, with the
+// disconnect notifier still called only when the connection is lost.
+#[cfg(all(test, feature = "use-mock-routing"))]
+#[test]
+fn network_observer() {
+    use ffi_utils::test_utils::call_0;
+    use safe_core::NetworkEvent;
+    use std::os::raw::c_void;
+    use std::sync::mpsc::{self, Sender};
+    use std::time::Duration;
+
+    let app = create_app();
+    let app = Box::into_raw(Box::new(app));
+
+    let (tx, rx) = mpsc::channel::<i32>();
+    let tx_ptr: *const _ = &tx;
+
+    unsafe {
+        unwrap!(call_0(|ud, cb| app_set_network_observer(
+            app,
+            tx_ptr as *mut c_void,
+            observer_cb,
+            ud,
+            cb,
+        )));
+        unwrap!((*app).send(move |client, _| {
+            client.simulate_network_disconnect();
+            None
+        }));
+    }
+
+    let disconnected: i32 = NetworkEvent::Disconnected.into();
+    let event = unwrap!(rx.recv_timeout(Duration::from_secs(15)));
+    assert_eq!(event, disconnected);
+
+    unsafe { unwrap!(call_0(|ud, cb| app_reconnect(app, ud, cb))) };
+
+    let connecting: i32 = NetworkEvent::Connecting.into();
+    let event = unwrap!(rx.recv_timeout(Duration::from_secs(15)));
+    assert_eq!(event, connecting);
+    let connected: i32 = NetworkEvent::Connected.into();
+    let event = unwrap!(rx.recv_timeout(Duration::from_secs(15)));
+    assert_eq!(event, connected);
+
+    // The app doesn't reconnect by itself unless that's enabled in the config.
+    assert!(rx.recv_timeout(Duration::from_secs(1)).is_err());
+
+    unsafe { app_free(app) };
+
+    extern "C" fn observer_cb(user_data: *mut c_void, event: i32, attempt: u32) {
+        assert_eq!(attempt, 0);
+        unsafe {
+            let tx = user_data as *const Sender<i32>;
+            unwrap!((*tx).send(event));
+        }
+    }
+}
+
 // Test getting the app's container name.
 #[test]
 fn test_app_container_name() {
@@ -179,6 +383,8 @@ fn test_app_container_name() {
             app: app_info,
             app_container: true,
             containers: HashMap::new(),
+            manifest: None,
+            resp_key: None,
         },
     ));
 
@@ -207,13 +413,21 @@ fn app_authentication() {
         app: app_exchange_info.clone(),
         app_container: true,
         containers,
+        manifest: None,
+        resp_key: None,
     };
     let auth_req = unwrap!(auth_req.into_repr_c());
 
     let req_id = gen_req_id();
     let encoded: String = unsafe {
         unwrap!(call_1(|ud, cb| encode_auth_resp(
-            &auth, &auth_req, req_id, true, ud, cb
+            &auth,
+            &auth_req,
+            req_id,
+            IPC_ENCODING_BASE32,
+            true,
+            ud,
+            cb
         )))
     };
     let encoded = unwrap!(CString::new(encoded));
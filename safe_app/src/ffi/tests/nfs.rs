@@ -10,16 +10,19 @@
 use errors::AppError;
 use ffi::nfs::*;
 use ffi::object_cache::FileContextHandle;
-use ffi_utils::test_utils::{call_0, call_1, call_2, call_vec_u8};
-use ffi_utils::ErrorCode;
+use ffi_utils::test_utils::{call_0, call_1, call_2, call_vec, call_vec_u8};
+use ffi_utils::{ErrorCode, ReprC};
 use futures::Future;
-use safe_core::ffi::nfs::File;
+use safe_core::ffi::nfs::{FetchedFile, File};
 use safe_core::ffi::MDataInfo;
 use safe_core::ipc::Permission;
 use safe_core::nfs::{File as NativeFile, NfsError};
+use safe_core::utils;
 use std;
 use std::collections::HashMap;
 use std::ffi::CString;
+use std::os::raw::c_void;
+use std::sync::atomic::{AtomicUsize, Ordering, ATOMIC_USIZE_INIT};
 use test_utils::{create_app_by_req, create_auth_req_with_access, run};
 use App;
 
@@ -117,6 +120,73 @@ fn basics() {
     assert_eq!(version, 1);
 }
 
+// Test fetching many files at once.
+// 1. Insert two files with different metadata.
+// 2. Fetch them along with a non-existing file.
+// 3. Check that the files come in the order of the names, and the non-existing one is null.
+// 4. Fetch no files, with null names.
+#[test]
+fn fetch_files() {
+    let (app, container_info) = setup();
+
+    let file_names = ["file0.txt", "file1.txt", "file2.txt"];
+    let ffi_file_names: Vec<_> = file_names
+        .iter()
+        .map(|name| unwrap!(CString::new(*name)))
+        .collect();
+    let ffi_file_name_ptrs: Vec<_> = ffi_file_names.iter().map(|name| name.as_ptr()).collect();
+
+    // Insert the first and the last file.
+    for &(index, metadata) in &[(0, b"first"), (2, b"third")] {
+        let ffi_file = NativeFile::new(metadata.to_vec()).into_repr_c();
+
+        unsafe {
+            unwrap!(call_0(|ud, cb| dir_insert_file(
+                &app,
+                &container_info,
+                ffi_file_name_ptrs[index],
+                &ffi_file,
+                ud,
+                cb,
+            )))
+        }
+    }
+
+    // Fetch all of them.
+    let fetched: Vec<Fetched> = unsafe {
+        unwrap!(call_vec(|ud, cb| dir_fetch_files(
+            &app,
+            &container_info,
+            ffi_file_name_ptrs.as_ptr(),
+            ffi_file_name_ptrs.len(),
+            ud,
+            cb,
+        )))
+    };
+    assert_eq!(fetched.len(), 3);
+
+    let (file0, version0) = unwrap!(fetched[0].0.as_ref());
+    assert_eq!(file0.user_metadata(), b"first");
+    assert_eq!(*version0, 0);
+    assert!(fetched[1].0.is_none());
+    let (file2, version2) = unwrap!(fetched[2].0.as_ref());
+    assert_eq!(file2.user_metadata(), b"third");
+    assert_eq!(*version2, 0);
+
+    // Fetch none of them.
+    let fetched: Vec<Fetched> = unsafe {
+        unwrap!(call_vec(|ud, cb| dir_fetch_files(
+            &app,
+            &container_info,
+            std::ptr::null(),
+            0,
+            ud,
+            cb,
+        )))
+    };
+    assert!(fetched.is_empty());
+}
+
 // Test NFS functions for writing and updating file contents.
 // 1. Create an empty file, open it for writing, write contents.
 // 2. Insert file into a container.
@@ -1172,3 +1242,237 @@ fn write_chunks(
         unwrap!(call_1(|ud, cb| file_close(app, write_h, ud, cb)))
     }
 }
+
+// Test that the progress of writing and reading a file is reported in bytes, that chunks read
+// more than once count only once, and that returning `false` from the progress callback
+// cancels the remaining transfers.
+#[test]
+fn file_progress() {
+    const SIZE: u64 = 3 * 1024 * 1024;
+
+    static WRITE_DONE: AtomicUsize = ATOMIC_USIZE_INIT;
+    static READ_DONE: AtomicUsize = ATOMIC_USIZE_INIT;
+    static CANCEL_REPORTS: AtomicUsize = ATOMIC_USIZE_INIT;
+
+    // The size of the file is known up front.
+    extern "C" fn write_progress(_user_data: *mut c_void, done: u64, total: u64) -> bool {
+        assert_eq!(total, SIZE);
+        assert!(done <= total);
+        WRITE_DONE.store(done as usize, Ordering::SeqCst);
+        true
+    }
+
+    extern "C" fn read_progress(_user_data: *mut c_void, done: u64, total: u64) -> bool {
+        assert_eq!(total, SIZE);
+        assert!(done <= total);
+        READ_DONE.store(done as usize, Ordering::SeqCst);
+        true
+    }
+
+    extern "C" fn cancel_progress(_user_data: *mut c_void, _done: u64, _total: u64) -> bool {
+        let _ = CANCEL_REPORTS.fetch_add(1, Ordering::SeqCst);
+        false
+    }
+
+    let (app, container_info) = setup();
+
+    let file = NativeFile::new(Vec::new());
+    let content = unwrap!(utils::generate_random_vector::<u8>(SIZE as usize));
+
+    let written_file: NativeFile = unsafe {
+        let write_h = unwrap!(call_1(|ud, cb| file_open(
+            &app,
+            &container_info,
+            &file.into_repr_c(),
+            OPEN_MODE_OVERWRITE,
+            ud,
+            cb,
+        )));
+        unwrap!(call_0(|ud, cb| file_set_write_progress_callback(
+            &app,
+            write_h,
+            content.len() as u64,
+            std::ptr::null_mut(),
+            write_progress,
+            ud,
+            cb
+        )));
+        unwrap!(call_0(|ud, cb| file_write(
+            &app,
+            write_h,
+            content.as_ptr(),
+            content.len(),
+            ud,
+            cb
+        )));
+        unwrap!(call_1(|ud, cb| file_close(&app, write_h, ud, cb)))
+    };
+    assert_eq!(WRITE_DONE.load(Ordering::SeqCst), SIZE as usize);
+
+    unsafe {
+        let read_h = unwrap!(call_1(|ud, cb| file_open(
+            &app,
+            &container_info,
+            &written_file.clone().into_repr_c(),
+            OPEN_MODE_READ,
+            ud,
+            cb,
+        )));
+        unwrap!(call_0(|ud, cb| file_set_read_progress_callback(
+            &app,
+            read_h,
+            std::ptr::null_mut(),
+            read_progress,
+            ud,
+            cb
+        )));
+
+        for _ in 0..2 {
+            let read = unwrap!(call_vec_u8(|ud, cb| file_read(
+                &app,
+                read_h,
+                0,
+                FILE_READ_TO_END,
+                ud,
+                cb
+            )));
+            assert_eq!(read, content);
+        }
+        assert_eq!(READ_DONE.load(Ordering::SeqCst), SIZE as usize);
+
+        let _: NativeFile = unwrap!(call_1(|ud, cb| file_close(&app, read_h, ud, cb)));
+    }
+
+    unsafe {
+        let read_h = unwrap!(call_1(|ud, cb| file_open(
+            &app,
+            &container_info,
+            &written_file.into_repr_c(),
+            OPEN_MODE_READ,
+            ud,
+            cb,
+        )));
+        unwrap!(call_0(|ud, cb| file_set_read_progress_callback(
+            &app,
+            read_h,
+            std::ptr::null_mut(),
+            cancel_progress,
+            ud,
+            cb
+        )));
+
+        // The first chunk fetched cancels fetching the rest of them.
+        let _ = call_vec_u8(|ud, cb| file_read(&app, read_h, 0, 1, ud, cb));
+        assert!(CANCEL_REPORTS.load(Ordering::SeqCst) > 0);
+
+        let res = call_vec_u8(|ud, cb| file_read(&app, read_h, 0, FILE_READ_TO_END, ud, cb));
+        assert!(res.is_err());
+    }
+}
+
+// Test that the progress of writing and reading a small file, kept along with the location of
+// its content instead of in chunks, is reported in bytes too.
+#[test]
+fn small_file_progress() {
+    const SIZE: u64 = 1024;
+
+    static WRITE_DONE: AtomicUsize = ATOMIC_USIZE_INIT;
+    static READ_DONE: AtomicUsize = ATOMIC_USIZE_INIT;
+
+    extern "C" fn write_progress(_user_data: *mut c_void, done: u64, total: u64) -> bool {
+        assert_eq!(total, SIZE);
+        WRITE_DONE.store(done as usize, Ordering::SeqCst);
+        true
+    }
+
+    extern "C" fn read_progress(_user_data: *mut c_void, done: u64, total: u64) -> bool {
+        assert_eq!(total, SIZE);
+        READ_DONE.store(done as usize, Ordering::SeqCst);
+        true
+    }
+
+    let (app, container_info) = setup();
+
+    let file = NativeFile::new(Vec::new());
+    let content = unwrap!(utils::generate_random_vector::<u8>(SIZE as usize));
+
+    let written_file: NativeFile = unsafe {
+        let write_h = unwrap!(call_1(|ud, cb| file_open(
+            &app,
+            &container_info,
+            &file.into_repr_c(),
+            OPEN_MODE_OVERWRITE,
+            ud,
+            cb,
+        )));
+        unwrap!(call_0(|ud, cb| file_set_write_progress_callback(
+            &app,
+            write_h,
+            content.len() as u64,
+            std::ptr::null_mut(),
+            write_progress,
+            ud,
+            cb
+        )));
+        unwrap!(call_0(|ud, cb| file_write(
+            &app,
+            write_h,
+            content.as_ptr(),
+            content.len(),
+            ud,
+            cb
+        )));
+        unwrap!(call_1(|ud, cb| file_close(&app, write_h, ud, cb)))
+    };
+    assert_eq!(WRITE_DONE.load(Ordering::SeqCst), SIZE as usize);
+
+    unsafe {
+        let read_h = unwrap!(call_1(|ud, cb| file_open(
+            &app,
+            &container_info,
+            &written_file.into_repr_c(),
+            OPEN_MODE_READ,
+            ud,
+            cb,
+        )));
+
+        // The content has been fetched along with its location when opening the file.
+        unwrap!(call_0(|ud, cb| file_set_read_progress_callback(
+            &app,
+            read_h,
+            std::ptr::null_mut(),
+            read_progress,
+            ud,
+            cb
+        )));
+        assert_eq!(READ_DONE.load(Ordering::SeqCst), SIZE as usize);
+
+        let read = unwrap!(call_vec_u8(|ud, cb| file_read(
+            &app,
+            read_h,
+            0,
+            FILE_READ_TO_END,
+            ud,
+            cb
+        )));
+        assert_eq!(read, content);
+
+        let _: NativeFile = unwrap!(call_1(|ud, cb| file_close(&app, read_h, ud, cb)));
+    }
+}
+
+struct Fetched(Option<(NativeFile, u64)>);
+
+impl ReprC for Fetched {
+    type C = *const FetchedFile;
+    type Error = NfsError;
+
+    unsafe fn clone_from_repr_c(c_repr: Self::C) -> Result<Self, Self::Error> {
+        if (*c_repr).file.is_null() {
+            Ok(Fetched(None))
+        } else {
+            let file = NativeFile::clone_from_repr_c((*c_repr).file)?;
+            Ok(Fetched(Some((file, (*c_repr).version))))
+        }
+    }
+}
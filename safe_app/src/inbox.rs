@@ -0,0 +1,396 @@
+// Copyright 2018 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under the MIT license <LICENSE-MIT
+// http://opensource.org/licenses/MIT> or the Modified BSD license <LICENSE-BSD
+// https://opensource.org/licenses/BSD-3-Clause>, at your option. This file may not be copied,
+// modified, or distributed except according to those terms. Please review the Licences for the
+// specific language governing permissions and limitations relating to use of the SAFE Network
+// Software.
+
+//! Inboxes anyone can send messages to, but only their owner can read.
+//!
+//! An inbox is an unencrypted root `MutableData` publishing the public encryption key
+//! of the app which created it, along with the names of the shards messages are
+//! stored in. Shards are unencrypted `MutableData` anyone is allowed to insert entries
+//! into. Every message is sealed to the published key and stored under the hash of
+//! its cipher text in the last shard. Entries which can't be opened are skipped when
+//! listing, as anyone could have inserted them.
+//!
+//! Deleted messages are left behind as empty entries, which still count towards the
+//! entry limit of their shard. So once the last shard holds `SHARD_ROTATION_ENTRIES`
+//! entries, the owner moves new messages on to a new shard when deleting a message.
+//! Shards all messages of which have been deleted are dropped from the inbox.
+
+use client::AppClient;
+use errors::AppError;
+use futures::future::{self, Either};
+use futures::Future;
+use maidsafe_utilities::serialisation::{deserialise, serialise};
+use routing::{
+    Action, ClientError, EntryActions, MutableData, PermissionSet, User, Value, XorName,
+    MAX_MUTABLE_DATA_ENTRIES,
+};
+use rust_sodium::crypto::{box_, sealedbox, sign};
+use safe_core::ipc::resp::{MDataEntry, MDataKey, MDataValue};
+use safe_core::{Client, CoreError, FutureExt, MDataInfo};
+use tiny_keccak::sha3_256;
+use AppFuture;
+
+/// `MutableData` type tag for an inbox and its shards.
+pub const INBOX_TAG: u64 = 15_002;
+/// Entry holding the public encryption key messages are sealed to.
+pub const ENC_PK_KEY: &[u8] = b"_encPk";
+/// Entry holding the names of the shards of the inbox. New messages are sent to the
+/// last one.
+pub const SHARDS_KEY: &[u8] = b"_shards";
+/// Number of entries, deleted ones included, after which new messages are moved on
+/// to a new shard. Shards keep accepting messages up to the entry limit of
+/// `MutableData`, until the owner gets to move them on.
+pub const SHARD_ROTATION_ENTRIES: usize = MAX_MUTABLE_DATA_ENTRIES as usize / 2;
+
+/// Creates a new inbox for this app and returns its name. Anyone is allowed to
+/// send messages to it, while only this app is allowed to delete them.
+pub fn create(client: &AppClient) -> Box<AppFuture<XorName>> {
+    let owner = fry!(client.owner_key().ok_or(AppError::UnregisteredClientAccess));
+    let app_key = fry!(
+        client
+            .public_signing_key()
+            .ok_or(AppError::UnregisteredClientAccess)
+    );
+    let enc_pk = fry!(
+        client
+            .public_encryption_key()
+            .ok_or(AppError::UnregisteredClientAccess)
+    );
+
+    let shard = fry!(new_shard(owner, app_key));
+    let name = fry!(MDataInfo::random_public(INBOX_TAG)).name;
+    let perms = btree_map![
+        User::Key(app_key) => PermissionSet::new().allow(Action::Update)
+    ];
+    let entries = btree_map![
+        ENC_PK_KEY.to_vec() => Value {
+            content: fry!(serialise(&enc_pk)),
+            entry_version: 0,
+        },
+        SHARDS_KEY.to_vec() => Value {
+            content: fry!(serialise(&vec![*shard.name()])),
+            entry_version: 0,
+        }
+    ];
+    let data = fry!(
+        MutableData::new(name, INBOX_TAG, perms, entries, btree_set![owner])
+            .map_err(CoreError::from)
+    );
+
+    let c2 = client.clone();
+
+    client
+        .put_mdata(shard)
+        .and_then(move |()| c2.put_mdata(data))
+        .map(move |()| name)
+        .map_err(AppError::from)
+        .into_box()
+}
+
+/// Seals the message to the key published in the inbox and inserts it. Returns
+/// the key the message is stored under. Fails with `ClientError::TooManyEntries`
+/// if the owner hasn't moved new messages on to a new shard in time.
+pub fn send(client: &AppClient, inbox: XorName, content: &[u8]) -> Box<AppFuture<Vec<u8>>> {
+    let content = content.to_vec();
+    let c2 = client.clone();
+
+    get_root(client, inbox)
+        .and_then(move |(enc_pk, shards, _)| {
+            let shard = *fry!(shards.last().ok_or(CoreError::ReceivedUnexpectedData));
+            let cipher_text = sealedbox::seal(&content, &enc_pk);
+            let key = sha3_256(&cipher_text).to_vec();
+            let actions = EntryActions::new().ins(key.clone(), cipher_text, 0).into();
+
+            c2.mutate_mdata_entries(shard, INBOX_TAG, actions)
+                .map(move |()| key)
+                .map_err(AppError::from)
+                .into_box()
+        }).into_box()
+}
+
+/// Lists the messages in the inbox, opened with the encryption key of this app.
+pub fn list(client: &AppClient, inbox: XorName) -> Box<AppFuture<Vec<MDataEntry>>> {
+    let (enc_pk, enc_sk) = fry!(
+        client
+            .encryption_keypair()
+            .ok_or(AppError::UnregisteredClientAccess)
+    );
+    let c2 = client.clone();
+
+    get_root(client, inbox)
+        .and_then(move |(_, shards, _)| {
+            let listings = shards
+                .into_iter()
+                .map(move |shard| c2.list_mdata_entries(shard, INBOX_TAG));
+            future::join_all(listings).map_err(AppError::from)
+        }).map(move |listings| {
+            listings
+                .into_iter()
+                .flat_map(|entries| entries.into_iter())
+                .filter_map(|(key, value)| {
+                    // Deleted messages are left behind as empty entries, which don't
+                    // open, just like anything not sealed to our key.
+                    let content = sealedbox::open(&value.content, &enc_pk, &enc_sk).ok()?;
+                    Some(MDataEntry {
+                        key: MDataKey::from_routing(key),
+                        value: MDataValue {
+                            content,
+                            entry_version: value.entry_version,
+                        },
+                    })
+                }).collect()
+        }).into_box()
+}
+
+/// Deletes a message from the inbox, then moves new messages on to a new shard
+/// if the last one is full enough.
+pub fn delete(client: &AppClient, inbox: XorName, key: Vec<u8>) -> Box<AppFuture<()>> {
+    let c2 = client.clone();
+    let c3 = client.clone();
+    let c4 = client.clone();
+
+    get_root(client, inbox)
+        .and_then(move |(_, shards, version)| {
+            let lookups: Vec<_> = shards
+                .iter()
+                .map(|&shard| {
+                    let key = key.clone();
+                    c2.get_mdata_value(shard, INBOX_TAG, key.clone())
+                        .then(move |res| match res {
+                            Ok(ref value) if !value.content.is_empty() => {
+                                Ok(Some((shard, key, value.entry_version)))
+                            }
+                            Ok(_)
+                            | Err(CoreError::RoutingClientError(ClientError::NoSuchEntry)) => {
+                                Ok(None)
+                            }
+                            Err(error) => Err(error),
+                        })
+                }).collect();
+
+            future::join_all(lookups)
+                .map_err(AppError::from)
+                .and_then(move |found| {
+                    let (shard, key, entry_version) = found
+                        .into_iter()
+                        .flatten()
+                        .next()
+                        .ok_or(CoreError::RoutingClientError(ClientError::NoSuchEntry))?;
+                    Ok((shard, key, entry_version, shards, version))
+                })
+        }).and_then(move |(shard, key, entry_version, shards, version)| {
+            let actions = EntryActions::new().del(key, entry_version + 1).into();
+            c3.mutate_mdata_entries(shard, INBOX_TAG, actions)
+                .map(move |()| (shards, version))
+                .map_err(AppError::from)
+        }).and_then(move |(shards, version)| rotate(&c4, inbox, shards, version))
+        .into_box()
+}
+
+// Fetches the key messages are sealed to and the names of the shards of the inbox,
+// along with the version of the entry holding them.
+fn get_root(
+    client: &AppClient,
+    inbox: XorName,
+) -> Box<AppFuture<(box_::PublicKey, Vec<XorName>, u64)>> {
+    client
+        .list_mdata_entries(inbox, INBOX_TAG)
+        .map_err(AppError::from)
+        .and_then(|mut entries| {
+            let enc_pk = entries
+                .remove(ENC_PK_KEY)
+                .ok_or(CoreError::ReceivedUnexpectedData)?;
+            let shards = entries
+                .remove(SHARDS_KEY)
+                .ok_or(CoreError::ReceivedUnexpectedData)?;
+
+            Ok((
+                deserialise(&enc_pk.content)?,
+                deserialise(&shards.content)?,
+                shards.entry_version,
+            ))
+        }).into_box()
+}
+
+// Drops the shards all messages of which have been deleted, except the last one,
+// and moves new messages on to a new shard if the last one holds
+// `SHARD_ROTATION_ENTRIES` entries or more.
+fn rotate(
+    client: &AppClient,
+    inbox: XorName,
+    shards: Vec<XorName>,
+    version: u64,
+) -> Box<AppFuture<()>> {
+    let owner = fry!(client.owner_key().ok_or(AppError::UnregisteredClientAccess));
+    let app_key = fry!(
+        client
+            .public_signing_key()
+            .ok_or(AppError::UnregisteredClientAccess)
+    );
+    let current = *fry!(shards.last().ok_or(CoreError::ReceivedUnexpectedData));
+
+    let c2 = client.clone();
+    let c3 = client.clone();
+    let listings: Vec<_> = shards
+        .iter()
+        .map(|&shard| {
+            client
+                .list_mdata_entries(shard, INBOX_TAG)
+                .map(move |entries| (shard, entries))
+        }).collect();
+
+    future::join_all(listings)
+        .map_err(AppError::from)
+        .and_then(move |listings| {
+            let full = listings.iter().any(|&(shard, ref entries)| {
+                shard == current && entries.len() >= SHARD_ROTATION_ENTRIES
+            });
+            let mut kept: Vec<_> = listings
+                .into_iter()
+                .filter(|&(shard, ref entries)| {
+                    shard == current || entries.values().any(|value| !value.content.is_empty())
+                }).map(|(shard, _)| shard)
+                .collect();
+
+            let put_shard = if full {
+                let data = fry!(new_shard(owner, app_key));
+                kept.push(*data.name());
+                Either::A(c2.put_mdata(data))
+            } else {
+                Either::B(future::ok(()))
+            };
+
+            if kept == shards {
+                return ok!(());
+            }
+
+            let content = fry!(serialise(&kept));
+            let actions = EntryActions::new()
+                .update(SHARDS_KEY.to_vec(), content, version + 1)
+                .into();
+
+            put_shard
+                .and_then(move |()| c3.mutate_mdata_entries(inbox, INBOX_TAG, actions))
+                .map_err(AppError::from)
+                .into_box()
+        }).into_box()
+}
+
+fn new_shard(owner: sign::PublicKey, app_key: sign::PublicKey) -> Result<MutableData, AppError> {
+    let name = MDataInfo::random_public(INBOX_TAG)?.name;
+    let perms = btree_map![
+        User::Anyone => PermissionSet::new().allow(Action::Insert),
+        User::Key(app_key) => PermissionSet::new().allow(Action::Delete)
+    ];
+
+    Ok(
+        MutableData::new(name, INBOX_TAG, perms, btree_map![], btree_set![owner])
+            .map_err(CoreError::from)?,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_utils::{create_app, run};
+
+    // Test sending messages to an inbox owned by another app, then listing and
+    // deleting them.
+    #[test]
+    fn send_list_and_delete() {
+        let owner = create_app();
+        let sender = create_app();
+
+        let inbox = run(&owner, |client, _| create(client));
+
+        let key0 = run(&sender, move |client, _| send(client, inbox, b"hello"));
+        let key1 = run(&sender, move |client, _| send(client, inbox, b"world"));
+
+        // Only the owner can read the messages.
+        let messages = run(&sender, move |client, _| list(client, inbox));
+        assert!(messages.is_empty());
+
+        let messages = run(&owner, move |client, _| list(client, inbox));
+        assert_eq!(messages.len(), 2);
+        let message0 = unwrap!(messages.iter().find(|entry| entry.key.0 == key0));
+        assert_eq!(message0.value.content, b"hello".to_vec());
+        let message1 = unwrap!(messages.iter().find(|entry| entry.key.0 == key1));
+        assert_eq!(message1.value.content, b"world".to_vec());
+
+        // Only the owner can delete them.
+        let key = key0.clone();
+        run(&sender, move |client, _| {
+            delete(client, inbox, key).then(|res| {
+                match res {
+                    Err(AppError::CoreError(CoreError::RoutingClientError(
+                        ClientError::AccessDenied,
+                    ))) => (),
+                    x => panic!("Unexpected {:?}", x),
+                }
+                Ok(())
+            })
+        });
+
+        run(&owner, move |client, _| delete(client, inbox, key0));
+        let messages = run(&owner, move |client, _| list(client, inbox));
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0].key.0, key1);
+    }
+
+    // Test that new messages are moved on to a new shard once the current one is
+    // full enough, and that drained shards are dropped from the inbox.
+    #[test]
+    fn rotate_shards() {
+        let owner = create_app();
+        let sender = create_app();
+
+        let inbox = run(&owner, |client, _| create(client));
+
+        let mut keys = Vec::new();
+        for i in 0..SHARD_ROTATION_ENTRIES {
+            let content = format!("message {}", i).into_bytes();
+            keys.push(run(&sender, move |client, _| send(client, inbox, &content)));
+        }
+
+        let (_, shards, _) = run(&owner, move |client, _| get_root(client, inbox));
+        assert_eq!(shards.len(), 1);
+
+        // Deleting a message moves new messages on to a new shard.
+        let key = keys.remove(0);
+        run(&owner, move |client, _| delete(client, inbox, key));
+
+        let (_, shards, _) = run(&owner, move |client, _| get_root(client, inbox));
+        assert_eq!(shards.len(), 2);
+
+        let key = run(&sender, move |client, _| send(client, inbox, b"next"));
+        let shard = shards[1];
+        let value = run(&owner, move |client, _| {
+            client
+                .get_mdata_value(shard, INBOX_TAG, key)
+                .map_err(AppError::from)
+        });
+        assert!(!value.content.is_empty());
+
+        // Messages are listed from all the shards.
+        let messages = run(&owner, move |client, _| list(client, inbox));
+        assert_eq!(messages.len(), SHARD_ROTATION_ENTRIES);
+
+        // Once all its messages are deleted, the first shard is dropped.
+        for key in keys {
+            run(&owner, move |client, _| delete(client, inbox, key));
+        }
+
+        let (_, new_shards, _) = run(&owner, move |client, _| get_root(client, inbox));
+        assert_eq!(new_shards, vec![shards[1]]);
+
+        let messages = run(&owner, move |client, _| list(client, inbox));
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0].value.content, b"next".to_vec());
+    }
+}
@@ -77,6 +77,8 @@ extern crate config_file_handler;
 extern crate ffi_utils;
 extern crate futures;
 #[macro_use]
+extern crate lazy_static;
+#[macro_use]
 extern crate log;
 extern crate lru_cache;
 extern crate maidsafe_utilities;
@@ -115,6 +117,7 @@ pub use ffi::access_container::*;
 pub use ffi::cipher_opt::*;
 pub use ffi::crypto::*;
 pub use ffi::immutable_data::*;
+pub use ffi::inbox::*;
 pub use ffi::ipc::*;
 pub use ffi::logging::*;
 pub use ffi::mdata_info::*;
@@ -122,9 +125,11 @@ pub use ffi::mutable_data::entries::*;
 pub use ffi::mutable_data::entry_actions::*;
 pub use ffi::mutable_data::metadata::*;
 pub use ffi::mutable_data::permissions::*;
+pub use ffi::mutable_data::share_offer::*;
 pub use ffi::mutable_data::*;
 pub use ffi::nfs::*;
 pub use ffi::object_cache::*;
+pub use ffi::public_id::*;
 #[cfg(any(test, feature = "testing"))]
 pub use ffi::test_utils::*;
 pub use ffi::*;
@@ -132,6 +137,7 @@ pub use ffi::*;
 pub mod cipher_opt;
 mod client;
 mod errors;
+pub mod inbox;
 pub mod object_cache;
 pub mod permissions;
 
@@ -158,12 +164,14 @@ use safe_core::ipc::resp::{access_container_enc_key, AccessContainerEntry};
 use safe_core::ipc::{AccessContInfo, AppKeys, AuthGranted, BootstrapConfig};
 #[cfg(feature = "use-mock-routing")]
 use safe_core::MockRouting as Routing;
-use safe_core::{event_loop, CoreMsg, CoreMsgTx, NetworkEvent, NetworkTx};
+use safe_core::{
+    event_loop, CancellationToken, CoreMsg, CoreMsgTx, NetworkEvent, NetworkTx,
+};
 use std::cell::RefCell;
 use std::collections::HashMap;
 use std::rc::Rc;
 use std::sync::mpsc as std_mpsc;
-use std::sync::Mutex;
+use std::sync::{Arc, Mutex};
 use tokio_core::reactor::{Core, Handle};
 
 macro_rules! try_tx {
@@ -177,26 +185,64 @@ macro_rules! try_tx {
 
 type AppFuture<T> = Future<Item = T, Error = AppError>;
 type AppMsgTx = CoreMsgTx<AppClient, AppContext>;
+type NetworkObserver = Box<FnMut(NetworkEvent) + Send>;
 
 /// Handle to an application instance.
 pub struct App {
     core_tx: Mutex<AppMsgTx>,
-    _core_joiner: Joiner,
+    network_observer: Arc<Mutex<Option<NetworkObserver>>>,
+    // Token of the operation this handle belongs to, if it's an operation handle.
+    operation: Option<CancellationToken>,
+    // Only the handle which owns the event loop has its joiner.
+    core_joiner: Option<Joiner>,
 }
 
 impl App {
-    /// Send a message to app's event loop.
+    /// Send a message to app's event loop. If this is an operation handle, the message belongs
+    /// to the operation and is cancelled with it.
     pub fn send<F>(&self, f: F) -> Result<(), AppError>
     where
         F: FnOnce(&AppClient, &AppContext) -> Option<Box<Future<Item = (), Error = ()>>>
             + Send
             + 'static,
     {
-        let msg = CoreMsg::new(f);
+        let msg = match self.operation {
+            Some(ref token) => CoreMsg::cancellable(token.clone(), f),
+            None => CoreMsg::new(f),
+        };
         let core_tx = unwrap!(self.core_tx.lock());
         core_tx.unbounded_send(msg).map_err(AppError::from)
     }
 
+    /// Create a handle to a new cancellable operation of this app. The messages sent through
+    /// the returned handle belong to the operation: once it's cancelled, their pending network
+    /// requests are abandoned and they fail with `CoreError::OperationCancelled`.
+    /// The operation handle doesn't keep the app alive.
+    pub fn operation(&self) -> Self {
+        let core_tx = unwrap!(self.core_tx.lock());
+        App {
+            core_tx: Mutex::new(core_tx.clone()),
+            network_observer: Arc::clone(&self.network_observer),
+            operation: Some(CancellationToken::new()),
+            core_joiner: None,
+        }
+    }
+
+    /// Returns the token of the operation, if this is an operation handle.
+    pub fn operation_token(&self) -> Option<&CancellationToken> {
+        self.operation.as_ref()
+    }
+
+    /// Set the observer called with every `NetworkEvent` of the connection, replacing the
+    /// previous one. Unlike the disconnect notifier, it's also told when the app is connecting,
+    /// connected, reconnecting or gave up connecting.
+    pub fn set_network_observer<O>(&self, observer: O)
+    where
+        O: FnMut(NetworkEvent) + Send + 'static,
+    {
+        *unwrap!(self.network_observer.lock()) = Some(Box::new(observer));
+    }
+
     /// Create unregistered app.
     pub fn unregistered<N>(
         disconnect_notifier: N,
@@ -327,6 +373,8 @@ impl App {
             + 'static,
     {
         let (tx, rx) = std_mpsc::sync_channel(0);
+        let network_observer = Arc::new(Mutex::new(None::<NetworkObserver>));
+        let network_observer2 = Arc::clone(&network_observer);
 
         let joiner = thread::named("App Event Loop", move || {
             let el = try_tx!(Core::new(), tx);
@@ -341,6 +389,9 @@ impl App {
                         if let NetworkEvent::Disconnected = event {
                             disconnect_notifier()
                         }
+                        if let Some(ref mut observer) = *unwrap!(network_observer2.lock()) {
+                            observer(event)
+                        }
                     }).for_each(|_| Ok(())),
             );
 
@@ -356,13 +407,20 @@ impl App {
 
         Ok(App {
             core_tx: Mutex::new(core_tx),
-            _core_joiner: joiner,
+            network_observer,
+            operation: None,
+            core_joiner: Some(joiner),
         })
     }
 }
 
 impl Drop for App {
     fn drop(&mut self) {
+        // Operation handles leave the event loop running.
+        if self.core_joiner.is_none() {
+            return;
+        }
+
         let core_tx = match self.core_tx.lock() {
             Ok(core_tx) => core_tx,
             Err(err) => {
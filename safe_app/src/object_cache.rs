@@ -12,14 +12,12 @@
 
 use super::errors::AppError;
 use cipher_opt::CipherOpt;
-use client::AppClient;
+use ffi::immutable_data::{SelfEncryptorReader, SelfEncryptorWriter};
 use ffi::nfs::FileContext;
 use ffi::object_cache::*;
 use routing::{EntryAction, PermissionSet, User, Value};
 use rust_sodium::crypto::{box_, sign};
 use safe_core::crypto::{shared_box, shared_sign};
-use safe_core::SelfEncryptionStorage;
-use self_encryption::{SelfEncryptor, SequentialEncryptor};
 use std::cell::{Cell, RefCell, RefMut};
 use std::collections::{BTreeMap, HashMap};
 
@@ -32,8 +30,8 @@ pub struct ObjectCache {
     mdata_entries: Store<BTreeMap<Vec<u8>, Value>>,
     mdata_entry_actions: Store<BTreeMap<Vec<u8>, EntryAction>>,
     mdata_permissions: Store<BTreeMap<User, PermissionSet>>,
-    se_reader: Store<SelfEncryptor<SelfEncryptionStorage<AppClient>>>,
-    se_writer: Store<SequentialEncryptor<SelfEncryptionStorage<AppClient>>>,
+    se_reader: Store<SelfEncryptorReader>,
+    se_writer: Store<SelfEncryptorWriter>,
     pub_sign_key: Store<sign::PublicKey>,
     sec_sign_key: Store<shared_sign::SecretKey>,
     file: Store<FileContext>,
@@ -152,7 +150,7 @@ impl_cache!(mdata_permissions,
             remove_mdata_permissions);
 impl_cache!(
     se_reader,
-    SelfEncryptor<SelfEncryptionStorage<AppClient>>,
+    SelfEncryptorReader,
     SelfEncryptorReaderHandle,
     InvalidSelfEncryptorHandle,
     get_se_reader,
@@ -161,7 +159,7 @@ impl_cache!(
 );
 impl_cache!(
     se_writer,
-    SequentialEncryptor<SelfEncryptionStorage<AppClient>>,
+    SelfEncryptorWriter,
     SelfEncryptorWriterHandle,
     InvalidSelfEncryptorHandle,
     get_se_writer,
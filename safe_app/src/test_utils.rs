@@ -105,6 +105,8 @@ pub fn create_auth_req(
         app: app_info,
         app_container,
         containers,
+        manifest: None,
+        resp_key: None,
     }
 }
 
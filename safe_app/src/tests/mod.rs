@@ -160,6 +160,8 @@ pub fn login_registered_with_low_balance() {
             app: app_info,
             app_container: false,
             containers: HashMap::new(),
+            manifest: None,
+            resp_key: None,
         },
     ));
 
@@ -184,6 +186,8 @@ fn authorise_app(
             app: app_info.clone(),
             app_container,
             containers: HashMap::new(),
+            manifest: None,
+            resp_key: None,
         },
     ));
 
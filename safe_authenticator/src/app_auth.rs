@@ -13,10 +13,12 @@ use access_container;
 use app_container;
 use client::AuthClient;
 use config::{self, AppInfo, Apps};
+use devices;
 use futures::future::{self, Either};
 use futures::Future;
 use ipc::update_container_perms;
 use routing::ClientError;
+use rust_sodium::crypto::sign;
 use safe_core::client;
 use safe_core::ipc::req::{AuthReq, ContainerPermissions, Permission};
 use safe_core::ipc::resp::{AccessContInfo, AccessContainerEntry, AppKeys, AuthGranted};
@@ -137,9 +139,21 @@ pub fn authenticate(client: &AuthClient, auth_req: AuthReq) -> Box<AuthFuture<Au
     let permissions = auth_req.containers.clone();
     let app_container = auth_req.app_container;
 
+    // The developer key is only remembered once the manifest signature is checked, as the
+    // request may not have been decoded by the authenticator.
+    let developer_key = match auth_req.manifest {
+        Some(ref manifest) => {
+            fry!(manifest.verify(&app_id));
+            Some(manifest.developer_key)
+        }
+        None => None,
+    };
+
     let c2 = client.clone();
     let c3 = client.clone();
     let c4 = client.clone();
+    let c5 = client.clone();
+    let c6 = client.clone();
 
     config::list_apps(client)
         .join(check_revocation(client, app_id.clone()))
@@ -177,16 +191,52 @@ pub fn authenticate(client: &AuthClient, auth_req: AuthReq) -> Box<AuthFuture<Au
                 }
             }
         }).and_then(move |(app, app_state, app_id)| {
-            match app_state {
+            let auth_granted = match app_state {
                 AppState::Authenticated => {
                     // Return info of the already registered app
-                    authenticated_app(&c4, app, app_id, app_container)
+                    authenticated_app(&c4, app, app_id.clone(), app_container)
                 }
                 AppState::NotAuthenticated | AppState::Revoked => {
                     // Register a new app or restore a previously registered app
                     authenticate_new_app(&c4, app, app_container, permissions)
                 }
+            };
+            auth_granted.map(move |auth_granted| (auth_granted, app_id))
+        }).and_then(move |(auth_granted, app_id)| {
+            // Re-authenticated apps are attributed to the current device too.
+            devices::attach_app(&c5, app_id.clone()).map(move |()| (auth_granted, app_id))
+        }).and_then(move |(auth_granted, app_id)| {
+            remember_developer(&c6, app_id, developer_key).map(move |()| auth_granted)
+        }).into_box()
+}
+
+// Remembers the developer key of an app authorised with a signed manifest, so that
+// a later request for the same app ID from a different developer can be detected.
+fn remember_developer(
+    client: &AuthClient,
+    app_id: String,
+    developer_key: Option<sign::PublicKey>,
+) -> Box<AuthFuture<()>> {
+    let developer_key = match developer_key {
+        Some(developer_key) => developer_key,
+        None => return ok!(()),
+    };
+    let c2 = client.clone();
+
+    config::list_app_developers(client)
+        .and_then(move |(version, developers)| {
+            if developers.contains_key(&app_id) {
+                return ok!(());
             }
+
+            config::insert_app_developer(
+                &c2,
+                developers,
+                config::next_version(version),
+                app_id,
+                developer_key,
+            ).map(|_| ())
+            .into_box()
         }).into_box()
 }
 
@@ -23,8 +23,9 @@ use rust_sodium::crypto::sign::Seed;
 use rust_sodium::crypto::{box_, sign};
 use safe_core::client::account::Account;
 use safe_core::client::{
-    setup_routing, spawn_routing_thread, ClientInner, IMMUT_DATA_CACHE_SIZE, REQUEST_TIMEOUT_SECS,
+    setup_chunk_cache, setup_routing, spawn_routing_thread, ClientInner, IMMUT_DATA_CACHE_SIZE,
 };
+use safe_core::config_handler::RequestPolicy;
 use safe_core::crypto::{shared_box, shared_secretbox, shared_sign};
 #[cfg(any(test, feature = "testing"))]
 use safe_core::utils::seed::{divide_seed, SEED_SUBPARTS};
@@ -33,7 +34,6 @@ use std::cell::RefCell;
 use std::collections::{BTreeMap, HashMap};
 use std::fmt;
 use std::rc::Rc;
-use std::time::Duration;
 use tiny_keccak::sha3_256;
 use tokio_core::reactor::Handle;
 use AuthFuture;
@@ -43,6 +43,8 @@ use AuthMsgTx;
 pub struct AuthClient {
     inner: Rc<RefCell<ClientInner<AuthClient, ()>>>,
     auth_inner: Rc<RefCell<AuthInner>>,
+    // Policy of the requests made through this handle, overriding the ones of the client.
+    request_policy: Option<RequestPolicy>,
 }
 
 impl AuthClient {
@@ -193,15 +195,14 @@ where {
             })?;
 
         // Create the client
-        let joiner = spawn_routing_thread(routing_rx, core_tx.clone(), net_tx.clone());
+        let joiner = spawn_routing_thread(routing_rx, core_tx.clone());
 
-        Ok(AuthClient {
+        let client = AuthClient {
             inner: Rc::new(RefCell::new(ClientInner::new(
                 el_handle,
                 routing,
                 HashMap::with_capacity(10),
                 LruCache::new(IMMUT_DATA_CACHE_SIZE),
-                Duration::from_secs(REQUEST_TIMEOUT_SECS),
                 joiner,
                 core_tx,
                 net_tx,
@@ -212,8 +213,13 @@ where {
                 user_cred,
                 cm_addr,
                 session_packet_version: 0,
+                device_id: None,
             })),
-        })
+            request_policy: None,
+        };
+
+        setup_chunk_cache(&client);
+        Ok(client)
     }
 
     /// This is a Gateway function to the Maidsafe network. This will help login to an already
@@ -333,15 +339,14 @@ where {
         let (mut routing, routing_rx) = setup_routing(Some(id_packet), None)?;
         routing = routing_wrapper_fn(routing);
 
-        let joiner = spawn_routing_thread(routing_rx, core_tx.clone(), net_tx.clone());
+        let joiner = spawn_routing_thread(routing_rx, core_tx.clone());
 
-        Ok(AuthClient {
+        let client = AuthClient {
             inner: Rc::new(RefCell::new(ClientInner::new(
                 el_handle,
                 routing,
                 HashMap::with_capacity(10),
                 LruCache::new(IMMUT_DATA_CACHE_SIZE),
-                Duration::from_secs(REQUEST_TIMEOUT_SECS),
                 joiner,
                 core_tx,
                 net_tx,
@@ -352,8 +357,13 @@ where {
                 user_cred,
                 cm_addr,
                 session_packet_version: acc_version,
+                device_id: None,
             })),
-        })
+            request_policy: None,
+        };
+
+        setup_chunk_cache(&client);
+        Ok(client)
     }
 
     /// Get Maidsafe specific configuration's Root Directory ID if available in
@@ -464,6 +474,18 @@ where {
         let account = &mut auth_inner.acc;
         account.root_dirs_created = val;
     }
+
+    /// Returns the ID of the device this session has been registered as, if any.
+    pub fn device_id(&self) -> Option<String> {
+        let auth_inner = self.auth_inner.borrow();
+        auth_inner.device_id.clone()
+    }
+
+    /// Sets the ID of the device this session has been registered as.
+    pub fn set_device_id(&self, device_id: Option<String>) {
+        let mut auth_inner = self.auth_inner.borrow_mut();
+        auth_inner.device_id = device_id;
+    }
 }
 
 impl Client for AuthClient {
@@ -516,6 +538,17 @@ impl Client for AuthClient {
         let auth_inner = self.auth_inner.borrow();
         Some(auth_inner.acc.maid_keys.sign_pk)
     }
+
+    fn with_request_policy(&self, policy: RequestPolicy) -> Self {
+        AuthClient {
+            request_policy: Some(policy),
+            ..self.clone()
+        }
+    }
+
+    fn request_policy_override(&self) -> Option<RequestPolicy> {
+        self.request_policy
+    }
 }
 
 impl fmt::Debug for AuthClient {
@@ -529,6 +562,7 @@ impl Clone for AuthClient {
         AuthClient {
             inner: Rc::clone(&self.inner),
             auth_inner: Rc::clone(&self.auth_inner),
+            request_policy: self.request_policy,
         }
     }
 }
@@ -539,6 +573,7 @@ struct AuthInner {
     user_cred: UserCred,
     cm_addr: Authority<XorName>,
     session_packet_version: u64,
+    device_id: Option<String>,
 }
 
 // ------------------------------------------------------------
@@ -750,7 +785,8 @@ mod tests {
         );
     }
 
-    // Test restarting routing after a network disconnect.
+    // Test restarting routing after a network disconnect. The client doesn't reconnect by
+    // itself, as that isn't enabled by default.
     #[cfg(feature = "use-mock-routing")]
     #[test]
     fn restart_routing() {
@@ -762,10 +798,12 @@ mod tests {
 
         let (tx, rx) = mpsc::channel();
         let (hook, keep_alive) = futures::oneshot();
+        let (disconnected_tx, disconnected_rx) = futures::oneshot();
+        let mut disconnected_tx = Some(disconnected_tx);
 
         let _joiner = thread::named("Network Observer", move || {
             match unwrap!(rx.recv()) {
-                NetworkEvent::Disconnected => (),
+                NetworkEvent::Connecting => (),
                 x => panic!("Unexpected network event: {:?}", x),
             }
             match unwrap!(rx.recv()) {
@@ -776,11 +814,111 @@ mod tests {
         });
 
         random_client_with_net_obs(
-            move |net_event| unwrap!(tx.send(net_event)),
+            move |net_event| match net_event {
+                NetworkEvent::Disconnected => {
+                    let _ = unwrap!(disconnected_tx.take()).send(());
+                }
+                net_event => unwrap!(tx.send(net_event)),
+            },
             move |client| {
+                let client2 = client.clone();
+
                 client.simulate_network_disconnect();
-                unwrap!(client.restart_routing());
-                keep_alive
+
+                disconnected_rx
+                    .map_err(|_| CoreError::OperationAborted)
+                    .and_then(move |()| {
+                        unwrap!(client2.restart_routing());
+                        keep_alive.map_err(|_| CoreError::OperationAborted)
+                    })
+            },
+        );
+    }
+
+    // Test reconnecting automatically after a network disconnect once it's enabled, with the
+    // requests made in the meantime held until the client is connected again.
+    #[cfg(feature = "use-mock-routing")]
+    #[test]
+    fn auto_reconnect() {
+        use futures::sync::oneshot;
+        use safe_core::client::{Backoff, ConnectionState};
+        use safe_core::NetworkEvent;
+        use test_utils::random_client_with_net_obs;
+
+        let (reconnecting_tx, reconnecting_rx) = oneshot::channel();
+        let (connected_tx, connected_rx) = oneshot::channel();
+        let mut reconnecting_tx = Some(reconnecting_tx);
+        let mut connected_tx = Some(connected_tx);
+
+        random_client_with_net_obs(
+            move |net_event| match net_event {
+                NetworkEvent::Disconnected => (),
+                NetworkEvent::Reconnecting(1) => {
+                    let _ = unwrap!(reconnecting_tx.take()).send(());
+                }
+                NetworkEvent::Connected => {
+                    let _ = unwrap!(connected_tx.take()).send(());
+                }
+                x => panic!("Unexpected network event: {:?}", x),
+            },
+            move |client| {
+                let client2 = client.clone();
+                let client3 = client.clone();
+
+                client.set_reconnect_backoff(Some(Backoff::default()));
+                client.simulate_network_disconnect();
+
+                reconnecting_rx
+                    .map_err(|_| CoreError::OperationAborted)
+                    .and_then(move |()| {
+                        assert_eq!(client2.connection_state(), ConnectionState::Reconnecting(1));
+                        client2.get_account_info()
+                    }).and_then(move |_| {
+                        assert_eq!(client3.connection_state(), ConnectionState::Connected);
+                        connected_rx.map_err(|_| CoreError::OperationAborted)
+                    })
+            },
+        );
+    }
+
+    // Test that the client is disconnected after a network disconnect when reconnecting isn't
+    // enabled, with the requests made afterwards failing instead of being sent.
+    #[cfg(feature = "use-mock-routing")]
+    #[test]
+    fn disconnect_without_reconnect() {
+        use futures::sync::oneshot;
+        use routing::InterfaceError;
+        use safe_core::client::ConnectionState;
+        use safe_core::NetworkEvent;
+        use test_utils::random_client_with_net_obs;
+
+        let (disconnected_tx, disconnected_rx) = oneshot::channel();
+        let mut disconnected_tx = Some(disconnected_tx);
+
+        random_client_with_net_obs(
+            move |net_event| match net_event {
+                NetworkEvent::Disconnected => {
+                    let _ = unwrap!(disconnected_tx.take()).send(());
+                }
+                x => panic!("Unexpected network event: {:?}", x),
+            },
+            move |client| {
+                let client2 = client.clone();
+
+                client.set_reconnect_backoff(None);
+                client.simulate_network_disconnect();
+
+                disconnected_rx
+                    .map_err(|_| CoreError::OperationAborted)
+                    .and_then(move |()| {
+                        assert_eq!(client2.connection_state(), ConnectionState::Disconnected);
+                        client2.get_account_info().then(|result| match result {
+                            Err(CoreError::RoutingInterfaceError(InterfaceError::NotConnected)) => {
+                                Ok(())
+                            }
+                            result => panic!("Unexpected {:?}", result),
+                        })
+                    })
             },
         );
     }
@@ -821,4 +959,404 @@ mod tests {
                 })
         })
     }
+
+    // Test that timed out requests are retried as their policy says, but mutations which aren't
+    // idempotent aren't unless it allows it, and that a policy can be overridden by a client
+    // handle, including for the requests made through it later on.
+    #[cfg(feature = "use-mock-routing")]
+    #[test]
+    fn request_policy() {
+        use futures::future;
+        use rand;
+        use safe_core::config_handler::RequestPolicies;
+        use std::time::{Duration, Instant};
+        use test_utils::random_client;
+
+        let policy = RequestPolicy {
+            timeout: Duration::from_millis(200),
+            max_retries: 2,
+            retry_delay: Duration::from_millis(50),
+            retry_mutations: false,
+        };
+
+        random_client(move |client| {
+            let client2 = client.clone();
+            let client3 = client.clone();
+
+            client.set_simulate_timeout(true);
+            client.set_request_policies(RequestPolicies {
+                get_mdata_value: Some(policy),
+                mutation: Some(policy),
+                ..RequestPolicies::default()
+            });
+
+            // Sent three times, with delays of 50 and 100 ms between them.
+            let start = Instant::now();
+            client
+                .get_mdata_value(rand::random(), 10_000, vec![0])
+                .then(move |result| {
+                    match result {
+                        Err(CoreError::RequestTimeout) => (),
+                        result => panic!("Unexpected {:?}", result),
+                    }
+                    assert!(start.elapsed() >= Duration::from_millis(750));
+
+                    // Sent once.
+                    let start = Instant::now();
+                    client2
+                        .del_auth_key(sign::gen_keypair().0, 1)
+                        .then(move |result| Ok::<_, CoreError>((result, start)))
+                }).then(move |result| {
+                    let (result, start) = unwrap!(result);
+                    match result {
+                        Err(CoreError::RequestTimeout) => (),
+                        result => panic!("Unexpected {:?}", result),
+                    }
+                    assert!(start.elapsed() < Duration::from_millis(600));
+
+                    // Sent twice.
+                    let policy = RequestPolicy {
+                        max_retries: 1,
+                        retry_mutations: true,
+                        ..policy
+                    };
+                    let client3 = client3.with_request_policy(policy);
+                    let start = Instant::now();
+                    future::ok(())
+                        .and_then(move |()| client3.del_auth_key(sign::gen_keypair().0, 1))
+                        .then(move |result| Ok::<_, CoreError>((result, start)))
+                }).then(|result| {
+                    let (result, start) = unwrap!(result);
+                    match result {
+                        Err(CoreError::RequestTimeout) => (),
+                        result => panic!("Unexpected {:?}", result),
+                    }
+                    assert!(start.elapsed() >= Duration::from_millis(450));
+                    Ok::<_, CoreError>(())
+                })
+        })
+    }
+
+    // Test that identical reads in flight at the same time are sent only once, and all of them
+    // get the response, be it success or failure.
+    #[cfg(feature = "use-mock-routing")]
+    #[test]
+    fn coalesce_reads() {
+        use futures::future;
+        use rand;
+        use routing::{MutableData, Request};
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::sync::Arc;
+
+        let idata_name: XorName = rand::random();
+        let mdata_name: XorName = rand::random();
+        let reads = Arc::new(AtomicUsize::new(0));
+        let reads2 = Arc::clone(&reads);
+
+        let c = move |el_h, core_tx, net_tx| {
+            let acc_locator = unwrap!(utils::generate_random_string(10));
+            let acc_password = unwrap!(utils::generate_random_string(10));
+            let invitation = unwrap!(utils::generate_random_string(10));
+            let reads = Arc::clone(&reads2);
+
+            AuthClient::registered_with_hook(
+                &acc_locator,
+                &acc_password,
+                &invitation,
+                el_h,
+                core_tx,
+                net_tx,
+                move |mut routing| {
+                    let reads = Arc::clone(&reads);
+                    routing.set_request_hook(move |req| {
+                        match *req {
+                            Request::GetIData { name, .. } if name == idata_name => {
+                                let _ = reads.fetch_add(1, Ordering::SeqCst);
+                            }
+                            Request::GetMDataValue { name, .. } if name == mdata_name => {
+                                let _ = reads.fetch_add(1, Ordering::SeqCst);
+                            }
+                            _ => (),
+                        }
+                        None
+                    });
+                    routing
+                },
+            )
+        };
+
+        setup_client(&(), c, move |client| {
+            let client2 = client.clone();
+            let client3 = client.clone();
+            let owner = unwrap!(client.owner_key());
+            let value = Value {
+                content: vec![1],
+                entry_version: 0,
+            };
+            let data = unwrap!(MutableData::new(
+                mdata_name,
+                DIR_TAG,
+                BTreeMap::new(),
+                btree_map![vec![0] => value.clone()],
+                btree_set![owner],
+            ));
+
+            client
+                .put_mdata(data)
+                .and_then(move |()| {
+                    let gets: Vec<_> = (0..3)
+                        .map(|_| client2.get_mdata_value(mdata_name, DIR_TAG, vec![0]))
+                        .collect();
+                    future::join_all(gets)
+                }).and_then(move |values| {
+                    assert_eq!(values, vec![value.clone(), value.clone(), value]);
+                    assert_eq!(reads.load(Ordering::SeqCst), 1);
+
+                    let gets: Vec<_> = (0..3)
+                        .map(|_| client3.get_idata(idata_name).then(Ok::<_, CoreError>))
+                        .collect();
+                    future::join_all(gets).map(move |results| (results, reads))
+                }).map(|(results, reads)| {
+                    for result in results {
+                        match result {
+                            Err(CoreError::RoutingClientError(ClientError::NoSuchData)) => (),
+                            result => panic!("Unexpected {:?}", result),
+                        }
+                    }
+                    assert_eq!(reads.load(Ordering::SeqCst), 2);
+                })
+        })
+    }
+
+    // Test that the `MutableData` read cache returns what's been read recently without going to
+    // the network, and that it's invalidated by the client's own mutations.
+    #[cfg(feature = "use-mock-routing")]
+    #[test]
+    fn mdata_cache() {
+        use rand;
+        use routing::EntryActions;
+        use std::time::Duration;
+        use test_utils::random_client;
+
+        random_client(|client| {
+            let client2 = client.clone();
+            let client3 = client.clone();
+            let client4 = client.clone();
+            let client5 = client.clone();
+
+            let name = rand::random();
+            let owner = unwrap!(client.owner_key());
+            let value = Value {
+                content: vec![1],
+                entry_version: 0,
+            };
+            let data = unwrap!(MutableData::new(
+                name,
+                DIR_TAG,
+                BTreeMap::new(),
+                btree_map![vec![0] => value.clone()],
+                btree_set![owner],
+            ));
+
+            client.enable_mdata_cache(Duration::from_secs(60), 10);
+            client.set_timeout(Duration::from_millis(250));
+
+            client
+                .put_mdata(data)
+                .and_then(move |()| client2.list_mdata_entries(name, DIR_TAG))
+                .and_then(move |entries| {
+                    assert_eq!(entries, btree_map![vec![0] => value.clone()]);
+
+                    // Served from the cache while the network doesn't respond.
+                    client3.set_simulate_timeout(true);
+                    client3
+                        .get_mdata_value(name, DIR_TAG, vec![0])
+                        .map(move |cached| (cached, value))
+                }).and_then(move |(cached, value)| {
+                    assert_eq!(cached, value);
+
+                    client4.set_simulate_timeout(false);
+                    let actions = EntryActions::new().update(vec![0], vec![2], 1).into();
+                    client4.mutate_mdata_entries(name, DIR_TAG, actions)
+                }).and_then(move |()| {
+                    // The mutation has invalidated the cache.
+                    client5.set_simulate_timeout(true);
+                    client5
+                        .list_mdata_entries(name, DIR_TAG)
+                        .then(move |result| match result {
+                            Err(CoreError::RequestTimeout) => {
+                                client5.set_simulate_timeout(false);
+                                client5.list_mdata_entries(name, DIR_TAG)
+                            }
+                            result => panic!("Unexpected {:?}", result),
+                        })
+                }).map(|entries| {
+                    let value = Value {
+                        content: vec![2],
+                        entry_version: 1,
+                    };
+                    assert_eq!(entries, btree_map![vec![0] => value]);
+                })
+        })
+    }
+
+    // Test that writes of entries by another client are served stale from the `MutableData` read
+    // cache, and that it's invalidated when the version is seen to change.
+    #[cfg(feature = "use-mock-routing")]
+    #[test]
+    fn mdata_cache_invalidation_by_other_client() {
+        use rand;
+        use routing::{Action, EntryActions, PermissionSet, User};
+        use std::time::Duration;
+        use test_utils::{create_authenticator, run};
+        use Authenticator;
+
+        let (auth, locator, password) = create_authenticator();
+        let other_auth = unwrap!(Authenticator::login(locator, password, || ()));
+
+        let name = rand::random();
+        let old_value = Value {
+            content: vec![1],
+            entry_version: 0,
+        };
+        let new_value = Value {
+            content: vec![2],
+            entry_version: 1,
+        };
+
+        let value = old_value.clone();
+        run(&auth, move |client| {
+            let client2 = client.clone();
+            let owner = unwrap!(client.owner_key());
+            let data = unwrap!(MutableData::new(
+                name,
+                DIR_TAG,
+                BTreeMap::new(),
+                btree_map![vec![0] => value],
+                btree_set![owner],
+            ));
+
+            client.enable_mdata_cache(Duration::from_secs(60), 10);
+            client
+                .put_mdata(data)
+                .and_then(move |()| client2.list_mdata_entries(name, DIR_TAG))
+                .map(|_| ())
+                .map_err(AuthError::from)
+        });
+
+        run(&other_auth, move |client| {
+            let actions = EntryActions::new().update(vec![0], vec![2], 1).into();
+            client
+                .mutate_mdata_entries(name, DIR_TAG, actions)
+                .map_err(AuthError::from)
+        });
+
+        // The entry write of the other client doesn't change the version, so it isn't noticed.
+        let value = old_value.clone();
+        run(&auth, move |client| {
+            client
+                .get_mdata_value(name, DIR_TAG, vec![0])
+                .map(move |cached| assert_eq!(cached, value))
+                .map_err(AuthError::from)
+        });
+
+        run(&other_auth, move |client| {
+            let permissions = PermissionSet::new().allow(Action::Insert);
+            client
+                .set_mdata_user_permissions(name, DIR_TAG, User::Anyone, permissions, 1)
+                .map_err(AuthError::from)
+        });
+
+        // The new version read drops all that's cached.
+        run(&auth, move |client| {
+            let client2 = client.clone();
+            client
+                .get_mdata_version(name, DIR_TAG)
+                .and_then(move |version| {
+                    assert_eq!(version, 1);
+                    client2.list_mdata_entries(name, DIR_TAG)
+                }).map(move |entries| assert_eq!(entries, btree_map![vec![0] => new_value]))
+                .map_err(AuthError::from)
+        });
+    }
+
+    // Test that what's cached in the `MutableData` read cache expires, after which writes of
+    // entries by another client are seen.
+    #[cfg(feature = "use-mock-routing")]
+    #[test]
+    fn mdata_cache_expiry() {
+        use rand;
+        use routing::EntryActions;
+        use std::thread;
+        use std::time::Duration;
+        use test_utils::{create_authenticator, run};
+        use Authenticator;
+
+        let (auth, locator, password) = create_authenticator();
+        let other_auth = unwrap!(Authenticator::login(locator, password, || ()));
+
+        let name = rand::random();
+        let old_value = Value {
+            content: vec![1],
+            entry_version: 0,
+        };
+        let new_value = Value {
+            content: vec![2],
+            entry_version: 1,
+        };
+
+        let value = old_value.clone();
+        run(&auth, move |client| {
+            let client2 = client.clone();
+            let owner = unwrap!(client.owner_key());
+            let data = unwrap!(MutableData::new(
+                name,
+                DIR_TAG,
+                BTreeMap::new(),
+                btree_map![vec![0] => value],
+                btree_set![owner],
+            ));
+
+            client.enable_mdata_cache(Duration::from_millis(500), 10);
+            client.set_timeout(Duration::from_millis(250));
+            client
+                .put_mdata(data)
+                .and_then(move |()| client2.get_mdata_value(name, DIR_TAG, vec![0]))
+                .map(|_| ())
+                .map_err(AuthError::from)
+        });
+
+        run(&other_auth, move |client| {
+            let actions = EntryActions::new().update(vec![0], vec![2], 1).into();
+            client
+                .mutate_mdata_entries(name, DIR_TAG, actions)
+                .map_err(AuthError::from)
+        });
+
+        // Served stale from the cache while the network doesn't respond.
+        run(&auth, move |client| {
+            client.set_simulate_timeout(true);
+            client
+                .get_mdata_value(name, DIR_TAG, vec![0])
+                .map(move |cached| assert_eq!(cached, old_value))
+                .map_err(AuthError::from)
+        });
+
+        thread::sleep(Duration::from_millis(600));
+
+        // Expired, so it goes to the network.
+        run(&auth, move |client| {
+            let client2 = client.clone();
+            client
+                .get_mdata_value(name, DIR_TAG, vec![0])
+                .then(move |result| match result {
+                    Err(CoreError::RequestTimeout) => {
+                        client2.set_simulate_timeout(false);
+                        client2.get_mdata_value(name, DIR_TAG, vec![0])
+                    }
+                    result => panic!("Unexpected {:?}", result),
+                }).map(move |value| assert_eq!(value, new_value))
+                .map_err(AuthError::from)
+        });
+    }
 }
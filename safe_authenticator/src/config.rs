@@ -9,16 +9,19 @@
 use super::{AuthError, AuthFuture};
 use client::AuthClient;
 use futures::future::{self, Either, Loop};
-use futures::Future;
+use futures::{stream, Future, Stream};
 use maidsafe_utilities::serialisation::{deserialise, serialise};
-use routing::{ClientError, EntryActions, EntryError};
-use safe_core::ipc::req::AppExchangeInfo;
+use routing::{ClientError, EntryActions, EntryError, XorName};
+use rust_sodium::crypto::sign;
+use safe_core::immutable_data;
+use safe_core::ipc::req::{AppExchangeInfo, ContainerPermissions, ShareMData};
 use safe_core::ipc::resp::AppKeys;
 use safe_core::ipc::IpcError;
 use safe_core::{Client, CoreError, FutureExt};
 use serde::de::DeserializeOwned;
 use serde::Serialize;
-use std::collections::{HashMap, VecDeque};
+use std::collections::{BTreeSet, HashMap, VecDeque};
+use std::time::{SystemTime, UNIX_EPOCH};
 use tiny_keccak::sha3_256;
 
 /// App data stored in the authenticator configuration.
@@ -42,11 +45,124 @@ pub const KEY_APPS: &[u8] = b"apps";
 /// Config file key under which the revocation queue is stored.
 pub const KEY_APP_REVOCATION_QUEUE: &[u8] = b"revocation-queue";
 
+/// Config file key under which the user devices are stored.
+pub const KEY_DEVICES: &[u8] = b"devices";
+
+/// Config file key under which the app developer keys are stored.
+pub const KEY_APP_DEVELOPERS: &[u8] = b"app-developers";
+
+/// Config file key under which the audit log is stored.
+pub const KEY_AUDIT_LOG: &[u8] = b"audit-log";
+
+/// Config file key under which the names of the audit log archives are stored.
+pub const KEY_AUDIT_LOG_ARCHIVES: &[u8] = b"audit-log-archives";
+
+/// Config file key under which the mutable data shared with apps is stored.
+pub const KEY_SHARED_MDATA: &[u8] = b"shared-mdata";
+
+/// Config file key under which the names of the user-defined public root containers are stored.
+pub const KEY_PUBLIC_CONTAINERS: &[u8] = b"public-containers";
+
+/// Maximum number of entries kept in the audit log itself. Once the log reaches this limit,
+/// its entries are moved to an archive so that the log still fits into a single config entry.
+pub const AUDIT_LOG_MAX_ENTRIES: usize = 500;
+
 /// Maps from a SHA-3 hash of an app ID to app info.
 pub type Apps = HashMap<[u8; 32], AppInfo>;
 /// Contains a queue of revocations that are currently running or have failed.
 /// String refers to `app_id`.
 pub type RevocationQueue = VecDeque<String>;
+/// Maps from a device ID to device info.
+pub type Devices = HashMap<String, DeviceInfo>;
+/// Maps from an app ID to the signing key of its developer, as seen in the first
+/// signed manifest the app has been authorised with.
+pub type AppDevelopers = HashMap<String, sign::PublicKey>;
+/// Log of the decisions made by the authenticator, oldest first.
+pub type AuditLog = VecDeque<AuditEntry>;
+/// Names of the immutable data the audit log entries have been archived to, oldest first.
+pub type AuditLogArchives = Vec<XorName>;
+/// Maps from an app ID to the mutable data shared with the app and the permissions granted.
+pub type SharedMData = HashMap<String, Vec<ShareMData>>;
+/// Names of the user-defined root containers of the public type.
+pub type PublicContainers = BTreeSet<String>;
+
+/// Device the user has logged in to the authenticator from.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct DeviceInfo {
+    /// User-facing name of the device.
+    pub name: String,
+    /// Time the device was registered, in seconds since the Unix epoch.
+    pub registered: u64,
+    /// Time of the last login from the device, in seconds since the Unix epoch.
+    pub last_login: u64,
+    /// IDs of the apps authorised from the device.
+    pub apps: BTreeSet<String>,
+}
+
+impl DeviceInfo {
+    /// Construct a new device info registered at the current time.
+    pub fn new(name: String) -> Self {
+        let now = unix_timestamp();
+
+        DeviceInfo {
+            name,
+            registered: now,
+            last_login: now,
+            apps: BTreeSet::new(),
+        }
+    }
+}
+
+/// Request or operation recorded in the audit log.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum AuditAction {
+    /// App authentication request.
+    Auth {
+        /// Whether the app asked for its own container.
+        app_container: bool,
+        /// Requested containers and permissions.
+        containers: HashMap<String, ContainerPermissions>,
+    },
+    /// Request for additional containers permissions.
+    Containers(HashMap<String, ContainerPermissions>),
+    /// Request to share mutable data.
+    ShareMData(Vec<ShareMData>),
+    /// Revocation of the app access.
+    Revoke,
+}
+
+/// Single audit log record: which app asked for what, when, and the decision.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct AuditEntry {
+    /// ID of the app the decision concerns.
+    pub app_id: String,
+    /// What the app asked for.
+    pub action: AuditAction,
+    /// Whether the request has been granted.
+    pub granted: bool,
+    /// Time of the decision, in seconds since the Unix epoch.
+    pub timestamp: u64,
+}
+
+impl AuditEntry {
+    /// Construct a new audit log entry timestamped with the current time.
+    pub fn new(app_id: String, action: AuditAction, granted: bool) -> Self {
+        AuditEntry {
+            app_id,
+            action,
+            granted,
+            timestamp: unix_timestamp(),
+        }
+    }
+}
+
+/// Current time in seconds since the Unix epoch.
+pub fn unix_timestamp() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or(0)
+}
 
 /// Bump the current version to obtain new version.
 pub fn next_version(version: Option<u64>) -> u64 {
@@ -98,6 +214,32 @@ pub fn remove_app(
     })
 }
 
+/// Get the developer keys of the apps authorised with a signed manifest.
+pub fn list_app_developers(
+    client: &AuthClient,
+) -> Box<AuthFuture<(Option<u64>, AppDevelopers)>> {
+    get_entry(client, KEY_APP_DEVELOPERS)
+}
+
+/// Remember the developer key of the given app. A key already known for the app
+/// is never replaced.
+pub fn insert_app_developer(
+    client: &AuthClient,
+    developers: AppDevelopers,
+    new_version: u64,
+    app_id: String,
+    developer_key: sign::PublicKey,
+) -> Box<AuthFuture<(u64, AppDevelopers)>> {
+    mutate_entry(client, KEY_APP_DEVELOPERS, developers, new_version, move |developers| {
+        if developers.contains_key(&app_id) {
+            false
+        } else {
+            let _ = developers.insert(app_id.clone(), developer_key);
+            true
+        }
+    })
+}
+
 /// Get authenticator's revocation queue.
 /// Returns version and the revocation queue in a tuple.
 /// If the queue is not found on the config file, returns `None`.
@@ -191,6 +333,282 @@ pub fn repush_to_app_revocation_queue(
     )
 }
 
+/// Retrieves the devices the user has logged in from.
+pub fn list_devices(client: &AuthClient) -> Box<AuthFuture<(Option<u64>, Devices)>> {
+    get_entry(client, KEY_DEVICES)
+}
+
+/// Atomically apply `f` to the devices and store them in the network.
+/// `f` should return `false` if it didn't modify the devices.
+pub fn mutate_devices<F>(
+    client: &AuthClient,
+    devices: Devices,
+    new_version: u64,
+    f: F,
+) -> Box<AuthFuture<(u64, Devices)>>
+where
+    F: Fn(&mut Devices) -> bool + 'static,
+{
+    mutate_entry(client, KEY_DEVICES, devices, new_version, f)
+}
+
+/// Retrieves the mutable data shared with apps.
+pub fn list_shared_mdata(client: &AuthClient) -> Box<AuthFuture<(Option<u64>, SharedMData)>> {
+    get_entry(client, KEY_SHARED_MDATA)
+}
+
+/// Record the mutable data shared with the app. Permissions granted on mutable data already
+/// shared with the app replace the ones granted before.
+pub fn insert_shared_mdata(
+    client: &AuthClient,
+    app_id: String,
+    mdata: Vec<ShareMData>,
+) -> Box<AuthFuture<()>> {
+    let c2 = client.clone();
+
+    list_shared_mdata(client)
+        .and_then(move |(version, shared)| {
+            mutate_entry(&c2, KEY_SHARED_MDATA, shared, next_version(version), move |shared| {
+                let app_mdata = shared.entry(app_id.clone()).or_insert_with(Vec::new);
+                let mut modified = false;
+
+                for mdata in &mdata {
+                    match app_mdata
+                        .iter()
+                        .position(|item| item.name == mdata.name && item.type_tag == mdata.type_tag)
+                    {
+                        Some(index) => {
+                            if app_mdata[index].perms != mdata.perms {
+                                app_mdata[index].perms = mdata.perms;
+                                modified = true;
+                            }
+                        }
+                        None => {
+                            app_mdata.push(mdata.clone());
+                            modified = true;
+                        }
+                    }
+                }
+                modified
+            })
+        }).map(|_| ())
+        .into_box()
+}
+
+/// Forget the mutable data shared with the app, once its access is revoked.
+pub fn remove_shared_mdata(client: &AuthClient, app_id: &str) -> Box<AuthFuture<()>> {
+    let c2 = client.clone();
+    let app_id = app_id.to_string();
+
+    list_shared_mdata(client)
+        .and_then(move |(version, shared)| {
+            if !shared.contains_key(&app_id) {
+                return ok!(());
+            }
+
+            mutate_entry(&c2, KEY_SHARED_MDATA, shared, next_version(version), move |shared| {
+                shared.remove(&app_id).is_some()
+            }).map(|_| ())
+            .into_box()
+        }).into_box()
+}
+
+/// Retrieves the names of the user-defined root containers of the public type.
+pub fn list_public_containers(
+    client: &AuthClient,
+) -> Box<AuthFuture<(Option<u64>, PublicContainers)>> {
+    get_entry(client, KEY_PUBLIC_CONTAINERS)
+}
+
+/// Record the given user-defined root containers as being of the public type.
+pub fn insert_public_containers(client: &AuthClient, names: Vec<String>) -> Box<AuthFuture<()>> {
+    if names.is_empty() {
+        return ok!(());
+    }
+    let c2 = client.clone();
+
+    list_public_containers(client)
+        .and_then(move |(version, containers)| {
+            mutate_entry(
+                &c2,
+                KEY_PUBLIC_CONTAINERS,
+                containers,
+                next_version(version),
+                move |containers| {
+                    let len = containers.len();
+                    containers.extend(names.iter().cloned());
+                    containers.len() != len
+                },
+            )
+        }).map(|_| ())
+        .into_box()
+}
+
+/// Forget the type of the removed user-defined root container.
+pub fn remove_public_container(client: &AuthClient, name: String) -> Box<AuthFuture<()>> {
+    let c2 = client.clone();
+
+    list_public_containers(client)
+        .and_then(move |(version, containers)| {
+            if !containers.contains(&name) {
+                return ok!(());
+            }
+
+            mutate_entry(
+                &c2,
+                KEY_PUBLIC_CONTAINERS,
+                containers,
+                next_version(version),
+                move |containers| containers.remove(&name),
+            ).map(|_| ())
+            .into_box()
+        }).into_box()
+}
+
+/// Get authenticator's audit log.
+/// Returns version and the audit log in a tuple.
+/// If the log is not found on the config file, returns `None`.
+pub fn get_audit_log(client: &AuthClient) -> Box<AuthFuture<(Option<u64>, AuditLog)>> {
+    get_entry(client, KEY_AUDIT_LOG)
+}
+
+/// Get the names of the archives the audit log entries have been moved to.
+/// Returns version and the names in a tuple.
+pub fn list_audit_log_archives(
+    client: &AuthClient,
+) -> Box<AuthFuture<(Option<u64>, AuditLogArchives)>> {
+    get_entry(client, KEY_AUDIT_LOG_ARCHIVES)
+}
+
+/// Get the entries of the audit log archive with the given name.
+pub fn get_audit_log_archive(client: &AuthClient, name: XorName) -> Box<AuthFuture<AuditLog>> {
+    let enc_key = fry!(
+        client
+            .secret_symmetric_key()
+            .ok_or_else(|| AuthError::Unexpected("Encryption key not available".to_owned()))
+    );
+
+    immutable_data::get_value(client, &name, Some(enc_key))
+        .map_err(AuthError::from)
+        .and_then(|value| Ok(deserialise(&value)?))
+        .into_box()
+}
+
+/// Get all the entries of the audit log, oldest first: the archived ones followed
+/// by the ones still in the log.
+pub fn get_full_audit_log(client: &AuthClient) -> Box<AuthFuture<AuditLog>> {
+    let c2 = client.clone();
+    let c3 = client.clone();
+
+    list_audit_log_archives(client)
+        .and_then(move |(_, archives)| {
+            stream::iter_ok(archives)
+                .and_then(move |name| get_audit_log_archive(&c2, name))
+                .concat2()
+        }).and_then(move |mut entries| {
+            get_audit_log(&c3).map(move |(_, log)| {
+                entries.extend(log);
+                entries
+            })
+        }).into_box()
+}
+
+/// Append `entry` to the audit log and put it onto the network.
+/// Existing entries are never modified or removed.
+pub fn append_to_audit_log(
+    client: &AuthClient,
+    log: AuditLog,
+    new_version: u64,
+    entry: AuditEntry,
+) -> Box<AuthFuture<(u64, AuditLog)>> {
+    trace!("Recording audit log entry for app with ID {}...", entry.app_id);
+
+    mutate_entry(client, KEY_AUDIT_LOG, log, new_version, move |log| {
+        log.push_back(entry.clone());
+        true
+    })
+}
+
+/// Move the entries of the audit log to a new archive, then remove them from the log
+/// and put it onto the network. Entries appended to the log meanwhile, e.g. from
+/// another device, are kept in it.
+pub fn archive_audit_log(
+    client: &AuthClient,
+    log: AuditLog,
+    new_version: u64,
+) -> Box<AuthFuture<(u64, AuditLog)>> {
+    trace!("Archiving {} audit log entries...", log.len());
+
+    let c2 = client.clone();
+    let c3 = client.clone();
+    let c4 = client.clone();
+    let c5 = client.clone();
+
+    let enc_key = fry!(
+        client
+            .secret_symmetric_key()
+            .ok_or_else(|| AuthError::Unexpected("Encryption key not available".to_owned()))
+    );
+    let serialised = fry!(serialise(&log));
+
+    immutable_data::create(client, &serialised, Some(enc_key))
+        .and_then(move |data| {
+            let name = *data.name();
+            c2.put_idata(data).map(move |()| name)
+        }).map_err(AuthError::from)
+        .and_then(move |name| list_audit_log_archives(&c3).map(move |res| (name, res)))
+        .and_then(move |(name, (version, archives))| {
+            mutate_entry(
+                &c4,
+                KEY_AUDIT_LOG_ARCHIVES,
+                archives,
+                next_version(version),
+                move |archives| {
+                    if archives.contains(&name) {
+                        false
+                    } else {
+                        archives.push(name);
+                        true
+                    }
+                },
+            )
+        }).and_then(move |_| {
+            let archived: Vec<_> = log.iter().cloned().collect();
+
+            mutate_entry(&c5, KEY_AUDIT_LOG, log, new_version, move |log| {
+                // The log may have been archived from another device meanwhile.
+                if log.iter().take(archived.len()).eq(archived.iter()) {
+                    let _ = log.drain(..archived.len());
+                    true
+                } else {
+                    false
+                }
+            })
+        }).into_box()
+}
+
+/// Record a decision in the audit log, archiving the log first if it's full.
+/// Failure to do so is returned to the caller, so that a decision is never reported
+/// without being recorded.
+pub fn record_audit_entry(client: &AuthClient, entry: AuditEntry) -> Box<AuthFuture<()>> {
+    let c2 = client.clone();
+    let c3 = client.clone();
+
+    get_audit_log(client)
+        .and_then(move |(version, log)| {
+            if log.len() < AUDIT_LOG_MAX_ENTRIES {
+                return ok!((version, log));
+            }
+
+            archive_audit_log(&c2, log, next_version(version))
+                .map(|(version, log)| (Some(version), log))
+                .into_box()
+        }).and_then(move |(version, log)| {
+            append_to_audit_log(&c3, log, next_version(version), entry)
+        }).map(|_| ())
+        .into_box()
+}
+
 fn get_entry<T>(client: &AuthClient, key: &[u8]) -> Box<AuthFuture<(Option<u64>, T)>>
 where
     T: Default + DeserializeOwned + Serialize + 'static,
@@ -0,0 +1,321 @@
+// Copyright 2018 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+//! User devices and remote logout.
+//!
+//! Every authenticator session can be registered as a device. Apps authorised
+//! during the session are attributed to that device, so that all of them can be
+//! revoked at once when the device is lost.
+
+use app_auth::{app_state, AppState};
+use client::AuthClient;
+use config::{self, DeviceInfo};
+use futures::{future, stream, Future, Stream};
+use revocation::{delete_app_auth_key, revoke_app};
+use safe_core::utils::generate_readable_string;
+use safe_core::{Client, FutureExt};
+use {AuthError, AuthFuture};
+
+/// Register the current session as a new device. Apps authorised from now on are
+/// attributed to this device. Returns the ID of the new device.
+pub fn register(client: &AuthClient, name: String) -> Box<AuthFuture<String>> {
+    let c2 = client.clone();
+    let c3 = client.clone();
+    let device_id = fry!(generate_readable_string(16));
+
+    config::list_devices(client)
+        .and_then(move |(version, devices)| {
+            let device = DeviceInfo::new(name);
+            let id = device_id.clone();
+
+            config::mutate_devices(&c2, devices, config::next_version(version), move |devices| {
+                let _ = devices.insert(id.clone(), device.clone());
+                true
+            }).map(move |_| device_id)
+        }).map(move |device_id| {
+            c3.set_device_id(Some(device_id.clone()));
+            device_id
+        }).into_box()
+}
+
+/// Resume a previously registered device in the current session, updating its last login time.
+pub fn resume(client: &AuthClient, device_id: String) -> Box<AuthFuture<()>> {
+    let c2 = client.clone();
+    let c3 = client.clone();
+
+    config::list_devices(client)
+        .and_then(move |(version, devices)| {
+            if !devices.contains_key(&device_id) {
+                return err!(AuthError::from("Unknown device"));
+            }
+
+            let id = device_id.clone();
+            let now = config::unix_timestamp();
+            config::mutate_devices(&c2, devices, config::next_version(version), move |devices| {
+                match devices.get_mut(&id) {
+                    Some(device) => {
+                        device.last_login = now;
+                        true
+                    }
+                    None => false,
+                }
+            }).map(move |_| device_id)
+            .into_box()
+        }).map(move |device_id| c3.set_device_id(Some(device_id)))
+        .into_box()
+}
+
+/// Attribute the app to the device of the current session.
+/// Does nothing if the session hasn't been registered as a device.
+pub fn attach_app(client: &AuthClient, app_id: String) -> Box<AuthFuture<()>> {
+    let c2 = client.clone();
+    let device_id = match client.device_id() {
+        Some(device_id) => device_id,
+        None => return ok!(()),
+    };
+
+    config::list_devices(client)
+        .and_then(move |(version, devices)| {
+            let attached = devices
+                .get(&device_id)
+                .map(|device| device.apps.contains(&app_id))
+                .unwrap_or(true);
+            if attached {
+                return ok!(());
+            }
+
+            config::mutate_devices(&c2, devices, config::next_version(version), move |devices| {
+                match devices.get_mut(&device_id) {
+                    Some(device) => device.apps.insert(app_id.clone()),
+                    None => false,
+                }
+            }).map(|_| ())
+            .into_box()
+        }).into_box()
+}
+
+/// Revoke all apps authorised from the given device and remove the device.
+pub fn revoke_device(client: &AuthClient, device_id: String) -> Box<AuthFuture<()>> {
+    let c2 = client.clone();
+    let c3 = client.clone();
+    let c4 = client.clone();
+
+    config::list_devices(client)
+        .and_then(move |(_, devices)| {
+            let apps = devices
+                .get(&device_id)
+                .map(|device| device.apps.iter().cloned().collect::<Vec<_>>())
+                .ok_or_else(|| AuthError::from("Unknown device"))?;
+            Ok((device_id, apps))
+        }).and_then(move |(device_id, apps)| {
+            authenticated_apps(&c4, apps).map(move |apps| (device_id, apps))
+        }).and_then(move |(device_id, apps)| revoke_apps(&c2, apps).map(move |()| device_id))
+        .and_then(move |device_id| {
+            // The revocation has modified the devices, so fetch them again.
+            config::list_devices(&c3).and_then(move |(version, devices)| {
+                config::mutate_devices(&c3, devices, config::next_version(version), move |devices| {
+                    devices.remove(&device_id).is_some()
+                })
+            })
+        }).map(|_| ())
+        .into_box()
+}
+
+/// Sign out everywhere: revoke all authorised apps, which rotates the encryption keys
+/// of the containers they had access to, delete all remaining auth keys and forget
+/// all devices but the current one.
+pub fn sign_out_everywhere(client: &AuthClient) -> Box<AuthFuture<()>> {
+    let c2 = client.clone();
+    let c3 = client.clone();
+    let c4 = client.clone();
+    let c5 = client.clone();
+    let c6 = client.clone();
+
+    config::list_apps(client)
+        .and_then(move |(_, apps)| {
+            let apps = apps.values().map(|app| app.info.id.clone()).collect();
+            authenticated_apps(&c2, apps)
+        }).and_then(move |apps| revoke_apps(&c3, apps))
+        .and_then(move |()| c4.list_auth_keys_and_version().map_err(AuthError::from))
+        .and_then(move |(keys, _)| {
+            let own_key = c5.public_signing_key();
+            let c7 = c5.clone();
+
+            stream::iter_ok(keys.into_iter().filter(move |key| Some(*key) != own_key))
+                .for_each(move |key| delete_app_auth_key(&c7, key))
+        }).and_then(move |()| {
+            let current = c6.device_id();
+
+            config::list_devices(&c6).and_then(move |(version, devices)| {
+                if devices.is_empty() {
+                    return ok!(());
+                }
+
+                config::mutate_devices(&c6, devices, config::next_version(version), move |devices| {
+                    let len = devices.len();
+                    devices.retain(|id, _| Some(id) == current.as_ref());
+                    let mut modified = devices.len() != len;
+
+                    for device in devices.values_mut() {
+                        if !device.apps.is_empty() {
+                            device.apps.clear();
+                            modified = true;
+                        }
+                    }
+
+                    modified
+                }).map(|_| ())
+                .into_box()
+            })
+        }).into_box()
+}
+
+// Filters out the apps which aren't currently authenticated.
+fn authenticated_apps(client: &AuthClient, app_ids: Vec<String>) -> Box<AuthFuture<Vec<String>>> {
+    let c2 = client.clone();
+
+    config::list_apps(client)
+        .and_then(move |(_, apps)| {
+            let states = app_ids
+                .into_iter()
+                .map(|app_id| app_state(&c2, &apps, &app_id).map(move |state| (app_id, state)))
+                .collect::<Vec<_>>();
+            future::join_all(states)
+        }).map(|states| {
+            states
+                .into_iter()
+                .filter(|&(_, ref state)| *state == AppState::Authenticated)
+                .map(|(app_id, _)| app_id)
+                .collect()
+        }).into_box()
+}
+
+// Revokes the given apps one by one.
+fn revoke_apps(client: &AuthClient, apps: Vec<String>) -> Box<AuthFuture<()>> {
+    let client = client.clone();
+
+    stream::iter_ok(apps)
+        .for_each(move |app_id| {
+            let c2 = client.clone();
+            revoke_app(&client, &app_id).and_then(move |()| detach_app(&c2, app_id))
+        }).into_box()
+}
+
+// Removes the revoked app from all devices it's been attributed to.
+fn detach_app(client: &AuthClient, app_id: String) -> Box<AuthFuture<()>> {
+    let c2 = client.clone();
+
+    config::list_devices(client)
+        .and_then(move |(version, devices)| {
+            if !devices.values().any(|device| device.apps.contains(&app_id)) {
+                return ok!(());
+            }
+
+            config::mutate_devices(&c2, devices, config::next_version(version), move |devices| {
+                let mut modified = false;
+                for device in devices.values_mut() {
+                    modified |= device.apps.remove(&app_id);
+                }
+                modified
+            }).map(|_| ())
+            .into_box()
+        }).into_box()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use safe_core::ipc::AuthReq;
+    use std::collections::HashMap;
+    use test_utils::{create_account_and_login, rand_app, register_app, register_rand_app, run};
+    use Authenticator;
+
+    fn app_state_of(auth: &Authenticator, app_id: &str) -> AppState {
+        let app_id = app_id.to_string();
+
+        run(auth, move |client| {
+            let c2 = client.clone();
+            config::list_apps(client).and_then(move |(_, apps)| app_state(&c2, &apps, &app_id))
+        })
+    }
+
+    // Test revoking a single device and signing out everywhere.
+    // 1. Register device A and authorise app A from it.
+    // 2. Register device B and authorise app B from it.
+    // 3. Verify that both devices are listed with their apps.
+    // 4. Revoke device A. App A must be revoked and device A forgotten, while app B
+    //    stays authenticated.
+    // 5. Sign out everywhere. App B must be revoked, no auth keys must remain and
+    //    device B (the current one) must be kept with no apps.
+    #[test]
+    fn revoke_device_and_sign_out_everywhere() {
+        let auth = create_account_and_login();
+
+        let device_a = run(&auth, |client| register(client, "phone".to_string()));
+        let (app_a, _) = unwrap!(register_rand_app(&auth, true, HashMap::new()));
+
+        let device_b = run(&auth, |client| register(client, "laptop".to_string()));
+        let (app_b, _) = unwrap!(register_rand_app(&auth, true, HashMap::new()));
+
+        let devices = run(&auth, |client| config::list_devices(client).map(|(_, d)| d));
+        assert_eq!(devices.len(), 2);
+        assert!(unwrap!(devices.get(&device_a)).apps.contains(&app_a));
+        assert!(unwrap!(devices.get(&device_b)).apps.contains(&app_b));
+
+        {
+            let device_a = device_a.clone();
+            run(&auth, move |client| revoke_device(client, device_a));
+        }
+
+        assert_eq!(app_state_of(&auth, &app_a), AppState::Revoked);
+        assert_eq!(app_state_of(&auth, &app_b), AppState::Authenticated);
+        let devices = run(&auth, |client| config::list_devices(client).map(|(_, d)| d));
+        assert!(!devices.contains_key(&device_a));
+
+        run(&auth, |client| sign_out_everywhere(client));
+
+        assert_eq!(app_state_of(&auth, &app_b), AppState::Revoked);
+        let keys = run(&auth, |client| {
+            client
+                .list_auth_keys_and_version()
+                .map(|(keys, _)| keys)
+                .map_err(AuthError::from)
+        });
+        assert!(keys.is_empty());
+
+        let devices = run(&auth, |client| config::list_devices(client).map(|(_, d)| d));
+        assert_eq!(devices.len(), 1);
+        assert!(unwrap!(devices.get(&device_b)).apps.is_empty());
+    }
+
+    // Test that re-authenticated apps are attributed to the current device.
+    // 1. Register device A and authorise an app from it.
+    // 2. Register device B and authorise the same app again.
+    // 3. Verify that the app is attributed to both devices.
+    #[test]
+    fn attach_reauthenticated_app() {
+        let auth = create_account_and_login();
+
+        let device_a = run(&auth, |client| register(client, "phone".to_string()));
+        let auth_req = AuthReq {
+            app: rand_app(),
+            app_container: false,
+            containers: HashMap::new(),
+            manifest: None,
+            resp_key: None,
+        };
+        let _ = unwrap!(register_app(&auth, &auth_req));
+
+        let device_b = run(&auth, |client| register(client, "laptop".to_string()));
+        let _ = unwrap!(register_app(&auth, &auth_req));
+
+        let devices = run(&auth, |client| config::list_devices(client).map(|(_, d)| d));
+        assert!(unwrap!(devices.get(&device_a)).apps.contains(&auth_req.app.id));
+        assert!(unwrap!(devices.get(&device_b)).apps.contains(&auth_req.app.id));
+    }
+}
@@ -16,7 +16,8 @@ use maidsafe_utilities::serialisation::SerialisationError;
 use routing::ClientError;
 use safe_core::ipc::IpcError;
 use safe_core::nfs::NfsError;
-use safe_core::CoreError;
+use safe_core::{CoreError, SelfEncryptionStorageError};
+use self_encryption::SelfEncryptionError;
 use std::error::Error;
 use std::ffi::NulError;
 use std::fmt::{self, Display, Formatter};
@@ -46,6 +47,7 @@ mod codes {
     pub const ERR_REQUEST_TIMEOUT: i32 = -17;
     pub const ERR_CONFIG_FILE: i32 = -18;
     pub const ERR_IO: i32 = -19;
+    pub const ERR_OPERATION_CANCELLED: i32 = -20;
 
     // routing Client errors
     pub const ERR_ACCESS_DENIED: i32 = -100;
@@ -77,6 +79,11 @@ mod codes {
     pub const ERR_SHARE_MDATA_DENIED: i32 = -206;
     pub const ERR_INVALID_OWNER: i32 = -207;
     pub const ERR_INCOMPATIBLE_MOCK_STATUS: i32 = -208;
+    pub const ERR_INVALID_MANIFEST: i32 = -209;
+    pub const ERR_MANIFEST_KEY_MISMATCH: i32 = -210;
+    pub const ERR_UPGRADE_REQUIRED: i32 = -211;
+    pub const ERR_INVALID_SEALED_RESP: i32 = -212;
+    pub const ERR_INVALID_SHARE_OFFER: i32 = -213;
 
     // NFS errors.
     pub const ERR_FILE_EXISTS: i32 = -300;
@@ -245,6 +252,11 @@ impl ErrorCode for AuthError {
                 IpcError::ShareMDataDenied => ERR_SHARE_MDATA_DENIED,
                 IpcError::InvalidOwner(..) => ERR_INVALID_OWNER,
                 IpcError::IncompatibleMockStatus => ERR_INCOMPATIBLE_MOCK_STATUS,
+                IpcError::InvalidManifest => ERR_INVALID_MANIFEST,
+                IpcError::ManifestKeyMismatch => ERR_MANIFEST_KEY_MISMATCH,
+                IpcError::UpgradeRequired(_) => ERR_UPGRADE_REQUIRED,
+                IpcError::InvalidSealedResp => ERR_INVALID_SEALED_RESP,
+                IpcError::InvalidShareOffer => ERR_INVALID_SHARE_OFFER,
             },
             AuthError::NfsError(ref err) => match *err {
                 NfsError::CoreError(ref err) => core_error_code(err),
@@ -252,7 +264,7 @@ impl ErrorCode for AuthError {
                 NfsError::FileNotFound => ERR_FILE_NOT_FOUND,
                 NfsError::InvalidRange => ERR_INVALID_RANGE,
                 NfsError::EncodeDecodeError(_) => ERR_ENCODE_DECODE_ERROR,
-                NfsError::SelfEncryption(_) => ERR_SELF_ENCRYPTION,
+                NfsError::SelfEncryption(ref err) => self_encryption_error_code(err),
                 NfsError::Unexpected(_) => ERR_UNEXPECTED,
             },
             AuthError::EncodeDecodeError => ERR_ENCODE_DECODE_ERROR,
@@ -300,11 +312,22 @@ fn core_error_code(err: &CoreError) -> i32 {
         CoreError::UnsupportedSaltSizeForPwHash => ERR_UNSUPPORTED_SALT_SIZE_FOR_PW_HASH,
         CoreError::UnsuccessfulPwHash => ERR_UNSUCCESSFUL_PW_HASH,
         CoreError::OperationAborted => ERR_OPERATION_ABORTED,
+        CoreError::OperationCancelled => ERR_OPERATION_CANCELLED,
         CoreError::MpidMessagingError(_) => ERR_MPID_MESSAGING_ERROR,
-        CoreError::SelfEncryption(_) => ERR_SELF_ENCRYPTION,
+        CoreError::SelfEncryption(ref err) => self_encryption_error_code(err),
         CoreError::RequestTimeout => ERR_REQUEST_TIMEOUT,
         CoreError::ConfigError(_) => ERR_CONFIG_FILE,
         CoreError::IoError(_) => ERR_IO,
         CoreError::Unexpected(_) => ERR_UNEXPECTED,
     }
 }
+
+fn self_encryption_error_code(err: &SelfEncryptionError<SelfEncryptionStorageError>) -> i32 {
+    match *err {
+        SelfEncryptionError::Storage(SelfEncryptionStorageError(ref err)) => match **err {
+            CoreError::OperationCancelled => ERR_OPERATION_CANCELLED,
+            _ => ERR_SELF_ENCRYPTION,
+        },
+        _ => ERR_SELF_ENCRYPTION,
+    }
+}
@@ -8,24 +8,30 @@
 
 use app_auth::{app_state, AppState};
 use app_container;
-use config;
+use config::{self, AuditAction, AuditEntry};
 use ffi_utils::{
     catch_unwind_cb, from_c_str, vec_into_raw_parts, FfiResult, OpaqueCtx, SafePtr, FFI_RESULT_OK,
 };
 use futures::Future;
 use maidsafe_utilities::serialisation::deserialise;
+use permissions_report::{
+    permissions_report, AppPermissions as NativeAppPermissions,
+    MDataPermissionsReport as NativeMDataPermissionsReport,
+};
 use routing::User::Key;
 use routing::XorName;
-use safe_core::ffi::arrays::XorNameArray;
-use safe_core::ffi::ipc::req::{AppExchangeInfo, ContainerPermissions};
+use safe_core::ffi::arrays::{SignPublicKey, XorNameArray};
+use safe_core::ffi::ipc::req::{AppExchangeInfo, ContainerPermissions, PermissionSet, ShareMData};
 use safe_core::ffi::ipc::resp::AppAccess;
-use safe_core::ipc::req::containers_into_vec;
+use safe_core::ipc::req::{containers_into_vec, permission_set_into_repr_c};
 use safe_core::ipc::resp::{AccessContainerEntry, AppAccess as NativeAppAccess};
 use safe_core::ipc::{access_container_enc_key, IpcError};
 use safe_core::utils::symmetric_decrypt;
 use safe_core::{Client, FutureExt};
 use std::collections::HashMap;
+use std::ffi::CString;
 use std::os::raw::{c_char, c_void};
+use std::ptr;
 use AuthError;
 use Authenticator;
 
@@ -55,6 +61,206 @@ impl Drop for RegisteredApp {
     }
 }
 
+/// Audit log entry kind: app authentication request.
+pub const AUDIT_AUTH: u32 = 0;
+/// Audit log entry kind: request for additional containers permissions.
+pub const AUDIT_CONTAINERS: u32 = 1;
+/// Audit log entry kind: request to share mutable data.
+pub const AUDIT_SHARE_MDATA: u32 = 2;
+/// Audit log entry kind: revocation of the app access.
+pub const AUDIT_REVOKE: u32 = 3;
+
+/// Decision recorded in the authenticator audit log
+#[repr(C)]
+pub struct AuditLogEntry {
+    /// UTF-8 encoded id of the app the decision concerns
+    pub app_id: *const c_char,
+    /// Kind of the request, one of the `AUDIT_*` constants
+    pub kind: u32,
+    /// Whether the request has been granted
+    pub granted: bool,
+    /// Time of the decision, in seconds since the Unix epoch
+    pub timestamp: u64,
+    /// Whether the app asked for its own container (`AUDIT_AUTH` only)
+    pub app_container: bool,
+    /// Requested containers (`AUDIT_AUTH` and `AUDIT_CONTAINERS` only)
+    pub containers: *const ContainerPermissions,
+    /// Length of the containers array
+    pub containers_len: usize,
+    /// Capacity of the containers array. Internal data required
+    /// for the Rust allocator.
+    pub containers_cap: usize,
+    /// Requested mutable data (`AUDIT_SHARE_MDATA` only)
+    pub mdata: *const ShareMData,
+    /// Length of the mdata array
+    pub mdata_len: usize,
+    /// Capacity of the mdata array. Internal data required
+    /// for the Rust allocator.
+    pub mdata_cap: usize,
+}
+
+impl Drop for AuditLogEntry {
+    fn drop(&mut self) {
+        unsafe {
+            let _ = CString::from_raw(self.app_id as *mut _);
+            let _ = Vec::from_raw_parts(
+                self.containers as *mut ContainerPermissions,
+                self.containers_len,
+                self.containers_cap,
+            );
+            let _ = Vec::from_raw_parts(
+                self.mdata as *mut ShareMData,
+                self.mdata_len,
+                self.mdata_cap,
+            );
+        }
+    }
+}
+
+fn audit_entry_into_repr_c(entry: AuditEntry) -> Result<AuditLogEntry, AuthError> {
+    let (kind, app_container, containers, mdata) = match entry.action {
+        AuditAction::Auth {
+            app_container,
+            containers,
+        } => (AUDIT_AUTH, app_container, containers, Vec::new()),
+        AuditAction::Containers(containers) => {
+            (AUDIT_CONTAINERS, false, containers, Vec::new())
+        }
+        AuditAction::ShareMData(mdata) => (AUDIT_SHARE_MDATA, false, HashMap::new(), mdata),
+        AuditAction::Revoke => (AUDIT_REVOKE, false, HashMap::new(), Vec::new()),
+    };
+
+    let containers = containers_into_vec(containers)?;
+    let mdata = mdata
+        .into_iter()
+        .map(|mdata| mdata.into_repr_c())
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let (containers, containers_len, containers_cap) = vec_into_raw_parts(containers);
+    let (mdata, mdata_len, mdata_cap) = vec_into_raw_parts(mdata);
+
+    Ok(AuditLogEntry {
+        app_id: CString::new(entry.app_id)?.into_raw(),
+        kind,
+        granted: entry.granted,
+        timestamp: entry.timestamp,
+        app_container,
+        containers,
+        containers_len,
+        containers_cap,
+        mdata,
+        mdata_len,
+        mdata_cap,
+    })
+}
+
+/// Permissions of a single app on a single MD
+#[repr(C)]
+pub struct AppPermissions {
+    /// Public sign key of the app
+    pub sign_key: SignPublicKey,
+    /// UTF-8 encoded id of the app, or null if the key doesn't belong to any registered app
+    pub app_id: *const c_char,
+    /// Whether the app has any permissions set on the MD
+    pub has_actual: bool,
+    /// Permissions set on the MD (valid only if `has_actual` is true)
+    pub actual: PermissionSet,
+    /// Whether the authenticator has granted the app any permissions on the MD
+    pub has_expected: bool,
+    /// Permissions granted by the authenticator (valid only if `has_expected` is true)
+    pub expected: PermissionSet,
+    /// True if the permissions set on the MD differ from the granted ones
+    pub mismatch: bool,
+}
+
+impl Drop for AppPermissions {
+    fn drop(&mut self) {
+        if !self.app_id.is_null() {
+            unsafe {
+                let _ = CString::from_raw(self.app_id as *mut _);
+            }
+        }
+    }
+}
+
+/// Permissions of all apps on a standard container or a shared MD
+#[repr(C)]
+pub struct MDataPermissionsReport {
+    /// UTF-8 encoded name of the standard container, or null for shared MD
+    pub container_name: *const c_char,
+    /// Name of the MD
+    pub name: XorNameArray,
+    /// Type tag of the MD
+    pub type_tag: u64,
+    /// Permissions of each app having or expected to have access
+    pub apps: *const AppPermissions,
+    /// Length of the apps array
+    pub apps_len: usize,
+    /// Capacity of the apps array. Internal data required
+    /// for the Rust allocator.
+    pub apps_cap: usize,
+}
+
+impl Drop for MDataPermissionsReport {
+    fn drop(&mut self) {
+        unsafe {
+            if !self.container_name.is_null() {
+                let _ = CString::from_raw(self.container_name as *mut _);
+            }
+            let _ = Vec::from_raw_parts(
+                self.apps as *mut AppPermissions,
+                self.apps_len,
+                self.apps_cap,
+            );
+        }
+    }
+}
+
+fn app_permissions_into_repr_c(app: NativeAppPermissions) -> Result<AppPermissions, AuthError> {
+    let mismatch = app.is_mismatch();
+    let app_id = match app.app_id {
+        Some(app_id) => CString::new(app_id)?.into_raw(),
+        None => ptr::null(),
+    };
+
+    Ok(AppPermissions {
+        sign_key: app.sign_key.0,
+        app_id,
+        has_actual: app.actual.is_some(),
+        actual: app.actual.map(permission_set_into_repr_c).unwrap_or_default(),
+        has_expected: app.expected.is_some(),
+        expected: app
+            .expected
+            .map(permission_set_into_repr_c)
+            .unwrap_or_default(),
+        mismatch,
+    })
+}
+
+fn mdata_permissions_report_into_repr_c(
+    report: NativeMDataPermissionsReport,
+) -> Result<MDataPermissionsReport, AuthError> {
+    let apps = report
+        .apps
+        .into_iter()
+        .map(app_permissions_into_repr_c)
+        .collect::<Result<Vec<_>, _>>()?;
+    let container_name = match report.container {
+        Some(name) => CString::new(name)?.into_raw(),
+        None => ptr::null(),
+    };
+    let (apps, apps_len, apps_cap) = vec_into_raw_parts(apps);
+
+    Ok(MDataPermissionsReport {
+        container_name,
+        name: report.name.0,
+        type_tag: report.type_tag,
+        apps,
+        apps_len,
+        apps_cap,
+    })
+}
+
 /// Removes a revoked app from the authenticator config.
 #[no_mangle]
 pub unsafe extern "C" fn auth_rm_revoked_app(
@@ -310,13 +516,101 @@ pub unsafe extern "C" fn auth_apps_accessing_mutable_data(
     })
 }
 
+/// Get the audit log of the decisions made by the authenticator, oldest first,
+/// including the entries moved to archives.
+#[no_mangle]
+pub unsafe extern "C" fn auth_audit_log(
+    auth: *const Authenticator,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(
+        user_data: *mut c_void,
+        result: *const FfiResult,
+        audit_log_entries: *const AuditLogEntry,
+        audit_log_entries_len: usize,
+    ),
+) {
+    let user_data = OpaqueCtx(user_data);
+
+    catch_unwind_cb(user_data.0, o_cb, || -> Result<_, AuthError> {
+        (*auth).send(move |client| {
+            config::get_full_audit_log(client)
+                .and_then(move |log| {
+                    let entries = log
+                        .into_iter()
+                        .map(audit_entry_into_repr_c)
+                        .collect::<Result<Vec<_>, _>>()?;
+
+                    o_cb(
+                        user_data.0,
+                        FFI_RESULT_OK,
+                        entries.as_safe_ptr(),
+                        entries.len(),
+                    );
+
+                    Ok(())
+                }).map_err(move |e| {
+                    call_result_cb!(Err::<(), _>(e), user_data, o_cb);
+                }).into_box()
+                .into()
+        })?;
+
+        Ok(())
+    })
+}
+
+/// Return the effective permissions of every app on every standard container and
+/// every MD shared through the authenticator, flagging the apps whose permissions
+/// on the MD differ from the ones granted by the authenticator.
+#[no_mangle]
+pub unsafe extern "C" fn auth_permissions_report(
+    auth: *const Authenticator,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(
+        user_data: *mut c_void,
+        result: *const FfiResult,
+        reports: *const MDataPermissionsReport,
+        reports_len: usize,
+    ),
+) {
+    let user_data = OpaqueCtx(user_data);
+
+    catch_unwind_cb(user_data.0, o_cb, || -> Result<_, AuthError> {
+        (*auth).send(move |client| {
+            permissions_report(client)
+                .and_then(move |reports| {
+                    let reports = reports
+                        .into_iter()
+                        .map(mdata_permissions_report_into_repr_c)
+                        .collect::<Result<Vec<_>, _>>()?;
+
+                    o_cb(
+                        user_data.0,
+                        FFI_RESULT_OK,
+                        reports.as_safe_ptr(),
+                        reports.len(),
+                    );
+
+                    Ok(())
+                }).map_err(move |e| {
+                    call_result_cb!(Err::<(), _>(e), user_data, o_cb);
+                }).into_box()
+                .into()
+        })?;
+
+        Ok(())
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use app_container::fetch;
     use config;
     use errors::{ERR_UNEXPECTED, ERR_UNKNOWN_APP};
-    use ffi_utils::test_utils::call_0;
+    use ffi::ipc::{auth_revoke_app, encode_auth_resp, IPC_ENCODING_BASE32};
+    use ffi_utils::test_utils::{call_0, call_1, call_vec};
+    use ffi_utils::{ReprC, StringError};
+    use futures::{stream, Stream};
     use revocation::revoke_app;
     use safe_core::ipc::AuthReq;
     use test_utils::{
@@ -360,6 +654,8 @@ mod tests {
                 app: app_info.clone(),
                 app_container: false,
                 containers: HashMap::new(),
+                manifest: None,
+                resp_key: None,
             },
         ));
 
@@ -419,6 +715,8 @@ mod tests {
                 app: app_info.clone(),
                 app_container: true,
                 containers: HashMap::new(),
+                manifest: None,
+                resp_key: None,
             },
         ));
 
@@ -477,6 +775,8 @@ mod tests {
                 app: app_info,
                 app_container: true,
                 containers: HashMap::new(),
+                manifest: None,
+                resp_key: None,
             },
         ));
 
@@ -495,4 +795,145 @@ mod tests {
             Ok(_) => panic!("File not removed"),
         }
     }
+
+    #[derive(Debug, PartialEq)]
+    struct AuditRecord {
+        app_id: String,
+        kind: u32,
+        granted: bool,
+    }
+
+    impl ReprC for AuditRecord {
+        type C = *const AuditLogEntry;
+        type Error = StringError;
+
+        unsafe fn clone_from_repr_c(c_repr: Self::C) -> Result<Self, Self::Error> {
+            Ok(AuditRecord {
+                app_id: from_c_str((*c_repr).app_id)?,
+                kind: (*c_repr).kind,
+                granted: (*c_repr).granted,
+            })
+        }
+    }
+
+    // Test that the authenticator decisions are recorded in the audit log.
+    // 1. Verify that the audit log of a new account is empty.
+    // 2. Deny an authentication request of app A.
+    // 3. Grant an authentication request of app B.
+    // 4. Revoke app B.
+    // 5. Verify that the audit log lists all three decisions in order.
+    #[test]
+    fn audit_log() {
+        let auth = create_account_and_login();
+
+        let log: Vec<AuditRecord> =
+            unsafe { unwrap!(call_vec(|ud, cb| auth_audit_log(&auth, ud, cb))) };
+        assert!(log.is_empty());
+
+        let denied_req = AuthReq {
+            app: rand_app(),
+            app_container: false,
+            containers: HashMap::new(),
+            manifest: None,
+            resp_key: None,
+        };
+        let granted_req = AuthReq {
+            app: rand_app(),
+            app_container: false,
+            containers: HashMap::new(),
+            manifest: None,
+            resp_key: None,
+        };
+
+        for &(req, is_granted) in &[(&denied_req, false), (&granted_req, true)] {
+            let ffi_req = unwrap!(req.clone().into_repr_c());
+            let _: String = unsafe {
+                unwrap!(call_1(|ud, cb| encode_auth_resp(
+                    &auth,
+                    &ffi_req,
+                    0,
+                    IPC_ENCODING_BASE32,
+                    is_granted,
+                    ud,
+                    cb
+                )))
+            };
+        }
+
+        let app_id = unwrap!(CString::new(granted_req.app.id.clone()));
+        let _: String = unsafe {
+            unwrap!(call_1(|ud, cb| auth_revoke_app(
+                &auth,
+                app_id.as_ptr(),
+                ud,
+                cb
+            )))
+        };
+
+        let log: Vec<AuditRecord> =
+            unsafe { unwrap!(call_vec(|ud, cb| auth_audit_log(&auth, ud, cb))) };
+        assert_eq!(
+            log,
+            vec![
+                AuditRecord {
+                    app_id: denied_req.app.id,
+                    kind: AUDIT_AUTH,
+                    granted: false,
+                },
+                AuditRecord {
+                    app_id: granted_req.app.id.clone(),
+                    kind: AUDIT_AUTH,
+                    granted: true,
+                },
+                AuditRecord {
+                    app_id: granted_req.app.id,
+                    kind: AUDIT_REVOKE,
+                    granted: true,
+                },
+            ]
+        );
+    }
+
+    // Test that the audit log entries are archived instead of dropped once the log is full.
+    // 1. Record one entry more than the log holds.
+    // 2. Verify that the log itself only holds the last entry, the others having been
+    //    moved to an archive.
+    // 3. Verify that the audit log returned to the user still lists all the entries in order.
+    #[test]
+    fn audit_log_archive() {
+        let auth = create_account_and_login();
+        let app_ids: Vec<_> = (0..config::AUDIT_LOG_MAX_ENTRIES + 1)
+            .map(|i| format!("app-{}", i))
+            .collect();
+
+        {
+            let app_ids = app_ids.clone();
+
+            run(&auth, move |client| {
+                let client = client.clone();
+
+                stream::iter_ok(app_ids).for_each(move |app_id| {
+                    let entry = AuditEntry::new(app_id, AuditAction::Revoke, true);
+                    config::record_audit_entry(&client, entry)
+                })
+            });
+        }
+
+        let (_, log) = run(&auth, |client| config::get_audit_log(client));
+        assert_eq!(log.len(), 1);
+
+        let (_, archives) = run(&auth, |client| config::list_audit_log_archives(client));
+        assert_eq!(archives.len(), 1);
+
+        let log: Vec<AuditRecord> =
+            unsafe { unwrap!(call_vec(|ud, cb| auth_audit_log(&auth, ud, cb))) };
+        let expected: Vec<_> = app_ids
+            .into_iter()
+            .map(|app_id| AuditRecord {
+                app_id,
+                kind: AUDIT_REVOKE,
+                granted: true,
+            }).collect();
+        assert_eq!(log, expected);
+    }
 }
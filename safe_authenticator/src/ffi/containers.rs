@@ -0,0 +1,114 @@
+// Copyright 2018 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use ffi_utils::{catch_unwind_cb, from_c_str, FfiResult, OpaqueCtx, ReprC, FFI_RESULT_OK};
+use futures::Future;
+use safe_core::ffi::MDataInfo as FfiMDataInfo;
+use safe_core::FutureExt;
+use std::os::raw::{c_char, c_void};
+use std::slice;
+use std_dirs::{add_root_container, remove_root_container, ContainerTemplate as NativeTemplate};
+use AuthError;
+use Authenticator;
+
+/// User-defined root container
+#[repr(C)]
+pub struct ContainerTemplate {
+    /// UTF-8 encoded container name
+    pub name: *const c_char,
+    /// `true` if the container entries are to be encrypted, `false` otherwise
+    pub encrypted: bool,
+    /// `true` if the container is of the public type, `false` if it's of the private type
+    pub public: bool,
+}
+
+impl ReprC for NativeTemplate {
+    type C = *const ContainerTemplate;
+    type Error = AuthError;
+
+    unsafe fn clone_from_repr_c(raw: *const ContainerTemplate) -> Result<Self, AuthError> {
+        Ok(NativeTemplate {
+            name: from_c_str((*raw).name)?,
+            encrypted: (*raw).encrypted,
+            public: (*raw).public,
+        })
+    }
+}
+
+/// Converts an array of `ContainerTemplate`s into their native representation.
+pub unsafe fn templates_clone_from_repr_c(
+    templates: *const ContainerTemplate,
+    templates_len: usize,
+) -> Result<Vec<NativeTemplate>, AuthError> {
+    if templates_len == 0 {
+        return Ok(Vec::new());
+    }
+
+    slice::from_raw_parts(templates, templates_len)
+        .iter()
+        .map(|template| NativeTemplate::clone_from_repr_c(template))
+        .collect()
+}
+
+/// Create a new root container and add it to the authenticator entry.
+/// Returns the `MDataInfo` of the new container.
+#[no_mangle]
+pub unsafe extern "C" fn auth_add_root_container(
+    auth: *const Authenticator,
+    template: *const ContainerTemplate,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(
+        user_data: *mut c_void,
+        result: *const FfiResult,
+        mdata_info: *const FfiMDataInfo,
+    ),
+) {
+    let user_data = OpaqueCtx(user_data);
+
+    catch_unwind_cb(user_data.0, o_cb, || -> Result<_, AuthError> {
+        let template = NativeTemplate::clone_from_repr_c(template)?;
+
+        (*auth).send(move |client| {
+            add_root_container(client, template)
+                .map(move |mdata_info| {
+                    let mdata_info = mdata_info.into_repr_c();
+                    o_cb(user_data.0, FFI_RESULT_OK, &mdata_info);
+                }).map_err(move |e| {
+                    call_result_cb!(Err::<(), _>(e), user_data, o_cb);
+                }).into_box()
+                .into()
+        })?;
+
+        Ok(())
+    })
+}
+
+/// Remove a user-defined root container from the authenticator entry.
+/// The standard containers can't be removed. The container data is left intact.
+#[no_mangle]
+pub unsafe extern "C" fn auth_remove_root_container(
+    auth: *const Authenticator,
+    name: *const c_char,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(user_data: *mut c_void, result: *const FfiResult),
+) {
+    let user_data = OpaqueCtx(user_data);
+
+    catch_unwind_cb(user_data.0, o_cb, || -> Result<_, AuthError> {
+        let name = from_c_str(name)?;
+
+        (*auth).send(move |client| {
+            remove_root_container(client, name)
+                .then(move |res| {
+                    call_result_cb!(res, user_data, o_cb);
+                    Ok(())
+                }).into_box()
+                .into()
+        })
+    })
+}
@@ -0,0 +1,221 @@
+// Copyright 2018 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use config::{self, DeviceInfo};
+use devices::{register, resume, revoke_device, sign_out_everywhere};
+use ffi_utils::{
+    catch_unwind_cb, from_c_str, vec_into_raw_parts, FfiResult, OpaqueCtx, SafePtr, FFI_RESULT_OK,
+};
+use futures::Future;
+use safe_core::ffi::ipc::req::AppExchangeInfo;
+use safe_core::FutureExt;
+use std::ffi::CString;
+use std::os::raw::{c_char, c_void};
+use tiny_keccak::sha3_256;
+use AuthError;
+use Authenticator;
+
+/// Device the user has logged in to the authenticator from
+#[repr(C)]
+pub struct Device {
+    /// UTF-8 encoded unique device identifier
+    pub id: *const c_char,
+    /// UTF-8 encoded user-facing device name
+    pub name: *const c_char,
+    /// Time the device was registered, in seconds since the Unix epoch
+    pub registered: u64,
+    /// Time of the last login from the device, in seconds since the Unix epoch
+    pub last_login: u64,
+    /// Apps authorised from the device
+    pub apps: *const AppExchangeInfo,
+    /// Length of the apps array
+    pub apps_len: usize,
+    /// Capacity of the apps array. Internal data required
+    /// for the Rust allocator.
+    pub apps_cap: usize,
+}
+
+impl Drop for Device {
+    fn drop(&mut self) {
+        unsafe {
+            let _ = CString::from_raw(self.id as *mut _);
+            let _ = CString::from_raw(self.name as *mut _);
+            let _ = Vec::from_raw_parts(
+                self.apps as *mut AppExchangeInfo,
+                self.apps_len,
+                self.apps_cap,
+            );
+        }
+    }
+}
+
+/// Register the current authenticator session as a new device.
+/// Apps authorised from now on are attributed to this device.
+#[no_mangle]
+pub unsafe extern "C" fn auth_register_device(
+    auth: *const Authenticator,
+    name: *const c_char,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(user_data: *mut c_void, result: *const FfiResult, device_id: *const c_char),
+) {
+    let user_data = OpaqueCtx(user_data);
+
+    catch_unwind_cb(user_data.0, o_cb, || -> Result<_, AuthError> {
+        let name = from_c_str(name)?;
+
+        (*auth).send(move |client| {
+            register(client, name)
+                .and_then(move |device_id| {
+                    let device_id = CString::new(device_id)?;
+                    o_cb(user_data.0, FFI_RESULT_OK, device_id.as_ptr());
+                    Ok(())
+                }).map_err(move |e| {
+                    call_result_cb!(Err::<(), _>(e), user_data, o_cb);
+                }).into_box()
+                .into()
+        })?;
+
+        Ok(())
+    })
+}
+
+/// Resume a previously registered device in the current authenticator session.
+#[no_mangle]
+pub unsafe extern "C" fn auth_resume_device(
+    auth: *const Authenticator,
+    device_id: *const c_char,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(user_data: *mut c_void, result: *const FfiResult),
+) {
+    let user_data = OpaqueCtx(user_data);
+
+    catch_unwind_cb(user_data.0, o_cb, || -> Result<_, AuthError> {
+        let device_id = from_c_str(device_id)?;
+
+        (*auth).send(move |client| {
+            resume(client, device_id)
+                .then(move |res| {
+                    call_result_cb!(res, user_data, o_cb);
+                    Ok(())
+                }).into_box()
+                .into()
+        })
+    })
+}
+
+/// Get a list of devices the user has logged in from.
+#[no_mangle]
+pub unsafe extern "C" fn auth_devices(
+    auth: *const Authenticator,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(
+        user_data: *mut c_void,
+        result: *const FfiResult,
+        devices: *const Device,
+        devices_len: usize,
+    ),
+) {
+    let user_data = OpaqueCtx(user_data);
+
+    catch_unwind_cb(user_data.0, o_cb, || -> Result<_, AuthError> {
+        (*auth).send(move |client| {
+            config::list_devices(client)
+                .join(config::list_apps(client))
+                .and_then(move |((_, devices), (_, apps))| {
+                    let devices = devices
+                        .into_iter()
+                        .map(|(id, device)| -> Result<Device, AuthError> {
+                            let DeviceInfo {
+                                name,
+                                registered,
+                                last_login,
+                                apps: app_ids,
+                            } = device;
+
+                            // Apps removed from the config are not listed.
+                            let device_apps = app_ids
+                                .iter()
+                                .filter_map(|app_id| apps.get(&sha3_256(app_id.as_bytes())))
+                                .map(|app| app.info.clone().into_repr_c())
+                                .collect::<Result<Vec<_>, _>>()?;
+                            let (apps_ptr, apps_len, apps_cap) = vec_into_raw_parts(device_apps);
+
+                            Ok(Device {
+                                id: CString::new(id)?.into_raw(),
+                                name: CString::new(name)?.into_raw(),
+                                registered,
+                                last_login,
+                                apps: apps_ptr,
+                                apps_len,
+                                apps_cap,
+                            })
+                        }).collect::<Result<Vec<_>, _>>()?;
+
+                    o_cb(
+                        user_data.0,
+                        FFI_RESULT_OK,
+                        devices.as_safe_ptr(),
+                        devices.len(),
+                    );
+
+                    Ok(())
+                }).map_err(move |e| {
+                    call_result_cb!(Err::<(), _>(e), user_data, o_cb);
+                }).into_box()
+                .into()
+        })?;
+
+        Ok(())
+    })
+}
+
+/// Revoke all apps authorised from the given device and forget the device.
+#[no_mangle]
+pub unsafe extern "C" fn auth_revoke_device(
+    auth: *const Authenticator,
+    device_id: *const c_char,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(user_data: *mut c_void, result: *const FfiResult),
+) {
+    let user_data = OpaqueCtx(user_data);
+
+    catch_unwind_cb(user_data.0, o_cb, || -> Result<_, AuthError> {
+        let device_id = from_c_str(device_id)?;
+
+        (*auth).send(move |client| {
+            revoke_device(client, device_id)
+                .then(move |res| {
+                    call_result_cb!(res, user_data, o_cb);
+                    Ok(())
+                }).into_box()
+                .into()
+        })
+    })
+}
+
+/// Sign out everywhere: revoke all authorised apps, delete all remaining auth keys
+/// and forget all devices except the current one.
+#[no_mangle]
+pub unsafe extern "C" fn auth_sign_out_everywhere(
+    auth: *const Authenticator,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(user_data: *mut c_void, result: *const FfiResult),
+) {
+    let user_data = OpaqueCtx(user_data);
+
+    catch_unwind_cb(user_data.0, o_cb, || -> Result<_, AuthError> {
+        (*auth).send(move |client| {
+            sign_out_everywhere(client)
+                .then(move |res| {
+                    call_result_cb!(res, user_data, o_cb);
+                    Ok(())
+                }).into_box()
+                .into()
+        })
+    })
+}
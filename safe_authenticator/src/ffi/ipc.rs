@@ -6,28 +6,43 @@
 // KIND, either express or implied. Please review the Licences for the specific language governing
 // permissions and limitations relating to use of the SAFE Network Software.
 
-use access_container;
-use app_auth;
-use config;
-use ffi_utils::{catch_unwind_cb, from_c_str, FfiResult, OpaqueCtx, ReprC, SafePtr, FFI_RESULT_OK};
-use futures::{stream, Future, Stream};
-use ipc::{decode_ipc_msg, decode_share_mdata_req, encode_response, update_container_perms};
+use config::{self, AuditAction, AuditEntry};
+use ffi_utils::{
+    catch_unwind_cb, from_c_str, vec_clone_from_raw_parts, FfiResult, OpaqueCtx, ReprC, SafePtr,
+    FFI_RESULT_OK,
+};
+use futures::{future, Future};
+use ipc::{
+    decode_ipc_msg, decode_share_mdata_req, decode_share_offer_req, deny_share_offer,
+    encode_auth_response, encode_response, grant_auth, grant_containers, grant_share_mdata,
+    grant_share_offer, process_batch,
+};
 use revocation::{flush_app_revocation_queue, revoke_app};
-use routing::{ClientError, User};
-use safe_core::ffi::ipc::req::{AuthReq, ContainersReq, ShareMDataReq};
+use safe_core::ffi::ipc::req::{
+    AuthReq, BatchReq, ContainersReq, ShareMDataReq, ShareOfferReq, ShareOfferTerms,
+};
 use safe_core::ffi::ipc::resp::MetadataResponse;
 use safe_core::ipc::req::{
-    AuthReq as NativeAuthReq, ContainersReq as NativeContainersReq, IpcReq,
-    ShareMDataReq as NativeShareMDataReq,
+    AuthReq as NativeAuthReq, BatchItem, BatchReq as NativeBatchReq,
+    ContainersReq as NativeContainersReq, IpcReq, ShareMDataReq as NativeShareMDataReq,
+    ShareOfferReq as NativeShareOfferReq,
 };
 use safe_core::ipc::resp::IpcResp;
-use safe_core::ipc::{decode_msg, IpcError, IpcMsg};
-use safe_core::{client, Client, CoreError, FutureExt};
+use safe_core::ipc::{decode_msg, IpcEncoding, IpcError, IpcMsg};
+use safe_core::{client, CoreError, FutureExt};
 use std::ffi::CStr;
 use std::os::raw::{c_char, c_void};
 use {AuthError, Authenticator};
 
+/// IPC message encoding: bincode in base32.
+pub const IPC_ENCODING_BASE32: u32 = 0;
+/// IPC message encoding: self-describing JSON, for messages starting with the JSON prefix.
+pub const IPC_ENCODING_JSON: u32 = 1;
+
 /// Decodes a given encoded IPC message without requiring an authorised account.
+///
+/// `o_unregistered` receives the encoding of the message (`IPC_ENCODING_BASE32` or
+/// `IPC_ENCODING_JSON`), which has to be passed to `encode_unregistered_resp`.
 #[no_mangle]
 pub unsafe extern "C" fn auth_unregistered_decode_ipc_msg(
     msg: *const c_char,
@@ -35,6 +50,7 @@ pub unsafe extern "C" fn auth_unregistered_decode_ipc_msg(
     o_unregistered: extern "C" fn(
         user_data: *mut c_void,
         req_id: u32,
+        encoding: u32,
         extra_data: *const u8,
         extra_data_len: usize,
     ),
@@ -44,7 +60,14 @@ pub unsafe extern "C" fn auth_unregistered_decode_ipc_msg(
 
     catch_unwind_cb(user_data.0, o_err, || -> Result<_, AuthError> {
         let msg_raw = CStr::from_ptr(msg).to_str()?;
-        let msg = decode_msg(msg_raw)?;
+        let encoding = IpcEncoding::of(msg_raw);
+        let msg = match decode_msg(msg_raw) {
+            Ok(msg) => msg,
+            Err(IpcError::UpgradeRequired(version)) => {
+                return upgrade_required(version, encoding, user_data, o_err);
+            }
+            Err(error) => return Err(AuthError::from(error)),
+        };
 
         match msg {
             IpcMsg::Req {
@@ -54,6 +77,7 @@ pub unsafe extern "C" fn auth_unregistered_decode_ipc_msg(
                 o_unregistered(
                     user_data.0,
                     req_id,
+                    encoding_into_repr_c(encoding),
                     extra_data.as_safe_ptr(),
                     extra_data.len(),
                 );
@@ -72,22 +96,37 @@ pub unsafe extern "C" fn auth_unregistered_decode_ipc_msg(
 }
 
 /// Decodes a given encoded IPC message and calls a corresponding callback.
+///
+/// The callbacks receive the encoding of the message (`IPC_ENCODING_BASE32` or
+/// `IPC_ENCODING_JSON`), which has to be passed to the `encode_*_resp` function
+/// responding to it.
+///
+/// Batches of requests and share offers are only decoded by `auth_decode_ipc_msg_v3`
+/// and `auth_decode_ipc_msg_v4`: for those, `o_err` receives an error along with a
+/// response telling the app the authenticator has to be upgraded.
 #[no_mangle]
 pub unsafe extern "C" fn auth_decode_ipc_msg(
     auth: *const Authenticator,
     msg: *const c_char,
     user_data: *mut c_void,
-    o_auth: extern "C" fn(user_data: *mut c_void, req_id: u32, req: *const AuthReq),
-    o_containers: extern "C" fn(user_data: *mut c_void, req_id: u32, req: *const ContainersReq),
+    o_auth: extern "C" fn(user_data: *mut c_void, req_id: u32, encoding: u32, req: *const AuthReq),
+    o_containers: extern "C" fn(
+        user_data: *mut c_void,
+        req_id: u32,
+        encoding: u32,
+        req: *const ContainersReq,
+    ),
     o_unregistered: extern "C" fn(
         user_data: *mut c_void,
         req_id: u32,
+        encoding: u32,
         extra_data: *const u8,
         extra_data_len: usize,
     ),
     o_share_mdata: extern "C" fn(
         user_data: *mut c_void,
         req_id: u32,
+        encoding: u32,
         req: *const ShareMDataReq,
         metadata: *const MetadataResponse,
         metadata_len: usize,
@@ -97,99 +136,466 @@ pub unsafe extern "C" fn auth_decode_ipc_msg(
     let user_data = OpaqueCtx(user_data);
 
     catch_unwind_cb(user_data.0, o_err, || -> Result<_, AuthError> {
-        let msg_raw = CStr::from_ptr(msg).to_str()?;
-        let msg = decode_msg(msg_raw)?;
+        decode_ipc_msg_impl(
+            auth,
+            msg,
+            user_data,
+            o_auth,
+            o_containers,
+            o_unregistered,
+            o_share_mdata,
+            None,
+            None,
+            o_err,
+        )
+    })
+}
 
-        (*auth).send(move |client| {
-            let c1 = client.clone();
-            decode_ipc_msg(client, msg)
-                .and_then(move |msg| match msg {
-                    Ok(IpcMsg::Req {
-                        req: IpcReq::Auth(auth_req),
-                        req_id,
-                    }) => {
-                        let repr_c = fry!(auth_req.into_repr_c().map_err(AuthError::IpcError));
-                        o_auth(user_data.0, req_id, &repr_c);
-                        ok!(())
-                    }
-                    Ok(IpcMsg::Req {
-                        req: IpcReq::Containers(cont_req),
-                        req_id,
-                    }) => {
-                        let repr_c = fry!(cont_req.into_repr_c().map_err(AuthError::IpcError));
-                        o_containers(user_data.0, req_id, &repr_c);
-                        ok!(())
-                    }
-                    Ok(IpcMsg::Req {
-                        req: IpcReq::Unregistered(extra_data),
+/// Decodes a given encoded IPC message, including batches of requests added by IPC
+/// version 3, and calls a corresponding callback. The callbacks receive the encoding
+/// of the message, as with `auth_decode_ipc_msg`.
+///
+/// For a batch, `o_batch` receives the metadata of the mutable data of all the
+/// share mutable data requests in the batch, in order. Share offers are only decoded
+/// by `auth_decode_ipc_msg_v4`.
+#[no_mangle]
+pub unsafe extern "C" fn auth_decode_ipc_msg_v3(
+    auth: *const Authenticator,
+    msg: *const c_char,
+    user_data: *mut c_void,
+    o_auth: extern "C" fn(user_data: *mut c_void, req_id: u32, encoding: u32, req: *const AuthReq),
+    o_containers: extern "C" fn(
+        user_data: *mut c_void,
+        req_id: u32,
+        encoding: u32,
+        req: *const ContainersReq,
+    ),
+    o_unregistered: extern "C" fn(
+        user_data: *mut c_void,
+        req_id: u32,
+        encoding: u32,
+        extra_data: *const u8,
+        extra_data_len: usize,
+    ),
+    o_share_mdata: extern "C" fn(
+        user_data: *mut c_void,
+        req_id: u32,
+        encoding: u32,
+        req: *const ShareMDataReq,
+        metadata: *const MetadataResponse,
+        metadata_len: usize,
+    ),
+    o_batch: extern "C" fn(
+        user_data: *mut c_void,
+        req_id: u32,
+        encoding: u32,
+        req: *const BatchReq,
+        metadata: *const MetadataResponse,
+        metadata_len: usize,
+    ),
+    o_err: extern "C" fn(user_data: *mut c_void, result: *const FfiResult, response: *const c_char),
+) {
+    let user_data = OpaqueCtx(user_data);
+
+    catch_unwind_cb(user_data.0, o_err, || -> Result<_, AuthError> {
+        decode_ipc_msg_impl(
+            auth,
+            msg,
+            user_data,
+            o_auth,
+            o_containers,
+            o_unregistered,
+            o_share_mdata,
+            Some(o_batch),
+            None,
+            o_err,
+        )
+    })
+}
+
+/// Decodes a given encoded IPC message, including batches of requests added by IPC
+/// version 3 and share offers added by IPC version 4, and calls a corresponding
+/// callback. The callbacks receive the encoding of the message, as with
+/// `auth_decode_ipc_msg`.
+///
+/// For a batch, `o_batch` receives the metadata of the mutable data of all the
+/// share mutable data requests in the batch, in order. For a share offer,
+/// `o_share_offer` receives the terms of the offer to present to the user.
+#[no_mangle]
+pub unsafe extern "C" fn auth_decode_ipc_msg_v4(
+    auth: *const Authenticator,
+    msg: *const c_char,
+    user_data: *mut c_void,
+    o_auth: extern "C" fn(user_data: *mut c_void, req_id: u32, encoding: u32, req: *const AuthReq),
+    o_containers: extern "C" fn(
+        user_data: *mut c_void,
+        req_id: u32,
+        encoding: u32,
+        req: *const ContainersReq,
+    ),
+    o_unregistered: extern "C" fn(
+        user_data: *mut c_void,
+        req_id: u32,
+        encoding: u32,
+        extra_data: *const u8,
+        extra_data_len: usize,
+    ),
+    o_share_mdata: extern "C" fn(
+        user_data: *mut c_void,
+        req_id: u32,
+        encoding: u32,
+        req: *const ShareMDataReq,
+        metadata: *const MetadataResponse,
+        metadata_len: usize,
+    ),
+    o_batch: extern "C" fn(
+        user_data: *mut c_void,
+        req_id: u32,
+        encoding: u32,
+        req: *const BatchReq,
+        metadata: *const MetadataResponse,
+        metadata_len: usize,
+    ),
+    o_share_offer: extern "C" fn(
+        user_data: *mut c_void,
+        req_id: u32,
+        encoding: u32,
+        req: *const ShareOfferReq,
+        terms: *const ShareOfferTerms,
+    ),
+    o_err: extern "C" fn(user_data: *mut c_void, result: *const FfiResult, response: *const c_char),
+) {
+    let user_data = OpaqueCtx(user_data);
+
+    catch_unwind_cb(user_data.0, o_err, || -> Result<_, AuthError> {
+        decode_ipc_msg_impl(
+            auth,
+            msg,
+            user_data,
+            o_auth,
+            o_containers,
+            o_unregistered,
+            o_share_mdata,
+            Some(o_batch),
+            Some(o_share_offer),
+            o_err,
+        )
+    })
+}
+
+unsafe fn decode_ipc_msg_impl(
+    auth: *const Authenticator,
+    msg: *const c_char,
+    user_data: OpaqueCtx,
+    o_auth: extern "C" fn(user_data: *mut c_void, req_id: u32, encoding: u32, req: *const AuthReq),
+    o_containers: extern "C" fn(
+        user_data: *mut c_void,
+        req_id: u32,
+        encoding: u32,
+        req: *const ContainersReq,
+    ),
+    o_unregistered: extern "C" fn(
+        user_data: *mut c_void,
+        req_id: u32,
+        encoding: u32,
+        extra_data: *const u8,
+        extra_data_len: usize,
+    ),
+    o_share_mdata: extern "C" fn(
+        user_data: *mut c_void,
+        req_id: u32,
+        encoding: u32,
+        req: *const ShareMDataReq,
+        metadata: *const MetadataResponse,
+        metadata_len: usize,
+    ),
+    o_batch: Option<
+        extern "C" fn(
+            user_data: *mut c_void,
+            req_id: u32,
+            encoding: u32,
+            req: *const BatchReq,
+            metadata: *const MetadataResponse,
+            metadata_len: usize,
+        ),
+    >,
+    o_share_offer: Option<
+        extern "C" fn(
+            user_data: *mut c_void,
+            req_id: u32,
+            encoding: u32,
+            req: *const ShareOfferReq,
+            terms: *const ShareOfferTerms,
+        ),
+    >,
+    o_err: extern "C" fn(user_data: *mut c_void, result: *const FfiResult, response: *const c_char),
+) -> Result<(), AuthError> {
+    let msg_raw = CStr::from_ptr(msg).to_str()?;
+    let encoding = IpcEncoding::of(msg_raw);
+    let msg = match decode_msg(msg_raw) {
+        Ok(msg) => msg,
+        Err(IpcError::UpgradeRequired(version)) => {
+            return upgrade_required(version, encoding, user_data, o_err);
+        }
+        Err(error) => return Err(AuthError::from(error)),
+    };
+
+    // Messages the caller can't report are rejected as if they were of an unsupported
+    // IPC version, so that the app learns the authenticator has to be upgraded.
+    let required_version = match msg {
+        IpcMsg::Req { ref req, .. } => match *req {
+            IpcReq::Batch(_) if o_batch.is_none() => Some(3),
+            IpcReq::ShareOffer(_) if o_share_offer.is_none() => Some(4),
+            _ => None,
+        },
+        _ => None,
+    };
+    if let Some(version) = required_version {
+        return upgrade_required(version, encoding, user_data, o_err);
+    }
+
+    let ffi_encoding = encoding_into_repr_c(encoding);
+
+    (*auth).send(move |client| {
+        let c1 = client.clone();
+        decode_ipc_msg(client, msg, encoding)
+            .and_then(move |msg| match msg {
+                Ok(IpcMsg::Req {
+                    req: IpcReq::Auth(auth_req),
+                    req_id,
+                }) => {
+                    let repr_c = fry!(auth_req.into_repr_c().map_err(AuthError::IpcError));
+                    o_auth(user_data.0, req_id, ffi_encoding, &repr_c);
+                    ok!(())
+                }
+                Ok(IpcMsg::Req {
+                    req: IpcReq::Containers(cont_req),
+                    req_id,
+                }) => {
+                    let repr_c = fry!(cont_req.into_repr_c().map_err(AuthError::IpcError));
+                    o_containers(user_data.0, req_id, ffi_encoding, &repr_c);
+                    ok!(())
+                }
+                Ok(IpcMsg::Req {
+                    req: IpcReq::Unregistered(extra_data),
+                    req_id,
+                }) => {
+                    o_unregistered(
+                        user_data.0,
                         req_id,
-                    }) => {
-                        o_unregistered(
+                        ffi_encoding,
+                        extra_data.as_safe_ptr(),
+                        extra_data.len(),
+                    );
+                    ok!(())
+                }
+                Ok(IpcMsg::Req {
+                    req: IpcReq::ShareMData(share_mdata_req),
+                    req_id,
+                }) => decode_share_mdata_req(&c1, &share_mdata_req)
+                    .and_then(move |metadata_cont| {
+                        let share_mdata_req_repr_c = share_mdata_req.into_repr_c()?;
+
+                        let mut ffi_metadata_cont = Vec::with_capacity(metadata_cont.len());
+                        for metadata in metadata_cont {
+                            if let Some(metadata) = metadata {
+                                ffi_metadata_cont.push(metadata);
+                            } else {
+                                ffi_metadata_cont.push(MetadataResponse::invalid());
+                            }
+                        }
+
+                        o_share_mdata(
                             user_data.0,
                             req_id,
-                            extra_data.as_safe_ptr(),
-                            extra_data.len(),
+                            ffi_encoding,
+                            &share_mdata_req_repr_c,
+                            ffi_metadata_cont.as_ptr(),
+                            ffi_metadata_cont.len(),
                         );
-                        ok!(())
-                    }
-                    Ok(IpcMsg::Req {
-                        req: IpcReq::ShareMData(share_mdata_req),
-                        req_id,
-                    }) => decode_share_mdata_req(&c1, &share_mdata_req)
-                        .and_then(move |metadata_cont| {
-                            let share_mdata_req_repr_c = share_mdata_req.into_repr_c()?;
-
-                            let mut ffi_metadata_cont = Vec::with_capacity(metadata_cont.len());
-                            for metadata in metadata_cont {
-                                if let Some(metadata) = metadata {
-                                    ffi_metadata_cont.push(metadata);
-                                } else {
-                                    ffi_metadata_cont.push(MetadataResponse::invalid());
-                                }
+
+                        Ok(())
+                    }).into_box(),
+                Ok(IpcMsg::Req {
+                    req: IpcReq::Batch(batch_req),
+                    req_id,
+                }) => {
+                    let share_mdata_reqs: Vec<_> = batch_req
+                        .items
+                        .iter()
+                        .filter_map(|item| match *item {
+                            BatchItem::ShareMData(ref req) => {
+                                Some(decode_share_mdata_req(&c1, req))
+                            }
+                            BatchItem::Auth(_) | BatchItem::Containers(_) => None,
+                        }).collect();
+
+                    future::join_all(share_mdata_reqs)
+                        .and_then(move |metadata_conts| {
+                            let batch_req_repr_c = batch_req.into_repr_c()?;
+
+                            let ffi_metadata_cont: Vec<_> = metadata_conts
+                                .into_iter()
+                                .flat_map(|metadata_cont| metadata_cont.into_iter())
+                                .map(|metadata| metadata.unwrap_or_else(MetadataResponse::invalid))
+                                .collect();
+
+                            if let Some(o_batch) = o_batch {
+                                o_batch(
+                                    user_data.0,
+                                    req_id,
+                                    ffi_encoding,
+                                    &batch_req_repr_c,
+                                    ffi_metadata_cont.as_ptr(),
+                                    ffi_metadata_cont.len(),
+                                );
                             }
 
-                            o_share_mdata(
+                            Ok(())
+                        }).into_box()
+                }
+                Ok(IpcMsg::Req {
+                    req: IpcReq::ShareOffer(share_offer_req),
+                    req_id,
+                }) => decode_share_offer_req(&c1, &share_offer_req)
+                    .and_then(move |terms| {
+                        let share_offer_req_repr_c = share_offer_req.into_repr_c()?;
+                        if let Some(o_share_offer) = o_share_offer {
+                            o_share_offer(
                                 user_data.0,
                                 req_id,
-                                &share_mdata_req_repr_c,
-                                ffi_metadata_cont.as_ptr(),
-                                ffi_metadata_cont.len(),
+                                ffi_encoding,
+                                &share_offer_req_repr_c,
+                                &terms,
                             );
+                        }
+                        Ok(())
+                    }).into_box(),
+                Err((error_code, description, err)) => {
+                    let res = FfiResult {
+                        error_code,
+                        description: description.as_ptr(),
+                    };
+                    o_err(user_data.0, &res, err.as_ptr());
+                    ok!(())
+                }
+                Ok(IpcMsg::Resp { .. })
+                | Ok(IpcMsg::Revoked { .. })
+                | Ok(IpcMsg::Err(..))
+                | Ok(IpcMsg::SealedResp { .. }) => {
+                    let err = AuthError::Unexpected(
+                        "Unexpected msg \
+                         type"
+                            .to_owned(),
+                    );
+                    call_result_cb!(Err::<(), _>(err), user_data, o_err);
+                    ok!(())
+                }
+            }).map_err(move |err| {
+                call_result_cb!(Err::<(), _>(err), user_data, o_err);
+            }).into_box()
+            .into()
+    })?;
+    Ok(())
+}
 
-                            Ok(())
-                        }).into_box(),
-                    Err((error_code, description, err)) => {
+// Reports that the message uses a newer IPC protocol version than this authenticator
+// supports, along with an encoded response for the app.
+fn upgrade_required(
+    version: u32,
+    encoding: IpcEncoding,
+    user_data: OpaqueCtx,
+    o_err: extern "C" fn(user_data: *mut c_void, result: *const FfiResult, response: *const c_char),
+) -> Result<(), AuthError> {
+    let resp = encode_response(&IpcMsg::Err(IpcError::UpgradeRequired(version)), encoding)?;
+    let (error_code, description) = ffi_error!(AuthError::from(IpcError::UpgradeRequired(
+        version
+    )));
+    let res = FfiResult {
+        error_code,
+        description: description.as_ptr(),
+    };
+    o_err(user_data.0, &res, resp.as_ptr());
+    Ok(())
+}
+
+/// Encode share mutable data response. `encoding` is the encoding of the request, as
+/// received when decoding it.
+#[no_mangle]
+pub unsafe extern "C" fn encode_share_mdata_resp(
+    auth: *const Authenticator,
+    req: *const ShareMDataReq,
+    req_id: u32,
+    encoding: u32,
+    is_granted: bool,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(user_data: *mut c_void, result: *const FfiResult, response: *const c_char),
+) {
+    let user_data = OpaqueCtx(user_data);
+
+    catch_unwind_cb(user_data.0, o_cb, || -> Result<(), AuthError> {
+        let encoding = encoding_from_repr_c(encoding)?;
+        let share_mdata_req = NativeShareMDataReq::clone_from_repr_c(req)?;
+        let app_id = share_mdata_req.app.id.clone();
+        let action = AuditAction::ShareMData(share_mdata_req.mdata.clone());
+
+        if is_granted {
+            (*auth).send(move |client| {
+                grant_share_mdata(client, share_mdata_req)
+                    .and_then(move |()| {
+                        let resp = encode_response(
+                            &IpcMsg::Resp {
+                                req_id,
+                                resp: IpcResp::ShareMData(Ok(())),
+                            },
+                            encoding,
+                        )?;
+                        o_cb(user_data.0, FFI_RESULT_OK, resp.as_ptr());
+                        Ok(())
+                    }).map_err(move |e| {
+                        call_result_cb!(Err::<(), _>(e), user_data, o_cb);
+                    }).into_box()
+                    .into()
+            })?;
+        } else {
+            (*auth).send(move |client| {
+                config::record_audit_entry(client, AuditEntry::new(app_id, action, false))
+                    .and_then(move |()| {
+                        let resp = encode_response(
+                            &IpcMsg::Resp {
+                                req_id,
+                                resp: IpcResp::ShareMData(Err(IpcError::ShareMDataDenied)),
+                            },
+                            encoding,
+                        )?;
+                        let (error_code, description) =
+                            ffi_error!(AuthError::from(IpcError::ShareMDataDenied));
                         let res = FfiResult {
                             error_code,
                             description: description.as_ptr(),
                         };
-                        o_err(user_data.0, &res, err.as_ptr());
-                        ok!(())
-                    }
-                    Ok(IpcMsg::Resp { .. }) | Ok(IpcMsg::Revoked { .. }) | Ok(IpcMsg::Err(..)) => {
-                        let err = AuthError::Unexpected(
-                            "Unexpected msg \
-                             type"
-                                .to_owned(),
-                        );
-                        call_result_cb!(Err::<(), _>(err), user_data, o_err);
-                        ok!(())
-                    }
-                }).map_err(move |err| {
-                    call_result_cb!(Err::<(), _>(err), user_data, o_err);
-                }).into_box()
-                .into()
-        })?;
+                        o_cb(user_data.0, &res, resp.as_ptr());
+                        Ok(())
+                    }).map_err(move |e| {
+                        call_result_cb!(Err::<(), _>(e), user_data, o_cb);
+                    }).into_box()
+                    .into()
+            })?;
+        }
         Ok(())
     })
 }
 
-/// Encode share mutable data response.
+/// Accepts or declines an offer of access to a mutable data made by another user
+/// and encodes the response. If accepted, the response carries the acceptance,
+/// which the app has to deliver to the app which made the offer. `encoding` is the
+/// encoding of the request, as received when decoding it.
 #[no_mangle]
-pub unsafe extern "C" fn encode_share_mdata_resp(
+pub unsafe extern "C" fn encode_share_offer_resp(
     auth: *const Authenticator,
-    req: *const ShareMDataReq,
+    req: *const ShareOfferReq,
     req_id: u32,
+    encoding: u32,
     is_granted: bool,
     user_data: *mut c_void,
     o_cb: extern "C" fn(user_data: *mut c_void, result: *const FfiResult, response: *const c_char),
@@ -197,57 +603,51 @@ pub unsafe extern "C" fn encode_share_mdata_resp(
     let user_data = OpaqueCtx(user_data);
 
     catch_unwind_cb(user_data.0, o_cb, || -> Result<(), AuthError> {
-        let share_mdata_req = NativeShareMDataReq::clone_from_repr_c(req)?;
+        let encoding = encoding_from_repr_c(encoding)?;
+        let share_offer_req = NativeShareOfferReq::clone_from_repr_c(req)?;
+
         if is_granted {
             (*auth).send(move |client| {
-                let client_cloned0 = client.clone();
-                let client_cloned1 = client.clone();
-                let user_data = user_data.0;
-                config::get_app(client, &share_mdata_req.app.id)
-                    .and_then(move |app_info| {
-                        let user = User::Key(app_info.keys.sign_pk);
-                        let num_mdata = share_mdata_req.mdata.len();
-                        stream::iter_ok(share_mdata_req.mdata.into_iter())
-                            .map(move |mdata| {
-                                client_cloned0
-                                    .get_mdata_shell(mdata.name, mdata.type_tag)
-                                    .map(|md| (md.version(), mdata))
-                            }).buffer_unordered(num_mdata)
-                            .map(move |(version, mdata)| {
-                                client_cloned1.set_mdata_user_permissions(
-                                    mdata.name,
-                                    mdata.type_tag,
-                                    user,
-                                    mdata.perms,
-                                    version + 1,
-                                )
-                            }).buffer_unordered(num_mdata)
-                            .map_err(AuthError::CoreError)
-                            .for_each(|()| Ok(()))
-                            .and_then(move |()| {
-                                let resp = encode_response(&IpcMsg::Resp {
-                                    req_id,
-                                    resp: IpcResp::ShareMData(Ok(())),
-                                }).map_err(AuthError::IpcError)?;
-                                o_cb(user_data, FFI_RESULT_OK, resp.as_ptr());
-                                Ok(())
-                            }).into_box()
+                grant_share_offer(client, share_offer_req)
+                    .and_then(move |granted| {
+                        let resp = encode_response(
+                            &IpcMsg::Resp {
+                                req_id,
+                                resp: IpcResp::ShareOffer(Ok(granted)),
+                            },
+                            encoding,
+                        )?;
+                        o_cb(user_data.0, FFI_RESULT_OK, resp.as_ptr());
+                        Ok(())
                     }).map_err(move |e| {
                         call_result_cb!(Err::<(), _>(e), user_data, o_cb);
                     }).into_box()
                     .into()
             })?;
         } else {
-            let resp = encode_response(&IpcMsg::Resp {
-                req_id,
-                resp: IpcResp::ShareMData(Err(IpcError::ShareMDataDenied)),
+            (*auth).send(move |client| {
+                deny_share_offer(client, share_offer_req)
+                    .and_then(move |()| {
+                        let resp = encode_response(
+                            &IpcMsg::Resp {
+                                req_id,
+                                resp: IpcResp::ShareOffer(Err(IpcError::ShareMDataDenied)),
+                            },
+                            encoding,
+                        )?;
+                        let (error_code, description) =
+                            ffi_error!(AuthError::from(IpcError::ShareMDataDenied));
+                        let res = FfiResult {
+                            error_code,
+                            description: description.as_ptr(),
+                        };
+                        o_cb(user_data.0, &res, resp.as_ptr());
+                        Ok(())
+                    }).map_err(move |e| {
+                        call_result_cb!(Err::<(), _>(e), user_data, o_cb);
+                    }).into_box()
+                    .into()
             })?;
-            let (error_code, description) = ffi_error!(AuthError::from(IpcError::ShareMDataDenied));
-            let res = FfiResult {
-                error_code,
-                description: description.as_ptr(),
-            };
-            o_cb(user_data.0, &res, resp.as_ptr());
         }
         Ok(())
     })
@@ -269,7 +669,7 @@ pub unsafe extern "C" fn auth_revoke_app(
         (*auth).send(move |client| {
             revoke_app(client, &app_id)
                 .and_then(move |_| {
-                    let resp = encode_response(&IpcMsg::Revoked { app_id })?;
+                    let resp = encode_response(&IpcMsg::Revoked { app_id }, IpcEncoding::Base32)?;
                     o_cb(user_data.0, FFI_RESULT_OK, resp.as_ptr());
                     Ok(())
                 }).map_err(move |e| {
@@ -303,10 +703,12 @@ pub unsafe extern "C" fn auth_flush_app_revocation_queue(
     })
 }
 
-/// Encodes a response to unregistered client authentication request.
+/// Encodes a response to unregistered client authentication request, with the
+/// `encoding` of the request.
 #[no_mangle]
 pub unsafe extern "C" fn encode_unregistered_resp(
     req_id: u32,
+    encoding: u32,
     is_granted: bool,
     user_data: *mut c_void,
     o_cb: extern "C" fn(user_data: *mut c_void, result: *const FfiResult, response: *const c_char),
@@ -314,20 +716,27 @@ pub unsafe extern "C" fn encode_unregistered_resp(
     let user_data = OpaqueCtx(user_data);
 
     catch_unwind_cb(user_data.0, o_cb, || -> Result<(), AuthError> {
+        let encoding = encoding_from_repr_c(encoding)?;
         if !is_granted {
-            let resp = encode_response(&IpcMsg::Resp {
-                req_id,
-                resp: IpcResp::Unregistered(Err(IpcError::AuthDenied)),
-            })?;
+            let resp = encode_response(
+                &IpcMsg::Resp {
+                    req_id,
+                    resp: IpcResp::Unregistered(Err(IpcError::AuthDenied)),
+                },
+                encoding,
+            )?;
 
             o_cb(user_data.0, FFI_RESULT_OK, resp.as_ptr());
         } else {
             let bootstrap_cfg = client::bootstrap_config()?;
 
-            let resp = encode_response(&IpcMsg::Resp {
-                req_id,
-                resp: IpcResp::Unregistered(Ok(bootstrap_cfg)),
-            })?;
+            let resp = encode_response(
+                &IpcMsg::Resp {
+                    req_id,
+                    resp: IpcResp::Unregistered(Ok(bootstrap_cfg)),
+                },
+                encoding,
+            )?;
 
             o_cb(user_data.0, FFI_RESULT_OK, resp.as_ptr());
         }
@@ -335,12 +744,13 @@ pub unsafe extern "C" fn encode_unregistered_resp(
     })
 }
 
-/// Provides and encodes an Authenticator response.
+/// Provides and encodes an Authenticator response, with the `encoding` of the request.
 #[no_mangle]
 pub unsafe extern "C" fn encode_auth_resp(
     auth: *const Authenticator,
     req: *const AuthReq,
     req_id: u32,
+    encoding: u32,
     is_granted: bool,
     user_data: *mut c_void,
     o_cb: extern "C" fn(user_data: *mut c_void, result: *const FfiResult, response: *const c_char),
@@ -348,32 +758,63 @@ pub unsafe extern "C" fn encode_auth_resp(
     let user_data = OpaqueCtx(user_data);
 
     catch_unwind_cb(user_data.0, o_cb, || -> Result<(), AuthError> {
+        let encoding = encoding_from_repr_c(encoding)?;
         let auth_req = NativeAuthReq::clone_from_repr_c(req)?;
+        let app_id = auth_req.app.id.clone();
+        let action = AuditAction::Auth {
+            app_container: auth_req.app_container,
+            containers: auth_req.containers.clone(),
+        };
+
+        let resp_key = auth_req.resp_key;
 
         if !is_granted {
-            let resp = encode_response(&IpcMsg::Resp {
-                req_id,
-                resp: IpcResp::Auth(Err(IpcError::AuthDenied)),
-            })?;
+            (*auth).send(move |client| {
+                let c2 = client.clone();
 
-            o_cb(user_data.0, FFI_RESULT_OK, resp.as_ptr());
+                config::record_audit_entry(client, AuditEntry::new(app_id, action, false))
+                    .and_then(move |()| {
+                        let resp = encode_auth_response(
+                            &c2,
+                            req_id,
+                            IpcResp::Auth(Err(IpcError::AuthDenied)),
+                            resp_key,
+                            encoding,
+                        )?;
+
+                        o_cb(user_data.0, FFI_RESULT_OK, resp.as_ptr());
+                        Ok(())
+                    }).map_err(move |e| {
+                        call_result_cb!(Err::<(), _>(e), user_data, o_cb);
+                    }).into_box()
+                    .into()
+            })?;
         } else {
             (*auth).send(move |client| {
-                app_auth::authenticate(client, auth_req)
+                let c2 = client.clone();
+                let c3 = client.clone();
+
+                grant_auth(client, auth_req)
                     .and_then(move |auth_granted| {
-                        let resp = encode_response(&IpcMsg::Resp {
+                        let resp = encode_auth_response(
+                            &c2,
                             req_id,
-                            resp: IpcResp::Auth(Ok(auth_granted)),
-                        })?;
+                            IpcResp::Auth(Ok(auth_granted)),
+                            resp_key,
+                            encoding,
+                        )?;
 
                         o_cb(user_data.0, FFI_RESULT_OK, resp.as_ptr());
                         Ok(())
                     }).or_else(move |e| -> Result<(), AuthError> {
                         let (error_code, description) = ffi_error!(e);
-                        let resp = encode_response(&IpcMsg::Resp {
+                        let resp = encode_auth_response(
+                            &c3,
                             req_id,
-                            resp: IpcResp::Auth(Err(e.into())),
-                        })?;
+                            IpcResp::Auth(Err(e.into())),
+                            resp_key,
+                            encoding,
+                        )?;
                         let res = FfiResult {
                             error_code,
                             description: description.as_ptr(),
@@ -391,12 +832,57 @@ pub unsafe extern "C" fn encode_auth_resp(
     })
 }
 
-/// Update containers permissions for an App.
+/// Provides and encodes a response to a batch of requests. `approvals` holds the
+/// user's decision for each request of the batch, in order. If the batch takes a
+/// single decision, all the decisions have to be the same. The approved requests
+/// are granted one by one, so a request failing to be granted doesn't undo the
+/// requests granted before it: its failure is reported in its own response. The
+/// response is encoded with the `encoding` of the request.
+#[no_mangle]
+pub unsafe extern "C" fn encode_batch_resp(
+    auth: *const Authenticator,
+    req: *const BatchReq,
+    req_id: u32,
+    encoding: u32,
+    approvals: *const bool,
+    approvals_len: usize,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(user_data: *mut c_void, result: *const FfiResult, response: *const c_char),
+) {
+    let user_data = OpaqueCtx(user_data);
+
+    catch_unwind_cb(user_data.0, o_cb, || -> Result<(), AuthError> {
+        let encoding = encoding_from_repr_c(encoding)?;
+        let batch_req = NativeBatchReq::clone_from_repr_c(req)?;
+        let approvals = vec_clone_from_raw_parts(approvals, approvals_len);
+        let resp_key = batch_req.resp_key();
+
+        (*auth).send(move |client| {
+            let c2 = client.clone();
+
+            process_batch(client, batch_req, approvals)
+                .and_then(move |resp| {
+                    let resp = encode_auth_response(&c2, req_id, resp, resp_key, encoding)?;
+                    o_cb(user_data.0, FFI_RESULT_OK, resp.as_ptr());
+                    Ok(())
+                }).map_err(move |e| {
+                    call_result_cb!(Err::<(), _>(e), user_data, o_cb);
+                }).into_box()
+                .into()
+        })?;
+
+        Ok(())
+    })
+}
+
+/// Update containers permissions for an App, and encode the response with the `encoding`
+/// of the request.
 #[no_mangle]
 pub unsafe extern "C" fn encode_containers_resp(
     auth: *const Authenticator,
     req: *const ContainersReq,
     req_id: u32,
+    encoding: u32,
     is_granted: bool,
     user_data: *mut c_void,
     o_cb: extern "C" fn(user_data: *mut c_void, result: *const FfiResult, response: *const c_char),
@@ -404,73 +890,53 @@ pub unsafe extern "C" fn encode_containers_resp(
     let user_data = OpaqueCtx(user_data);
 
     catch_unwind_cb(user_data.0, o_cb, || -> Result<(), AuthError> {
+        let encoding = encoding_from_repr_c(encoding)?;
         let cont_req = NativeContainersReq::clone_from_repr_c(req)?;
+        let action = AuditAction::Containers(cont_req.containers.clone());
 
         if !is_granted {
-            let resp = encode_response(&IpcMsg::Resp {
-                req_id,
-                resp: IpcResp::Containers(Err(IpcError::AuthDenied)),
-            })?;
-
-            o_cb(user_data.0, FFI_RESULT_OK, resp.as_ptr());
-        } else {
-            let permissions = cont_req.containers.clone();
             let app_id = cont_req.app.id.clone();
 
             (*auth).send(move |client| {
-                let c2 = client.clone();
-                let c3 = client.clone();
-                let c4 = client.clone();
-
-                config::get_app(client, &app_id)
-                    .and_then(move |app| {
-                        let sign_pk = app.keys.sign_pk;
-                        update_container_perms(&c2, permissions, sign_pk)
-                            .map(move |perms| (app, perms))
-                    }).and_then(move |(app, mut perms)| {
-                        let app_keys = app.keys;
-
-                        access_container::fetch_entry(&c3, &app_id, app_keys.clone()).then(
-                            move |res| {
-                                let version = match res {
-                                    // Updating an existing entry
-                                    Ok((version, Some(mut existing_perms))) => {
-                                        for (key, val) in perms {
-                                            let _ = existing_perms.insert(key, val);
-                                        }
-                                        perms = existing_perms;
-
-                                        version + 1
-                                    }
-
-                                    // Adding a new access container entry
-                                    Ok((_, None))
-                                    | Err(AuthError::CoreError(CoreError::RoutingClientError(
-                                        ClientError::NoSuchEntry,
-                                    ))) => 0,
-
-                                    // Error has occurred while trying to get an
-                                    // existing entry
-                                    Err(e) => return Err(e),
-                                };
-                                Ok((version, app_id, app_keys, perms))
+                config::record_audit_entry(client, AuditEntry::new(app_id, action, false))
+                    .and_then(move |()| {
+                        let resp = encode_response(
+                            &IpcMsg::Resp {
+                                req_id,
+                                resp: IpcResp::Containers(Err(IpcError::AuthDenied)),
                             },
-                        )
-                    }).and_then(move |(version, app_id, app_keys, perms)| {
-                        access_container::put_entry(&c4, &app_id, &app_keys, &perms, version)
-                    }).and_then(move |_| {
-                        let resp = encode_response(&IpcMsg::Resp {
-                            req_id,
-                            resp: IpcResp::Containers(Ok(())),
-                        })?;
+                            encoding,
+                        )?;
+
+                        o_cb(user_data.0, FFI_RESULT_OK, resp.as_ptr());
+                        Ok(())
+                    }).map_err(move |e| {
+                        call_result_cb!(Err::<(), _>(e), user_data, o_cb);
+                    }).into_box()
+                    .into()
+            })?;
+        } else {
+            (*auth).send(move |client| {
+                grant_containers(client, cont_req)
+                    .and_then(move |()| {
+                        let resp = encode_response(
+                            &IpcMsg::Resp {
+                                req_id,
+                                resp: IpcResp::Containers(Ok(())),
+                            },
+                            encoding,
+                        )?;
                         o_cb(user_data.0, FFI_RESULT_OK, resp.as_ptr());
                         Ok(())
                     }).or_else(move |e| -> Result<(), AuthError> {
                         let (error_code, description) = ffi_error!(e);
-                        let resp = encode_response(&IpcMsg::Resp {
-                            req_id,
-                            resp: IpcResp::Containers(Err(e.into())),
-                        })?;
+                        let resp = encode_response(
+                            &IpcMsg::Resp {
+                                req_id,
+                                resp: IpcResp::Containers(Err(e.into())),
+                            },
+                            encoding,
+                        )?;
                         let res = FfiResult {
                             error_code,
                             description: description.as_ptr(),
@@ -486,3 +952,18 @@ pub unsafe extern "C" fn encode_containers_resp(
         Ok(())
     });
 }
+
+fn encoding_into_repr_c(encoding: IpcEncoding) -> u32 {
+    match encoding {
+        IpcEncoding::Base32 => IPC_ENCODING_BASE32,
+        IpcEncoding::Json => IPC_ENCODING_JSON,
+    }
+}
+
+fn encoding_from_repr_c(encoding: u32) -> Result<IpcEncoding, AuthError> {
+    match encoding {
+        IPC_ENCODING_BASE32 => Ok(IpcEncoding::Base32),
+        IPC_ENCODING_JSON => Ok(IpcEncoding::Json),
+        _ => Err(AuthError::IpcError(IpcError::InvalidMsg)),
+    }
+}
@@ -8,17 +8,22 @@
 
 /// Apps management
 pub mod apps;
+/// Root containers management
+pub mod containers;
+/// User devices management
+pub mod devices;
 /// Authenticator communication with apps
 pub mod ipc;
 /// Logging utilities
 pub mod logging;
 
+use self::containers::{templates_clone_from_repr_c, ContainerTemplate};
 use config_file_handler;
 use errors::AuthError;
 use ffi_utils::{catch_unwind_cb, from_c_str, FfiResult, OpaqueCtx, FFI_RESULT_OK};
 use futures::Future;
 use safe_core::ffi::AccountInfo;
-use safe_core::{Client, FutureExt};
+use safe_core::{Client, CoreError, FutureExt};
 use std::ffi::{CStr, CString, OsStr};
 use std::os::raw::{c_char, c_void};
 use Authenticator;
@@ -64,6 +69,51 @@ pub unsafe extern "C" fn create_acc(
     })
 }
 
+/// Create a registered client with the given root containers created in addition
+/// to the standard ones. Behaves like `create_acc` otherwise.
+#[no_mangle]
+pub unsafe extern "C" fn create_acc_with_containers(
+    account_locator: *const c_char,
+    account_password: *const c_char,
+    invitation: *const c_char,
+    containers: *const ContainerTemplate,
+    containers_len: usize,
+    user_data: *mut c_void,
+    o_disconnect_notifier_cb: extern "C" fn(user_data: *mut c_void),
+    o_cb: extern "C" fn(
+        user_data: *mut c_void,
+        result: *const FfiResult,
+        authenticator: *mut Authenticator,
+    ),
+) {
+    let user_data = OpaqueCtx(user_data);
+
+    catch_unwind_cb(user_data, o_cb, || -> Result<_, AuthError> {
+        trace!("Authenticator - create a client account with custom containers.");
+
+        let acc_locator = from_c_str(account_locator)?;
+        let acc_password = from_c_str(account_password)?;
+        let invitation = from_c_str(invitation)?;
+        let containers = templates_clone_from_repr_c(containers, containers_len)?;
+
+        let authenticator = Authenticator::create_acc_with_containers(
+            acc_locator,
+            acc_password,
+            invitation,
+            containers,
+            move || o_disconnect_notifier_cb(user_data.0),
+        )?;
+
+        o_cb(
+            user_data.0,
+            FFI_RESULT_OK,
+            Box::into_raw(Box::new(authenticator)),
+        );
+
+        Ok(())
+    })
+}
+
 /// Log into a registered account. This or any one of the other companion
 /// functions to get an authenticator instance must be called before initiating
 /// any operation allowed for authenticator. The `user_data` parameter corresponds to the
@@ -103,6 +153,10 @@ pub unsafe extern "C" fn login(
 }
 
 /// Try to restore a failed connection with the network.
+///
+/// Breaking change: the connection which is replaced isn't reported as lost, so
+/// `o_disconnect_notifier_cb` is no longer called when reconnecting while the authenticator is
+/// still connected.
 #[no_mangle]
 pub unsafe extern "C" fn auth_reconnect(
     auth: *mut Authenticator,
@@ -123,6 +177,30 @@ pub unsafe extern "C" fn auth_reconnect(
     })
 }
 
+/// Set the callback called with the code of every `NetworkEvent` of the connection, and the
+/// number of the attempt when reconnecting, replacing the previous one. Unlike
+/// `o_disconnect_notifier_cb`, it's also called when the authenticator is connecting,
+/// connected, reconnecting or gave up connecting. `observer_user_data` is passed to it, and
+/// must stay valid until the authenticator is freed.
+#[no_mangle]
+pub unsafe extern "C" fn auth_set_network_observer(
+    auth: *const Authenticator,
+    observer_user_data: *mut c_void,
+    o_observer_cb: extern "C" fn(user_data: *mut c_void, event: i32, attempt: u32),
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(user_data: *mut c_void, result: *const FfiResult),
+) {
+    catch_unwind_cb(user_data, o_cb, || -> Result<_, AuthError> {
+        let observer_user_data = OpaqueCtx(observer_user_data);
+        (*auth).set_network_observer(move |event| {
+            let attempt = event.attempt();
+            o_observer_cb(observer_user_data.0, event.into(), attempt)
+        });
+        o_cb(user_data, FFI_RESULT_OK);
+        Ok(())
+    })
+}
+
 /// Get the account usage statistics.
 #[no_mangle]
 pub unsafe extern "C" fn auth_account_info(
@@ -200,6 +278,53 @@ pub unsafe extern "C" fn auth_free(auth: *mut Authenticator) {
     let _ = Box::from_raw(auth);
 }
 
+/// Create a cancellable operation of the authenticator. The returned handle is used in place
+/// of `auth` for the calls which belong to the operation. Once `auth_operation_cancel` is called
+/// with it, their pending network requests are abandoned and they fail with
+/// `ERR_OPERATION_CANCELLED`.
+/// The handle must be released with `auth_operation_free`, and can't be used once `auth` is
+/// freed.
+#[no_mangle]
+pub unsafe extern "C" fn auth_operation_new(
+    auth: *const Authenticator,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(
+        user_data: *mut c_void,
+        result: *const FfiResult,
+        operation: *mut Authenticator,
+    ),
+) {
+    catch_unwind_cb(user_data, o_cb, || -> Result<_, AuthError> {
+        let operation = (*auth).operation();
+        o_cb(user_data, FFI_RESULT_OK, Box::into_raw(Box::new(operation)));
+        Ok(())
+    })
+}
+
+/// Cancel the operation. This can be called from any thread.
+#[no_mangle]
+pub unsafe extern "C" fn auth_operation_cancel(
+    operation: *const Authenticator,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(user_data: *mut c_void, result: *const FfiResult),
+) {
+    catch_unwind_cb(user_data, o_cb, || -> Result<_, AuthError> {
+        let token = (*operation)
+            .operation_token()
+            .ok_or(CoreError::OperationForbidden)?;
+        token.cancel();
+        o_cb(user_data, FFI_RESULT_OK);
+        Ok(())
+    })
+}
+
+/// Discard and clean up the operation handle. This doesn't cancel the operation.
+/// Using `operation` after a call to this function is undefined behaviour.
+#[no_mangle]
+pub unsafe extern "C" fn auth_operation_free(operation: *mut Authenticator) {
+    let _ = Box::from_raw(operation);
+}
+
 /// Returns true if this crate was compiled against mock-routing.
 #[no_mangle]
 pub extern "C" fn auth_is_mock() -> bool {
@@ -209,6 +334,7 @@ pub extern "C" fn auth_is_mock() -> bool {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use access_container;
     use ffi::auth_is_mock;
     use ffi_utils::test_utils::call_1;
     use routing::ImmutableData;
@@ -216,6 +342,8 @@ mod tests {
     use safe_core::utils;
     use std::ffi::CString;
     use std::os::raw::c_void;
+    use std_dirs::public_containers;
+    use test_utils::run;
     use Authenticator;
 
     // Test mock detection when compiled against mock-routing.
@@ -273,6 +401,57 @@ mod tests {
         }
     }
 
+    // Test creating an account with user-defined root containers.
+    // 1. Create an account with a public unencrypted and a private encrypted container.
+    // 2. Verify that both are in the authenticator entry with the requested encryption.
+    // 3. Verify that only the public one is of the public type.
+    #[test]
+    fn create_account_with_containers() {
+        let acc_locator = unwrap!(CString::new(unwrap!(utils::generate_random_string(10))));
+        let acc_password = unwrap!(CString::new(unwrap!(utils::generate_random_string(10))));
+        let invitation = unwrap!(CString::new(unwrap!(utils::generate_random_string(10))));
+
+        let blog = unwrap!(CString::new("_blog"));
+        let diary = unwrap!(CString::new("_diary"));
+        let containers = [
+            ContainerTemplate {
+                name: blog.as_ptr(),
+                encrypted: false,
+                public: true,
+            },
+            ContainerTemplate {
+                name: diary.as_ptr(),
+                encrypted: true,
+                public: false,
+            },
+        ];
+
+        let auth: *mut Authenticator = unsafe {
+            unwrap!(call_1(|ud, cb| create_acc_with_containers(
+                acc_locator.as_ptr(),
+                acc_password.as_ptr(),
+                invitation.as_ptr(),
+                containers.as_ptr(),
+                containers.len(),
+                ud,
+                disconnect_cb,
+                cb,
+            )))
+        };
+
+        let dirs = run(unsafe { &*auth }, |client| {
+            access_container::fetch_authenticator_entry(client).map(|(_, dirs)| dirs)
+        });
+        assert!(unwrap!(dirs.get("_blog")).enc_info.is_none());
+        assert!(unwrap!(dirs.get("_diary")).enc_info.is_some());
+
+        let public = run(unsafe { &*auth }, |client| public_containers(client));
+        assert!(public.contains("_blog"));
+        assert!(!public.contains("_diary"));
+
+        unsafe { auth_free(auth) };
+    }
+
     // Test disconnection and reconnection with the authenticator.
     #[cfg(all(test, feature = "use-mock-routing"))]
     #[test]
@@ -325,12 +504,10 @@ mod tests {
                 _ => panic!("Disconnect callback was called"),
             }
 
-            // The reconnection should be fine if we're already connected.
+            // The reconnection should be fine if we're already connected, and the disconnection of
+            // the replaced routing isn't reported.
             unsafe { unwrap!(call_0(|ud, cb| auth_reconnect(auth, ud, cb))) };
 
-            // disconnect_cb should be called.
-            unwrap!(rx.recv_timeout(Duration::from_secs(15)));
-
             // This should time out.
             let result = rx.recv_timeout(Duration::from_secs(1));
             match result {
@@ -348,6 +525,58 @@ mod tests {
         }
     }
 
+    // Test that the network observer is told about every change of the connection.
+    #[cfg(all(test, feature = "use-mock-routing"))]
+    #[test]
+    fn network_observer() {
+        use ffi_utils::test_utils::call_0;
+        use safe_core::NetworkEvent;
+        use std::sync::mpsc::{self, Sender};
+        use std::time::Duration;
+        use test_utils::create_account_and_login;
+
+        let auth = Box::into_raw(Box::new(create_account_and_login()));
+
+        let (tx, rx) = mpsc::channel::<i32>();
+        let tx_ptr: *const _ = &tx;
+
+        unsafe {
+            unwrap!(call_0(|ud, cb| auth_set_network_observer(
+                auth,
+                tx_ptr as *mut c_void,
+                observer_cb,
+                ud,
+                cb,
+            )));
+            unwrap!((*auth).send(move |client| {
+                client.simulate_network_disconnect();
+                None
+            }));
+        }
+
+        let disconnected: i32 = NetworkEvent::Disconnected.into();
+        let event = unwrap!(rx.recv_timeout(Duration::from_secs(15)));
+        assert_eq!(event, disconnected);
+
+        unsafe { unwrap!(call_0(|ud, cb| auth_reconnect(auth, ud, cb))) };
+
+        let connecting: i32 = NetworkEvent::Connecting.into();
+        let event = unwrap!(rx.recv_timeout(Duration::from_secs(15)));
+        assert_eq!(event, connecting);
+        let connected: i32 = NetworkEvent::Connected.into();
+        let event = unwrap!(rx.recv_timeout(Duration::from_secs(15)));
+        assert_eq!(event, connected);
+
+        unsafe { auth_free(auth) };
+
+        extern "C" fn observer_cb(user_data: *mut c_void, event: i32, _attempt: u32) {
+            unsafe {
+                let tx = user_data as *const Sender<i32>;
+                unwrap!((*tx).send(event));
+            }
+        }
+    }
+
     // Test account usage statistics before and after a mutation.
     #[test]
     fn account_info() {
@@ -391,6 +620,82 @@ mod tests {
         unsafe { auth_free(auth) };
     }
 
+    // Test that cancelling an operation fails its pending requests with
+    // `ERR_OPERATION_CANCELLED`, while the calls made through the authenticator handle itself
+    // aren't affected.
+    #[cfg(feature = "use-mock-routing")]
+    #[test]
+    fn cancel_operation() {
+        use errors::ERR_OPERATION_CANCELLED;
+        use ffi_utils::test_utils::call_0;
+        use std::thread;
+        use std::time::Duration;
+        use test_utils::create_account_and_login;
+
+        let auth = Box::into_raw(Box::new(create_account_and_login()));
+
+        // Keep the requests pending until they're cancelled.
+        unsafe {
+            unwrap!((*auth).send(|client| {
+                client.set_simulate_timeout(true);
+                None
+            }));
+        }
+
+        let operation: *mut Authenticator =
+            unsafe { unwrap!(call_1(|ud, cb| auth_operation_new(auth, ud, cb))) };
+
+        let operation_addr = operation as usize;
+        let canceller = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(500));
+            let operation = operation_addr as *const Authenticator;
+            unsafe { unwrap!(call_0(|ud, cb| auth_operation_cancel(operation, ud, cb))) };
+        });
+
+        let stats: Result<AccountInfo, i32> =
+            unsafe { call_1(|ud, cb| auth_account_info(operation, ud, cb)) };
+        match stats {
+            Err(ERR_OPERATION_CANCELLED) => (),
+            Err(_) => panic!("Unexpected error"),
+            Ok(_) => panic!("Unexpected success"),
+        }
+        unwrap!(canceller.join());
+
+        // The authenticator handle isn't an operation, so it can't be cancelled.
+        let res = unsafe { call_0(|ud, cb| auth_operation_cancel(auth, ud, cb)) };
+        assert!(res.is_err());
+
+        unsafe {
+            unwrap!((*auth).send(|client| {
+                client.set_simulate_timeout(false);
+                None
+            }));
+        }
+
+        let stats: AccountInfo =
+            unsafe { unwrap!(call_1(|ud, cb| auth_account_info(auth, ud, cb))) };
+        assert!(stats.mutations_available > 0);
+
+        unsafe {
+            auth_operation_free(operation);
+            auth_free(auth);
+        }
+    }
+
+    // Test that a cancelled self-encryption storage error is reported as
+    // `ERR_OPERATION_CANCELLED`.
+    #[test]
+    fn self_encryption_cancelled_error_code() {
+        use errors::ERR_OPERATION_CANCELLED;
+        use ffi_utils::ErrorCode;
+        use safe_core::{CoreError, SelfEncryptionStorageError};
+        use self_encryption::SelfEncryptionError;
+
+        let err = SelfEncryptionStorageError(Box::new(CoreError::OperationCancelled));
+        let err = AuthError::from(CoreError::SelfEncryption(SelfEncryptionError::Storage(err)));
+        assert_eq!(err.error_code(), ERR_OPERATION_CANCELLED);
+    }
+
     extern "C" fn disconnect_cb(_user_data: *mut c_void) {
         panic!("Disconnect occurred")
     }
@@ -8,43 +8,86 @@
 
 use super::{AuthError, AuthFuture};
 use access_container;
-use app_auth::{app_state, AppState};
+use app_auth::{self, app_state, AppState};
 use client::AuthClient;
-use config;
+use config::{self, AppInfo, AuditAction, AuditEntry};
+use devices;
 use ffi_utils::StringError;
 use futures::future::{self, Either};
-use futures::Future;
+use futures::{stream, Future, Stream};
 use maidsafe_utilities::serialisation::deserialise;
 use routing::{ClientError, User, XorName};
-use rust_sodium::crypto::sign;
+use rust_sodium::crypto::{box_, sign};
+use safe_core::ffi::ipc::req::ShareOfferTerms as FfiShareOfferTerms;
 use safe_core::ffi::ipc::resp::MetadataResponse as FfiUserMetadata;
 use safe_core::ipc::req::{
-    container_perms_into_permission_set, ContainerPermissions, IpcReq, ShareMDataReq,
+    container_perms_into_permission_set, permission_set_into_repr_c, AuthReq, BatchItem,
+    BatchReq, ContainerPermissions, ContainersReq, IpcReq, ShareMData, ShareMDataReq,
+    ShareOfferReq,
 };
-use safe_core::ipc::resp::{AccessContainerEntry, IpcResp, UserMetadata, METADATA_KEY};
-use safe_core::ipc::{self, IpcError, IpcMsg};
+use safe_core::ipc::resp::{
+    AccessContainerEntry, AuthGranted, IpcResp, ShareOfferGranted, UserMetadata, METADATA_KEY,
+};
+use safe_core::ipc::{self, IpcEncoding, IpcError, IpcMsg, ShareOffer, SignedShareOffer};
 use safe_core::{recovery, Client, CoreError, FutureExt};
 use std::collections::HashMap;
 use std::ffi::CString;
+use tiny_keccak::sha3_256;
 
 /// Decodes a given encoded IPC message and returns either an `IpcMsg` struct or
-/// an error code + description & an encoded `IpcMsg::Resp` in case of an error
+/// an error code + description & an encoded `IpcMsg::Resp` in case of an error.
+/// `encoding` is the encoding of the message, which the responses to it are
+/// encoded with.
 #[cfg_attr(feature = "cargo-clippy", allow(type_complexity))]
 pub fn decode_ipc_msg(
     client: &AuthClient,
     msg: IpcMsg,
+    encoding: IpcEncoding,
 ) -> Box<AuthFuture<Result<IpcMsg, (i32, CString, CString)>>> {
     match msg {
         IpcMsg::Req {
             req: IpcReq::Auth(auth_req),
             req_id,
         } => {
-            // Ok status should be returned for all app states (including
-            // Revoked and Authenticated).
-            ok!(Ok(IpcMsg::Req {
-                req_id,
-                req: IpcReq::Auth(auth_req),
-            }))
+            let c2 = client.clone();
+
+            verify_manifest(client, &auth_req)
+                .and_then(move |res| match res {
+                    // Ok status should be returned for all app states (including
+                    // Revoked and Authenticated).
+                    Ok(()) => Either::A(future::ok(Ok(IpcMsg::Req {
+                        req_id,
+                        req: IpcReq::Auth(auth_req),
+                    }))),
+                    Err(error) => {
+                        let entry = AuditEntry::new(
+                            auth_req.app.id,
+                            AuditAction::Auth {
+                                app_container: auth_req.app_container,
+                                containers: auth_req.containers,
+                            },
+                            false,
+                        );
+
+                        let resp_key = auth_req.resp_key;
+                        let c3 = c2.clone();
+
+                        Either::B(config::record_audit_entry(&c2, entry).and_then(move |()| {
+                            let (error_code, description) =
+                                ffi_error!(AuthError::from(error.into_ipc_error()));
+
+                            let resp = encode_auth_response(
+                                &c3,
+                                req_id,
+                                IpcResp::Auth(Err(error.into_ipc_error())),
+                                resp_key,
+                                encoding,
+                            )?;
+
+                            Ok(Err((error_code, description, resp)))
+                        }))
+                    }
+                }).into_box()
         }
         IpcMsg::Req {
             req: IpcReq::Unregistered(extra_data),
@@ -67,32 +110,103 @@ pub fn decode_ipc_msg(
             let app_id = cont_req.app.id.clone();
 
             let c2 = client.clone();
+            let c3 = client.clone();
 
             config::list_apps(client)
                 .and_then(move |(_config_version, config)| app_state(&c2, &config, &app_id))
                 .and_then(move |app_state| {
                     match app_state {
-                        AppState::Authenticated => Ok(Ok(IpcMsg::Req {
+                        AppState::Authenticated => Either::A(future::ok(Ok(IpcMsg::Req {
                             req_id,
                             req: IpcReq::Containers(cont_req),
-                        })),
+                        }))),
                         AppState::Revoked | AppState::NotAuthenticated => {
                             // App is not authenticated
-                            let (error_code, description) =
-                                ffi_error!(AuthError::from(IpcError::UnknownApp));
+                            let entry = AuditEntry::new(
+                                cont_req.app.id,
+                                AuditAction::Containers(cont_req.containers),
+                                false,
+                            );
 
-                            let resp = IpcMsg::Resp {
-                                resp: IpcResp::Auth(Err(IpcError::UnknownApp)),
-                                req_id,
-                            };
-                            let resp = encode_response(&resp)?;
+                            Either::B(config::record_audit_entry(&c3, entry).and_then(
+                                move |()| {
+                                    let (error_code, description) =
+                                        ffi_error!(AuthError::from(IpcError::UnknownApp));
 
-                            Ok(Err((error_code, description, resp)))
+                                    let resp = IpcMsg::Resp {
+                                        resp: IpcResp::Auth(Err(IpcError::UnknownApp)),
+                                        req_id,
+                                    };
+                                    let resp = encode_response(&resp, encoding)?;
+
+                                    Ok(Err((error_code, description, resp)))
+                                },
+                            ))
                         }
                     }
                 }).into_box()
         }
-        IpcMsg::Resp { .. } | IpcMsg::Revoked { .. } | IpcMsg::Err(..) => {
+        IpcMsg::Req {
+            req: IpcReq::Batch(batch_req),
+            req_id,
+        } => {
+            let c2 = client.clone();
+
+            check_batch(client, &batch_req)
+                .and_then(move |res| match res {
+                    Ok(()) => Either::A(future::ok(Ok(IpcMsg::Req {
+                        req_id,
+                        req: IpcReq::Batch(batch_req),
+                    }))),
+                    Err(error) => {
+                        let resp_key = batch_req.resp_key();
+                        let c3 = c2.clone();
+
+                        Either::B(record_batch_denial(&c2, batch_req.items).and_then(
+                            move |()| {
+                                let (error_code, description) =
+                                    ffi_error!(AuthError::from(error.into_ipc_error()));
+
+                                let resp = encode_auth_response(
+                                    &c3,
+                                    req_id,
+                                    IpcResp::Batch(Err(error.into_ipc_error())),
+                                    resp_key,
+                                    encoding,
+                                )?;
+
+                                Ok(Err((error_code, description, resp)))
+                            },
+                        ))
+                    }
+                }).into_box()
+        }
+        IpcMsg::Req {
+            req: IpcReq::ShareOffer(share_offer_req),
+            req_id,
+        } => check_share_offer(client, &share_offer_req)
+            .and_then(move |res| match res {
+                Ok(()) => Ok(Ok(IpcMsg::Req {
+                    req_id,
+                    req: IpcReq::ShareOffer(share_offer_req),
+                })),
+                Err(error) => {
+                    let (error_code, description) =
+                        ffi_error!(AuthError::from(error.into_ipc_error()));
+
+                    let resp = IpcMsg::Resp {
+                        resp: IpcResp::ShareOffer(Err(error.into_ipc_error())),
+                        req_id,
+                    };
+                    let resp = encode_response(&resp, encoding)?;
+
+                    Ok(Err((error_code, description, resp)))
+                }
+            }).into_box(),
+        IpcMsg::Resp { .. }
+        | IpcMsg::Revoked { .. }
+        | IpcMsg::Err(..)
+        | IpcMsg::SealedResp { .. } => {
             return err!(AuthError::IpcError(IpcError::InvalidMsg));
         }
     }
@@ -146,11 +260,432 @@ pub fn update_container_perms(
         .into_box()
 }
 
-pub fn encode_response(msg: &IpcMsg) -> Result<CString, IpcError> {
-    let resp = ipc::encode_msg(msg)?;
+/// Authorises the app and records it in the audit log.
+pub fn grant_auth(client: &AuthClient, auth_req: AuthReq) -> Box<AuthFuture<AuthGranted>> {
+    let c2 = client.clone();
+    let app_id = auth_req.app.id.clone();
+    let action = AuditAction::Auth {
+        app_container: auth_req.app_container,
+        containers: auth_req.containers.clone(),
+    };
+
+    app_auth::authenticate(client, auth_req)
+        .and_then(move |auth_granted| {
+            config::record_audit_entry(&c2, AuditEntry::new(app_id, action, true))
+                .map(move |()| auth_granted)
+        }).into_box()
+}
+
+/// Grants the app access to the requested containers and records it in the audit log.
+pub fn grant_containers(client: &AuthClient, cont_req: ContainersReq) -> Box<AuthFuture<()>> {
+    let c2 = client.clone();
+    let c3 = client.clone();
+    let c4 = client.clone();
+    let c5 = client.clone();
+    let c6 = client.clone();
+
+    let action = AuditAction::Containers(cont_req.containers.clone());
+    let permissions = cont_req.containers;
+    let app_id = cont_req.app.id;
+
+    config::get_app(client, &app_id)
+        .and_then(move |app| {
+            let sign_pk = app.keys.sign_pk;
+            update_container_perms(&c2, permissions, sign_pk).map(move |perms| (app, perms))
+        }).and_then(move |(app, mut perms)| {
+            let app_keys = app.keys;
+
+            access_container::fetch_entry(&c3, &app_id, app_keys.clone()).then(move |res| {
+                let version = match res {
+                    // Updating an existing entry
+                    Ok((version, Some(mut existing_perms))) => {
+                        for (key, val) in perms {
+                            let _ = existing_perms.insert(key, val);
+                        }
+                        perms = existing_perms;
+
+                        version + 1
+                    }
+
+                    // Adding a new access container entry
+                    Ok((_, None))
+                    | Err(AuthError::CoreError(CoreError::RoutingClientError(
+                        ClientError::NoSuchEntry,
+                    ))) => 0,
+
+                    // Error has occurred while trying to get an
+                    // existing entry
+                    Err(e) => return Err(e),
+                };
+                Ok((version, app_id, app_keys, perms))
+            })
+        }).and_then(move |(version, app_id, app_keys, perms)| {
+            access_container::put_entry(&c4, &app_id, &app_keys, &perms, version)
+                .map(move |()| app_id)
+        }).and_then(move |app_id| devices::attach_app(&c5, app_id.clone()).map(move |()| app_id))
+        .and_then(move |app_id| {
+            config::record_audit_entry(&c6, AuditEntry::new(app_id, action, true))
+        }).into_box()
+}
+
+/// Grants the app the requested permissions to the mutable data and records it
+/// in the audit log.
+pub fn grant_share_mdata(client: &AuthClient, req: ShareMDataReq) -> Box<AuthFuture<()>> {
+    let c2 = client.clone();
+    let c3 = client.clone();
+    let c4 = client.clone();
+    let c5 = client.clone();
+    let c6 = client.clone();
+
+    let action = AuditAction::ShareMData(req.mdata.clone());
+    let shared = req.mdata.clone();
+    let app_id = req.app.id.clone();
+
+    config::get_app(client, &req.app.id)
+        .and_then(move |app_info| {
+            let user = User::Key(app_info.keys.sign_pk);
+            let num_mdata = req.mdata.len();
+            stream::iter_ok(req.mdata.into_iter())
+                .map(move |mdata| {
+                    c2.get_mdata_shell(mdata.name, mdata.type_tag)
+                        .map(|md| (md.version(), mdata))
+                }).buffer_unordered(num_mdata)
+                .map(move |(version, mdata)| {
+                    c3.set_mdata_user_permissions(
+                        mdata.name,
+                        mdata.type_tag,
+                        user,
+                        mdata.perms,
+                        version + 1,
+                    )
+                }).buffer_unordered(num_mdata)
+                .map_err(AuthError::CoreError)
+                .for_each(|()| Ok(()))
+        }).and_then(move |()| {
+            config::insert_shared_mdata(&c4, app_id.clone(), shared).map(move |()| app_id)
+        }).and_then(move |app_id| devices::attach_app(&c5, app_id.clone()).map(move |()| app_id))
+        .and_then(move |app_id| {
+            config::record_audit_entry(&c6, AuditEntry::new(app_id, action, true))
+        }).into_box()
+}
+
+/// Accepts the share offer for the app of the request and records it in the audit
+/// log. The response carries the acceptance, to be delivered to the app which made
+/// the offer so that it grants the permissions.
+pub fn grant_share_offer(
+    client: &AuthClient,
+    req: ShareOfferReq,
+) -> Box<AuthFuture<ShareOfferGranted>> {
+    let c2 = client.clone();
+    let c3 = client.clone();
+    let c4 = client.clone();
+    let app_id = req.app.id.clone();
+
+    config::get_app(client, &app_id)
+        .and_then(move |app| {
+            let offer = fry!(open_offer(&req, &app));
+            let acceptance = fry!(
+                ipc::seal_share_acceptance(&offer, vec![app.keys.sign_pk])
+            );
+            let ShareOffer { mdata, perms, .. } = offer.offer;
+
+            let shared = vec![ShareMData {
+                type_tag: mdata.type_tag,
+                name: mdata.name,
+                perms,
+            }];
+            let action = AuditAction::ShareMData(shared.clone());
+            let granted = ShareOfferGranted {
+                mdata,
+                perms,
+                acceptance,
+            };
+
+            config::insert_shared_mdata(&c2, app_id.clone(), shared)
+                .and_then(move |()| devices::attach_app(&c3, app_id.clone()).map(move |()| app_id))
+                .and_then(move |app_id| {
+                    config::record_audit_entry(&c4, AuditEntry::new(app_id, action, true))
+                }).map(move |()| granted)
+                .into_box()
+        }).into_box()
+}
+
+/// Records the denial of the share offer in the audit log.
+pub fn deny_share_offer(client: &AuthClient, req: ShareOfferReq) -> Box<AuthFuture<()>> {
+    let c2 = client.clone();
+
+    config::get_app(client, &req.app.id)
+        .and_then(move |app| {
+            let offer = fry!(open_offer(&req, &app)).offer;
+            let action = AuditAction::ShareMData(vec![ShareMData {
+                type_tag: offer.mdata.type_tag,
+                name: offer.mdata.name,
+                perms: offer.perms,
+            }]);
+
+            config::record_audit_entry(&c2, AuditEntry::new(req.app.id, action, false))
+        }).into_box()
+}
+
+/// Processes a batch of requests, one at a time and in order. `approvals` holds
+/// the user's decision for each request. Returns the response to the batch.
+pub fn process_batch(
+    client: &AuthClient,
+    batch_req: BatchReq,
+    approvals: Vec<bool>,
+) -> Box<AuthFuture<IpcResp>> {
+    if approvals.len() != batch_req.items.len() {
+        return err!(AuthError::Unexpected(
+            "Expected a decision for each request of the batch".to_owned()
+        ));
+    }
+
+    let all_denied = approvals.iter().all(|approved| !approved);
+    if batch_req.single_decision && !all_denied && approvals.contains(&false) {
+        return err!(AuthError::Unexpected(
+            "Batch can only be approved or denied as a whole".to_owned()
+        ));
+    }
+    let denied = batch_req.single_decision && all_denied;
+
+    let client = client.clone();
+
+    stream::iter_ok(batch_req.items.into_iter().zip(approvals))
+        .and_then(move |(item, approved)| process_batch_item(&client, item, approved))
+        .collect()
+        .map(move |resps| {
+            if denied {
+                IpcResp::Batch(Err(IpcError::AuthDenied))
+            } else {
+                IpcResp::Batch(Ok(resps))
+            }
+        }).into_box()
+}
+
+// Grants or denies a single request of a batch. Failures are reported in the
+// response to the request, so that they don't affect the rest of the batch.
+fn process_batch_item(
+    client: &AuthClient,
+    item: BatchItem,
+    approved: bool,
+) -> Box<AuthFuture<IpcResp>> {
+    if !approved {
+        let resp = match item {
+            BatchItem::Auth(_) => IpcResp::Auth(Err(IpcError::AuthDenied)),
+            BatchItem::Containers(_) => IpcResp::Containers(Err(IpcError::AuthDenied)),
+            BatchItem::ShareMData(_) => IpcResp::ShareMData(Err(IpcError::ShareMDataDenied)),
+        };
+        let entry = AuditEntry::new(item.app().id.clone(), audit_action(&item), false);
+
+        return config::record_audit_entry(client, entry)
+            .map(move |()| resp)
+            .into_box();
+    }
+
+    match item {
+        BatchItem::Auth(req) => grant_auth(client, req)
+            .then(|res| Ok(IpcResp::Auth(res.map_err(AuthError::into))))
+            .into_box(),
+        BatchItem::Containers(req) => grant_containers(client, req)
+            .then(|res| Ok(IpcResp::Containers(res.map_err(AuthError::into))))
+            .into_box(),
+        BatchItem::ShareMData(req) => grant_share_mdata(client, req)
+            .then(|res| Ok(IpcResp::ShareMData(res.map_err(AuthError::into))))
+            .into_box(),
+    }
+}
+
+// Records the denial of each request of the batch in the audit log.
+fn record_batch_denial(client: &AuthClient, items: Vec<BatchItem>) -> Box<AuthFuture<()>> {
+    let client = client.clone();
+
+    stream::iter_ok(items)
+        .for_each(move |item| {
+            let entry = AuditEntry::new(item.app().id.clone(), audit_action(&item), false);
+            config::record_audit_entry(&client, entry)
+        }).into_box()
+}
+
+fn audit_action(item: &BatchItem) -> AuditAction {
+    match *item {
+        BatchItem::Auth(ref req) => AuditAction::Auth {
+            app_container: req.app_container,
+            containers: req.containers.clone(),
+        },
+        BatchItem::Containers(ref req) => AuditAction::Containers(req.containers.clone()),
+        BatchItem::ShareMData(ref req) => AuditAction::ShareMData(req.mdata.clone()),
+    }
+}
+
+/// Encodes a response with the given encoding, which should be the encoding of the
+/// request it responds to.
+pub fn encode_response(msg: &IpcMsg, encoding: IpcEncoding) -> Result<CString, IpcError> {
+    let resp = ipc::encode_msg_as(msg, encoding)?;
     Ok(CString::new(resp).map_err(StringError::from)?)
 }
 
+/// Encodes the response to an authorisation request with the given encoding. If the
+/// request carries a response key, the response is signed with the account key and
+/// sealed to it.
+pub fn encode_auth_response(
+    client: &AuthClient,
+    req_id: u32,
+    resp: IpcResp,
+    resp_key: Option<box_::PublicKey>,
+    encoding: IpcEncoding,
+) -> Result<CString, IpcError> {
+    let msg = if let Some(resp_key) = resp_key {
+        let (sign_pk, sign_sk) = client
+            .signing_keypair()
+            .ok_or_else(|| IpcError::Unexpected("Signing keys not available".to_owned()))?;
+        ipc::seal_resp(req_id, &resp, &resp_key, sign_pk, &sign_sk)?
+    } else {
+        IpcMsg::Resp { req_id, resp }
+    };
+
+    encode_response(&msg, encoding)
+}
+
+#[derive(Clone, Copy)]
+enum ManifestError {
+    Invalid,
+    KeyMismatch,
+}
+
+impl ManifestError {
+    fn into_ipc_error(self) -> IpcError {
+        match self {
+            ManifestError::Invalid => IpcError::InvalidManifest,
+            ManifestError::KeyMismatch => IpcError::ManifestKeyMismatch,
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+enum BatchError {
+    Invalid,
+    Manifest(ManifestError),
+    UnknownApp,
+}
+
+impl BatchError {
+    fn into_ipc_error(self) -> IpcError {
+        match self {
+            BatchError::Invalid => IpcError::InvalidMsg,
+            BatchError::Manifest(error) => error.into_ipc_error(),
+            BatchError::UnknownApp => IpcError::UnknownApp,
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+enum ShareOfferError {
+    Invalid,
+    UnknownApp,
+}
+
+impl ShareOfferError {
+    fn into_ipc_error(self) -> IpcError {
+        match self {
+            ShareOfferError::Invalid => IpcError::InvalidShareOffer,
+            ShareOfferError::UnknownApp => IpcError::UnknownApp,
+        }
+    }
+}
+
+// Checks the app the offer is to be accepted for is authorised and that the offer
+// opens with the encryption key of that app.
+fn check_share_offer(
+    client: &AuthClient,
+    req: &ShareOfferReq,
+) -> Box<AuthFuture<Result<(), ShareOfferError>>> {
+    let c2 = client.clone();
+    let app_id = req.app.id.clone();
+    let req = req.clone();
+
+    config::list_apps(client)
+        .and_then(move |(_, config)| {
+            let app = config.get(&sha3_256(app_id.as_bytes())).cloned();
+            app_state(&c2, &config, &app_id).map(move |app_state| (app_state, app))
+        }).map(move |(app_state, app)| match (app_state, app) {
+            (AppState::Authenticated, Some(app)) => open_offer(&req, &app)
+                .map(|_| ())
+                .map_err(|_| ShareOfferError::Invalid),
+            _ => Err(ShareOfferError::UnknownApp),
+        }).into_box()
+}
+
+// Offers are sealed to the encryption key of the app they're to be accepted for,
+// which is the key the app publishes under the public IDs it registers.
+fn open_offer(req: &ShareOfferReq, app: &AppInfo) -> Result<SignedShareOffer, IpcError> {
+    ipc::open_share_offer(&req.offer, &app.keys.enc_pk, &app.keys.enc_sk)
+        .map_err(|_| IpcError::InvalidShareOffer)
+}
+
+// Checks that the batch isn't empty, that all its requests come from the same app
+// and that only the first one is an authorisation request. The manifest of the
+// authorisation request is verified. Without one, the app has to be authorised already.
+fn check_batch(
+    client: &AuthClient,
+    batch_req: &BatchReq,
+) -> Box<AuthFuture<Result<(), BatchError>>> {
+    let app_id = match batch_req.items.first() {
+        Some(item) => item.app().id.clone(),
+        None => return ok!(Err(BatchError::Invalid)),
+    };
+
+    let single_app = batch_req.items.iter().all(|item| item.app().id == app_id);
+    let auth_first = batch_req.items.iter().skip(1).all(|item| match *item {
+        BatchItem::Auth(_) => false,
+        BatchItem::Containers(_) | BatchItem::ShareMData(_) => true,
+    });
+    if !single_app || !auth_first {
+        return ok!(Err(BatchError::Invalid));
+    }
+
+    if let BatchItem::Auth(ref auth_req) = batch_req.items[0] {
+        return verify_manifest(client, auth_req)
+            .map(|res| res.map_err(BatchError::Manifest))
+            .into_box();
+    }
+
+    let c2 = client.clone();
+
+    config::list_apps(client)
+        .and_then(move |(_, config)| app_state(&c2, &config, &app_id))
+        .map(|app_state| match app_state {
+            AppState::Authenticated => Ok(()),
+            AppState::Revoked | AppState::NotAuthenticated => Err(BatchError::UnknownApp),
+        }).into_box()
+}
+
+// Checks the signature of the app manifest attached to the request, if any, and
+// that the app ID hasn't been authorised before with a manifest of a different
+// developer (or with a manifest when there's none now).
+fn verify_manifest(
+    client: &AuthClient,
+    auth_req: &AuthReq,
+) -> Box<AuthFuture<Result<(), ManifestError>>> {
+    let app_id = auth_req.app.id.clone();
+    let developer_key = match auth_req.manifest {
+        Some(ref manifest) => {
+            if manifest.verify(&app_id).is_err() {
+                return ok!(Err(ManifestError::Invalid));
+            }
+            Some(manifest.developer_key)
+        }
+        None => None,
+    };
+
+    config::list_app_developers(client)
+        .map(move |(_, developers)| match developers.get(&app_id) {
+            Some(known_key) if Some(*known_key) != developer_key => {
+                Err(ManifestError::KeyMismatch)
+            }
+            _ => Ok(()),
+        }).into_box()
+}
+
 enum ShareMDataError {
     InvalidOwner(XorName, u64),
     InvalidMetadata,
@@ -235,3 +770,37 @@ pub fn decode_share_mdata_req(
             }
         }).into_box()
 }
+
+/// Returns the terms of the share offer to present to the user, along with the
+/// owner of the shared mutable data.
+pub fn decode_share_offer_req(
+    client: &AuthClient,
+    req: &ShareOfferReq,
+) -> Box<AuthFuture<FfiShareOfferTerms>> {
+    let c2 = client.clone();
+    let req = req.clone();
+
+    config::get_app(client, &req.app.id)
+        .and_then(move |app| {
+            let offer = open_offer(&req, &app)?.offer;
+            Ok((offer.mdata.name, offer.mdata.type_tag, offer.perms))
+        }).and_then(move |(name, type_tag, perms)| {
+            c2.get_mdata_shell(name, type_tag)
+                .map_err(AuthError::from)
+                .map(move |shell| (name, type_tag, perms, shell))
+        }).and_then(move |(name, type_tag, perms, shell)| {
+            let owner = shell
+                .owners()
+                .iter()
+                .next()
+                .cloned()
+                .ok_or_else(|| AuthError::Unexpected("Mutable data has no owner".to_owned()))?;
+
+            Ok(FfiShareOfferTerms {
+                name: name.0,
+                type_tag,
+                perms: permission_set_into_repr_c(perms),
+                owner: owner.0,
+            })
+        }).into_box()
+}
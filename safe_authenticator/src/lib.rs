@@ -84,6 +84,7 @@ extern crate serde_derive;
 extern crate rust_sodium;
 #[macro_use]
 extern crate safe_core;
+extern crate self_encryption;
 extern crate tiny_keccak;
 extern crate tokio_core;
 #[macro_use]
@@ -95,6 +96,8 @@ extern crate rand;
 pub mod ffi;
 
 pub use ffi::apps::*;
+pub use ffi::containers::*;
+pub use ffi::devices::*;
 pub use ffi::ipc::*;
 pub use ffi::logging::*;
 pub use ffi::*;
@@ -104,8 +107,11 @@ mod app_auth;
 mod app_container;
 mod client;
 mod config;
+mod devices;
 mod errors;
 mod ipc;
+mod permissions_report;
+mod repair;
 mod revocation;
 mod std_dirs;
 
@@ -118,6 +124,8 @@ mod tests;
 
 pub use self::errors::AuthError;
 pub use client::AuthClient;
+pub use repair::RepairReport;
+pub use std_dirs::ContainerTemplate;
 
 use futures::stream::Stream;
 use futures::sync::mpsc;
@@ -125,9 +133,11 @@ use futures::Future;
 use maidsafe_utilities::thread::{self, Joiner};
 #[cfg(feature = "use-mock-routing")]
 use safe_core::MockRouting;
-use safe_core::{event_loop, CoreMsg, CoreMsgTx, FutureExt, NetworkEvent, NetworkTx};
+use safe_core::{
+    event_loop, CancellationToken, CoreMsg, CoreMsgTx, FutureExt, NetworkEvent, NetworkTx,
+};
 use std::sync::mpsc::sync_channel;
-use std::sync::Mutex;
+use std::sync::{Arc, Mutex};
 use tokio_core::reactor::{Core, Handle};
 
 /// Future type specialised with `AuthError` as an error type.
@@ -135,6 +145,8 @@ pub type AuthFuture<T> = Future<Item = T, Error = AuthError>;
 /// Transmitter of AuthClient messages.
 pub type AuthMsgTx = CoreMsgTx<AuthClient, ()>;
 
+type NetworkObserver = Box<FnMut(NetworkEvent) + Send>;
+
 macro_rules! try_tx {
     ($result:expr, $tx:ident) => {
         match $result {
@@ -150,20 +162,76 @@ macro_rules! try_tx {
 pub struct Authenticator {
     /// Channel to communicate with the core event loop.
     pub core_tx: Mutex<AuthMsgTx>,
-    _core_joiner: Joiner,
+    network_observer: Arc<Mutex<Option<NetworkObserver>>>,
+    // Token of the operation this handle belongs to, if it's an operation handle.
+    operation: Option<CancellationToken>,
+    // Only the handle which owns the event loop has its joiner.
+    core_joiner: Option<Joiner>,
 }
 
 impl Authenticator {
-    /// Send a message to the authenticator event loop.
+    /// Send a message to the authenticator event loop. If this is an operation handle, the
+    /// message belongs to the operation and is cancelled with it.
     pub fn send<F>(&self, f: F) -> Result<(), AuthError>
     where
         F: FnOnce(&AuthClient) -> Option<Box<Future<Item = (), Error = ()>>> + Send + 'static,
     {
-        let msg = CoreMsg::new(|client, _| f(client));
+        let msg = match self.operation {
+            Some(ref token) => CoreMsg::cancellable(token.clone(), move |client, _| f(client)),
+            None => CoreMsg::new(move |client, _| f(client)),
+        };
         let core_tx = unwrap!(self.core_tx.lock());
         core_tx.unbounded_send(msg).map_err(AuthError::from)
     }
 
+    /// Create a handle to a new cancellable operation of this authenticator. The messages sent
+    /// through the returned handle belong to the operation: once it's cancelled, their pending
+    /// network requests are abandoned and they fail with `CoreError::OperationCancelled`.
+    /// The operation handle doesn't keep the authenticator alive.
+    pub fn operation(&self) -> Self {
+        let core_tx = unwrap!(self.core_tx.lock());
+        Authenticator {
+            core_tx: Mutex::new(core_tx.clone()),
+            network_observer: Arc::clone(&self.network_observer),
+            operation: Some(CancellationToken::new()),
+            core_joiner: None,
+        }
+    }
+
+    /// Returns the token of the operation, if this is an operation handle.
+    pub fn operation_token(&self) -> Option<&CancellationToken> {
+        self.operation.as_ref()
+    }
+
+    /// Set the observer called with every `NetworkEvent` of the connection, replacing the
+    /// previous one. Unlike the disconnect notifier, it's also told when the authenticator is
+    /// connecting, connected, reconnecting or gave up connecting.
+    pub fn set_network_observer<O>(&self, observer: O)
+    where
+        O: FnMut(NetworkEvent) + Send + 'static,
+    {
+        *unwrap!(self.network_observer.lock()) = Some(Box::new(observer));
+    }
+
+    /// Find the inconsistencies left in the authenticator state by interrupted operations
+    /// (auth keys without apps in the config, access container entries of unknown apps and
+    /// standard containers missing from the access container) and fix them.
+    /// If `dry_run` is set, only report the inconsistencies without fixing them.
+    pub fn check_and_repair(&self, dry_run: bool) -> Result<RepairReport, AuthError> {
+        let (tx, rx) = sync_channel(1);
+
+        self.send(move |client| {
+            repair::check_and_repair(client, dry_run)
+                .then(move |res| {
+                    unwrap!(tx.send(res));
+                    Ok(())
+                }).into_box()
+                .into()
+        })?;
+
+        rx.recv()?
+    }
+
     /// Create a new account.
     pub fn create_acc<S, N>(
         locator: S,
@@ -171,6 +239,28 @@ impl Authenticator {
         invitation: S,
         disconnect_notifier: N,
     ) -> Result<Self, AuthError>
+    where
+        N: FnMut() + Send + 'static,
+        S: Into<String>,
+    {
+        Self::create_acc_with_containers(
+            locator,
+            password,
+            invitation,
+            Vec::new(),
+            disconnect_notifier,
+        )
+    }
+
+    /// Create a new account with the given root containers created in addition
+    /// to the standard ones.
+    pub fn create_acc_with_containers<S, N>(
+        locator: S,
+        password: S,
+        invitation: S,
+        containers: Vec<ContainerTemplate>,
+        disconnect_notifier: N,
+    ) -> Result<Self, AuthError>
     where
         N: FnMut() + Send + 'static,
         S: Into<String>,
@@ -183,6 +273,7 @@ impl Authenticator {
             move |el_h, core_tx, net_tx| {
                 AuthClient::registered(&locator, &password, &invitation, el_h, core_tx, net_tx)
             },
+            containers,
             disconnect_notifier,
         )
     }
@@ -190,6 +281,7 @@ impl Authenticator {
     /// Create a new account.
     fn create_acc_impl<F: 'static + Send, N>(
         create_client_fn: F,
+        containers: Vec<ContainerTemplate>,
         mut disconnect_notifier: N,
     ) -> Result<Self, AuthError>
     where
@@ -197,6 +289,8 @@ impl Authenticator {
         F: FnOnce(Handle, AuthMsgTx, NetworkTx) -> Result<AuthClient, AuthError>,
     {
         let (tx, rx) = sync_channel(0);
+        let network_observer = Arc::new(Mutex::new(None::<NetworkObserver>));
+        let network_observer2 = Arc::clone(&network_observer);
 
         let joiner = thread::named("Core Event Loop", move || {
             let el = try_tx!(Core::new(), tx);
@@ -211,6 +305,11 @@ impl Authenticator {
                     if let Ok(NetworkEvent::Disconnected) = net_event {
                         disconnect_notifier();
                     }
+                    if let Ok(net_event) = net_event {
+                        if let Some(ref mut observer) = *unwrap!(network_observer2.lock()) {
+                            observer(net_event);
+                        }
+                    }
                     ok!(())
                 }).for_each(|_| Ok(()));
             el_h.spawn(net_obs_fut);
@@ -218,7 +317,7 @@ impl Authenticator {
             let client = try_tx!(create_client_fn(el_h, core_tx.clone(), net_tx), tx);
 
             unwrap!(core_tx.unbounded_send(CoreMsg::new(move |client, &()| {
-                std_dirs::create(client)
+                std_dirs::create(client, &containers)
                     .map_err(|error| AuthError::AccountContainersCreation(error.to_string()))
                     .then(move |res| {
                         match res {
@@ -246,7 +345,9 @@ impl Authenticator {
 
         Ok(Authenticator {
             core_tx: Mutex::new(core_tx),
-            _core_joiner: joiner,
+            network_observer,
+            operation: None,
+            core_joiner: Some(joiner),
         })
     }
 
@@ -277,6 +378,8 @@ impl Authenticator {
         N: FnMut() + Send + 'static,
     {
         let (tx, rx) = sync_channel(0);
+        let network_observer = Arc::new(Mutex::new(None::<NetworkObserver>));
+        let network_observer2 = Arc::clone(&network_observer);
 
         let joiner = thread::named("Core Event Loop", move || {
             let el = try_tx!(Core::new(), tx);
@@ -291,6 +394,11 @@ impl Authenticator {
                     if let Ok(NetworkEvent::Disconnected) = net_event {
                         disconnect_notifier();
                     }
+                    if let Ok(net_event) = net_event {
+                        if let Some(ref mut observer) = *unwrap!(network_observer2.lock()) {
+                            observer(net_event);
+                        }
+                    }
                     ok!(())
                 }).for_each(|_| Ok(()));
             el_h.spawn(net_obs_fut);
@@ -305,7 +413,7 @@ impl Authenticator {
                 let core_tx3 = core_tx.clone();
 
                 unwrap!(core_tx.unbounded_send(CoreMsg::new(move |client, &()| {
-                    std_dirs::create(client)
+                    std_dirs::create(client, &[])
                         .map(move |()| {
                             unwrap!(tx.send(Ok(core_tx2)));
                         }).map_err(move |e| {
@@ -332,7 +440,9 @@ impl Authenticator {
 
         Ok(Authenticator {
             core_tx: Mutex::new(core_tx),
-            _core_joiner: joiner,
+            network_observer,
+            operation: None,
+            core_joiner: Some(joiner),
         })
     }
 }
@@ -399,6 +509,7 @@ impl Authenticator {
                     routing_wrapper_fn,
                 )
             },
+            Vec::new(),
             disconnect_notifier,
         )
     }
@@ -436,6 +547,11 @@ impl Authenticator {
 
 impl Drop for Authenticator {
     fn drop(&mut self) {
+        // Operation handles leave the event loop running.
+        if self.core_joiner.is_none() {
+            return;
+        }
+
         debug!("Authenticator is now being dropped.");
 
         let core_tx = unwrap!(self.core_tx.lock());
@@ -0,0 +1,336 @@
+// Copyright 2018 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+//! Report of the effective app permissions on the standard containers and on
+//! the mutable data shared with apps.
+
+use access_container;
+use client::AuthClient;
+use config::{self, AppInfo};
+use futures::{future, Future};
+use routing::{ClientError, PermissionSet, User, XorName};
+use rust_sodium::crypto::sign;
+use safe_core::ipc::req::container_perms_into_permission_set;
+use safe_core::ipc::resp::AccessContainerEntry;
+use safe_core::{Client, CoreError, FutureExt};
+use std::collections::{BTreeSet, HashMap};
+use std_dirs::{DEFAULT_PRIVATE_DIRS, DEFAULT_PUBLIC_DIRS};
+use {AuthError, AuthFuture};
+
+/// Permissions of a single key on a single `MutableData`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AppPermissions {
+    /// Public sign key of the app.
+    pub sign_key: sign::PublicKey,
+    /// ID of the app, or `None` if the key doesn't belong to any registered app.
+    pub app_id: Option<String>,
+    /// Permissions actually set on the `MutableData`.
+    pub actual: Option<PermissionSet>,
+    /// Permissions the authenticator has granted to the app.
+    pub expected: Option<PermissionSet>,
+}
+
+impl AppPermissions {
+    /// Returns `true` if the permissions set on the `MutableData` differ from
+    /// the ones the authenticator has granted.
+    pub fn is_mismatch(&self) -> bool {
+        self.actual != self.expected
+    }
+}
+
+/// Permissions of all apps on a single `MutableData`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MDataPermissionsReport {
+    /// Name of the standard container, or `None` for shared mutable data.
+    pub container: Option<String>,
+    /// Name of the `MutableData`.
+    pub name: XorName,
+    /// Type tag of the `MutableData`.
+    pub type_tag: u64,
+    /// Permissions of each app having or expected to have access.
+    pub apps: Vec<AppPermissions>,
+}
+
+struct ReportTarget {
+    container: Option<String>,
+    name: XorName,
+    type_tag: u64,
+    expected: HashMap<sign::PublicKey, PermissionSet>,
+}
+
+/// Builds the permissions report for every standard container and every
+/// `MutableData` shared with apps through the authenticator.
+///
+/// Expected permissions come from the apps' access container entries and from
+/// the record of the mutable data shared with the apps.
+pub fn permissions_report(client: &AuthClient) -> Box<AuthFuture<Vec<MDataPermissionsReport>>> {
+    let c2 = client.clone();
+    let c3 = client.clone();
+
+    access_container::fetch_authenticator_entry(client)
+        .join3(config::list_apps(client), config::list_shared_mdata(client))
+        .and_then(move |((_, root_containers), (_, apps), (_, shared_mdata))| {
+            let entries = apps
+                .into_iter()
+                .map(|(_, app)| fetch_app_entry(&c2, app))
+                .collect::<Vec<_>>();
+
+            future::join_all(entries)
+                .map(move |apps| (root_containers, apps, shared_mdata))
+        }).and_then(move |(root_containers, apps, shared_mdata)| {
+            let mut targets = Vec::new();
+
+            // Standard containers, as seen by the access container entries.
+            for name in DEFAULT_PRIVATE_DIRS.iter().chain(DEFAULT_PUBLIC_DIRS.iter()) {
+                let mdata_info = match root_containers.get(*name) {
+                    Some(mdata_info) => mdata_info,
+                    None => continue,
+                };
+
+                let expected = apps
+                    .iter()
+                    .filter_map(|&(ref app, ref entry)| {
+                        let &(_, ref perms) = entry.as_ref()?.get(*name)?;
+                        Some((app.keys.sign_pk, container_perms_into_permission_set(perms)))
+                    }).collect();
+
+                targets.push(ReportTarget {
+                    container: Some(name.to_string()),
+                    name: mdata_info.name,
+                    type_tag: mdata_info.type_tag,
+                    expected,
+                });
+            }
+
+            // Mutable data shared with the apps still having access.
+            let app_keys: HashMap<_, _> = apps
+                .iter()
+                .filter(|&&(_, ref entry)| entry.is_some())
+                .map(|&(ref app, _)| (app.info.id.clone(), app.keys.sign_pk))
+                .collect();
+            let mut shared: Vec<ReportTarget> = Vec::new();
+
+            for (app_id, mdata) in shared_mdata {
+                let sign_pk = match app_keys.get(&app_id) {
+                    Some(sign_pk) => *sign_pk,
+                    None => continue,
+                };
+
+                for mdata in mdata {
+                    let index = match shared.iter().position(|target| {
+                        target.name == mdata.name && target.type_tag == mdata.type_tag
+                    }) {
+                        Some(index) => index,
+                        None => {
+                            shared.push(ReportTarget {
+                                container: None,
+                                name: mdata.name,
+                                type_tag: mdata.type_tag,
+                                expected: HashMap::new(),
+                            });
+                            shared.len() - 1
+                        }
+                    };
+                    let _ = shared[index].expected.insert(sign_pk, mdata.perms);
+                }
+            }
+            targets.extend(shared);
+
+            let app_ids: HashMap<_, _> = apps
+                .into_iter()
+                .map(|(app, _)| (app.keys.sign_pk, app.info.id))
+                .collect();
+
+            let reports = targets
+                .into_iter()
+                .map(|target| target_report(&c3, target, app_ids.clone()))
+                .collect::<Vec<_>>();
+
+            future::join_all(reports)
+        }).into_box()
+}
+
+// Fetches the access container entry of the app, treating a missing entry as
+// an empty one.
+fn fetch_app_entry(
+    client: &AuthClient,
+    app: AppInfo,
+) -> Box<AuthFuture<(AppInfo, Option<AccessContainerEntry>)>> {
+    access_container::fetch_entry(client, &app.info.id, app.keys.clone())
+        .then(move |res| match res {
+            Ok((_, entry)) => Ok((app, entry)),
+            Err(AuthError::CoreError(CoreError::RoutingClientError(ClientError::NoSuchEntry))) => {
+                Ok((app, None))
+            }
+            Err(error) => Err(error),
+        }).into_box()
+}
+
+fn target_report(
+    client: &AuthClient,
+    target: ReportTarget,
+    app_ids: HashMap<sign::PublicKey, String>,
+) -> Box<AuthFuture<MDataPermissionsReport>> {
+    client
+        .list_mdata_permissions(target.name, target.type_tag)
+        .map_err(AuthError::from)
+        .map(move |permissions| {
+            let actual: HashMap<_, _> = permissions
+                .into_iter()
+                .filter_map(|(user, perms)| match user {
+                    User::Key(sign_pk) => Some((sign_pk, perms)),
+                    User::Anyone => None,
+                }).collect();
+
+            let keys: BTreeSet<_> = actual.keys().chain(target.expected.keys()).collect();
+            let apps = keys
+                .into_iter()
+                .map(|sign_pk| AppPermissions {
+                    sign_key: *sign_pk,
+                    app_id: app_ids.get(sign_pk).cloned(),
+                    actual: actual.get(sign_pk).cloned(),
+                    expected: target.expected.get(sign_pk).cloned(),
+                }).collect();
+
+            MDataPermissionsReport {
+                container: target.container,
+                name: target.name,
+                type_tag: target.type_tag,
+                apps,
+            }
+        }).into_box()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ipc::grant_share_mdata;
+    use rand;
+    use routing::{Action, MutableData};
+    use safe_core::ipc::req::{ContainerPermissions, Permission, ShareMData, ShareMDataReq};
+    use safe_core::ipc::AuthReq;
+    use test_utils::{create_account_and_login, rand_app, register_app, register_rand_app, run};
+
+    // Test that the report flags permissions diverging from the access container.
+    // 1. Register an app with read access to `_documents`.
+    // 2. Verify that the report lists the app on `_documents` with matching permissions.
+    // 3. Give the app insert permission on `_music` behind the authenticator's back.
+    // 4. Verify that the report flags the app on `_music` as a mismatch.
+    #[test]
+    fn flags_mismatched_permissions() {
+        let auth = create_account_and_login();
+
+        let mut containers: HashMap<String, ContainerPermissions> = HashMap::new();
+        let _ = containers.insert("_documents".to_string(), btree_set![Permission::Read]);
+        let (app_id, auth_granted) = unwrap!(register_rand_app(&auth, false, containers));
+        let sign_pk = auth_granted.app_keys.sign_pk;
+
+        let report = run(&auth, |client| permissions_report(client));
+        let documents = unwrap!(
+            report
+                .iter()
+                .find(|report| report.container == Some("_documents".to_string()))
+        );
+        let app = unwrap!(documents.apps.iter().find(|app| app.sign_key == sign_pk));
+        assert_eq!(app.app_id, Some(app_id.clone()));
+        assert!(app.actual.is_some());
+        assert!(!app.is_mismatch());
+
+        run(&auth, move |client| {
+            let c2 = client.clone();
+
+            access_container::fetch_authenticator_entry(client).and_then(move |(_, mut dirs)| {
+                let music = unwrap!(dirs.remove("_music"));
+                c2.get_mdata_version(music.name, music.type_tag)
+                    .and_then(move |version| {
+                        c2.set_mdata_user_permissions(
+                            music.name,
+                            music.type_tag,
+                            User::Key(sign_pk),
+                            PermissionSet::new().allow(Action::Insert),
+                            version + 1,
+                        )
+                    }).map_err(AuthError::from)
+            })
+        });
+
+        let report = run(&auth, |client| permissions_report(client));
+        let music = unwrap!(
+            report
+                .iter()
+                .find(|report| report.container == Some("_music".to_string()))
+        );
+        let app = unwrap!(music.apps.iter().find(|app| app.sign_key == sign_pk));
+        assert_eq!(app.app_id, Some(app_id));
+        assert!(app.actual.is_some());
+        assert_eq!(app.expected, None);
+        assert!(app.is_mismatch());
+    }
+
+    // Test that the report expects the permissions granted on shared mutable data.
+    // 1. Register an app and share a mutable data with it.
+    // 2. Verify that the report lists the app on the mutable data with matching permissions.
+    #[test]
+    fn shared_mdata_permissions() {
+        let auth = create_account_and_login();
+
+        let mut containers: HashMap<String, ContainerPermissions> = HashMap::new();
+        let _ = containers.insert("_documents".to_string(), btree_set![Permission::Read]);
+        let auth_req = AuthReq {
+            app: rand_app(),
+            app_container: false,
+            containers,
+            manifest: None,
+            resp_key: None,
+        };
+        let auth_granted = unwrap!(register_app(&auth, &auth_req));
+        let sign_pk = auth_granted.app_keys.sign_pk;
+
+        let name: XorName = rand::random();
+        let perms = PermissionSet::new().allow(Action::Insert);
+        let app = auth_req.app.clone();
+
+        run(&auth, move |client| {
+            let c2 = client.clone();
+            let owner = unwrap!(client.owner_key());
+            let mdata = unwrap!(MutableData::new(
+                name,
+                0,
+                btree_map![],
+                btree_map![],
+                btree_set![owner],
+            ));
+
+            client
+                .put_mdata(mdata)
+                .map_err(AuthError::from)
+                .and_then(move |()| {
+                    let req = ShareMDataReq {
+                        app,
+                        mdata: vec![ShareMData {
+                            type_tag: 0,
+                            name,
+                            perms,
+                        }],
+                    };
+                    grant_share_mdata(&c2, req)
+                })
+        });
+
+        let report = run(&auth, |client| permissions_report(client));
+        let shared = unwrap!(
+            report
+                .iter()
+                .find(|report| report.container.is_none() && report.name == name)
+        );
+        let app = unwrap!(shared.apps.iter().find(|app| app.sign_key == sign_pk));
+        assert_eq!(app.app_id, Some(auth_req.app.id));
+        assert_eq!(app.expected, Some(perms));
+        assert!(!app.is_mismatch());
+    }
+}
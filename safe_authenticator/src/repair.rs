@@ -0,0 +1,258 @@
+// Copyright 2018 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+//! Detection and repair of inconsistencies left in the authenticator state by
+//! interrupted operations.
+
+use access_container::{self, AUTHENTICATOR_ENTRY};
+use client::AuthClient;
+use config;
+use futures::{stream, Future, Stream};
+use revocation::delete_app_auth_key;
+use routing::EntryActions;
+use rust_sodium::crypto::sign;
+use safe_core::{recovery, Client, FutureExt, MDataInfo};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std_dirs::{self, DEFAULT_PRIVATE_DIRS, DEFAULT_PUBLIC_DIRS};
+use {AuthError, AuthFuture};
+
+/// Inconsistencies found in the authenticator state.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct RepairReport {
+    /// Auth keys registered in the Maid Manager which don't belong to any app
+    /// in the authenticator config.
+    pub orphaned_auth_keys: Vec<sign::PublicKey>,
+    /// Encrypted keys of the access container entries which belong neither to the
+    /// authenticator nor to any app in the authenticator config.
+    pub unknown_access_container_entries: Vec<Vec<u8>>,
+    /// Standard containers missing from the authenticator entry.
+    pub missing_std_dirs: Vec<String>,
+}
+
+impl RepairReport {
+    /// Returns `true` if no inconsistencies have been found.
+    pub fn is_empty(&self) -> bool {
+        self.orphaned_auth_keys.is_empty()
+            && self.unknown_access_container_entries.is_empty()
+            && self.missing_std_dirs.is_empty()
+    }
+}
+
+struct Findings {
+    report: RepairReport,
+    // Versions of the unknown access container entries.
+    unknown_entries: BTreeMap<Vec<u8>, u64>,
+    auth_entry_version: u64,
+    auth_entry: HashMap<String, MDataInfo>,
+}
+
+/// Finds the inconsistencies in the authenticator state and, unless `dry_run` is set,
+/// fixes them. Returns the inconsistencies found.
+pub fn check_and_repair(client: &AuthClient, dry_run: bool) -> Box<AuthFuture<RepairReport>> {
+    let c2 = client.clone();
+
+    check(client)
+        .and_then(move |findings| {
+            if dry_run {
+                ok!(findings.report)
+            } else {
+                repair(&c2, findings)
+            }
+        }).into_box()
+}
+
+fn check(client: &AuthClient) -> Box<AuthFuture<Findings>> {
+    let access_container = client.access_container();
+    let own_key = client.public_signing_key();
+    let sk = fry!(
+        client
+            .secret_symmetric_key()
+            .ok_or_else(|| AuthError::Unexpected("Secret symmetric key not found".to_string()))
+    );
+    let auth_entry_key = fry!(access_container::enc_key(
+        &access_container,
+        AUTHENTICATOR_ENTRY,
+        &sk
+    ));
+
+    config::list_apps(client)
+        .join4(
+            client.list_auth_keys_and_version().map_err(AuthError::from),
+            client
+                .list_mdata_entries(access_container.name, access_container.type_tag)
+                .map_err(AuthError::from),
+            access_container::fetch_authenticator_entry(client),
+        ).and_then(
+            move |((_, apps), (auth_keys, _), entries, (auth_entry_version, auth_entry))| {
+                let app_keys: HashSet<_> = apps.values().map(|app| app.keys.sign_pk).collect();
+                let orphaned_auth_keys = auth_keys
+                    .into_iter()
+                    .filter(|key| !app_keys.contains(key) && Some(*key) != own_key)
+                    .collect();
+
+                let mut known_entries = HashSet::new();
+                let _ = known_entries.insert(auth_entry_key);
+                for app in apps.values() {
+                    let _ = known_entries.insert(access_container::enc_key(
+                        &access_container,
+                        &app.info.id,
+                        &app.keys.enc_key,
+                    )?);
+                }
+
+                // Empty entries have been deleted already.
+                let unknown_entries: BTreeMap<_, _> = entries
+                    .into_iter()
+                    .filter(|&(ref key, ref value)| {
+                        !value.content.is_empty() && !known_entries.contains(key)
+                    }).map(|(key, value)| (key, value.entry_version))
+                    .collect();
+
+                let missing_std_dirs = DEFAULT_PRIVATE_DIRS
+                    .iter()
+                    .chain(DEFAULT_PUBLIC_DIRS.iter())
+                    .filter(|name| !auth_entry.contains_key(**name))
+                    .map(|name| name.to_string())
+                    .collect();
+
+                Ok(Findings {
+                    report: RepairReport {
+                        orphaned_auth_keys,
+                        unknown_access_container_entries: unknown_entries.keys().cloned().collect(),
+                        missing_std_dirs,
+                    },
+                    unknown_entries,
+                    auth_entry_version,
+                    auth_entry,
+                })
+            },
+        ).into_box()
+}
+
+fn repair(client: &AuthClient, findings: Findings) -> Box<AuthFuture<RepairReport>> {
+    let Findings {
+        report,
+        unknown_entries,
+        auth_entry_version,
+        auth_entry,
+    } = findings;
+
+    let c2 = client.clone();
+    let keys_fut = stream::iter_ok(report.orphaned_auth_keys.clone())
+        .for_each(move |key| delete_app_auth_key(&c2, key));
+
+    let entries_fut = if unknown_entries.is_empty() {
+        ok!(())
+    } else {
+        let access_container = client.access_container();
+        let actions = unknown_entries
+            .into_iter()
+            .fold(EntryActions::new(), |actions, (key, version)| {
+                actions.del(key, version + 1)
+            });
+
+        recovery::mutate_mdata_entries(
+            client,
+            access_container.name,
+            access_container.type_tag,
+            actions.into(),
+        ).map_err(AuthError::from)
+        .into_box()
+    };
+
+    let dirs_fut = if report.missing_std_dirs.is_empty() {
+        ok!(())
+    } else {
+        std_dirs::create_missing_std_dirs(client, auth_entry_version, auth_entry)
+    };
+
+    keys_fut
+        .join3(entries_fut, dirs_fut)
+        .map(move |_| report)
+        .into_box()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use app_auth::{app_state, AppState};
+    use safe_core::ipc::AppKeys;
+    use test_utils::{create_account_and_login, register_rand_app, run};
+    use Authenticator;
+
+    fn break_state(auth: &Authenticator) -> sign::PublicKey {
+        let (stray_key, _) = sign::gen_keypair();
+
+        run(auth, move |client| {
+            let c2 = client.clone();
+            let c3 = client.clone();
+            let c4 = client.clone();
+            let c5 = client.clone();
+            let owner_key = unwrap!(client.owner_key());
+
+            // Auth key without an app in the config.
+            client
+                .list_auth_keys_and_version()
+                .and_then(move |(_, version)| c2.ins_auth_key(stray_key, version + 1))
+                .map_err(AuthError::from)
+                // Access container entry of an unknown app.
+                .and_then(move |()| {
+                    access_container::put_entry(
+                        &c3,
+                        "unknown-app",
+                        &AppKeys::random(owner_key),
+                        &HashMap::new(),
+                        0,
+                    )
+                })
+                // Standard container missing from the authenticator entry.
+                .and_then(move |()| access_container::fetch_authenticator_entry(&c4))
+                .and_then(move |(version, mut dirs)| {
+                    let _ = dirs.remove("_music");
+                    access_container::put_authenticator_entry(&c5, &dirs, version + 1)
+                })
+        });
+
+        stray_key
+    }
+
+    // Test finding and fixing the inconsistencies.
+    // 1. Register an app.
+    // 2. Put a stray auth key, an access container entry of an unknown app and remove
+    //    `_music` from the authenticator entry.
+    // 3. Verify that a dry run reports all three inconsistencies, but doesn't fix them.
+    // 4. Repair the state and verify that a subsequent check finds nothing.
+    // 5. Verify that the registered app is still authenticated.
+    #[test]
+    fn check_and_repair_fixes_inconsistencies() {
+        let auth = create_account_and_login();
+        let (app_id, _) = unwrap!(register_rand_app(&auth, true, HashMap::new()));
+        let stray_key = break_state(&auth);
+
+        for _ in 0..2 {
+            let report = unwrap!(auth.check_and_repair(true));
+            assert_eq!(report.orphaned_auth_keys, vec![stray_key]);
+            assert_eq!(report.unknown_access_container_entries.len(), 1);
+            assert_eq!(report.missing_std_dirs, vec!["_music".to_string()]);
+        }
+
+        let report = unwrap!(auth.check_and_repair(false));
+        assert!(!report.is_empty());
+
+        let report = unwrap!(auth.check_and_repair(true));
+        assert!(report.is_empty(), "{:?}", report);
+
+        run(&auth, move |client| {
+            let c2 = client.clone();
+
+            config::list_apps(client)
+                .and_then(move |(_, apps)| app_state(&c2, &apps, &app_id))
+                .map(|state| assert_eq!(state, AppState::Authenticated))
+        });
+    }
+}
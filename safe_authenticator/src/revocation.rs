@@ -9,7 +9,7 @@
 use super::{AuthError, AuthFuture};
 use access_container::{self, AUTHENTICATOR_ENTRY};
 use client::AuthClient;
-use config::{self, AppInfo, RevocationQueue};
+use config::{self, AppInfo, AuditAction, AuditEntry, RevocationQueue};
 use futures::future::{self, Either, Loop};
 use futures::Future;
 use routing::{ClientError, EntryActions, User, Value};
@@ -55,10 +55,10 @@ pub fn flush_app_revocation_queue(client: &AuthClient) -> Box<AuthFuture<()>> {
         }).into_box()
 }
 
-// Try to revoke all apps in the revocation queue. If app revocation results in an error, move the
-// app to the back of the queue. Keep track of failed apps and if one fails again after moving to
-// the end of the queue, return its error. In other words, we revoke all the apps that we can and
-// return an error for the first app that fails twice.
+// Try to revoke all apps in the revocation queue, recording each revocation in the audit log. If
+// app revocation results in an error, move the app to the back of the queue. Keep track of failed
+// apps and if one fails again after moving to the end of the queue, return its error. In other
+// words, we revoke all the apps that we can and return an error for the first app that fails twice.
 //
 // The exception to this is if we encounter a `SymmetricDecipherFailure` error, which we know is
 // irrecoverable, so in this case we remove the app from the queue and return an error immediately.
@@ -80,9 +80,15 @@ fn flush_app_revocation_queue_impl(
                 let f = revoke_single_app(&c2, &app_id)
                     .then(move |result| match result {
                         Ok(_) => {
+                            let c4 = c3.clone();
+                            let entry = AuditEntry::new(app_id.clone(), AuditAction::Revoke, true);
+
                             config::remove_from_app_revocation_queue(&c3, queue, version, &app_id)
-                                .map(|(version, queue)| (version, queue, moved_apps))
-                                .into_box()
+                                .join(config::remove_shared_mdata(&c3, &app_id))
+                                .and_then(move |((version, queue), ())| {
+                                    config::record_audit_entry(&c4, entry)
+                                        .map(move |()| (version, queue, moved_apps))
+                                }).into_box()
                         }
                         Err(AuthError::CoreError(CoreError::SymmetricDecipherFailure)) => {
                             // The app entry can't be decrypted. No way to revoke app, so just remove
@@ -156,9 +162,9 @@ fn revoke_single_app(client: &AuthClient, app_id: &str) -> Box<AuthFuture<()>> {
         }).into_box()
 }
 
-// Delete the app auth key from the Maid Manager - this prevents the app from
-// performing any more mutations.
-fn delete_app_auth_key(client: &AuthClient, key: sign::PublicKey) -> Box<AuthFuture<()>> {
+/// Delete the app auth key from the Maid Manager - this prevents the app from
+/// performing any more mutations. Does nothing if the key has been removed already.
+pub fn delete_app_auth_key(client: &AuthClient, key: sign::PublicKey) -> Box<AuthFuture<()>> {
     let client = client.clone();
 
     client
@@ -8,7 +8,7 @@
 
 use access_container::{self, AUTHENTICATOR_ENTRY};
 use client::AuthClient;
-use config::KEY_APPS;
+use config::{self, KEY_APPS};
 use futures::{future, Future};
 use maidsafe_utilities::serialisation::serialise;
 use routing::{ClientError, Value};
@@ -17,7 +17,7 @@ use safe_core::mdata_info;
 use safe_core::nfs::create_dir;
 use safe_core::utils::symmetric_encrypt;
 use safe_core::{Client, CoreError, FutureExt, MDataInfo, DIR_TAG};
-use std::collections::HashMap;
+use std::collections::{BTreeSet, HashMap};
 use {AuthError, AuthFuture};
 
 /// Default directories to be created at registration.
@@ -33,15 +33,47 @@ pub static DEFAULT_PRIVATE_DIRS: [&'static str; 6] = [
 /// Publicly accessible default directories to be created upon registration.
 pub static DEFAULT_PUBLIC_DIRS: [&'static str; 1] = ["_public"];
 
+/// User-defined root container to be created in addition to the standard ones.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ContainerTemplate {
+    /// Name of the container.
+    pub name: String,
+    /// If `true`, the container entries are encrypted, like the ones of `_documents`.
+    /// Otherwise they're stored in plain text, like the ones of `_public`.
+    pub encrypted: bool,
+    /// If `true`, the container is of the public type, like `_public`. Otherwise it's of
+    /// the private type, like `_documents`.
+    pub public: bool,
+}
+
+impl ContainerTemplate {
+    fn random_mdata_info(&self) -> Result<MDataInfo, CoreError> {
+        if self.encrypted {
+            MDataInfo::random_private(DIR_TAG)
+        } else {
+            MDataInfo::random_public(DIR_TAG)
+        }
+    }
+}
+
 /// Create the root directories and the standard directories for the access container.
-pub fn create(client: &AuthClient) -> Box<AuthFuture<()>> {
+/// The containers from `templates` are created alongside the standard ones when the
+/// access container doesn't exist yet.
+pub fn create(client: &AuthClient, templates: &[ContainerTemplate]) -> Box<AuthFuture<()>> {
     let c2 = client.clone();
     let c3 = client.clone();
     let c4 = client.clone();
+    let c5 = client.clone();
 
     // Initialise standard directories
     let access_container = client.access_container();
     let config_dir = client.config_root_dir();
+    let templates = templates.to_vec();
+    let public_containers: Vec<_> = templates
+        .iter()
+        .filter(|template| template.public)
+        .map(|template| template.name.clone())
+        .collect();
 
     // Try to get default dirs from the access container
     let access_cont_fut = access_container::fetch_authenticator_entry(&c2)
@@ -50,30 +82,46 @@ pub fn create(client: &AuthClient) -> Box<AuthFuture<()>> {
                 Ok((_, default_containers)) => {
                     // Make sure that all default dirs have been created
                     create_std_dirs(&c3, &default_containers)
+                        .map(|()| Vec::new())
+                        .into_box()
                 }
                 Err(AuthError::CoreError(CoreError::RoutingClientError(
                     ClientError::NoSuchData,
                 ))) => {
                     // Access container hasn't been created yet
-                    let access_cont_value = fry!(random_std_dirs())
+                    let mut access_cont_value: HashMap<_, _> = fry!(random_std_dirs())
                         .into_iter()
                         .map(|(name, md_info)| (String::from(name), md_info))
                         .collect();
+                    for template in templates {
+                        if access_cont_value.contains_key(&template.name) {
+                            return err!(AuthError::AccountContainersCreation(format!(
+                                "Container {} already exists",
+                                template.name
+                            )));
+                        }
+                        let md_info = fry!(template.random_mdata_info());
+                        let _ = access_cont_value.insert(template.name, md_info);
+                    }
+
                     let std_dirs_fut = create_std_dirs(&c3, &access_cont_value);
                     let access_cont_fut =
                         create_access_container(&c3, &access_container, &access_cont_value);
 
                     future::join_all(vec![std_dirs_fut, access_cont_fut])
-                        .map(|_| ())
+                        .map(move |_| public_containers)
                         .into_box()
                 }
                 Err(e) => err!(e),
             }
         }).into_box();
 
-    future::join_all(vec![access_cont_fut, create_config_dir(&c2, &config_dir)])
-        .map_err(From::from)
-        .and_then(move |_| {
+    access_cont_fut
+        .join(create_config_dir(&c2, &config_dir))
+        .and_then(move |(public_containers, ())| {
+            // The type of the containers is recorded once the config dir exists.
+            config::insert_public_containers(&c5, public_containers)
+        }).and_then(move |()| {
             // Update account packet - root directories have been created successfully
             // (so we don't have to recover them after login).
             c4.set_std_dirs_created(true);
@@ -157,11 +205,114 @@ pub fn create_std_dirs(
     future::join_all(creations).map(|_| ()).into_box()
 }
 
+/// Creates the standard dirs missing from the authenticator entry and adds them to it.
+/// `version` is the current version of the authenticator entry.
+pub fn create_missing_std_dirs(
+    client: &AuthClient,
+    version: u64,
+    mut std_dirs: HashMap<String, MDataInfo>,
+) -> Box<AuthFuture<()>> {
+    let c2 = client.clone();
+    let missing: HashMap<_, _> = fry!(random_std_dirs())
+        .into_iter()
+        .filter(|&(name, _)| !std_dirs.contains_key(name))
+        .map(|(name, md_info)| (String::from(name), md_info))
+        .collect();
+
+    if missing.is_empty() {
+        return ok!(());
+    }
+
+    create_std_dirs(client, &missing)
+        .and_then(move |()| {
+            std_dirs.extend(missing);
+            access_container::put_authenticator_entry(&c2, &std_dirs, version + 1)
+        }).into_box()
+}
+
+/// Creates a new root container and adds it to the authenticator entry.
+/// Returns the `MDataInfo` of the new container.
+pub fn add_root_container(
+    client: &AuthClient,
+    template: ContainerTemplate,
+) -> Box<AuthFuture<MDataInfo>> {
+    let c2 = client.clone();
+    let c3 = client.clone();
+    let c4 = client.clone();
+
+    access_container::fetch_authenticator_entry(client)
+        .and_then(move |(version, mut root_containers)| {
+            if root_containers.contains_key(&template.name) {
+                return err!(AuthError::AccountContainersCreation(format!(
+                    "Container {} already exists",
+                    template.name
+                )));
+            }
+
+            let md_info = fry!(template.random_mdata_info());
+            let public_containers = if template.public {
+                vec![template.name.clone()]
+            } else {
+                Vec::new()
+            };
+            let _ = root_containers.insert(template.name, md_info.clone());
+
+            create_dir(&c2, &md_info, btree_map![], btree_map![])
+                .map_err(AuthError::from)
+                .and_then(move |()| config::insert_public_containers(&c4, public_containers))
+                .and_then(move |()| {
+                    access_container::put_authenticator_entry(&c3, &root_containers, version + 1)
+                }).map(move |()| md_info)
+                .into_box()
+        }).into_box()
+}
+
+/// Removes a user-defined root container from the authenticator entry.
+/// The standard containers can't be removed. The container data itself is left
+/// intact on the network.
+pub fn remove_root_container(client: &AuthClient, name: String) -> Box<AuthFuture<()>> {
+    if DEFAULT_PRIVATE_DIRS
+        .iter()
+        .chain(DEFAULT_PUBLIC_DIRS.iter())
+        .any(|std_dir| *std_dir == name)
+    {
+        return err!(AuthError::from(format!(
+            "Standard container {} can't be removed",
+            name
+        )));
+    }
+
+    let c2 = client.clone();
+    let c3 = client.clone();
+
+    access_container::fetch_authenticator_entry(client)
+        .and_then(move |(version, mut root_containers)| {
+            if root_containers.remove(&name).is_none() {
+                return err!(AuthError::NoSuchContainer(name));
+            }
+            access_container::put_authenticator_entry(&c2, &root_containers, version + 1)
+                .and_then(move |()| config::remove_public_container(&c3, name))
+                .into_box()
+        }).into_box()
+}
+
+/// Returns the names of the root containers of the public type, the standard and the
+/// user-defined ones.
+pub fn public_containers(client: &AuthClient) -> Box<AuthFuture<BTreeSet<String>>> {
+    config::list_public_containers(client)
+        .map(|(_, mut containers)| {
+            containers.extend(DEFAULT_PUBLIC_DIRS.iter().map(|name| name.to_string()));
+            containers
+        }).into_box()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use futures::Future;
+    use rand::{self, Rng};
     use test_utils::{create_account_and_login, run};
+    use Authenticator;
 
     // Test creation of default dirs.
     #[test]
@@ -200,4 +351,101 @@ mod tests {
             })
         });
     }
+
+    // Test user-defined root containers.
+    // 1. Create an account with a public and a private custom container.
+    // 2. Verify that both are in the authenticator entry, alongside the standard ones,
+    //    with the requested encryption.
+    // 3. Add an unencrypted private container and verify it's in the authenticator entry,
+    //    and that only the public containers are of the public type.
+    // 4. Verify that adding it again and removing a standard container fail.
+    // 5. Remove the custom containers and verify they're gone.
+    #[test]
+    fn custom_root_containers() {
+        let mut rng = rand::thread_rng();
+        let locator: String = rng.gen_ascii_chars().take(10).collect();
+        let password: String = rng.gen_ascii_chars().take(10).collect();
+        let invitation: String = rng.gen_ascii_chars().take(10).collect();
+
+        let auth = unwrap!(Authenticator::create_acc_with_containers(
+            locator,
+            password,
+            invitation,
+            vec![
+                ContainerTemplate {
+                    name: "_blog".to_string(),
+                    encrypted: false,
+                    public: true,
+                },
+                ContainerTemplate {
+                    name: "_diary".to_string(),
+                    encrypted: true,
+                    public: false,
+                },
+            ],
+            || (),
+        ));
+
+        let dirs = run(&auth, |client| {
+            access_container::fetch_authenticator_entry(client).map(|(_, dirs)| dirs)
+        });
+        assert_eq!(
+            dirs.len(),
+            DEFAULT_PUBLIC_DIRS.len() + DEFAULT_PRIVATE_DIRS.len() + 2
+        );
+        assert!(unwrap!(dirs.get("_blog")).enc_info.is_none());
+        assert!(unwrap!(dirs.get("_diary")).enc_info.is_some());
+
+        let template = ContainerTemplate {
+            name: "_notes".to_string(),
+            encrypted: false,
+            public: false,
+        };
+        let notes = {
+            let template = template.clone();
+            run(&auth, move |client| add_root_container(client, template))
+        };
+
+        let dirs = run(&auth, |client| {
+            access_container::fetch_authenticator_entry(client).map(|(_, dirs)| dirs)
+        });
+        assert_eq!(unwrap!(dirs.get("_notes")), &notes);
+        assert!(notes.enc_info.is_none());
+
+        let public = run(&auth, |client| public_containers(client));
+        assert!(public.contains("_public"));
+        assert!(public.contains("_blog"));
+        assert!(!public.contains("_diary"));
+        assert!(!public.contains("_notes"));
+
+        run(&auth, move |client| {
+            let c2 = client.clone();
+
+            add_root_container(client, template)
+                .then(|res| match res {
+                    Err(AuthError::AccountContainersCreation(_)) => Ok(()),
+                    x => panic!("Unexpected {:?}", x),
+                }).and_then(move |()| remove_root_container(&c2, "_documents".to_string()))
+                .then(|res| match res {
+                    Err(AuthError::Unexpected(_)) => Ok::<_, AuthError>(()),
+                    x => panic!("Unexpected {:?}", x),
+                })
+        });
+
+        for name in &["_blog", "_diary", "_notes"] {
+            let name = name.to_string();
+            run(&auth, move |client| remove_root_container(client, name));
+        }
+
+        let dirs = run(&auth, |client| {
+            access_container::fetch_authenticator_entry(client).map(|(_, dirs)| dirs)
+        });
+        assert_eq!(
+            dirs.len(),
+            DEFAULT_PUBLIC_DIRS.len() + DEFAULT_PRIVATE_DIRS.len()
+        );
+
+        let public = run(&auth, |client| public_containers(client));
+        assert!(!public.contains("_blog"));
+    }
 }
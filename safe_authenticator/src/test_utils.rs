@@ -17,19 +17,22 @@ use futures::{future, Future, IntoFuture};
 use ipc::decode_ipc_msg;
 use rand::{self, Rng};
 use revocation;
-use routing::User;
-use routing::XorName;
+use routing::{PermissionSet, User, XorName};
 use rust_sodium::crypto::sign;
 use safe_core::crypto::shared_secretbox;
 use safe_core::ffi::ipc::req::{
-    AuthReq as FfiAuthReq, ContainersReq as FfiContainersReq, ShareMDataReq as FfiShareMDataReq,
+    AuthReq as FfiAuthReq, BatchReq as FfiBatchReq, ContainersReq as FfiContainersReq,
+    ShareMDataReq as FfiShareMDataReq, ShareOfferReq as FfiShareOfferReq, ShareOfferTerms,
 };
 use safe_core::ffi::ipc::resp::MetadataResponse as FfiUserMetadata;
-use safe_core::ipc::req::{container_perms_into_permission_set, ContainerPermissions};
+use safe_core::ipc::req::{
+    container_perms_into_permission_set, permission_set_clone_from_repr_c, ContainerPermissions,
+};
 use safe_core::ipc::resp::AccessContainerEntry;
 use safe_core::ipc::resp::UserMetadata;
 use safe_core::ipc::{
-    self, AppExchangeInfo, AuthGranted, AuthReq, ContainersReq, IpcMsg, IpcReq, ShareMDataReq,
+    self, AppExchangeInfo, AuthGranted, AuthReq, BatchReq, ContainersReq, IpcEncoding, IpcMsg,
+    IpcReq, ShareMDataReq, ShareOfferReq,
 };
 use safe_core::nfs::file_helper::{self, Version};
 use safe_core::nfs::{File, Mode};
@@ -61,6 +64,17 @@ macro_rules! assert_match {
 pub enum Payload {
     /// Metadata.
     Metadata(Vec<(Option<UserMetadata>, XorName, u64)>),
+    /// Terms of a share offer.
+    ShareOfferTerms {
+        /// Name of the shared mutable data.
+        name: XorName,
+        /// Type tag of the shared mutable data.
+        type_tag: u64,
+        /// Permissions being offered.
+        perms: PermissionSet,
+        /// Owner of the shared mutable data.
+        owner: sign::PublicKey,
+    },
 }
 
 /// Channel type.
@@ -148,7 +162,9 @@ pub fn register_app(
     };
 
     // Invoke `decode_ipc_msg` and expect to get AuthReq back.
-    let ipc_req = run(authenticator, move |client| decode_ipc_msg(client, msg));
+    let ipc_req = run(authenticator, move |client| {
+        decode_ipc_msg(client, msg, IpcEncoding::Base32)
+    });
     match ipc_req {
         Ok(IpcMsg::Req {
             req: IpcReq::Auth(_),
@@ -173,6 +189,8 @@ pub fn register_rand_app(
         app: rand_app(),
         app_container,
         containers: containers_req,
+        manifest: None,
+        resp_key: None,
     };
 
     let auth_granted = register_app(authenticator, &auth_req)?;
@@ -440,9 +458,24 @@ where
 //       FFI function `ipc::decode_ipc_msg` should be only wrapper over it.
 /// Helper to decode IpcMsg.
 pub fn auth_decode_ipc_msg_helper(authenticator: &Authenticator, msg: &str) -> ChannelType {
+    auth_decode_ipc_msg_helper_with_version(authenticator, msg, ipc::IPC_VERSION)
+}
+
+/// Helper to decode IpcMsg with the FFI function of a front-end supporting the given
+/// IPC protocol version.
+pub fn auth_decode_ipc_msg_helper_with_version(
+    authenticator: &Authenticator,
+    msg: &str,
+    version: u32,
+) -> ChannelType {
     let (tx, rx) = mpsc::channel::<ChannelType>();
 
-    extern "C" fn auth_cb(user_data: *mut c_void, req_id: u32, req: *const FfiAuthReq) {
+    extern "C" fn auth_cb(
+        user_data: *mut c_void,
+        req_id: u32,
+        _encoding: u32,
+        req: *const FfiAuthReq,
+    ) {
         unsafe {
             let req = match AuthReq::clone_from_repr_c(req) {
                 Ok(req) => req,
@@ -458,7 +491,12 @@ pub fn auth_decode_ipc_msg_helper(authenticator: &Authenticator, msg: &str) -> C
         }
     }
 
-    extern "C" fn containers_cb(user_data: *mut c_void, req_id: u32, req: *const FfiContainersReq) {
+    extern "C" fn containers_cb(
+        user_data: *mut c_void,
+        req_id: u32,
+        _encoding: u32,
+        req: *const FfiContainersReq,
+    ) {
         unsafe {
             let req = match ContainersReq::clone_from_repr_c(req) {
                 Ok(req) => req,
@@ -477,6 +515,7 @@ pub fn auth_decode_ipc_msg_helper(authenticator: &Authenticator, msg: &str) -> C
     extern "C" fn share_mdata_cb(
         user_data: *mut c_void,
         req_id: u32,
+        _encoding: u32,
         req: *const FfiShareMDataReq,
         ffi_metadata: *const FfiUserMetadata,
         ffi_metadata_len: usize,
@@ -487,19 +526,7 @@ pub fn auth_decode_ipc_msg_helper(authenticator: &Authenticator, msg: &str) -> C
                 Err(_) => return send_via_user_data::<ChannelType>(user_data, Err((-2, None))),
             };
 
-            let metadatas: Vec<_> = slice::from_raw_parts(ffi_metadata, ffi_metadata_len)
-                .iter()
-                .map(|ffi_metadata| {
-                    (
-                        if ffi_metadata.name.is_null() {
-                            None
-                        } else {
-                            Some(unwrap!(UserMetadata::clone_from_repr_c(ffi_metadata)))
-                        },
-                        XorName(ffi_metadata.xor_name),
-                        ffi_metadata.type_tag,
-                    )
-                }).collect();
+            let metadatas = metadata_clone_from_repr_c(ffi_metadata, ffi_metadata_len);
 
             let msg = IpcMsg::Req {
                 req_id,
@@ -513,20 +540,123 @@ pub fn auth_decode_ipc_msg_helper(authenticator: &Authenticator, msg: &str) -> C
         }
     }
 
+    extern "C" fn batch_cb(
+        user_data: *mut c_void,
+        req_id: u32,
+        _encoding: u32,
+        req: *const FfiBatchReq,
+        ffi_metadata: *const FfiUserMetadata,
+        ffi_metadata_len: usize,
+    ) {
+        unsafe {
+            let req = match BatchReq::clone_from_repr_c(req) {
+                Ok(req) => req,
+                Err(_) => return send_via_user_data::<ChannelType>(user_data, Err((-2, None))),
+            };
+
+            let metadatas = metadata_clone_from_repr_c(ffi_metadata, ffi_metadata_len);
+
+            let msg = IpcMsg::Req {
+                req_id,
+                req: IpcReq::Batch(req),
+            };
+
+            send_via_user_data::<ChannelType>(
+                user_data,
+                Ok((msg, Some(Payload::Metadata(metadatas)))),
+            )
+        }
+    }
+
+    extern "C" fn share_offer_cb(
+        user_data: *mut c_void,
+        req_id: u32,
+        _encoding: u32,
+        req: *const FfiShareOfferReq,
+        terms: *const ShareOfferTerms,
+    ) {
+        unsafe {
+            let req = match ShareOfferReq::clone_from_repr_c(req) {
+                Ok(req) => req,
+                Err(_) => return send_via_user_data::<ChannelType>(user_data, Err((-2, None))),
+            };
+
+            let payload = Payload::ShareOfferTerms {
+                name: XorName((*terms).name),
+                type_tag: (*terms).type_tag,
+                perms: unwrap!(permission_set_clone_from_repr_c((*terms).perms)),
+                owner: sign::PublicKey((*terms).owner),
+            };
+
+            let msg = IpcMsg::Req {
+                req_id,
+                req: IpcReq::ShareOffer(req),
+            };
+
+            send_via_user_data::<ChannelType>(user_data, Ok((msg, Some(payload))))
+        }
+    }
+
+    unsafe fn metadata_clone_from_repr_c(
+        ffi_metadata: *const FfiUserMetadata,
+        ffi_metadata_len: usize,
+    ) -> Vec<(Option<UserMetadata>, XorName, u64)> {
+        slice::from_raw_parts(ffi_metadata, ffi_metadata_len)
+            .iter()
+            .map(|ffi_metadata| {
+                (
+                    if ffi_metadata.name.is_null() {
+                        None
+                    } else {
+                        Some(unwrap!(UserMetadata::clone_from_repr_c(ffi_metadata)))
+                    },
+                    XorName(ffi_metadata.xor_name),
+                    ffi_metadata.type_tag,
+                )
+            }).collect()
+    }
+
     let ffi_msg = unwrap!(CString::new(msg));
     let mut ud = Default::default();
 
     unsafe {
-        ::ffi::ipc::auth_decode_ipc_msg(
-            authenticator,
-            ffi_msg.as_ptr(),
-            sender_as_user_data(&tx, &mut ud),
-            auth_cb,
-            containers_cb,
-            unregistered_cb,
-            share_mdata_cb,
-            err_cb,
-        );
+        if version >= 4 {
+            ::ffi::ipc::auth_decode_ipc_msg_v4(
+                authenticator,
+                ffi_msg.as_ptr(),
+                sender_as_user_data(&tx, &mut ud),
+                auth_cb,
+                containers_cb,
+                unregistered_cb,
+                share_mdata_cb,
+                batch_cb,
+                share_offer_cb,
+                err_cb,
+            );
+        } else if version == 3 {
+            ::ffi::ipc::auth_decode_ipc_msg_v3(
+                authenticator,
+                ffi_msg.as_ptr(),
+                sender_as_user_data(&tx, &mut ud),
+                auth_cb,
+                containers_cb,
+                unregistered_cb,
+                share_mdata_cb,
+                batch_cb,
+                err_cb,
+            );
+        } else {
+            ::ffi::ipc::auth_decode_ipc_msg(
+                authenticator,
+                ffi_msg.as_ptr(),
+                sender_as_user_data(&tx, &mut ud),
+                auth_cb,
+                containers_cb,
+                unregistered_cb,
+                share_mdata_cb,
+                err_cb,
+            );
+        }
     };
 
     let ret = match rx.recv_timeout(Duration::from_secs(15)) {
@@ -541,6 +671,7 @@ pub fn auth_decode_ipc_msg_helper(authenticator: &Authenticator, msg: &str) -> C
 pub extern "C" fn unregistered_cb(
     user_data: *mut c_void,
     req_id: u32,
+    _encoding: u32,
     extra_data: *const u8,
     extra_data_len: usize,
 ) {
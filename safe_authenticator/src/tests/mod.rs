@@ -12,27 +12,35 @@ mod share_mdata;
 mod utils;
 
 use access_container as access_container_tools;
+use app_auth;
 use app_container;
 use config::{self, KEY_APPS};
-use errors::{AuthError, ERR_INVALID_MSG, ERR_OPERATION_FORBIDDEN, ERR_UNKNOWN_APP};
+use errors::{
+    AuthError, ERR_INVALID_MANIFEST, ERR_INVALID_MSG, ERR_MANIFEST_KEY_MISMATCH,
+    ERR_OPERATION_FORBIDDEN, ERR_UNKNOWN_APP, ERR_UPGRADE_REQUIRED,
+};
 use ffi::apps::*;
 use ffi::ipc::{
-    auth_revoke_app, encode_auth_resp, encode_containers_resp, encode_unregistered_resp,
+    auth_revoke_app, encode_auth_resp, encode_batch_resp, encode_containers_resp,
+    encode_unregistered_resp, IPC_ENCODING_BASE32, IPC_ENCODING_JSON,
 };
-use ffi_utils::test_utils::{call_1, call_vec, sender_as_user_data};
-use ffi_utils::{from_c_str, ErrorCode, ReprC, StringError};
+use ffi_utils::test_utils::{call_1, call_vec, send_via_user_data, sender_as_user_data};
+use ffi_utils::{from_c_str, ErrorCode, FfiResult, ReprC, StringError};
 use futures::{future, Future};
+use rust_sodium::crypto::sign;
 use safe_core::ffi::ipc::req::AppExchangeInfo as FfiAppExchangeInfo;
 use safe_core::ipc::{
-    self, AuthReq, BootstrapConfig, ContainersReq, IpcError, IpcMsg, IpcReq, IpcResp, Permission,
+    self, AppManifest, AuthReq, BatchItem, BatchReq, BootstrapConfig, ContainersReq, IpcError,
+    IpcMsg, IpcReq, IpcResp, Permission,
 };
 use safe_core::{app_container_name, mdata_info, Client};
 use std::collections::HashMap;
 use std::ffi::CString;
+use std::os::raw::{c_char, c_void};
 use std::sync::mpsc;
 use std::time::Duration;
 use std_dirs::{DEFAULT_PRIVATE_DIRS, DEFAULT_PUBLIC_DIRS};
-use test_utils::{self, ChannelType};
+use test_utils::{self, ChannelType, Payload};
 use tiny_keccak::sha3_256;
 
 #[cfg(feature = "use-mock-routing")]
@@ -257,6 +265,8 @@ mod mock_routing {
             app: test_utils::rand_app(),
             app_container: true,
             containers: utils::create_containers_req(),
+            manifest: None,
+            resp_key: None,
         };
         let app_id = auth_req.app.id.clone();
 
@@ -493,6 +503,8 @@ fn app_authentication() {
         app: app_exchange_info.clone(),
         app_container: true,
         containers,
+        manifest: None,
+        resp_key: None,
     };
 
     let msg = IpcMsg::Req {
@@ -525,6 +537,7 @@ fn app_authentication() {
                 &authenticator,
                 &auth_req,
                 req_id,
+                IPC_ENCODING_BASE32,
                 true, // is_granted
                 ud,
                 cb,
@@ -631,6 +644,8 @@ fn invalid_container_authentication() {
         app: app_exchange_info.clone(),
         app_container: true,
         containers,
+        manifest: None,
+        resp_key: None,
     };
 
     // Try to send IpcReq::Auth - it should fail
@@ -641,6 +656,7 @@ fn invalid_container_authentication() {
                 &authenticator,
                 &auth_req,
                 req_id,
+                IPC_ENCODING_BASE32,
                 true, // is_granted
                 ud,
                 cb,
@@ -668,6 +684,8 @@ fn unregistered_authentication() {
             app: test_utils::rand_app(),
             app_container: true,
             containers: utils::create_containers_req(),
+            manifest: None,
+            resp_key: None,
         }),
     };
     let encoded_msg = unwrap!(ipc::encode_msg(&msg));
@@ -704,8 +722,11 @@ fn unregistered_authentication() {
     let encoded_resp: String = unsafe {
         unwrap!(call_1(|ud, cb| {
             encode_unregistered_resp(
-                req_id, true, // is_granted
-                ud, cb,
+                req_id,
+                IPC_ENCODING_BASE32,
+                true, // is_granted
+                ud,
+                cb,
             )
         }))
     };
@@ -755,6 +776,8 @@ fn authenticated_app_can_be_authenticated_again() {
         app: test_utils::rand_app(),
         app_container: false,
         containers: Default::default(),
+        manifest: None,
+        resp_key: None,
     };
 
     let req_id = ipc::gen_req_id();
@@ -785,6 +808,7 @@ fn authenticated_app_can_be_authenticated_again() {
                 &authenticator,
                 &auth_req,
                 req_id,
+                IPC_ENCODING_BASE32,
                 true, // is_granted
                 ud,
                 cb,
@@ -853,6 +877,264 @@ fn containers_unknown_app() {
     };
 }
 
+// Test verification of the signed app manifest.
+// 1. Authorise an app with a manifest signed by developer A.
+// 2. A request for the same app ID with a tampered manifest must be rejected as invalid.
+// 3. A request for the same app ID with a manifest signed by developer B must be
+//    rejected as a key mismatch, and so must a request without a manifest.
+// 4. Another request with a manifest signed by developer A must be accepted.
+#[test]
+fn app_manifest_verification() {
+    let authenticator = test_utils::create_account_and_login();
+
+    let app = test_utils::rand_app();
+    let mut containers = HashMap::new();
+    let _ = containers.insert("_pictures".to_string(), btree_set![Permission::Read]);
+    let reasons = btree_map!["_pictures".to_string() => "To show your photos".to_string()];
+
+    let manifest = |pk, sk: &sign::SecretKey| {
+        unwrap!(AppManifest::new(
+            &app.id,
+            None,
+            Some("safe://photos".to_string()),
+            reasons.clone(),
+            pk,
+            sk,
+        ))
+    };
+    let (pk_a, sk_a) = sign::gen_keypair();
+    let (pk_b, sk_b) = sign::gen_keypair();
+
+    let auth_req = AuthReq {
+        app: app.clone(),
+        app_container: false,
+        containers,
+        manifest: Some(manifest(pk_a, &sk_a)),
+        resp_key: None,
+    };
+    let _ = unwrap!(test_utils::register_app(&authenticator, &auth_req));
+
+    let decode = |manifest: Option<AppManifest>| {
+        let msg = IpcMsg::Req {
+            req_id: ipc::gen_req_id(),
+            req: IpcReq::Auth(AuthReq {
+                manifest,
+                ..auth_req.clone()
+            }),
+        };
+        let encoded_msg = unwrap!(ipc::encode_msg(&msg));
+        test_utils::auth_decode_ipc_msg_helper(&authenticator, &encoded_msg)
+    };
+
+    let mut tampered = manifest(pk_a, &sk_a);
+    tampered.homepage = Some("safe://evil".to_string());
+    match decode(Some(tampered)) {
+        Err((
+            code,
+            Some(IpcMsg::Resp {
+                resp: IpcResp::Auth(Err(IpcError::InvalidManifest)),
+                ..
+            }),
+        ))
+            if code == ERR_INVALID_MANIFEST =>
+        {
+            ()
+        }
+        x => panic!("Unexpected {:?}", x),
+    };
+
+    for other in vec![Some(manifest(pk_b, &sk_b)), None] {
+        match decode(other) {
+            Err((
+                code,
+                Some(IpcMsg::Resp {
+                    resp: IpcResp::Auth(Err(IpcError::ManifestKeyMismatch)),
+                    ..
+                }),
+            ))
+                if code == ERR_MANIFEST_KEY_MISMATCH =>
+            {
+                ()
+            }
+            x => panic!("Unexpected {:?}", x),
+        };
+    }
+
+    match decode(Some(manifest(pk_a, &sk_a))) {
+        Ok(IpcMsg::Req {
+            req: IpcReq::Auth(_),
+            ..
+        }) => (),
+        x => panic!("Unexpected {:?}", x),
+    };
+}
+
+// Test that the developer of an app authorised without decoding the request is only
+// remembered if the manifest signature is valid.
+// 1. Authorise an app with a tampered manifest, bypassing the request decoding.
+// 2. Verify that the authorisation fails and the developer isn't remembered.
+#[test]
+fn app_manifest_unverified_developer() {
+    let authenticator = test_utils::create_account_and_login();
+
+    let app = test_utils::rand_app();
+    let (pk, sk) = sign::gen_keypair();
+    let mut manifest = unwrap!(AppManifest::new(
+        &app.id,
+        None,
+        None,
+        Default::default(),
+        pk,
+        &sk,
+    ));
+    manifest.homepage = Some("safe://evil".to_string());
+
+    let auth_req = AuthReq {
+        app: app.clone(),
+        app_container: false,
+        containers: HashMap::new(),
+        manifest: Some(manifest),
+        resp_key: None,
+    };
+    match test_utils::try_run(&authenticator, move |client| {
+        app_auth::authenticate(client, auth_req)
+    }) {
+        Err(AuthError::IpcError(IpcError::InvalidManifest)) => (),
+        x => panic!("Unexpected {:?}", x),
+    }
+
+    let developers = test_utils::run(&authenticator, |client| {
+        config::list_app_developers(client).map(|(_, developers)| developers)
+    });
+    assert!(!developers.contains_key(&app.id));
+}
+
+// Test that a JSON-encoded authorisation request is decoded like a bincode-encoded one.
+#[test]
+fn json_encoded_auth_request() {
+    let authenticator = test_utils::create_account_and_login();
+
+    let req_id = ipc::gen_req_id();
+    let auth_req = AuthReq {
+        app: test_utils::rand_app(),
+        app_container: true,
+        containers: utils::create_containers_req(),
+        manifest: None,
+        resp_key: None,
+    };
+    let msg = IpcMsg::Req {
+        req_id,
+        req: IpcReq::Auth(auth_req.clone()),
+    };
+    let encoded_msg = unwrap!(ipc::encode_msg_json(&msg));
+
+    match test_utils::auth_decode_ipc_msg_helper(&authenticator, &encoded_msg) {
+        Ok((
+            IpcMsg::Req {
+                req_id: received_req_id,
+                req: IpcReq::Auth(received_auth_req),
+            },
+            _,
+        )) => {
+            assert_eq!(received_req_id, req_id);
+            assert_eq!(received_auth_req, auth_req);
+        }
+        x => panic!("Unexpected {:?}", x),
+    };
+
+    // The response is JSON-encoded too.
+    let encoded_auth_resp: String = unsafe {
+        unwrap!(call_1(|ud, cb| {
+            let auth_req = unwrap!(auth_req.into_repr_c());
+            encode_auth_resp(
+                &authenticator,
+                &auth_req,
+                req_id,
+                IPC_ENCODING_JSON,
+                true, // is_granted
+                ud,
+                cb,
+            )
+        }))
+    };
+    assert!(encoded_auth_resp.starts_with(ipc::JSON_PREFIX));
+
+    match unwrap!(ipc::decode_msg(&encoded_auth_resp)) {
+        IpcMsg::Resp {
+            req_id: received_req_id,
+            resp: IpcResp::Auth(Ok(_)),
+        } => assert_eq!(received_req_id, req_id),
+        x => panic!("Unexpected {:?}", x),
+    }
+}
+
+// Test that the encoding of a request is reported when decoding it, so that the response
+// can be encoded the same way.
+#[test]
+fn json_encoded_unregistered_request() {
+    extern "C" fn unregistered_cb(
+        user_data: *mut c_void,
+        req_id: u32,
+        encoding: u32,
+        _extra_data: *const u8,
+        _extra_data_len: usize,
+    ) {
+        unsafe { send_via_user_data(user_data, Ok::<_, i32>((req_id, encoding))) }
+    }
+
+    extern "C" fn err_cb(user_data: *mut c_void, res: *const FfiResult, _resp: *const c_char) {
+        unsafe { send_via_user_data(user_data, Err::<(u32, u32), _>((*res).error_code)) }
+    }
+
+    let req_id = ipc::gen_req_id();
+    let msg = IpcMsg::Req {
+        req_id,
+        req: IpcReq::Unregistered(vec![1, 2, 3]),
+    };
+
+    for (encoded_msg, expected_encoding) in vec![
+        (unwrap!(ipc::encode_msg(&msg)), IPC_ENCODING_BASE32),
+        (unwrap!(ipc::encode_msg_json(&msg)), IPC_ENCODING_JSON),
+    ] {
+        let (tx, rx) = mpsc::channel::<Result<(u32, u32), i32>>();
+        let ffi_msg = unwrap!(CString::new(encoded_msg));
+        let mut ud = Default::default();
+
+        unsafe {
+            use ffi::ipc::auth_unregistered_decode_ipc_msg;
+            auth_unregistered_decode_ipc_msg(
+                ffi_msg.as_ptr(),
+                sender_as_user_data(&tx, &mut ud),
+                unregistered_cb,
+                err_cb,
+            );
+        }
+
+        let (received_req_id, encoding) =
+            unwrap!(unwrap!(rx.recv_timeout(Duration::from_secs(15))));
+        assert_eq!(received_req_id, req_id);
+        assert_eq!(encoding, expected_encoding);
+
+        let encoded_resp: String = unsafe {
+            unwrap!(call_1(|ud, cb| encode_unregistered_resp(
+                req_id, encoding, false, ud, cb
+            )))
+        };
+        assert_eq!(
+            encoded_resp.starts_with(ipc::JSON_PREFIX),
+            encoding == IPC_ENCODING_JSON
+        );
+
+        match unwrap!(ipc::decode_msg(&encoded_resp)) {
+            IpcMsg::Resp {
+                req_id: received_req_id,
+                resp: IpcResp::Unregistered(Err(IpcError::AuthDenied)),
+            } => assert_eq!(received_req_id, req_id),
+            x => panic!("Unexpected {:?}", x),
+        }
+    }
+}
+
 // Test making a containers access request.
 #[test]
 fn containers_access_request() {
@@ -865,6 +1147,8 @@ fn containers_access_request() {
         app: test_utils::rand_app(),
         app_container: true,
         containers: utils::create_containers_req(),
+        manifest: None,
+        resp_key: None,
     };
     let app_id = auth_req.app.id.clone();
 
@@ -891,6 +1175,7 @@ fn containers_access_request() {
                 &authenticator,
                 &cont_req,
                 req_id,
+                IPC_ENCODING_BASE32,
                 true, // is_granted
                 ud,
                 cb,
@@ -922,6 +1207,155 @@ fn containers_access_request() {
     );
 }
 
+// Test batches of requests.
+// 1. A batch mixing requests of different apps must be rejected.
+// 2. A batch with an authorisation and a containers request is decoded as a whole.
+// 3. A batch which can only be approved as a whole can't be approved partially.
+// 4. Approving the authorisation and denying the containers request authorises the app
+//    and reports the denial in the response for the second request.
+#[test]
+fn batch_request() {
+    let authenticator = test_utils::create_account_and_login();
+
+    let auth_req = AuthReq {
+        app: test_utils::rand_app(),
+        app_container: false,
+        containers: utils::create_containers_req(),
+        manifest: None,
+        resp_key: None,
+    };
+    let cont_req = ContainersReq {
+        app: auth_req.app.clone(),
+        containers: {
+            let mut containers = HashMap::new();
+            let _ = containers.insert("_downloads".to_string(), btree_set![Permission::Update]);
+            containers
+        },
+    };
+
+    let batch_req = BatchReq {
+        items: vec![
+            BatchItem::Auth(auth_req.clone()),
+            BatchItem::Containers(ContainersReq {
+                app: test_utils::rand_app(),
+                ..cont_req.clone()
+            }),
+        ],
+        single_decision: false,
+    };
+    let msg = IpcMsg::Req {
+        req_id: ipc::gen_req_id(),
+        req: IpcReq::Batch(batch_req),
+    };
+    let encoded_msg = unwrap!(ipc::encode_msg(&msg));
+
+    match test_utils::auth_decode_ipc_msg_helper(&authenticator, &encoded_msg) {
+        Err((
+            code,
+            Some(IpcMsg::Resp {
+                resp: IpcResp::Batch(Err(IpcError::InvalidMsg)),
+                ..
+            }),
+        ))
+            if code == ERR_INVALID_MSG =>
+        {
+            ()
+        }
+        x => panic!("Unexpected {:?}", x),
+    };
+
+    let req_id = ipc::gen_req_id();
+    let batch_req = BatchReq {
+        items: vec![BatchItem::Auth(auth_req), BatchItem::Containers(cont_req)],
+        single_decision: true,
+    };
+    let msg = IpcMsg::Req {
+        req_id,
+        req: IpcReq::Batch(batch_req.clone()),
+    };
+    let encoded_msg = unwrap!(ipc::encode_msg(&msg));
+
+    match test_utils::auth_decode_ipc_msg_helper(&authenticator, &encoded_msg) {
+        Ok((
+            IpcMsg::Req {
+                req: IpcReq::Batch(received_batch_req),
+                ..
+            },
+            Some(Payload::Metadata(metadata)),
+        )) => {
+            assert_eq!(received_batch_req, batch_req);
+            assert!(metadata.is_empty());
+        }
+        x => panic!("Unexpected {:?}", x),
+    };
+
+    let encode = |batch_req: &BatchReq, approvals: &[bool]| -> Result<String, i32> {
+        unsafe {
+            call_1(|ud, cb| {
+                let batch_req = unwrap!(batch_req.clone().into_repr_c());
+                encode_batch_resp(
+                    &authenticator,
+                    &batch_req,
+                    req_id,
+                    IPC_ENCODING_BASE32,
+                    approvals.as_ptr(),
+                    approvals.len(),
+                    ud,
+                    cb,
+                )
+            })
+        }
+    };
+
+    assert!(encode(&batch_req, &[true, false]).is_err());
+
+    let batch_req = BatchReq {
+        single_decision: false,
+        ..batch_req
+    };
+    let encoded_resp = unwrap!(encode(&batch_req, &[true, false]));
+
+    match ipc::decode_msg(&encoded_resp) {
+        Ok(IpcMsg::Resp {
+            resp: IpcResp::Batch(Ok(ref resps)),
+            ..
+        }) => match resps.as_slice() {
+            [IpcResp::Auth(Ok(_)), IpcResp::Containers(Err(IpcError::AuthDenied))] => (),
+            x => panic!("Unexpected {:?}", x),
+        },
+        x => panic!("Unexpected {:?}", x),
+    }
+}
+
+// Test that a front-end decoding messages with `auth_decode_ipc_msg`, which can't
+// report batches, answers a batch by telling the app to upgrade the authenticator.
+#[test]
+fn batch_request_requires_upgrade() {
+    let authenticator = test_utils::create_account_and_login();
+
+    let batch_req = BatchReq {
+        items: vec![BatchItem::Containers(ContainersReq {
+            app: test_utils::rand_app(),
+            containers: utils::create_containers_req(),
+        })],
+        single_decision: false,
+    };
+    let msg = IpcMsg::Req {
+        req_id: ipc::gen_req_id(),
+        req: IpcReq::Batch(batch_req),
+    };
+    let encoded_msg = unwrap!(ipc::encode_msg(&msg));
+
+    match test_utils::auth_decode_ipc_msg_helper_with_version(&authenticator, &encoded_msg, 2) {
+        Err((code, Some(IpcMsg::Err(IpcError::UpgradeRequired(3)))))
+            if code == ERR_UPGRADE_REQUIRED =>
+        {
+            ()
+        }
+        x => panic!("Unexpected {:?}", x),
+    };
+}
+
 struct RegisteredAppId(String);
 impl ReprC for RegisteredAppId {
     type C = *const RegisteredApp;
@@ -973,12 +1407,16 @@ fn lists_of_registered_and_revoked_apps() {
         app: test_utils::rand_app(),
         app_container: false,
         containers: Default::default(),
+        manifest: None,
+        resp_key: None,
     };
 
     let auth_req2 = AuthReq {
         app: test_utils::rand_app(),
         app_container: false,
         containers: Default::default(),
+        manifest: None,
+        resp_key: None,
     };
 
     let _ = unwrap!(test_utils::register_app(&authenticator, &auth_req1));
@@ -29,7 +29,7 @@ mod mock_routing {
     use access_container;
     use app_auth::{app_state, AppState};
     use client::AuthClient;
-    use config;
+    use config::{self, AuditAction};
     use ffi::ipc::auth_flush_app_revocation_queue;
     use ffi_utils::test_utils::call_0;
     use futures::future;
@@ -79,6 +79,8 @@ mod mock_routing {
             app: rand_app(),
             app_container: false,
             containers: create_containers_req(),
+            manifest: None,
+            resp_key: None,
         };
         let app_id = auth_req.app.id.clone();
         let auth_granted = unwrap!(register_app(&auth, &auth_req));
@@ -209,6 +211,8 @@ mod mock_routing {
             app: rand_app(),
             app_container: false,
             containers: create_containers_req(),
+            manifest: None,
+            resp_key: None,
         };
 
         let app_id = auth_req.app.id.clone();
@@ -236,7 +240,7 @@ mod mock_routing {
     // 2. Revoke both of them, but simulate network failure so both revocations would
     //    fail.
     // 3. Log in again and flush the revocation queue with no simulated failures.
-    // 4. Verify both apps are successfully revoked.
+    // 4. Verify both apps are successfully revoked, and the revocations are in the audit log.
     #[test]
     fn flushing_app_revocation_queue() {
         // Create account.
@@ -247,6 +251,8 @@ mod mock_routing {
             app: rand_app(),
             app_container: false,
             containers: create_containers_req(),
+            manifest: None,
+            resp_key: None,
         };
 
         let _ = unwrap!(register_app(&auth, &auth_req));
@@ -257,6 +263,8 @@ mod mock_routing {
             app: rand_app(),
             app_container: false,
             containers: create_containers_req(),
+            manifest: None,
+            resp_key: None,
         };
 
         let _ = unwrap!(register_app(&auth, &auth_req));
@@ -300,24 +308,38 @@ mod mock_routing {
             )))
         }
 
-        run(&auth, |client| {
-            let c2 = client.clone();
+        {
+            let app_id_0 = app_id_0.clone();
+            let app_id_1 = app_id_1.clone();
 
-            config::list_apps(client)
-                .then(move |res| {
-                    let (_, apps) = unwrap!(res);
-                    let f_0 = app_state(&c2, &apps, &app_id_0);
-                    let f_1 = app_state(&c2, &apps, &app_id_1);
+            run(&auth, |client| {
+                let c2 = client.clone();
 
-                    f_0.join(f_1)
-                }).then(move |res| {
-                    let (state_0, state_1) = unwrap!(res);
-                    assert_eq!(state_0, AppState::Revoked);
-                    assert_eq!(state_1, AppState::Revoked);
+                config::list_apps(client)
+                    .then(move |res| {
+                        let (_, apps) = unwrap!(res);
+                        let f_0 = app_state(&c2, &apps, &app_id_0);
+                        let f_1 = app_state(&c2, &apps, &app_id_1);
 
-                    Ok(())
-                })
-        })
+                        f_0.join(f_1)
+                    }).then(move |res| {
+                        let (state_0, state_1) = unwrap!(res);
+                        assert_eq!(state_0, AppState::Revoked);
+                        assert_eq!(state_1, AppState::Revoked);
+
+                        Ok(())
+                    })
+            })
+        }
+
+        let (_, audit_log) = run(&auth, |client| config::get_audit_log(client));
+        let revoked: Vec<_> = audit_log
+            .into_iter()
+            .filter(|entry| entry.action == AuditAction::Revoke && entry.granted)
+            .map(|entry| entry.app_id)
+            .collect();
+        assert!(revoked.contains(&app_id_0));
+        assert!(revoked.contains(&app_id_1));
     }
 
     // Test one app being revoked by multiple authenticator concurrently.
@@ -716,6 +738,8 @@ fn app_revocation() {
         app: rand_app(),
         app_container: false,
         containers: create_containers_req(),
+        manifest: None,
+        resp_key: None,
     };
     let app_id1 = auth_req1.app.id.clone();
     let auth_granted1 = unwrap!(register_app(&authenticator, &auth_req1));
@@ -724,6 +748,8 @@ fn app_revocation() {
         app: rand_app(),
         app_container: true,
         containers: create_containers_req(),
+        manifest: None,
+        resp_key: None,
     };
     let app_id2 = auth_req2.app.id.clone();
     let auth_granted2 = unwrap!(register_app(&authenticator, &auth_req2));
@@ -884,6 +910,8 @@ fn revocation_symmetric_decipher_failure() {
         app: rand_app(),
         app_container: false,
         containers: create_containers_req(),
+        manifest: None,
+        resp_key: None,
     };
     let app_id1 = auth_req1.app.id.clone();
     debug!("Registering app 1 with ID {}...", app_id1);
@@ -893,6 +921,8 @@ fn revocation_symmetric_decipher_failure() {
         app: rand_app(),
         app_container: true,
         containers: corrupt_containers,
+        manifest: None,
+        resp_key: None,
     };
     let app_id2 = auth_req2.app.id.clone();
     debug!("Registering app 2 with ID {}...", app_id2);
@@ -902,6 +932,8 @@ fn revocation_symmetric_decipher_failure() {
         app: rand_app(),
         app_container: false,
         containers: create_containers_req(),
+        manifest: None,
+        resp_key: None,
     };
     let app_id3 = auth_req3.app.id.clone();
     debug!("Registering app 3 with ID {}...", app_id3);
@@ -1009,6 +1041,8 @@ fn flushing_empty_app_revocation_queue_does_not_mutate_network() {
         app: rand_app(),
         app_container: false,
         containers: create_containers_req(),
+        manifest: None,
+        resp_key: None,
     };
     let _ = unwrap!(register_app(&auth, &auth_req));
     let app_id = auth_req.app.id;
@@ -192,6 +192,8 @@ fn setup() -> (Stash, PathBuf) {
             app: app_exchange_info,
             app_container: false,
             containers: containers.clone(),
+            manifest: None,
+            resp_key: None,
         }
     };
 
@@ -207,6 +209,8 @@ fn setup() -> (Stash, PathBuf) {
             app: app_exchange_info,
             app_container: false,
             containers: containers.clone(),
+            manifest: None,
+            resp_key: None,
         }
     };
 
@@ -6,21 +6,28 @@
 // KIND, either express or implied. Please review the Licences for the specific language governing
 // permissions and limitations relating to use of the SAFE Network Software.
 
-use errors::{AuthError, ERR_INVALID_OWNER, ERR_SHARE_MDATA_DENIED};
+use config;
+use errors::{
+    AuthError, ERR_INVALID_OWNER, ERR_INVALID_SHARE_OFFER, ERR_SHARE_MDATA_DENIED, ERR_UNKNOWN_APP,
+    ERR_UPGRADE_REQUIRED,
+};
 use ffi::apps::*;
-use ffi::ipc::encode_share_mdata_resp;
+use ffi::ipc::{encode_share_mdata_resp, IPC_ENCODING_BASE32};
 use ffi_utils::test_utils::{call_vec, send_via_user_data, sender_as_user_data};
 use ffi_utils::FfiResult;
 use futures::Future;
+use ipc::grant_share_offer;
 use maidsafe_utilities::serialisation::serialise;
 use rand;
 use routing::{Action, MutableData, PermissionSet, User, Value};
-use rust_sodium::crypto::sign;
+use rust_sodium::crypto::{box_, sign};
 use safe_core::ipc::req::AppExchangeInfo;
 use safe_core::ipc::resp::{AppAccess, UserMetadata, METADATA_KEY};
-use safe_core::ipc::{self, AuthReq, IpcMsg, IpcReq, ShareMData, ShareMDataReq};
-use safe_core::Client;
-use std::collections::BTreeMap;
+use safe_core::ipc::{
+    self, AuthReq, IpcError, IpcMsg, IpcReq, ShareMData, ShareMDataReq, ShareOffer, ShareOfferReq,
+};
+use safe_core::{Client, MDataInfo};
+use std::collections::{BTreeMap, HashMap};
 use std::ffi::CStr;
 use std::os::raw::{c_char, c_void};
 use std::sync::mpsc;
@@ -138,6 +145,8 @@ fn share_some_mdatas_with_valid_metadata() {
         app: app_id.clone(),
         app_container: false,
         containers: Default::default(),
+        manifest: None,
+        resp_key: None,
     };
 
     let app_auth = unwrap!(test_utils::register_app(&authenticator, &auth_req));
@@ -226,6 +235,7 @@ fn share_some_mdatas_with_valid_metadata() {
             &authenticator,
             &req_c,
             req_id,
+            IPC_ENCODING_BASE32,
             true,
             sender_as_user_data::<Result<(), (i32, String)>>(&tx, &mut ud),
             encode_share_mdata_cb,
@@ -316,6 +326,7 @@ fn share_some_mdatas_with_ownership_error() {
             &authenticator,
             &req_c,
             req_id,
+            IPC_ENCODING_BASE32,
             false,
             sender_as_user_data::<Result<(), (i32, String)>>(&tx, &mut ud),
             encode_share_mdata_cb,
@@ -420,6 +431,8 @@ fn auth_apps_accessing_mdatas() {
             app: app_id.clone(),
             app_container: false,
             containers: Default::default(),
+            manifest: None,
+            resp_key: None,
         };
 
         let app_auth = unwrap!(test_utils::register_app(&authenticator, &auth_req));
@@ -467,6 +480,7 @@ fn auth_apps_accessing_mdatas() {
                 &authenticator,
                 &req_c,
                 req_id,
+                IPC_ENCODING_BASE32,
                 true,
                 sender_as_user_data::<Result<(), (i32, String)>>(&tx, &mut ud),
                 encode_share_mdata_cb,
@@ -544,3 +558,134 @@ extern "C" fn encode_share_mdata_cb(
         send_via_user_data::<Result<(), (i32, String)>>(user_data, ret);
     }
 }
+
+// Test accepting an offer of access to mutable data owned by another user.
+#[test]
+fn accept_share_offer() {
+    let owner = test_utils::create_account_and_login();
+    let recipient = test_utils::create_account_and_login();
+
+    let owner_key = test_utils::run(&owner, move |client| {
+        ok!(unwrap!(client.public_signing_key()))
+    });
+    let (app_id, _) = unwrap!(test_utils::register_rand_app(
+        &recipient,
+        true,
+        HashMap::new()
+    ));
+    let app = test_utils::run(&recipient, move |client| {
+        config::get_app(client, &app_id)
+    });
+
+    let mdata_info = unwrap!(MDataInfo::random_private(15_000));
+    let mdata = unwrap!(MutableData::new(
+        mdata_info.name,
+        mdata_info.type_tag,
+        btree_map![],
+        btree_map![],
+        btree_set![owner_key],
+    ));
+    test_utils::run(&owner, move |client| {
+        client.put_mdata(mdata).map_err(AuthError::CoreError)
+    });
+
+    // Keys of the app making the offer.
+    let (sign_pk, sign_sk) = sign::gen_keypair();
+    let (reply_pk, reply_sk) = box_::gen_keypair();
+    let perms = PermissionSet::new().allow(Action::Insert);
+
+    let offer = ShareOffer {
+        mdata: mdata_info.clone(),
+        perms,
+        sign_pk,
+        reply_key: reply_pk,
+    };
+    let sealed = unwrap!(ipc::seal_share_offer(&offer, &sign_sk, &app.keys.enc_pk));
+
+    // Offers from unknown apps are rejected.
+    let req = ShareOfferReq {
+        app: test_utils::rand_app(),
+        offer: sealed.clone(),
+    };
+    let msg = IpcMsg::Req {
+        req_id: ipc::gen_req_id(),
+        req: IpcReq::ShareOffer(req),
+    };
+    let encoded_msg = unwrap!(ipc::encode_msg(&msg));
+    match test_utils::auth_decode_ipc_msg_helper(&recipient, &encoded_msg) {
+        Err((ERR_UNKNOWN_APP, _)) => (),
+        x => panic!("Unexpected {:?}", x),
+    };
+
+    // Offers which can't be opened by the recipient app are rejected.
+    let (other_key, _) = box_::gen_keypair();
+    let req = ShareOfferReq {
+        app: app.info.clone(),
+        offer: unwrap!(ipc::seal_share_offer(&offer, &sign_sk, &other_key)),
+    };
+    let msg = IpcMsg::Req {
+        req_id: ipc::gen_req_id(),
+        req: IpcReq::ShareOffer(req),
+    };
+    let encoded_msg = unwrap!(ipc::encode_msg(&msg));
+    match test_utils::auth_decode_ipc_msg_helper(&recipient, &encoded_msg) {
+        Err((ERR_INVALID_SHARE_OFFER, _)) => (),
+        x => panic!("Unexpected {:?}", x),
+    };
+
+    let req = ShareOfferReq {
+        app: app.info.clone(),
+        offer: sealed,
+    };
+    let msg = IpcMsg::Req {
+        req_id: ipc::gen_req_id(),
+        req: IpcReq::ShareOffer(req.clone()),
+    };
+    let encoded_msg = unwrap!(ipc::encode_msg(&msg));
+
+    // Front-ends decoding messages with `auth_decode_ipc_msg_v3` can't report offers.
+    match test_utils::auth_decode_ipc_msg_helper_with_version(&recipient, &encoded_msg, 3) {
+        Err((ERR_UPGRADE_REQUIRED, Some(IpcMsg::Err(IpcError::UpgradeRequired(4))))) => (),
+        x => panic!("Unexpected {:?}", x),
+    };
+
+    match unwrap!(test_utils::auth_decode_ipc_msg_helper(
+        &recipient,
+        &encoded_msg
+    )) {
+        (
+            IpcMsg::Req {
+                req: IpcReq::ShareOffer(..),
+                ..
+            },
+            Some(Payload::ShareOfferTerms {
+                name,
+                type_tag,
+                perms: offered_perms,
+                owner,
+            }),
+        ) => {
+            assert_eq!(name, mdata_info.name);
+            assert_eq!(type_tag, mdata_info.type_tag);
+            assert_eq!(offered_perms, perms);
+            assert_eq!(owner, owner_key);
+        }
+        x => panic!("Unexpected {:?}", x),
+    };
+
+    let granted = test_utils::run(&recipient, move |client| {
+        grant_share_offer(client, req)
+    });
+    assert_eq!(granted.mdata, mdata_info);
+    assert_eq!(granted.perms, perms);
+
+    let acceptance = unwrap!(ipc::open_share_acceptance(
+        &granted.acceptance,
+        &sign_pk,
+        &reply_pk,
+        &reply_sk
+    ));
+    assert_eq!(acceptance.name, mdata_info.name);
+    assert_eq!(acceptance.perms, perms);
+    assert_eq!(acceptance.keys, vec![app.keys.sign_pk]);
+}
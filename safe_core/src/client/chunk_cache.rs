@@ -0,0 +1,367 @@
+// Copyright 2018 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+//! Persistent cache of `ImmutableData` chunks.
+//!
+//! Every chunk is stored encrypted in its own file, with a key derived from the symmetric
+//! key of the client. Files are named after the hash of the chunk name and the cache key,
+//! so neither the names nor the contents of the chunks can be learned from the cache
+//! without the key. When the total size of the files goes over the limit, the least
+//! recently used ones are removed.
+//!
+//! The order in which the chunks were used is saved, encrypted as well, in an index file
+//! when the cache is dropped, so it survives a restart. Chunks missing from the index, as
+//! after a crash, are taken to be used after the ones in it, by the time they were stored.
+
+use crypto::shared_secretbox;
+use data_encoding::HEXLOWER;
+use errors::CoreError;
+use lru_cache::LruCache;
+use maidsafe_utilities::serialisation::{deserialise, serialise};
+use routing::{ImmutableData, XorName};
+use rust_sodium::crypto::secretbox;
+use std::fs::{self, File};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use tiny_keccak::sha3_256;
+use utils::{symmetric_decrypt, symmetric_encrypt};
+
+const CHUNK_CACHE_KEY_SALT: &[u8] = b"chunk_cache";
+// Name of the file holding the names of the cached files, from the least recently used.
+const INDEX_FILE_NAME: &str = "index";
+
+/// Encrypted on-disk cache of `ImmutableData`, with LRU eviction.
+pub struct ChunkCache {
+    dir: PathBuf,
+    key: shared_secretbox::Key,
+    max_size: u64,
+    size: u64,
+    // Sizes of the cached files, by their names.
+    files: LruCache<XorName, u64>,
+}
+
+impl ChunkCache {
+    /// Opens the cache in the given directory, creating it if needed, with the contents
+    /// encrypted with a key derived from `client_key`. Files which are already there are
+    /// kept, in the order they were used in before.
+    pub fn open<P: AsRef<Path>>(
+        dir: P,
+        client_key: &shared_secretbox::Key,
+        max_size: u64,
+    ) -> Result<Self, CoreError> {
+        let dir = dir.as_ref().to_path_buf();
+        let key = derive_key(client_key);
+        fs::create_dir_all(&dir)?;
+
+        let index = match read_index(&dir, &key) {
+            Ok(index) => index,
+            Err(error) => {
+                debug!("Failed to read the chunk cache index: {:?}", error);
+                Vec::new()
+            }
+        };
+
+        let mut existing = Vec::new();
+        for entry in fs::read_dir(&dir)? {
+            let entry = entry?;
+            let metadata = entry.metadata()?;
+            if !metadata.is_file() {
+                continue;
+            }
+            if let Some(file_name) = entry.file_name().to_str().and_then(decode_file_name) {
+                let position = index
+                    .iter()
+                    .position(|name| *name == file_name)
+                    .unwrap_or_else(|| index.len());
+                existing.push((position, metadata.modified()?, file_name, metadata.len()));
+            }
+        }
+        existing.sort();
+
+        let mut cache = ChunkCache {
+            dir,
+            key,
+            max_size,
+            size: 0,
+            files: LruCache::new(usize::max_value()),
+        };
+        for (_, _, file_name, len) in existing {
+            cache.size += len;
+            let _ = cache.files.insert(file_name, len);
+        }
+        cache.evict();
+
+        Ok(cache)
+    }
+
+    /// Returns the chunk with the given name, if it's cached. Files which fail to
+    /// decrypt or don't hash to the chunk name are removed.
+    pub fn get(&mut self, name: &XorName) -> Option<ImmutableData> {
+        let file_name = self.file_name(name);
+        if self.files.get_mut(&file_name).is_none() {
+            return None;
+        }
+
+        match self.read(&file_name) {
+            Ok(ref data) if data.name() == name => Some(data.clone()),
+            res => {
+                debug!("Discarding cached chunk {:?}: {:?}", name, res.err());
+                self.remove(&file_name);
+                None
+            }
+        }
+    }
+
+    /// Stores the chunk, evicting the least recently used ones if the cache is full.
+    pub fn insert(&mut self, data: &ImmutableData) -> Result<(), CoreError> {
+        let file_name = self.file_name(data.name());
+        if self.files.get_mut(&file_name).is_some() {
+            return Ok(());
+        }
+
+        let cipher_text = symmetric_encrypt(data.value(), &self.key, None)?;
+        let len = cipher_text.len() as u64;
+        if len > self.max_size {
+            return Ok(());
+        }
+
+        let mut file = File::create(self.path(&file_name))?;
+        file.write_all(&cipher_text)?;
+
+        self.size += len;
+        let _ = self.files.insert(file_name, len);
+        self.evict();
+
+        Ok(())
+    }
+
+    /// Total size of the cached files, in bytes.
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    // Saves the order the cached files were used in.
+    fn write_index(&self) -> Result<(), CoreError> {
+        let index: Vec<_> = self.files.iter().map(|(file_name, _)| *file_name).collect();
+        let cipher_text = symmetric_encrypt(&serialise(&index)?, &self.key, None)?;
+
+        // Write to a temporary file first, so a crash doesn't leave a partial index behind.
+        let path = self.dir.join(INDEX_FILE_NAME);
+        let temp_path = path.with_extension("tmp");
+        File::create(&temp_path)?.write_all(&cipher_text)?;
+        fs::rename(temp_path, path)?;
+
+        Ok(())
+    }
+
+    fn read(&self, file_name: &XorName) -> Result<ImmutableData, CoreError> {
+        let mut cipher_text = Vec::new();
+        let _ = File::open(self.path(file_name))?.read_to_end(&mut cipher_text)?;
+        let value = symmetric_decrypt(&cipher_text, &self.key)?;
+        Ok(ImmutableData::new(value))
+    }
+
+    fn remove(&mut self, file_name: &XorName) {
+        if let Some(len) = self.files.remove(file_name) {
+            self.size -= len;
+        }
+        if let Err(error) = fs::remove_file(self.path(file_name)) {
+            debug!("Failed to remove cached chunk: {:?}", error);
+        }
+    }
+
+    fn evict(&mut self) {
+        while self.size > self.max_size {
+            let file_name = match self.files.remove_lru() {
+                Some((file_name, len)) => {
+                    self.size -= len;
+                    file_name
+                }
+                None => break,
+            };
+            if let Err(error) = fs::remove_file(self.path(&file_name)) {
+                debug!("Failed to remove cached chunk: {:?}", error);
+            }
+        }
+    }
+
+    fn file_name(&self, name: &XorName) -> XorName {
+        let key: &secretbox::Key = &self.key;
+        let mut input = Vec::with_capacity(key.0.len() + name.0.len());
+        input.extend_from_slice(&key.0);
+        input.extend_from_slice(&name.0);
+        XorName(sha3_256(&input))
+    }
+
+    fn path(&self, file_name: &XorName) -> PathBuf {
+        self.dir.join(HEXLOWER.encode(&file_name.0))
+    }
+}
+
+impl Drop for ChunkCache {
+    fn drop(&mut self) {
+        if let Err(error) = self.write_index() {
+            debug!("Failed to write the chunk cache index: {:?}", error);
+        }
+    }
+}
+
+fn read_index(dir: &Path, key: &shared_secretbox::Key) -> Result<Vec<XorName>, CoreError> {
+    let path = dir.join(INDEX_FILE_NAME);
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+
+    let mut cipher_text = Vec::new();
+    let _ = File::open(path)?.read_to_end(&mut cipher_text)?;
+    Ok(deserialise(&symmetric_decrypt(&cipher_text, key)?)?)
+}
+
+// Keeps the cache key distinct from the key it's derived from, which is used for
+// data on the network.
+fn derive_key(client_key: &shared_secretbox::Key) -> shared_secretbox::Key {
+    let client_key: &secretbox::Key = client_key;
+    let mut input = CHUNK_CACHE_KEY_SALT.to_vec();
+    input.extend_from_slice(&client_key.0);
+    shared_secretbox::Key::from_raw(&sha3_256(&input))
+}
+
+fn decode_file_name(file_name: &str) -> Option<XorName> {
+    let bytes = HEXLOWER.decode(file_name.as_bytes()).ok()?;
+    if bytes.len() != 32 {
+        return None;
+    }
+    let mut name = [0; 32];
+    name.copy_from_slice(&bytes);
+    Some(XorName(name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use client::Client;
+    use futures::Future;
+    use std::env;
+    use utils;
+    use utils::test_utils::random_client;
+
+    fn temp_dir() -> PathBuf {
+        env::temp_dir().join(unwrap!(utils::generate_readable_string(10)))
+    }
+
+    // Test that chunks survive reopening the cache, but only with the same key, and
+    // that the least recently used ones are evicted when it's full.
+    #[test]
+    fn persist_and_evict() {
+        let dir = temp_dir();
+        let key = shared_secretbox::gen_key();
+
+        let data0 = ImmutableData::new(vec![0; 1000]);
+        let data1 = ImmutableData::new(vec![1; 1000]);
+        let data2 = ImmutableData::new(vec![2; 1000]);
+
+        {
+            let mut cache = unwrap!(ChunkCache::open(&dir, &key, 2500));
+            unwrap!(cache.insert(&data0));
+            unwrap!(cache.insert(&data1));
+            assert_eq!(cache.get(data0.name()), Some(data0.clone()));
+
+            // `data1` is the least recently used one now.
+            unwrap!(cache.insert(&data2));
+            assert!(cache.get(data1.name()).is_none());
+            assert!(cache.size() <= 2500);
+        }
+
+        {
+            let mut cache = unwrap!(ChunkCache::open(&dir, &key, 2500));
+            assert_eq!(cache.get(data0.name()), Some(data0.clone()));
+            assert_eq!(cache.get(data2.name()), Some(data2.clone()));
+        }
+
+        {
+            let mut cache = unwrap!(ChunkCache::open(&dir, &shared_secretbox::gen_key(), 2500));
+            assert!(cache.get(data0.name()).is_none());
+        }
+
+        unwrap!(fs::remove_dir_all(&dir));
+    }
+
+    // Test that the order the chunks were used in survives reopening the cache.
+    #[test]
+    fn persist_lru_order() {
+        let dir = temp_dir();
+        let key = shared_secretbox::gen_key();
+
+        let data0 = ImmutableData::new(vec![0; 1000]);
+        let data1 = ImmutableData::new(vec![1; 1000]);
+        let data2 = ImmutableData::new(vec![2; 1000]);
+
+        {
+            let mut cache = unwrap!(ChunkCache::open(&dir, &key, 2500));
+            unwrap!(cache.insert(&data0));
+            unwrap!(cache.insert(&data1));
+
+            // `data0` is stored first, but used last.
+            assert_eq!(cache.get(data0.name()), Some(data0.clone()));
+        }
+
+        {
+            let mut cache = unwrap!(ChunkCache::open(&dir, &key, 2500));
+            unwrap!(cache.insert(&data2));
+            assert!(cache.get(data1.name()).is_none());
+            assert_eq!(cache.get(data0.name()), Some(data0.clone()));
+            assert_eq!(cache.get(data2.name()), Some(data2.clone()));
+        }
+
+        unwrap!(fs::remove_dir_all(&dir));
+    }
+
+    // Test that the client reads chunks from the chunk cache, and puts the chunks it
+    // stores to it.
+    #[test]
+    fn client_uses_chunk_cache() {
+        let dir = temp_dir();
+        let dir2 = dir.clone();
+        let dir3 = dir.clone();
+
+        // Never put to the network, so it can only be read from the chunk cache.
+        let cached = ImmutableData::new(vec![0; 1000]);
+        let stored = ImmutableData::new(vec![1; 1000]);
+
+        random_client(move |client| {
+            let key = unwrap!(client.secret_symmetric_key());
+            {
+                let mut cache = unwrap!(ChunkCache::open(&dir, &key, 1_000_000));
+                unwrap!(cache.insert(&cached));
+            }
+            unwrap!(client.enable_chunk_cache(&dir, 1_000_000));
+
+            let client2 = client.clone();
+            let cached_name = *cached.name();
+
+            client
+                .get_idata(cached_name)
+                .then(move |res| {
+                    assert_eq!(unwrap!(res), cached);
+                    client2
+                        .put_idata(stored.clone())
+                        .map(move |()| (client2, stored))
+                }).then(move |res| {
+                    let (client, stored) = unwrap!(res);
+
+                    // Reopen the cache after the client is done with it.
+                    client.inner().borrow_mut().chunk_cache = None;
+                    let mut cache = unwrap!(ChunkCache::open(&dir2, &key, 1_000_000));
+                    assert_eq!(cache.get(stored.name()), Some(stored.clone()));
+                    Ok::<_, CoreError>(())
+                })
+        });
+
+        unwrap!(fs::remove_dir_all(&dir3));
+    }
+}
@@ -0,0 +1,231 @@
+// Copyright 2018 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+//! Coalescing of identical reads.
+//!
+//! A read made while an identical one is in flight isn't sent to the network. It waits for the
+//! response to the one in flight instead, under a message id of its own, so it still times out
+//! and gets cancelled on its own.
+
+use super::{Client, ClientInner};
+use errors::CoreError;
+use event::CoreEvent;
+use routing::{MessageId, XorName};
+use self_encryption::SelfEncryptionError;
+use self_encryption_storage::SelfEncryptionStorageError;
+use std::io;
+
+/// Read which identical reads can share the response of.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub enum ReadKey {
+    /// `ImmutableData` with the name.
+    IData(XorName),
+    /// Value of the entry with the key in `MutableData` with the name and type tag.
+    MDataValue(XorName, u64, Vec<u8>),
+}
+
+/// Requests waiting for the response to a read in flight.
+pub struct CoalescedRead {
+    key: ReadKey,
+    // Message ids of the hooks of the requests, starting with the one of the read itself.
+    waiters: Vec<MessageId>,
+}
+
+/// Register the read sent with the given message id, so identical reads can wait for it.
+pub fn start<C: Client, T>(inner: &mut ClientInner<C, T>, key: ReadKey, msg_id: MessageId) {
+    let _ = inner.in_flight.insert(key.clone(), msg_id);
+    let _ = inner.coalesced.insert(
+        msg_id,
+        CoalescedRead {
+            key,
+            waiters: vec![msg_id],
+        },
+    );
+}
+
+/// Wait for the response to the identical read in flight, if there's one. Returns the message id
+/// to hook the request under.
+pub fn join<C: Client, T>(inner: &mut ClientInner<C, T>, key: &ReadKey) -> Option<MessageId> {
+    let sent = *inner.in_flight.get(key)?;
+    let read = inner.coalesced.get_mut(&sent)?;
+
+    let msg_id = MessageId::new();
+    read.waiters.push(msg_id);
+    Some(msg_id)
+}
+
+/// Stop waiting under the given message id. The read is forgotten once nothing waits for it.
+pub fn leave<C: Client, T>(inner: &mut ClientInner<C, T>, msg_id: &MessageId) {
+    let sent = inner
+        .coalesced
+        .iter()
+        .find(|&(_, read)| read.waiters.contains(msg_id))
+        .map(|(sent, _)| *sent);
+    let sent = match sent {
+        Some(sent) => sent,
+        None => return,
+    };
+
+    let (key, empty) = match inner.coalesced.get_mut(&sent) {
+        Some(read) => {
+            read.waiters.retain(|waiter| waiter != msg_id);
+            (read.key.clone(), read.waiters.is_empty())
+        }
+        None => return,
+    };
+
+    // Once the request which sent the read gives up on it, identical reads are sent again
+    // instead of waiting for it, while the ones waiting already still get its response.
+    if sent == *msg_id || empty {
+        unlink(inner, &key, &sent);
+    }
+    if empty {
+        let _ = inner.coalesced.remove(&sent);
+    }
+}
+
+/// Finish the read sent with the given message id. Returns the message ids of the hooks of all
+/// the requests waiting for its response.
+pub fn finish<C: Client, T>(inner: &mut ClientInner<C, T>, msg_id: &MessageId) -> Vec<MessageId> {
+    match inner.coalesced.remove(msg_id) {
+        Some(read) => {
+            unlink(inner, &read.key, msg_id);
+            read.waiters
+        }
+        None => vec![*msg_id],
+    }
+}
+
+/// Forget all the reads in flight.
+pub fn clear<C: Client, T>(inner: &mut ClientInner<C, T>) {
+    inner.in_flight.clear();
+    inner.coalesced.clear();
+}
+
+// Stop identical reads from joining the one sent with the given message id, unless a newer one
+// has replaced it already.
+fn unlink<C: Client, T>(inner: &mut ClientInner<C, T>, key: &ReadKey, sent: &MessageId) {
+    if inner.in_flight.get(key) == Some(sent) {
+        let _ = inner.in_flight.remove(key);
+    }
+}
+
+/// Copy of the response to a read, for another request waiting for it.
+pub fn copy_event(event: &CoreEvent) -> CoreEvent {
+    match *event {
+        CoreEvent::GetAccountInfo(ref res) => CoreEvent::GetAccountInfo(copy_result(res)),
+        CoreEvent::Mutation(ref res) => CoreEvent::Mutation(copy_result(res)),
+        CoreEvent::GetIData(ref res) => CoreEvent::GetIData(copy_result(res)),
+        CoreEvent::GetMDataVersion(ref res) => CoreEvent::GetMDataVersion(copy_result(res)),
+        CoreEvent::ListMDataEntries(ref res) => CoreEvent::ListMDataEntries(copy_result(res)),
+        CoreEvent::ListMDataKeys(ref res) => CoreEvent::ListMDataKeys(copy_result(res)),
+        CoreEvent::ListMDataValues(ref res) => CoreEvent::ListMDataValues(copy_result(res)),
+        CoreEvent::GetMDataValue(ref res) => CoreEvent::GetMDataValue(copy_result(res)),
+        CoreEvent::ListMDataPermissions(ref res) => {
+            CoreEvent::ListMDataPermissions(copy_result(res))
+        }
+        CoreEvent::ListMDataUserPermissions(ref res) => {
+            CoreEvent::ListMDataUserPermissions(copy_result(res))
+        }
+        CoreEvent::ListAuthKeysAndVersion(ref res) => {
+            CoreEvent::ListAuthKeysAndVersion(copy_result(res))
+        }
+        CoreEvent::GetMDataShell(ref res) => CoreEvent::GetMDataShell(copy_result(res)),
+        CoreEvent::GetMData(ref res) => CoreEvent::GetMData(copy_result(res)),
+        CoreEvent::RateLimitExceeded => CoreEvent::RateLimitExceeded,
+    }
+}
+
+fn copy_result<T: Clone>(res: &Result<T, CoreError>) -> Result<T, CoreError> {
+    match *res {
+        Ok(ref value) => Ok(value.clone()),
+        Err(ref error) => Err(copy_error(error)),
+    }
+}
+
+// Copy of the error, of the same variant. Network responses only carry `RoutingClientError`s,
+// and every other error which can be rebuilt is copied too. The few wrapping errors of other
+// crates which can't be copied are only described.
+fn copy_error(error: &CoreError) -> CoreError {
+    match *error {
+        CoreError::AsymmetricDecipherFailure => CoreError::AsymmetricDecipherFailure,
+        CoreError::SymmetricDecipherFailure => CoreError::SymmetricDecipherFailure,
+        CoreError::ReceivedUnexpectedData => CoreError::ReceivedUnexpectedData,
+        CoreError::ReceivedUnexpectedEvent => CoreError::ReceivedUnexpectedEvent,
+        CoreError::VersionCacheMiss => CoreError::VersionCacheMiss,
+        CoreError::RootDirectoryExists => CoreError::RootDirectoryExists,
+        CoreError::RandomDataGenerationFailure => CoreError::RandomDataGenerationFailure,
+        CoreError::OperationForbidden => CoreError::OperationForbidden,
+        CoreError::Unexpected(ref description) => CoreError::Unexpected(description.clone()),
+        CoreError::RoutingClientError(ref error) => CoreError::RoutingClientError(error.clone()),
+        CoreError::UnsupportedSaltSizeForPwHash => CoreError::UnsupportedSaltSizeForPwHash,
+        CoreError::UnsuccessfulPwHash => CoreError::UnsuccessfulPwHash,
+        CoreError::OperationAborted => CoreError::OperationAborted,
+        CoreError::OperationCancelled => CoreError::OperationCancelled,
+        CoreError::RequestTimeout => CoreError::RequestTimeout,
+        CoreError::SelfEncryption(SelfEncryptionError::Storage(ref error)) => {
+            let error = SelfEncryptionStorageError(Box::new(copy_error(&error.0)));
+            CoreError::SelfEncryption(SelfEncryptionError::Storage(error))
+        }
+        CoreError::IoError(ref error) => {
+            CoreError::IoError(io::Error::new(error.kind(), error.to_string()))
+        }
+        CoreError::EncodeDecodeError(_)
+        | CoreError::RoutingError(_)
+        | CoreError::RoutingInterfaceError(_)
+        | CoreError::MpidMessagingError(_)
+        | CoreError::SelfEncryption(_)
+        | CoreError::ConfigError(_) => CoreError::Unexpected(error.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use routing::ClientError;
+
+    // Test that the requests waiting for a read get errors of the same variant as its own.
+    #[test]
+    fn copy_errors() {
+        let event =
+            CoreEvent::GetIData(Err(CoreError::RoutingClientError(ClientError::NoSuchData)));
+        match copy_event(&event) {
+            CoreEvent::GetIData(Err(CoreError::RoutingClientError(ClientError::NoSuchData))) => (),
+            x => panic!("Unexpected {:?}", x),
+        }
+
+        let event = CoreEvent::GetIData(Err(CoreError::RequestTimeout));
+        match copy_event(&event) {
+            CoreEvent::GetIData(Err(CoreError::RequestTimeout)) => (),
+            x => panic!("Unexpected {:?}", x),
+        }
+
+        let error = SelfEncryptionStorageError(Box::new(CoreError::OperationCancelled));
+        let event = CoreEvent::GetIData(Err(CoreError::SelfEncryption(
+            SelfEncryptionError::Storage(error),
+        )));
+        match copy_event(&event) {
+            CoreEvent::GetIData(Err(CoreError::SelfEncryption(SelfEncryptionError::Storage(
+                SelfEncryptionStorageError(error),
+            )))) => match *error {
+                CoreError::OperationCancelled => (),
+                ref x => panic!("Unexpected {:?}", x),
+            },
+            x => panic!("Unexpected {:?}", x),
+        }
+
+        let error = io::Error::new(io::ErrorKind::NotFound, "missing");
+        let event = CoreEvent::GetIData(Err(CoreError::IoError(error)));
+        match copy_event(&event) {
+            CoreEvent::GetIData(Err(CoreError::IoError(ref error))) => {
+                assert_eq!(error.kind(), io::ErrorKind::NotFound)
+            }
+            x => panic!("Unexpected {:?}", x),
+        }
+    }
+}
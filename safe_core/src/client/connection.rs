@@ -0,0 +1,278 @@
+// Copyright 2018 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+//! Connection of clients to the network.
+//!
+//! When the connection is lost, the client can try to reconnect with an exponential backoff,
+//! reporting every change of its state as a `NetworkEvent`. Requests made while it's
+//! (re)connecting are held until it's connected again, and fail if it gives up or doesn't try
+//! at all. Once it's connected, the mutations in its offline queue, if any, are replayed.
+
+use super::{
+    coalescing, new_routing, offline_queue, setup_routing, start_routing_thread,
+    wait_for_connection, Client, Routing,
+};
+use errors::CoreError;
+use event::NetworkEvent;
+use futures::sync::oneshot;
+use futures::{future, Future};
+use rand::{self, Rng};
+use routing::Event;
+use std::cmp;
+use std::mem;
+use std::sync::mpsc::Receiver;
+use std::thread;
+use std::time::Duration;
+use tokio_core::reactor::Timeout;
+use utils::FutureExt;
+
+/// State of the connection of a client to the network.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ConnectionState {
+    /// Connecting to the network.
+    Connecting,
+    /// Connected to the network.
+    Connected,
+    /// Lost the connection to the network.
+    Disconnected,
+    /// Trying to restore a lost connection, with the number of the attempt (starting at 1).
+    Reconnecting(u32),
+    /// Gave up connecting to the network.
+    Failed,
+}
+
+impl ConnectionState {
+    fn network_event(self) -> NetworkEvent {
+        match self {
+            ConnectionState::Connecting => NetworkEvent::Connecting,
+            ConnectionState::Connected => NetworkEvent::Connected,
+            ConnectionState::Disconnected => NetworkEvent::Disconnected,
+            ConnectionState::Reconnecting(attempt) => NetworkEvent::Reconnecting(attempt),
+            ConnectionState::Failed => NetworkEvent::Failed,
+        }
+    }
+}
+
+/// Exponential backoff between the attempts to reconnect.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(default)]
+pub struct Backoff {
+    /// Delay before the first attempt.
+    pub initial_delay: Duration,
+    /// Maximum delay. The delay doubles after every failed attempt up to this limit.
+    pub max_delay: Duration,
+    /// Number of attempts before giving up.
+    pub max_attempts: u32,
+}
+
+impl Default for Backoff {
+    fn default() -> Self {
+        Backoff {
+            initial_delay: Duration::from_millis(500),
+            max_delay: Duration::from_secs(30),
+            max_attempts: 10,
+        }
+    }
+}
+
+impl Backoff {
+    /// Delay before the given attempt. It's picked at random between half of the exponential
+    /// delay and the whole of it, so clients which lost their connection at the same time
+    /// don't all try to reconnect at the same time too.
+    pub fn delay(&self, attempt: u32) -> Duration {
+        let exponent = cmp::min(attempt.saturating_sub(1), 31);
+        let millis = as_millis(self.initial_delay).saturating_mul(1 << exponent);
+        let millis = cmp::min(millis, as_millis(self.max_delay));
+
+        Duration::from_millis(rand::thread_rng().gen_range(millis / 2, millis + 1))
+    }
+}
+
+/// Replace the routing of the client with a newly connected one.
+pub fn connect(client: &impl Client) -> Result<(), CoreError> {
+    let (routing, routing_rx) = setup_routing(client.full_id(), client.config())?;
+    replace_routing(client, routing, routing_rx);
+    Ok(())
+}
+
+/// Change the state of the connection and report it. Connecting releases the held requests,
+/// and failing or being disconnected makes them fail.
+pub fn set_state(client: &impl Client, state: ConnectionState) {
+    let (net_tx, held) = {
+        let inner = client.inner();
+        let mut inner = inner.borrow_mut();
+        inner.connection = state;
+
+        let held = match state {
+            ConnectionState::Connected
+            | ConnectionState::Disconnected
+            | ConnectionState::Failed => mem::replace(&mut inner.held_requests, Vec::new()),
+            ConnectionState::Connecting | ConnectionState::Reconnecting(_) => Vec::new(),
+        };
+        (inner.net_tx.clone(), held)
+    };
+
+    if let Err(error) = net_tx.unbounded_send(state.network_event()) {
+        trace!("Couldn't send {:?}: {:?}", state, error);
+    }
+
+    if let ConnectionState::Connected = state {
+        for hook in held {
+            let _ = hook.send(());
+        }
+        offline_queue::replay_in_background(client);
+    }
+}
+
+/// Handle the loss of the connection made by the given generation of routing, by reporting it
+/// and trying to reconnect if that's enabled. The loss of a routing which has been replaced
+/// already is ignored.
+pub fn handle_disconnect(client: &impl Client, generation: u64) {
+    let (current, backoff) = {
+        let inner = client.inner();
+        let inner = inner.borrow();
+        (inner.generation, inner.backoff)
+    };
+
+    if generation != current {
+        return;
+    }
+
+    set_state(client, ConnectionState::Disconnected);
+
+    if let Some(backoff) = backoff {
+        schedule_attempt(client, backoff, 1);
+    }
+}
+
+fn schedule_attempt<C: Client>(client: &C, backoff: Backoff, attempt: u32) {
+    set_state(client, ConnectionState::Reconnecting(attempt));
+
+    let el_handle = client.inner().borrow().el_handle.clone();
+    let timeout = match Timeout::new(backoff.delay(attempt), &el_handle) {
+        Ok(timeout) => timeout,
+        Err(error) => {
+            warn!("Timeout create error: {:?}", error);
+            return set_state(client, ConnectionState::Failed);
+        }
+    };
+
+    let client = client.clone();
+    el_handle.spawn(timeout.then(move |_| reconnect(client, backoff, attempt)));
+}
+
+fn reconnect<C: Client>(
+    client: C,
+    backoff: Backoff,
+    attempt: u32,
+) -> Box<Future<Item = (), Error = ()>> {
+    if !is_reconnecting(&client, attempt) {
+        return future::ok(()).into_box();
+    }
+
+    connect_in_background(&client)
+        .then(move |result| {
+            // The client may have been restarted manually in the meantime, in which case the
+            // new routing is dropped.
+            if !is_reconnecting(&client, attempt) {
+                return Ok(());
+            }
+
+            match result {
+                Ok((routing, routing_rx)) => {
+                    replace_routing(&client, routing, routing_rx);
+                    set_state(&client, ConnectionState::Connected);
+                }
+                Err(error) => {
+                    debug!("Reconnection attempt {} failed: {:?}", attempt, error);
+                    if attempt < backoff.max_attempts {
+                        schedule_attempt(&client, backoff, attempt + 1);
+                    } else {
+                        set_state(&client, ConnectionState::Failed);
+                    }
+                }
+            }
+            Ok(())
+        }).into_box()
+}
+
+fn is_reconnecting(client: &impl Client, attempt: u32) -> bool {
+    client.inner().borrow().connection == ConnectionState::Reconnecting(attempt)
+}
+
+// Create a new routing and wait for it to bootstrap on a separate thread, so the event loop
+// isn't blocked in the meantime.
+fn connect_in_background(
+    client: &impl Client,
+) -> Box<Future<Item = (Routing, Receiver<Event>), Error = CoreError>> {
+    let (routing, routing_rx) = fry!(new_routing(client.full_id(), client.config()));
+    let (tx, rx) = oneshot::channel();
+
+    let spawned = thread::Builder::new()
+        .name("Reconnect".to_string())
+        .spawn(move || {
+            let result = wait_for_connection(&routing_rx);
+            let _ = tx.send((result, routing_rx));
+        });
+    if let Err(error) = spawned {
+        return err!(error);
+    }
+
+    rx.map_err(|_| CoreError::OperationAborted)
+        .and_then(move |(result, routing_rx)| result.map(|()| (routing, routing_rx)))
+        .into_box()
+}
+
+// Replace the routing of the client with the given connected one. The responses to the requests
+// sent through the old one will never come, so they're dropped.
+fn replace_routing(client: &impl Client, routing: Routing, routing_rx: Receiver<Event>) {
+    let inner = client.inner();
+    let mut inner = inner.borrow_mut();
+
+    inner.generation += 1;
+    let joiner = start_routing_thread(routing_rx, inner.core_tx.clone(), inner.generation);
+
+    inner.hooks.clear();
+    coalescing::clear(&mut *inner);
+    inner.routing = routing;
+    inner.joiner = joiner;
+}
+
+fn as_millis(duration: Duration) -> u64 {
+    duration.as_secs() * 1000 + u64::from(duration.subsec_nanos()) / 1_000_000
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Test that the delays grow exponentially up to the limit, with the jitter keeping them
+    // between half and the whole of the exponential delay.
+    #[test]
+    fn backoff_delay() {
+        let backoff = Backoff {
+            initial_delay: Duration::from_millis(100),
+            max_delay: Duration::from_millis(1000),
+            max_attempts: 10,
+        };
+
+        for _ in 0..100 {
+            let delay = as_millis(backoff.delay(1));
+            assert!(delay >= 50 && delay <= 100);
+
+            let delay = as_millis(backoff.delay(3));
+            assert!(delay >= 200 && delay <= 400);
+
+            let delay = as_millis(backoff.delay(10));
+            assert!(delay >= 500 && delay <= 1000);
+
+            let delay = as_millis(backoff.delay(100));
+            assert!(delay >= 500 && delay <= 1000);
+        }
+    }
+}
@@ -16,6 +16,7 @@ use client::{
     setup_routing, spawn_routing_thread, Client, ClientInner, IMMUT_DATA_CACHE_SIZE,
     REQUEST_TIMEOUT_SECS,
 };
+use config_handler::RequestPolicy;
 use crypto::{shared_box, shared_secretbox, shared_sign};
 use errors::CoreError;
 use event::NetworkTx;
@@ -73,6 +74,8 @@ pub struct CoreClient {
     inner: Rc<RefCell<ClientInner<CoreClient, ()>>>,
     cm_addr: Authority<XorName>,
     keys: ClientKeys,
+    // Policy of the requests made through this handle, overriding the ones of the client.
+    request_policy: Option<RequestPolicy>,
 }
 
 impl CoreClient {
@@ -162,21 +165,21 @@ impl CoreClient {
             })?;
 
         // Create the client
-        let joiner = spawn_routing_thread(routing_rx, core_tx.clone(), net_tx.clone());
+        let joiner = spawn_routing_thread(routing_rx, core_tx.clone());
 
         Ok(Self {
-            inner: Rc::new(RefCell::new(ClientInner {
+            inner: Rc::new(RefCell::new(ClientInner::new(
                 el_handle,
                 routing,
-                hooks: HashMap::with_capacity(10),
-                cache: LruCache::new(IMMUT_DATA_CACHE_SIZE),
-                timeout: Duration::from_secs(REQUEST_TIMEOUT_SECS),
+                HashMap::with_capacity(10),
+                LruCache::new(IMMUT_DATA_CACHE_SIZE),
                 joiner,
-                net_tx,
                 core_tx,
-            })),
+                net_tx,
+            ))),
             cm_addr,
             keys: maid_keys,
+            request_policy: None,
         })
     }
 }
@@ -223,6 +226,17 @@ impl Client for CoreClient {
     fn owner_key(&self) -> Option<sign::PublicKey> {
         Some(self.keys.sign_pk)
     }
+
+    fn with_request_policy(&self, policy: RequestPolicy) -> Self {
+        CoreClient {
+            request_policy: Some(policy),
+            ..self.clone()
+        }
+    }
+
+    fn request_policy_override(&self) -> Option<RequestPolicy> {
+        self.request_policy
+    }
 }
 
 impl Clone for CoreClient {
@@ -231,6 +245,7 @@ impl Clone for CoreClient {
             inner: Rc::clone(&self.inner),
             cm_addr: self.cm_addr,
             keys: self.keys.clone(),
+            request_policy: self.request_policy,
         }
     }
 }
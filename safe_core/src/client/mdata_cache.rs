@@ -0,0 +1,244 @@
+// Copyright 2018 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+//! In-memory cache of `MutableData` reads.
+//!
+//! The entries, values and versions read are kept for a short time to live. All that's cached
+//! of a `MutableData` is dropped when its version is seen to change, and when the client mutates
+//! it. As reads in flight while the cache is invalidated may return what's just been mutated,
+//! they aren't cached.
+//!
+//! Writes of entries by other clients don't change the version of the `MutableData`, so they
+//! can't be noticed that way. Cached entries are dropped when an entry read afterwards turns out
+//! to have another entry version, but otherwise they can be stale for as long as the time to
+//! live.
+
+use lru_cache::LruCache;
+use routing::{Value, XorName};
+use std::collections::{BTreeMap, HashMap};
+use std::time::{Duration, Instant};
+
+/// Cache of `MutableData` entries, values and versions, by name and type tag, with LRU eviction.
+pub struct MDataCache {
+    ttl: Duration,
+    records: LruCache<(XorName, u64), Record>,
+    // Number of invalidations so far.
+    epoch: u64,
+}
+
+#[derive(Default)]
+struct Record {
+    version: Option<Cached<u64>>,
+    entries: Option<Cached<BTreeMap<Vec<u8>, Value>>>,
+    values: HashMap<Vec<u8>, Cached<Value>>,
+}
+
+struct Cached<T> {
+    value: T,
+    expiry: Instant,
+}
+
+impl<T: Clone> Cached<T> {
+    fn get(&self) -> Option<T> {
+        if Instant::now() < self.expiry {
+            Some(self.value.clone())
+        } else {
+            None
+        }
+    }
+}
+
+impl MDataCache {
+    /// Creates a cache keeping what's read for `ttl`, of up to `capacity` `MutableData`s.
+    pub fn new(ttl: Duration, capacity: usize) -> Self {
+        MDataCache {
+            ttl,
+            records: LruCache::new(capacity),
+            epoch: 0,
+        }
+    }
+
+    /// Returns the current epoch of the cache, which reads have to pass back to cache what they
+    /// return.
+    pub fn epoch(&self) -> u64 {
+        self.epoch
+    }
+
+    /// Returns the cached version of `MutableData`.
+    pub fn version(&mut self, name: XorName, tag: u64) -> Option<u64> {
+        self.records
+            .get_mut(&(name, tag))?
+            .version
+            .as_ref()
+            .and_then(Cached::get)
+    }
+
+    /// Returns the cached entries of `MutableData`.
+    pub fn entries(&mut self, name: XorName, tag: u64) -> Option<BTreeMap<Vec<u8>, Value>> {
+        self.records
+            .get_mut(&(name, tag))?
+            .entries
+            .as_ref()
+            .and_then(Cached::get)
+    }
+
+    /// Returns the cached value of the entry of `MutableData`, which is also found in its cached
+    /// entries.
+    pub fn value(&mut self, name: XorName, tag: u64, key: &[u8]) -> Option<Value> {
+        let record = self.records.get_mut(&(name, tag))?;
+        if let Some(value) = record.values.get(key).and_then(Cached::get) {
+            return Some(value);
+        }
+
+        let entries = record.entries.as_ref()?;
+        if Instant::now() < entries.expiry {
+            entries.value.get(key).cloned()
+        } else {
+            None
+        }
+    }
+
+    /// Caches the version of `MutableData` read in the given epoch. If it's changed, all that's
+    /// cached of it is dropped.
+    pub fn insert_version(&mut self, name: XorName, tag: u64, version: u64, epoch: u64) {
+        if epoch != self.epoch {
+            return;
+        }
+
+        let expiry = Instant::now() + self.ttl;
+        let record = self.record(name, tag);
+        if record
+            .version
+            .as_ref()
+            .map_or(false, |cached| cached.value != version)
+        {
+            *record = Record::default();
+        }
+        record.version = Some(Cached {
+            value: version,
+            expiry,
+        });
+    }
+
+    /// Caches the entries of `MutableData` read in the given epoch. The cached values of entries
+    /// with other entry versions are dropped.
+    pub fn insert_entries(
+        &mut self,
+        name: XorName,
+        tag: u64,
+        entries: BTreeMap<Vec<u8>, Value>,
+        epoch: u64,
+    ) {
+        if epoch != self.epoch {
+            return;
+        }
+
+        let expiry = Instant::now() + self.ttl;
+        let record = self.record(name, tag);
+        record.values.retain(|key, cached| {
+            entries.get(key).map_or(false, |value| {
+                value.entry_version == cached.value.entry_version
+            })
+        });
+        record.entries = Some(Cached {
+            value: entries,
+            expiry,
+        });
+    }
+
+    /// Caches the value of the entry of `MutableData` read in the given epoch. If the cached
+    /// entries have another version of it, they're dropped.
+    pub fn insert_value(
+        &mut self,
+        name: XorName,
+        tag: u64,
+        key: Vec<u8>,
+        value: Value,
+        epoch: u64,
+    ) {
+        if epoch != self.epoch {
+            return;
+        }
+
+        let expiry = Instant::now() + self.ttl;
+        let record = self.record(name, tag);
+        if record.entries.as_ref().map_or(false, |cached| {
+            cached
+                .value
+                .get(&key)
+                .map_or(true, |entry| entry.entry_version != value.entry_version)
+        }) {
+            record.entries = None;
+        }
+        let _ = record.values.insert(key, Cached { value, expiry });
+    }
+
+    /// Drops all that's cached of `MutableData`, as well as what the reads in flight return.
+    pub fn invalidate(&mut self, name: XorName, tag: u64) {
+        let _ = self.records.remove(&(name, tag));
+        self.epoch += 1;
+    }
+
+    fn record(&mut self, name: XorName, tag: u64) -> &mut Record {
+        if !self.records.contains_key(&(name, tag)) {
+            let _ = self.records.insert((name, tag), Record::default());
+        }
+        unwrap!(self.records.get_mut(&(name, tag)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand;
+    use std::thread;
+
+    // Test that what's cached expires, is dropped when the version changes or the data is
+    // invalidated, and isn't cached by reads from before an invalidation.
+    #[test]
+    fn expiry_and_invalidation() {
+        let mut cache = MDataCache::new(Duration::from_millis(200), 10);
+        let name = rand::random();
+        let value = Value {
+            content: vec![1],
+            entry_version: 0,
+        };
+        let entries = btree_map![vec![0] => value.clone()];
+
+        let epoch = cache.epoch();
+        cache.insert_version(name, 0, 1, epoch);
+        cache.insert_entries(name, 0, entries.clone(), epoch);
+        assert_eq!(cache.version(name, 0), Some(1));
+        assert_eq!(cache.entries(name, 0), Some(entries.clone()));
+        assert_eq!(cache.value(name, 0, &[0]), Some(value.clone()));
+        assert_eq!(cache.value(name, 1, &[0]), None);
+
+        // The same version keeps the entries, a new one drops them.
+        cache.insert_version(name, 0, 1, epoch);
+        assert_eq!(cache.entries(name, 0), Some(entries.clone()));
+        cache.insert_version(name, 0, 2, epoch);
+        assert_eq!(cache.version(name, 0), Some(2));
+        assert_eq!(cache.entries(name, 0), None);
+
+        // Reads from before the invalidation aren't cached.
+        cache.insert_value(name, 0, vec![0], value.clone(), epoch);
+        assert_eq!(cache.value(name, 0, &[0]), Some(value.clone()));
+        cache.invalidate(name, 0);
+        assert_eq!(cache.value(name, 0, &[0]), None);
+        assert_eq!(cache.version(name, 0), None);
+        cache.insert_value(name, 0, vec![0], value.clone(), epoch);
+        assert_eq!(cache.value(name, 0, &[0]), None);
+
+        let epoch = cache.epoch();
+        cache.insert_value(name, 0, vec![0], value.clone(), epoch);
+        assert_eq!(cache.value(name, 0, &[0]), Some(value));
+
+        thread::sleep(Duration::from_millis(300));
+        assert_eq!(cache.value(name, 0, &[0]), None);
+    }
+}
@@ -8,48 +8,67 @@
 
 /// User Account information.
 pub mod account;
+/// Persistent cache of `ImmutableData` chunks.
+pub mod chunk_cache;
 /// Client provided for testing purposes.
 #[cfg(any(test, feature = "testing"))]
 pub mod core_client;
+/// In-memory cache of `MutableData` reads.
+pub mod mdata_cache;
 /// `MDataInfo` utilities.
 pub mod mdata_info;
+/// Queue of mutations made while offline.
+pub mod offline_queue;
 /// Operations with recovery.
 pub mod recovery;
 
+mod coalescing;
+mod connection;
 #[cfg(feature = "use-mock-routing")]
 mod mock;
 mod routing_event_loop;
 
 pub use self::account::ClientKeys;
+pub use self::chunk_cache::ChunkCache;
+pub use self::connection::{Backoff, ConnectionState};
+pub use self::mdata_cache::MDataCache;
 pub use self::mdata_info::MDataInfo;
 #[cfg(feature = "use-mock-routing")]
 pub use self::mock::vault::mock_vault_path;
 #[cfg(feature = "use-mock-routing")]
 pub use self::mock::Routing as MockRouting;
+pub use self::offline_queue::{OfflineQueue, QueuedMutation};
 
+use self::coalescing::{CoalescedRead, ReadKey};
 #[cfg(feature = "use-mock-routing")]
 use self::mock::Routing;
 #[cfg(not(feature = "use-mock-routing"))]
 use routing::Client as Routing;
 
+use config_file_handler;
+use config_handler::{self, RequestKind, RequestPolicies, RequestPolicy};
 use crypto::{shared_box, shared_secretbox, shared_sign};
+use data_encoding::HEXLOWER;
 use errors::CoreError;
-use event::{CoreEvent, NetworkEvent, NetworkTx};
-use event_loop::{CoreFuture, CoreMsgTx};
+use event::{CoreEvent, NetworkTx};
+use event_loop::{Cancellable, CancellationToken, CoreFuture, CoreMsgTx};
 use futures::future::{self, Either, FutureResult, Loop, Then};
+use futures::stream::{self, Stream};
 use futures::sync::oneshot;
 use futures::{Complete, Future};
 use ipc::BootstrapConfig;
 use lru_cache::LruCache;
 use maidsafe_utilities::thread::{self, Joiner};
 use routing::{
-    AccountInfo, Authority, EntryAction, Event, FullId, ImmutableData, InterfaceError, MessageId,
-    MutableData, PermissionSet, User, Value, XorName,
+    AccountInfo, Authority, ClientError, EntryAction, Event, FullId, ImmutableData, InterfaceError,
+    MessageId, MutableData, PermissionSet, User, Value, XorName,
 };
 use rust_sodium::crypto::{box_, sign};
 use std::cell::RefCell;
+use std::cmp;
 use std::collections::{BTreeMap, BTreeSet, HashMap};
 use std::io;
+use std::path::{Path, PathBuf};
 use std::rc::Rc;
 use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
 use std::time::Duration;
@@ -60,6 +79,8 @@ use utils::FutureExt;
 pub const IMMUT_DATA_CACHE_SIZE: usize = 300;
 /// Request timeout in seconds.
 pub const REQUEST_TIMEOUT_SECS: u64 = 180;
+/// Maximum number of the reads of a batch in flight at the same time.
+pub const MAX_CONCURRENT_READS: usize = 8;
 
 const CONNECTION_TIMEOUT_SECS: u64 = 40;
 const RETRY_DELAY_MS: u64 = 800;
@@ -85,6 +106,35 @@ macro_rules! some_or_err {
     };
 }
 
+/// Enables the persistent chunk cache of the client if it's configured in the `safe_core`
+/// config file. Each client keeps its chunks in a subdirectory named after its public signing
+/// key. Failures are only logged, as the client works without the cache.
+pub fn setup_chunk_cache(client: &impl Client) {
+    let config = match config_handler::get_config().chunk_cache {
+        Some(config) => config,
+        None => return,
+    };
+    let sign_pk = match client.public_signing_key() {
+        Some(sign_pk) => sign_pk,
+        None => return,
+    };
+    let dir = match config.path {
+        Some(path) => PathBuf::from(path),
+        None => match config_file_handler::user_app_dir() {
+            Ok(dir) => dir.join("chunk_cache"),
+            Err(error) => {
+                warn!("Failed to get the chunk cache directory: {:?}", error);
+                return;
+            }
+        },
+    };
+
+    let dir = dir.join(HEXLOWER.encode(&sign_pk.0));
+    if let Err(error) = client.enable_chunk_cache(&dir, config.max_size) {
+        warn!("Failed to enable the chunk cache: {:?}", error);
+    }
+}
+
 /// Return the `crust::Config` associated with the `crust::Service` (if any).
 pub fn bootstrap_config() -> Result<BootstrapConfig, CoreError> {
     Ok(Routing::bootstrap_config()?)
@@ -139,43 +189,143 @@ pub trait Client: Clone + 'static {
     /// Return the owner signing key.
     fn owner_key(&self) -> Option<sign::PublicKey>;
 
-    /// Set request timeout.
+    /// Return a handle to the client whose requests follow the given policy instead of the ones
+    /// of the client. It shares everything else with the client. Its clones follow the policy
+    /// too, so the requests made through them later, e.g. in `and_then`, do as well.
+    fn with_request_policy(&self, policy: RequestPolicy) -> Self;
+
+    /// Return the policy set for the requests of this handle by `with_request_policy`, if any.
+    fn request_policy_override(&self) -> Option<RequestPolicy>;
+
+    /// Set request timeout. It overrides the timeouts of all the request policies.
     fn set_timeout(&self, duration: Duration) {
         let inner = self.inner();
-        inner.borrow_mut().timeout = duration;
+        inner.borrow_mut().timeout = Some(duration);
     }
 
-    /// Restart the routing client and reconnect to the network.
-    fn restart_routing(&self) -> Result<(), CoreError> {
-        let opt_id = self.full_id();
+    /// Set the policies of the requests by their type, replacing the ones from the config file.
+    fn set_request_policies(&self, policies: RequestPolicies) {
         let inner = self.inner();
-        let mut inner = inner.borrow_mut();
+        inner.borrow_mut().policies = policies;
+    }
+
+    /// Return the policy the requests of the given type follow.
+    fn request_policy(&self, kind: RequestKind) -> RequestPolicy {
+        if let Some(policy) = self.request_policy_override() {
+            return policy;
+        }
+
+        let inner = self.inner();
+        let inner = inner.borrow();
+        let mut policy = inner.policies.get(kind);
+        if let Some(timeout) = inner.timeout {
+            policy.timeout = timeout;
+        }
+        policy
+    }
 
-        let (routing, routing_rx) = setup_routing(opt_id, self.config())?;
+    /// Enable the persistent cache of immutable data in the given directory, limited to
+    /// `max_size` bytes. The cached chunks are encrypted with a key derived from the symmetric
+    /// key of the client.
+    fn enable_chunk_cache(&self, dir: &Path, max_size: u64) -> Result<(), CoreError> {
+        let key = self
+            .secret_symmetric_key()
+            .ok_or(CoreError::OperationForbidden)?;
+        let chunk_cache = ChunkCache::open(dir, &key, max_size)?;
 
-        let joiner = spawn_routing_thread(routing_rx, inner.core_tx.clone(), inner.net_tx.clone());
+        let inner = self.inner();
+        inner.borrow_mut().chunk_cache = Some(chunk_cache);
+        Ok(())
+    }
 
-        inner.hooks.clear();
-        inner.routing = routing;
-        inner.joiner = joiner;
+    /// Enable the in-memory cache of `MutableData` reads, keeping what's read for `ttl`, of up
+    /// to `capacity` `MutableData`s.
+    fn enable_mdata_cache(&self, ttl: Duration, capacity: usize) {
+        let inner = self.inner();
+        inner.borrow_mut().mdata_cache = Some(MDataCache::new(ttl, capacity));
+    }
 
-        inner.net_tx.unbounded_send(NetworkEvent::Connected)?;
+    /// Enable the queue of mutations made while offline, persisted to the given directory.
+    /// Mutations queued there before are replayed once the client is connected again. The
+    /// queued mutations which fail then are passed to `on_conflict` along with the error.
+    fn enable_offline_queue<F>(&self, path: &Path, on_conflict: F) -> Result<(), CoreError>
+    where
+        F: FnMut(QueuedMutation, CoreError) + 'static,
+    {
+        let key = self
+            .secret_symmetric_key()
+            .ok_or(CoreError::OperationForbidden)?;
+        let queue = OfflineQueue::open(path, key, Box::new(on_conflict))?;
 
+        let inner = self.inner();
+        inner.borrow_mut().offline_queue = Some(queue);
         Ok(())
     }
 
+    /// Return the mutations waiting in the offline queue, oldest first.
+    fn offline_mutations(&self) -> Result<Vec<QueuedMutation>, CoreError> {
+        self.inner()
+            .borrow()
+            .offline_queue
+            .as_ref()
+            .map_or_else(|| Ok(Vec::new()), OfflineQueue::mutations)
+    }
+
+    /// Replay the mutations waiting in the offline queue, and return how many have been
+    /// removed from it. This is done automatically when the client gets connected.
+    fn replay_offline_queue(&self) -> Box<CoreFuture<usize>> {
+        offline_queue::replay(self)
+    }
+
+    /// Restart the routing client and reconnect to the network.
+    fn restart_routing(&self) -> Result<(), CoreError> {
+        connection::set_state(self, ConnectionState::Connecting);
+
+        match connection::connect(self) {
+            Ok(()) => {
+                connection::set_state(self, ConnectionState::Connected);
+                Ok(())
+            }
+            Err(error) => {
+                connection::set_state(self, ConnectionState::Failed);
+                Err(error)
+            }
+        }
+    }
+
+    /// Return the state of the connection to the network.
+    fn connection_state(&self) -> ConnectionState {
+        self.inner().borrow().connection
+    }
+
+    /// Set the backoff between the attempts to reconnect after the connection has been lost,
+    /// or disable reconnecting automatically with `None`.
+    fn set_reconnect_backoff(&self, backoff: Option<Backoff>) {
+        let inner = self.inner();
+        inner.borrow_mut().backoff = backoff;
+    }
+
     #[doc(hidden)]
     fn fire_hook(&self, id: &MessageId, event: CoreEvent) {
-        // Using in `if` keeps borrow alive. Do not try to combine the 2 lines into one.
         let inner = self.inner();
-        let opt = inner.borrow_mut().hooks.remove(id);
-        if let Some(hook) = opt {
+        let mut hooks: Vec<_> = {
+            let mut inner = inner.borrow_mut();
+            let ids = coalescing::finish(&mut *inner, id);
+            ids.iter().filter_map(|id| inner.hooks.remove(id)).collect()
+        };
+
+        // Identical reads share the response.
+        if let Some(hook) = hooks.pop() {
+            for other in hooks {
+                let _ = other.send(coalescing::copy_event(&event));
+            }
             let _ = hook.send(event);
         }
     }
 
-    /// Get immutable data from the network. If the data exists locally in the cache then it will be
-    /// immediately returned without making an actual network request.
+    /// Get immutable data from the network. If the data exists locally in the cache, or in the
+    /// chunk cache if it's enabled, then it will be immediately returned without making an
+    /// actual network request.
     fn get_idata(&self, name: XorName) -> Box<CoreFuture<ImmutableData>> {
         trace!("GetIData for {:?}", name);
 
@@ -185,14 +335,33 @@ pub trait Client: Clone + 'static {
             return future::ok(data.clone()).into_box();
         }
 
+        let cached = inner
+            .borrow_mut()
+            .chunk_cache
+            .as_mut()
+            .and_then(|chunk_cache| chunk_cache.get(&name));
+        if let Some(data) = cached {
+            trace!("ImmutableData found in chunk cache.");
+            let _ = inner.borrow_mut().cache.insert(name, data.clone());
+            return future::ok(data).into_box();
+        }
+
         let inner = Rc::downgrade(&self.inner());
-        send(self, move |routing, msg_id| {
+        let key = ReadKey::IData(name);
+        send_read(self, RequestKind::GetIData, key, move |routing, msg_id| {
             routing.get_idata(Authority::NaeManager(name), name, msg_id)
         }).and_then(|event| match_event!(event, CoreEvent::GetIData))
         .map(move |data| {
             if let Some(inner) = inner.upgrade() {
+                let mut inner = inner.borrow_mut();
+
                 // Put to cache
-                let _ = inner.borrow_mut().cache.insert(*data.name(), data.clone());
+                let _ = inner.cache.insert(*data.name(), data.clone());
+                if let Some(ref mut chunk_cache) = inner.chunk_cache {
+                    if let Err(error) = chunk_cache.insert(&data) {
+                        debug!("Failed to put ImmutableData to chunk cache: {:?}", error);
+                    }
+                }
             }
             data
         }).into_box()
@@ -201,26 +370,22 @@ pub trait Client: Clone + 'static {
     // TODO All these return the same future from all branches. So convert to impl
     // Trait when it arrives in stable. Change from `Box<CoreFuture>` -> `impl
     // CoreFuture`.
-    /// Put immutable data onto the network.
+    /// Put immutable data onto the network. Once it's stored, it's also put to the chunk cache
+    /// if that's enabled. Goes through the offline queue if that's enabled.
     fn put_idata(&self, data: ImmutableData) -> Box<CoreFuture<()>> {
         trace!("PutIData for {:?}", data);
 
-        send_mutation(self, move |routing, dst, msg_id| {
-            routing.put_idata(dst, data.clone(), msg_id)
-        })
+        offline_queue::submit(self, QueuedMutation::PutIData(data))
     }
 
-    /// Put `MutableData` onto the network.
+    /// Put `MutableData` onto the network. Goes through the offline queue if that's enabled.
     fn put_mdata(&self, data: MutableData) -> Box<CoreFuture<()>> {
         trace!("PutMData for {:?}", data);
 
-        let requester = some_or_err!(self.public_signing_key());
-        send_mutation(self, move |routing, dst, msg_id| {
-            routing.put_mdata(dst, data.clone(), msg_id, requester)
-        })
+        offline_queue::submit(self, QueuedMutation::PutMData(data))
     }
 
-    /// Mutates `MutableData` entries in bulk.
+    /// Mutates `MutableData` entries in bulk. Goes through the offline queue if that's enabled.
     fn mutate_mdata_entries(
         &self,
         name: XorName,
@@ -229,43 +394,80 @@ pub trait Client: Clone + 'static {
     ) -> Box<CoreFuture<()>> {
         trace!("PutMData for {:?}", name);
 
-        let requester = some_or_err!(self.public_signing_key());
-        send_mutation(self, move |routing, dst, msg_id| {
-            routing.mutate_mdata_entries(dst, name, tag, actions.clone(), msg_id, requester)
-        })
+        offline_queue::submit(
+            self,
+            QueuedMutation::MutateMDataEntries { name, tag, actions },
+        )
     }
 
     /// Get entire `MutableData` from the network.
     fn get_mdata(&self, name: XorName, tag: u64) -> Box<CoreFuture<MutableData>> {
         trace!("GetMData for {:?}", name);
 
-        send(self, move |routing, msg_id| {
+        let inner = self.inner();
+        let epoch = with_mdata_cache(&inner, |cache| cache.epoch());
+        let inner = Rc::downgrade(&inner);
+
+        send(self, RequestKind::GetMData, move |routing, msg_id| {
             routing.get_mdata(Authority::NaeManager(name), name, tag, msg_id)
         }).and_then(|event| match_event!(event, CoreEvent::GetMData))
-        .into_box()
+        .map(move |data| {
+            if let (Some(inner), Some(epoch)) = (inner.upgrade(), epoch) {
+                let _ = with_mdata_cache(&inner, |cache| {
+                    cache.insert_version(name, tag, data.version(), epoch);
+                    cache.insert_entries(name, tag, data.entries().clone(), epoch);
+                });
+            }
+            data
+        }).into_box()
     }
 
     /// Get a shell (bare bones) version of `MutableData` from the network.
     fn get_mdata_shell(&self, name: XorName, tag: u64) -> Box<CoreFuture<MutableData>> {
         trace!("GetMDataShell for {:?}", name);
 
-        send(self, move |routing, msg_id| {
+        let inner = self.inner();
+        let epoch = with_mdata_cache(&inner, |cache| cache.epoch());
+        let inner = Rc::downgrade(&inner);
+
+        send(self, RequestKind::GetMData, move |routing, msg_id| {
             routing.get_mdata_shell(Authority::NaeManager(name), name, tag, msg_id)
         }).and_then(|event| match_event!(event, CoreEvent::GetMDataShell))
-        .into_box()
+        .map(move |data| {
+            if let (Some(inner), Some(epoch)) = (inner.upgrade(), epoch) {
+                let _ = with_mdata_cache(&inner, |cache| {
+                    cache.insert_version(name, tag, data.version(), epoch)
+                });
+            }
+            data
+        }).into_box()
     }
 
-    /// Get a current version of `MutableData` from the network.
+    /// Get a current version of `MutableData` from the network. It's never returned from the
+    /// `MutableData` read cache, but if the cache is enabled, a new version drops all that's
+    /// cached of the `MutableData`.
     fn get_mdata_version(&self, name: XorName, tag: u64) -> Box<CoreFuture<u64>> {
         trace!("GetMDataVersion for {:?}", name);
 
-        send(self, move |routing, msg_id| {
+        let inner = self.inner();
+        let epoch = with_mdata_cache(&inner, |cache| cache.epoch());
+        let inner = Rc::downgrade(&inner);
+
+        send(self, RequestKind::GetMData, move |routing, msg_id| {
             routing.get_mdata_version(Authority::NaeManager(name), name, tag, msg_id)
         }).and_then(|event| match_event!(event, CoreEvent::GetMDataVersion))
-        .into_box()
+        .map(move |version| {
+            if let (Some(inner), Some(epoch)) = (inner.upgrade(), epoch) {
+                let _ = with_mdata_cache(&inner, |cache| {
+                    cache.insert_version(name, tag, version, epoch)
+                });
+            }
+            version
+        }).into_box()
     }
 
-    /// Return a complete list of entries in `MutableData`.
+    /// Return a complete list of entries in `MutableData`. If the `MutableData` read cache is
+    /// enabled, entries listed recently are returned from it.
     fn list_mdata_entries(
         &self,
         name: XorName,
@@ -273,17 +475,32 @@ pub trait Client: Clone + 'static {
     ) -> Box<CoreFuture<BTreeMap<Vec<u8>, Value>>> {
         trace!("ListMDataEntries for {:?}", name);
 
-        send(self, move |routing, msg_id| {
+        let inner = self.inner();
+        if let Some(Some(entries)) = with_mdata_cache(&inner, |cache| cache.entries(name, tag)) {
+            trace!("MutableData entries found in cache.");
+            return future::ok(entries).into_box();
+        }
+        let epoch = with_mdata_cache(&inner, |cache| cache.epoch());
+        let inner = Rc::downgrade(&inner);
+
+        send(self, RequestKind::GetMData, move |routing, msg_id| {
             routing.list_mdata_entries(Authority::NaeManager(name), name, tag, msg_id)
         }).and_then(|event| match_event!(event, CoreEvent::ListMDataEntries))
-        .into_box()
+        .map(move |entries| {
+            if let (Some(inner), Some(epoch)) = (inner.upgrade(), epoch) {
+                let _ = with_mdata_cache(&inner, |cache| {
+                    cache.insert_entries(name, tag, entries.clone(), epoch)
+                });
+            }
+            entries
+        }).into_box()
     }
 
     /// Return a list of keys in `MutableData` stored on the network.
     fn list_mdata_keys(&self, name: XorName, tag: u64) -> Box<CoreFuture<BTreeSet<Vec<u8>>>> {
         trace!("ListMDataKeys for {:?}", name);
 
-        send(self, move |routing, msg_id| {
+        send(self, RequestKind::GetMData, move |routing, msg_id| {
             routing.list_mdata_keys(Authority::NaeManager(name), name, tag, msg_id)
         }).and_then(|event| match_event!(event, CoreEvent::ListMDataKeys))
         .into_box()
@@ -293,20 +510,80 @@ pub trait Client: Clone + 'static {
     fn list_mdata_values(&self, name: XorName, tag: u64) -> Box<CoreFuture<Vec<Value>>> {
         trace!("ListMDataValues for {:?}", name);
 
-        send(self, move |routing, msg_id| {
+        send(self, RequestKind::GetMData, move |routing, msg_id| {
             routing.list_mdata_values(Authority::NaeManager(name), name, tag, msg_id)
         }).and_then(|event| match_event!(event, CoreEvent::ListMDataValues))
         .into_box()
     }
 
-    /// Get a single entry from `MutableData`.
+    /// Get a single entry from `MutableData`. If the `MutableData` read cache is enabled, an
+    /// entry read or listed recently is returned from it.
     fn get_mdata_value(&self, name: XorName, tag: u64, key: Vec<u8>) -> Box<CoreFuture<Value>> {
         trace!("GetMDataValue for {:?}", name);
 
-        send(self, move |routing, msg_id| {
+        let inner = self.inner();
+        if let Some(Some(value)) = with_mdata_cache(&inner, |cache| cache.value(name, tag, &key)) {
+            trace!("MutableData value found in cache.");
+            return future::ok(value).into_box();
+        }
+        let epoch = with_mdata_cache(&inner, |cache| cache.epoch());
+        let inner = Rc::downgrade(&inner);
+
+        let read_key = ReadKey::MDataValue(name, tag, key.clone());
+        let cache_key = key.clone();
+        send_read(self, RequestKind::GetMDataValue, read_key, move |routing, msg_id| {
             routing.get_mdata_value(Authority::NaeManager(name), name, tag, key.clone(), msg_id)
         }).and_then(|event| match_event!(event, CoreEvent::GetMDataValue))
-        .into_box()
+        .map(move |value| {
+            if let (Some(inner), Some(epoch)) = (inner.upgrade(), epoch) {
+                let _ = with_mdata_cache(&inner, |cache| {
+                    cache.insert_value(name, tag, cache_key, value.clone(), epoch)
+                });
+            }
+            value
+        }).into_box()
+    }
+
+    /// Get the values of the given entries of `MutableData`, in the same order, with `None` for
+    /// the ones which don't exist. See `get_many` for how they're read.
+    fn get_mdata_values(
+        &self,
+        name: XorName,
+        tag: u64,
+        keys: Vec<Vec<u8>>,
+    ) -> Box<CoreFuture<Vec<Option<Value>>>> {
+        trace!("GetMDataValues for {:?}", name);
+
+        self.get_many(keys.into_iter().map(|key| (name, tag, key)).collect())
+    }
+
+    /// Get the values of entries of any `MutableData`s, given by the name and type tag of the
+    /// data and the key of the entry, in the same order. Entries which don't exist, or whose
+    /// data doesn't, are `None`.
+    ///
+    /// As the network can't read many entries in a single request, they're read one by one,
+    /// with up to `MAX_CONCURRENT_READS` reads in flight at the same time. Identical reads are
+    /// only sent once, and the ones found in the `MutableData` read cache aren't sent at all.
+    fn get_many(
+        &self,
+        entries: Vec<(XorName, u64, Vec<u8>)>,
+    ) -> Box<CoreFuture<Vec<Option<Value>>>> {
+        trace!("GetMany for {} entries", entries.len());
+
+        let client = self.clone();
+        stream::iter_ok(entries)
+            .map(move |(name, tag, key)| {
+                client
+                    .get_mdata_value(name, tag, key)
+                    .then(|result| match result {
+                        Ok(value) => Ok(Some(value)),
+                        Err(CoreError::RoutingClientError(ClientError::NoSuchEntry))
+                        | Err(CoreError::RoutingClientError(ClientError::NoSuchData)) => Ok(None),
+                        Err(error) => Err(error),
+                    })
+            }).buffered(MAX_CONCURRENT_READS)
+            .collect()
+            .into_box()
     }
 
     /// Get data from the network.
@@ -314,7 +591,7 @@ pub trait Client: Clone + 'static {
         trace!("Account info GET issued.");
 
         let dst = some_or_err!(self.cm_addr());
-        send(self, move |routing, msg_id| {
+        send(self, RequestKind::Account, move |routing, msg_id| {
             routing.get_account_info(dst, msg_id)
         }).and_then(|event| match_event!(event, CoreEvent::GetAccountInfo))
         .into_box()
@@ -328,7 +605,7 @@ pub trait Client: Clone + 'static {
     ) -> Box<CoreFuture<BTreeMap<User, PermissionSet>>> {
         trace!("ListMDataPermissions for {:?}", name);
 
-        send(self, move |routing, msg_id| {
+        send(self, RequestKind::GetMData, move |routing, msg_id| {
             routing.list_mdata_permissions(Authority::NaeManager(name), name, tag, msg_id)
         }).and_then(|event| match_event!(event, CoreEvent::ListMDataPermissions))
         .into_box()
@@ -343,14 +620,15 @@ pub trait Client: Clone + 'static {
     ) -> Box<CoreFuture<PermissionSet>> {
         trace!("ListMDataUserPermissions for {:?}", name);
 
-        send(self, move |routing, msg_id| {
+        send(self, RequestKind::GetMData, move |routing, msg_id| {
             let dst = Authority::NaeManager(name);
             routing.list_mdata_user_permissions(dst, name, tag, user, msg_id)
         }).and_then(|event| match_event!(event, CoreEvent::ListMDataUserPermissions))
         .into_box()
     }
 
-    /// Updates or inserts a permission set for a given user
+    /// Updates or inserts a permission set for a given user. Goes through the offline queue if
+    /// that's enabled.
     fn set_mdata_user_permissions(
         &self,
         name: XorName,
@@ -361,22 +639,20 @@ pub trait Client: Clone + 'static {
     ) -> Box<CoreFuture<()>> {
         trace!("SetMDataUserPermissions for {:?}", name);
 
-        let requester = some_or_err!(self.public_signing_key());
-        send_mutation(self, move |routing, dst, msg_id| {
-            routing.set_mdata_user_permissions(
-                dst,
+        offline_queue::submit(
+            self,
+            QueuedMutation::SetMDataUserPermissions {
                 name,
                 tag,
                 user,
                 permissions,
                 version,
-                msg_id,
-                requester,
-            )
-        })
+            },
+        )
     }
 
-    /// Deletes a permission set for a given user
+    /// Deletes a permission set for a given user. Goes through the offline queue if that's
+    /// enabled.
     fn del_mdata_user_permissions(
         &self,
         name: XorName,
@@ -386,10 +662,15 @@ pub trait Client: Clone + 'static {
     ) -> Box<CoreFuture<()>> {
         trace!("DelMDataUserPermissions for {:?}", name);
 
-        let requester = some_or_err!(self.public_signing_key());
-        send_mutation(self, move |routing, dst, msg_id| {
-            routing.del_mdata_user_permissions(dst, name, tag, user, version, msg_id, requester)
-        })
+        offline_queue::submit(
+            self,
+            QueuedMutation::DelMDataUserPermissions {
+                name,
+                tag,
+                user,
+                version,
+            },
+        )
     }
 
     /// Sends an ownership transfer request.
@@ -402,9 +683,10 @@ pub trait Client: Clone + 'static {
     ) -> Box<CoreFuture<()>> {
         trace!("ChangeMDataOwner for {:?}", name);
 
-        send_mutation(self, move |routing, dst, msg_id| {
+        let fut = send_mutation(self, RequestKind::Mutation, move |routing, dst, msg_id| {
             routing.change_mdata_owner(dst, name, tag, btree_set![new_owner], version, msg_id)
-        })
+        });
+        invalidate_mdata(self, name, tag, fut)
     }
 
     /// Fetches a list of authorised keys and version in MaidManager.
@@ -412,7 +694,7 @@ pub trait Client: Clone + 'static {
         trace!("ListAuthKeysAndVersion");
 
         let dst = some_or_err!(self.cm_addr());
-        send(self, move |routing, msg_id| {
+        send(self, RequestKind::Account, move |routing, msg_id| {
             routing.list_auth_keys_and_version(dst, msg_id)
         }).and_then(|event| match_event!(event, CoreEvent::ListAuthKeysAndVersion))
         .into_box()
@@ -422,7 +704,7 @@ pub trait Client: Clone + 'static {
     fn ins_auth_key(&self, key: sign::PublicKey, version: u64) -> Box<CoreFuture<()>> {
         trace!("InsAuthKey ({:?})", key);
 
-        send_mutation(self, move |routing, dst, msg_id| {
+        send_mutation(self, RequestKind::Mutation, move |routing, dst, msg_id| {
             routing.ins_auth_key(dst, key, version, msg_id)
         })
     }
@@ -431,7 +713,7 @@ pub trait Client: Clone + 'static {
     fn del_auth_key(&self, key: sign::PublicKey, version: u64) -> Box<CoreFuture<()>> {
         trace!("DelAuthKey ({:?})", key);
 
-        send_mutation(self, move |routing, dst, msg_id| {
+        send_mutation(self, RequestKind::Mutation, move |routing, dst, msg_id| {
             routing.del_auth_key(dst, key, version, msg_id)
         })
     }
@@ -481,11 +763,25 @@ pub struct ClientInner<C: Client, T> {
     el_handle: Handle,
     routing: Routing,
     hooks: HashMap<MessageId, Complete<CoreEvent>>,
+    // Reads in flight which identical reads can wait for, and the requests waiting for them.
+    in_flight: HashMap<ReadKey, MessageId>,
+    coalesced: HashMap<MessageId, CoalescedRead>,
     cache: LruCache<XorName, ImmutableData>,
-    timeout: Duration,
+    chunk_cache: Option<ChunkCache>,
+    mdata_cache: Option<MDataCache>,
+    offline_queue: Option<OfflineQueue>,
+    policies: RequestPolicies,
+    // Timeout set by `Client::set_timeout`, if any.
+    timeout: Option<Duration>,
     joiner: Joiner,
     core_tx: CoreMsgTx<C, T>,
     net_tx: NetworkTx,
+    connection: ConnectionState,
+    // Number of times the routing has been replaced.
+    generation: u64,
+    backoff: Option<Backoff>,
+    // Requests waiting for the client to be connected.
+    held_requests: Vec<Complete<()>>,
 }
 
 impl<C: Client, T> ClientInner<C, T> {
@@ -495,36 +791,59 @@ impl<C: Client, T> ClientInner<C, T> {
         routing: Routing,
         hooks: HashMap<MessageId, Complete<CoreEvent>>,
         cache: LruCache<XorName, ImmutableData>,
-        timeout: Duration,
         joiner: Joiner,
         core_tx: CoreMsgTx<C, T>,
         net_tx: NetworkTx,
     ) -> ClientInner<C, T> {
+        let config = config_handler::get_config();
+
         ClientInner {
             el_handle,
             routing,
             hooks,
+            in_flight: HashMap::new(),
+            coalesced: HashMap::new(),
             cache,
-            timeout,
+            chunk_cache: None,
+            mdata_cache: config
+                .mdata_cache
+                .map(|config| MDataCache::new(config.ttl, config.capacity)),
+            offline_queue: None,
+            policies: config.request_policies.unwrap_or_default(),
+            timeout: None,
             joiner,
             core_tx,
             net_tx,
+            connection: ConnectionState::Connected,
+            generation: 0,
+            backoff: config.reconnect,
+            held_requests: Vec::new(),
         }
     }
 }
 
 /// Spawn a routing thread and run the routing event loop.
-pub fn spawn_routing_thread<C, T>(
+pub fn spawn_routing_thread<C, T>(routing_rx: Receiver<Event>, core_tx: CoreMsgTx<C, T>) -> Joiner
+where
+    C: Client,
+    T: 'static,
+{
+    start_routing_thread(routing_rx, core_tx, 0)
+}
+
+// Spawn the routing thread for the given generation of routing, which is used to tell whether
+// a lost connection is still the current one.
+fn start_routing_thread<C, T>(
     routing_rx: Receiver<Event>,
     core_tx: CoreMsgTx<C, T>,
-    net_tx: NetworkTx,
+    generation: u64,
 ) -> Joiner
 where
     C: Client,
     T: 'static,
 {
     thread::named("Routing Event Loop", move || {
-        routing_event_loop::run(&routing_rx, core_tx, &net_tx)
+        routing_event_loop::run(&routing_rx, core_tx, generation)
     })
 }
 
@@ -532,6 +851,16 @@ where
 pub fn setup_routing(
     full_id: Option<FullId>,
     config: Option<BootstrapConfig>,
+) -> Result<(Routing, Receiver<Event>), CoreError> {
+    let (routing, routing_rx) = new_routing(full_id, config)?;
+    wait_for_connection(&routing_rx)?;
+    Ok((routing, routing_rx))
+}
+
+// Set up routing without waiting for it to connect to the network.
+fn new_routing(
+    full_id: Option<FullId>,
+    config: Option<BootstrapConfig>,
 ) -> Result<(Routing, Receiver<Event>), CoreError> {
     let (routing_tx, routing_rx) = mpsc::channel();
     let routing = Routing::new(
@@ -540,7 +869,11 @@ pub fn setup_routing(
         config,
         Duration::from_secs(REQUEST_TIMEOUT_SECS),
     )?;
+    Ok((routing, routing_rx))
+}
 
+// Wait for routing to connect to the network. This blocks for up to `CONNECTION_TIMEOUT_SECS`.
+fn wait_for_connection(routing_rx: &Receiver<Event>) -> Result<(), CoreError> {
     trace!("Waiting to get connected to the Network...");
     match routing_rx.recv_timeout(Duration::from_secs(CONNECTION_TIMEOUT_SECS)) {
         Ok(Event::Connected) => (),
@@ -561,51 +894,254 @@ pub fn setup_routing(
     }
     trace!("Connected to the Network.");
 
-    Ok((routing, routing_rx))
+    Ok(())
+}
+
+// Run `f` on the `MutableData` read cache, if it's enabled.
+fn with_mdata_cache<C, T, F, R>(inner: &RefCell<ClientInner<C, T>>, f: F) -> Option<R>
+where
+    C: Client,
+    F: FnOnce(&mut MDataCache) -> R,
+{
+    inner.borrow_mut().mdata_cache.as_mut().map(f)
+}
+
+// Drop what's cached of `MutableData` once the mutation of it is done. This happens even if it
+// failed, as a mutation which timed out may have been applied anyway.
+fn invalidate_mdata(
+    client: &impl Client,
+    name: XorName,
+    tag: u64,
+    future: Box<CoreFuture<()>>,
+) -> Box<CoreFuture<()>> {
+    let inner = Rc::downgrade(&client.inner());
+    future
+        .then(move |result| {
+            if let Some(inner) = inner.upgrade() {
+                let _ = with_mdata_cache(&inner, |cache| cache.invalidate(name, tag));
+            }
+            result
+        }).into_box()
+}
+
+// Put immutable data onto the network, bypassing the offline queue.
+fn send_put_idata(client: &impl Client, data: ImmutableData) -> Box<CoreFuture<()>> {
+    let inner = Rc::downgrade(&client.inner());
+    let cached = if client.inner().borrow().chunk_cache.is_some() {
+        Some(data.clone())
+    } else {
+        None
+    };
+
+    send_mutation(client, RequestKind::PutIData, move |routing, dst, msg_id| {
+        routing.put_idata(dst, data.clone(), msg_id)
+    }).map(move |()| {
+        if let (Some(inner), Some(data)) = (inner.upgrade(), cached) {
+            if let Some(ref mut chunk_cache) = inner.borrow_mut().chunk_cache {
+                if let Err(error) = chunk_cache.insert(&data) {
+                    debug!("Failed to put ImmutableData to chunk cache: {:?}", error);
+                }
+            }
+        }
+    }).into_box()
+}
+
+// Put `MutableData` onto the network, bypassing the offline queue.
+fn send_put_mdata(client: &impl Client, data: MutableData) -> Box<CoreFuture<()>> {
+    let requester = some_or_err!(client.public_signing_key());
+    let (name, tag) = (*data.name(), data.tag());
+    let fut = send_mutation(client, RequestKind::Mutation, move |routing, dst, msg_id| {
+        routing.put_mdata(dst, data.clone(), msg_id, requester)
+    });
+    invalidate_mdata(client, name, tag, fut)
+}
+
+// Mutate `MutableData` entries, bypassing the offline queue.
+fn send_mutate_mdata_entries(
+    client: &impl Client,
+    name: XorName,
+    tag: u64,
+    actions: BTreeMap<Vec<u8>, EntryAction>,
+) -> Box<CoreFuture<()>> {
+    let requester = some_or_err!(client.public_signing_key());
+    let fut = send_mutation(client, RequestKind::Mutation, move |routing, dst, msg_id| {
+        routing.mutate_mdata_entries(dst, name, tag, actions.clone(), msg_id, requester)
+    });
+    invalidate_mdata(client, name, tag, fut)
+}
+
+// Set the permissions of a user on `MutableData`, bypassing the offline queue.
+fn send_set_mdata_user_permissions(
+    client: &impl Client,
+    name: XorName,
+    tag: u64,
+    user: User,
+    permissions: PermissionSet,
+    version: u64,
+) -> Box<CoreFuture<()>> {
+    let requester = some_or_err!(client.public_signing_key());
+    let fut = send_mutation(client, RequestKind::Mutation, move |routing, dst, msg_id| {
+        routing.set_mdata_user_permissions(
+            dst,
+            name,
+            tag,
+            user,
+            permissions,
+            version,
+            msg_id,
+            requester,
+        )
+    });
+    invalidate_mdata(client, name, tag, fut)
+}
+
+// Delete the permissions of a user on `MutableData`, bypassing the offline queue.
+fn send_del_mdata_user_permissions(
+    client: &impl Client,
+    name: XorName,
+    tag: u64,
+    user: User,
+    version: u64,
+) -> Box<CoreFuture<()>> {
+    let requester = some_or_err!(client.public_signing_key());
+    let fut = send_mutation(client, RequestKind::Mutation, move |routing, dst, msg_id| {
+        routing.del_mdata_user_permissions(dst, name, tag, user, version, msg_id, requester)
+    });
+    invalidate_mdata(client, name, tag, fut)
+}
+
+/// Send a request and return a future that resolves to the response. The request is sent again
+/// if it times out, as many times as its policy allows.
+fn send<F>(client: &impl Client, kind: RequestKind, req: F) -> Box<CoreFuture<CoreEvent>>
+where
+    F: Fn(&mut Routing, MessageId) -> Result<(), InterfaceError> + 'static,
+{
+    send_request(client, kind, None, req)
+}
+
+/// Send a read request, unless an identical one is in flight already, and return a future that
+/// resolves to the response.
+fn send_read<F>(
+    client: &impl Client,
+    kind: RequestKind,
+    key: ReadKey,
+    req: F,
+) -> Box<CoreFuture<CoreEvent>>
+where
+    F: Fn(&mut Routing, MessageId) -> Result<(), InterfaceError> + 'static,
+{
+    send_request(client, kind, Some(key), req)
 }
 
-/// Send a request and return a future that resolves to the response.
-fn send<F>(client: &impl Client, req: F) -> Box<CoreFuture<CoreEvent>>
+fn send_request<F>(
+    client: &impl Client,
+    kind: RequestKind,
+    key: Option<ReadKey>,
+    req: F,
+) -> Box<CoreFuture<CoreEvent>>
 where
     F: Fn(&mut Routing, MessageId) -> Result<(), InterfaceError> + 'static,
 {
+    let policy = client.request_policy(kind);
     let inner = Rc::downgrade(&client.inner());
-    let func = move |_| {
+    let func = move |retries: u32| {
         if let Some(inner) = inner.upgrade() {
-            let msg_id = MessageId::new();
-            if let Err(error) = req(&mut inner.borrow_mut().routing, msg_id) {
-                return future::err(CoreError::from(error)).into_box();
+            // Requests made by a cancelled operation aren't sent at all.
+            let token = CancellationToken::current();
+            if token.as_ref().map_or(false, CancellationToken::is_cancelled) {
+                return future::err(CoreError::OperationCancelled).into_box();
             }
 
+            // Hold the request until the client is connected.
+            let connection = inner.borrow().connection;
+            match connection {
+                ConnectionState::Connected => (),
+                ConnectionState::Connecting | ConnectionState::Reconnecting(_) => {
+                    let (hook, rx) = oneshot::channel();
+                    inner.borrow_mut().held_requests.push(hook);
+
+                    let rx = rx
+                        .map(move |()| Loop::Continue(retries))
+                        .map_err(|_| CoreError::from(InterfaceError::NotConnected));
+                    return match token {
+                        Some(token) => Cancellable::new(rx, token).into_box(),
+                        None => rx.into_box(),
+                    };
+                }
+                ConnectionState::Disconnected | ConnectionState::Failed => {
+                    return future::err(CoreError::from(InterfaceError::NotConnected)).into_box();
+                }
+            }
+
+            let joined = match key {
+                Some(ref key) => coalescing::join(&mut *inner.borrow_mut(), key),
+                None => None,
+            };
+            let msg_id = match joined {
+                Some(msg_id) => msg_id,
+                None => {
+                    let msg_id = MessageId::new();
+                    if let Err(error) = req(&mut inner.borrow_mut().routing, msg_id) {
+                        return future::err(CoreError::from(error)).into_box();
+                    }
+                    if let Some(ref key) = key {
+                        coalescing::start(&mut *inner.borrow_mut(), key.clone(), msg_id);
+                    }
+                    msg_id
+                }
+            };
+
             let (hook, rx) = oneshot::channel();
             let _ = inner.borrow_mut().hooks.insert(msg_id, hook);
 
             let rx = rx.map_err(|_| CoreError::OperationAborted);
-            let rx = setup_timeout_and_retry_delay(&inner, msg_id, rx);
-            let rx = rx.map(|event| {
+            let rx = setup_timeout_and_retry_delay(&inner, msg_id, policy.timeout, rx);
+            let rx = match token {
+                Some(token) => setup_cancellation(&inner, msg_id, rx, token),
+                None => rx,
+            };
+            let rx = rx.map(move |event| {
                 if let CoreEvent::RateLimitExceeded = event {
-                    Loop::Continue(())
+                    Loop::Continue(retries)
                 } else {
                     Loop::Break(event)
                 }
             });
-            rx.into_box()
+
+            // A mutation which timed out may have been applied anyway, so it's retried only if
+            // it's idempotent or the policy says so.
+            if retries >= policy.max_retries || !(kind.is_idempotent() || policy.retry_mutations) {
+                return rx.into_box();
+            }
+
+            let el_handle = inner.borrow().el_handle.clone();
+            rx.or_else(move |error| {
+                if let CoreError::RequestTimeout = error {
+                    let delay = policy.retry_delay * 2u32.pow(cmp::min(retries, 16));
+                    debug!("{:?} request timed out, retrying in {:?}", kind, delay);
+                    let fut = timeout(delay, &el_handle)
+                        .then(move |_| Ok::<_, CoreError>(Loop::Continue(retries + 1)));
+                    return Either::A(fut);
+                }
+
+                Either::B(future::err(error))
+            }).into_box()
         } else {
             future::err(CoreError::OperationAborted).into_box()
         }
     };
 
-    future::loop_fn((), func).into_box()
+    future::loop_fn(0, func).into_box()
 }
 
 /// Sends a mutation request.
-fn send_mutation<F>(client: &impl Client, req: F) -> Box<CoreFuture<()>>
+fn send_mutation<F>(client: &impl Client, kind: RequestKind, req: F) -> Box<CoreFuture<()>>
 where
     F: Fn(&mut Routing, Authority<XorName>, MessageId) -> Result<(), InterfaceError> + 'static,
 {
     let dst = some_or_err!(client.cm_addr());
 
-    send(client, move |routing, msg_id| req(routing, dst, msg_id))
+    send(client, kind, move |routing, msg_id| req(routing, dst, msg_id))
         .and_then(|event| match_event!(event, CoreEvent::Mutation))
         .into_box()
 }
@@ -613,6 +1149,7 @@ where
 fn setup_timeout_and_retry_delay<C, T, F>(
     inner: &Rc<RefCell<ClientInner<C, T>>>,
     msg_id: MessageId,
+    duration: Duration,
     future: F,
 ) -> Box<CoreFuture<CoreEvent>>
 where
@@ -635,11 +1172,10 @@ where
     });
 
     // Fail if no response received within the timeout.
-    let duration = inner.borrow().timeout;
     let inner_weak = Rc::downgrade(inner);
     let timeout = timeout(duration, &inner.borrow().el_handle).then(move |result| {
         if let Some(inner) = inner_weak.upgrade() {
-            let _ = inner.borrow_mut().hooks.remove(&msg_id);
+            remove_hook(&mut *inner.borrow_mut(), &msg_id);
         }
 
         result
@@ -653,6 +1189,36 @@ where
         }).into_box()
 }
 
+// Fail with `CoreError::OperationCancelled` once the token is cancelled, dropping the hook of
+// the request.
+fn setup_cancellation<C, T>(
+    inner: &Rc<RefCell<ClientInner<C, T>>>,
+    msg_id: MessageId,
+    future: Box<CoreFuture<CoreEvent>>,
+    token: CancellationToken,
+) -> Box<CoreFuture<CoreEvent>>
+where
+    C: Client,
+    T: 'static,
+{
+    let inner_weak = Rc::downgrade(inner);
+    Cancellable::new(future, token)
+        .map_err(move |error| {
+            if let CoreError::OperationCancelled = error {
+                if let Some(inner) = inner_weak.upgrade() {
+                    remove_hook(&mut *inner.borrow_mut(), &msg_id);
+                }
+            }
+            error
+        }).into_box()
+}
+
+// Drop the hook of a request which is no longer waited for.
+fn remove_hook<C: Client, T>(inner: &mut ClientInner<C, T>, msg_id: &MessageId) {
+    let _ = inner.hooks.remove(msg_id);
+    coalescing::leave(inner, msg_id);
+}
+
 // Create a future that resolves into `CoreError::RequestTimeout` after the given time interval.
 fn timeout(duration: Duration, handle: &Handle) -> TimeoutFuture {
     let timeout = match Timeout::new(duration, handle) {
@@ -0,0 +1,539 @@
+// Copyright 2018 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+//! Write-ahead queue of mutations made while disconnected from the network.
+//!
+//! Once the queue is enabled for a client, its mutations which fail because it's disconnected
+//! are persisted to a directory, a file per mutation encrypted with the symmetric key of the
+//! client, and reported as successful. Mutations which time out aren't queued, as they may
+//! have been applied anyway. Once a mutation is queued, all the following ones are queued
+//! behind it, so they are applied in order. When the client gets connected again, the queued
+//! mutations are replayed as they are. Mutations which fail then, e.g. because the data
+//! has been changed in the meantime, are dropped from the queue and passed to the
+//! conflict handler along with the error, instead of being forced through.
+
+use super::{
+    send_del_mdata_user_permissions, send_mutate_mdata_entries, send_put_idata, send_put_mdata,
+    send_set_mdata_user_permissions, Client,
+};
+use crypto::shared_secretbox;
+use errors::CoreError;
+use event_loop::CoreFuture;
+use futures::future::{self, Either, Loop};
+use futures::Future;
+use maidsafe_utilities::serialisation::{deserialise, serialise};
+use routing::{
+    ClientError, EntryAction, ImmutableData, InterfaceError, MutableData, PermissionSet, User,
+    XorName,
+};
+use std::collections::{BTreeMap, VecDeque};
+use std::fs::{self, File};
+use std::io::{Read, Write};
+use std::mem;
+use std::path::{Path, PathBuf};
+use utils::{symmetric_decrypt, symmetric_encrypt, FutureExt};
+
+/// Mutation kept in the queue.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum QueuedMutation {
+    /// Put of `ImmutableData`.
+    PutIData(ImmutableData),
+    /// Put of `MutableData`.
+    PutMData(MutableData),
+    /// Mutation of `MutableData` entries.
+    MutateMDataEntries {
+        /// Name of the data.
+        name: XorName,
+        /// Type tag of the data.
+        tag: u64,
+        /// Actions to apply to the entries.
+        actions: BTreeMap<Vec<u8>, EntryAction>,
+    },
+    /// Setting of the permissions of a user on `MutableData`.
+    SetMDataUserPermissions {
+        /// Name of the data.
+        name: XorName,
+        /// Type tag of the data.
+        tag: u64,
+        /// User to set the permissions of.
+        user: User,
+        /// New permissions.
+        permissions: PermissionSet,
+        /// Version of the permissions of the data after the change.
+        version: u64,
+    },
+    /// Deletion of the permissions of a user on `MutableData`.
+    DelMDataUserPermissions {
+        /// Name of the data.
+        name: XorName,
+        /// Type tag of the data.
+        tag: u64,
+        /// User to delete the permissions of.
+        user: User,
+        /// Version of the permissions of the data after the change.
+        version: u64,
+    },
+}
+
+/// Handler of the queued mutations which fail when they're replayed.
+pub type ConflictHandler = Box<FnMut(QueuedMutation, CoreError)>;
+
+/// Queue of mutations made while offline, persisted to a directory.
+pub struct OfflineQueue {
+    dir: PathBuf,
+    key: shared_secretbox::Key,
+    // Sequence numbers of the queued mutations, oldest first. The mutations themselves are
+    // only kept in their files, named by the sequence numbers, so the queued chunks don't take
+    // up memory.
+    seqs: VecDeque<u64>,
+    on_conflict: Option<ConflictHandler>,
+    replaying: bool,
+}
+
+impl OfflineQueue {
+    /// Opens the queue persisted in the given directory, or an empty one if the directory
+    /// doesn't exist, encrypted with the given key.
+    pub fn open<P: AsRef<Path>>(
+        dir: P,
+        key: shared_secretbox::Key,
+        on_conflict: ConflictHandler,
+    ) -> Result<Self, CoreError> {
+        let dir = dir.as_ref().to_path_buf();
+        fs::create_dir_all(&dir)?;
+
+        // Other files, e.g. the temporary ones of interrupted writes, are ignored.
+        let mut seqs: Vec<u64> = Vec::new();
+        for entry in fs::read_dir(&dir)? {
+            let name = entry?.file_name();
+            if let Some(seq) = name.to_str().and_then(|name| name.parse().ok()) {
+                seqs.push(seq);
+            }
+        }
+        seqs.sort();
+
+        Ok(OfflineQueue {
+            dir,
+            key,
+            seqs: seqs.into_iter().collect(),
+            on_conflict: Some(on_conflict),
+            replaying: false,
+        })
+    }
+
+    /// Returns the mutations waiting to be replayed, oldest first.
+    pub fn mutations(&self) -> Result<Vec<QueuedMutation>, CoreError> {
+        self.seqs.iter().map(|seq| self.read(*seq)).collect()
+    }
+
+    /// Returns the number of mutations waiting to be replayed.
+    pub fn len(&self) -> usize {
+        self.seqs.len()
+    }
+
+    /// Returns `true` if there are no mutations waiting to be replayed.
+    pub fn is_empty(&self) -> bool {
+        self.seqs.is_empty()
+    }
+
+    fn front(&self) -> Result<Option<QueuedMutation>, CoreError> {
+        match self.seqs.front() {
+            Some(seq) => Ok(Some(self.read(*seq)?)),
+            None => Ok(None),
+        }
+    }
+
+    // Writes the mutation to a temporary file first, so a crash can't leave it corrupted.
+    fn push_back(&mut self, mutation: &QueuedMutation) -> Result<(), CoreError> {
+        let seq = self.seqs.back().map_or(0, |seq| seq + 1);
+        let plain_text = serialise(mutation)?;
+        let cipher_text = symmetric_encrypt(&plain_text, &self.key, None)?;
+
+        let path = self.path(seq);
+        let temp_path = path.with_extension("tmp");
+        {
+            let mut file = File::create(&temp_path)?;
+            file.write_all(&cipher_text)?;
+            file.sync_all()?;
+        }
+        fs::rename(&temp_path, &path)?;
+
+        self.seqs.push_back(seq);
+        Ok(())
+    }
+
+    fn pop_front(&mut self) -> Result<(), CoreError> {
+        if let Some(seq) = self.seqs.pop_front() {
+            fs::remove_file(self.path(seq))?;
+        }
+        Ok(())
+    }
+
+    fn read(&self, seq: u64) -> Result<QueuedMutation, CoreError> {
+        let mut cipher_text = Vec::new();
+        let _ = File::open(self.path(seq))?.read_to_end(&mut cipher_text)?;
+        Ok(deserialise(&symmetric_decrypt(&cipher_text, &self.key)?)?)
+    }
+
+    fn path(&self, seq: u64) -> PathBuf {
+        self.dir.join(seq.to_string())
+    }
+}
+
+/// Applies the mutation, unless the queue is enabled and there are mutations queued
+/// before it, and queues it if the client turns out to be offline.
+pub fn submit(client: &impl Client, mutation: QueuedMutation) -> Box<CoreFuture<()>> {
+    let inner = client.inner();
+    let queue_empty = inner
+        .borrow()
+        .offline_queue
+        .as_ref()
+        .map(OfflineQueue::is_empty);
+
+    match queue_empty {
+        None => send(client, mutation),
+        Some(false) => future::result(push_back(client, &mutation)).into_box(),
+        Some(true) => {
+            let client = client.clone();
+
+            send(&client, mutation.clone())
+                .or_else(move |error| {
+                    if is_offline_error(&error) {
+                        trace!("Client offline, queueing mutation: {:?}", error);
+                        push_back(&client, &mutation)
+                    } else {
+                        Err(error)
+                    }
+                }).into_box()
+        }
+    }
+}
+
+/// Applies the queued mutations in order, and returns how many have been removed from the
+/// queue. Mutations which fail are removed and passed to the conflict handler along with
+/// the error, unless they fail because the client is still offline, in which case replaying
+/// stops with that error. Nothing is replayed if the queue isn't enabled or it's being
+/// replayed already.
+pub fn replay(client: &impl Client) -> Box<CoreFuture<usize>> {
+    let started = match client.inner().borrow_mut().offline_queue {
+        Some(ref mut queue) => !mem::replace(&mut queue.replaying, true),
+        None => false,
+    };
+    if !started {
+        return ok!(0);
+    }
+
+    let client = client.clone();
+    let client2 = client.clone();
+
+    future::loop_fn(0, move |replayed| {
+        let front = match client.inner().borrow().offline_queue {
+            Some(ref queue) => queue.front(),
+            None => Ok(None),
+        };
+        let mutation = match front {
+            Ok(Some(mutation)) => mutation,
+            Ok(None) => return Either::A(future::ok(Loop::Break(replayed))),
+            Err(error) => return Either::A(future::err(error)),
+        };
+        let client = client.clone();
+
+        Either::B(send(&client, mutation.clone()).then(move |res| match res {
+            Ok(()) => {
+                pop_front(&client)?;
+                Ok(Loop::Continue(replayed + 1))
+            }
+            Err(error) if is_offline_error(&error) => Err(error),
+            Err(error) => {
+                pop_front(&client)?;
+                report_conflict(&client, mutation, error);
+                Ok(Loop::Continue(replayed + 1))
+            }
+        }))
+    }).then(move |res| {
+        let inner = client2.inner();
+        if let Some(ref mut queue) = inner.borrow_mut().offline_queue {
+            queue.replaying = false;
+        }
+        res
+    }).into_box()
+}
+
+/// Replays the queue in the background, once the client is connected.
+pub fn replay_in_background(client: &impl Client) {
+    let pending = client
+        .inner()
+        .borrow()
+        .offline_queue
+        .as_ref()
+        .map_or(false, |queue| !queue.is_empty());
+    if !pending {
+        return;
+    }
+
+    let el_handle = client.inner().borrow().el_handle.clone();
+    el_handle.spawn(
+        replay(client)
+            .map(|replayed| trace!("Replayed {} queued mutations", replayed))
+            .map_err(|error| debug!("Failed to replay queued mutations: {:?}", error)),
+    );
+}
+
+fn push_back(client: &impl Client, mutation: &QueuedMutation) -> Result<(), CoreError> {
+    match client.inner().borrow_mut().offline_queue {
+        Some(ref mut queue) => queue.push_back(mutation),
+        None => Ok(()),
+    }
+}
+
+fn pop_front(client: &impl Client) -> Result<(), CoreError> {
+    match client.inner().borrow_mut().offline_queue {
+        Some(ref mut queue) => queue.pop_front(),
+        None => Ok(()),
+    }
+}
+
+// The handler is called without the client borrowed, so it can use the client.
+fn report_conflict(client: &impl Client, mutation: QueuedMutation, error: CoreError) {
+    let inner = client.inner();
+    let on_conflict = inner
+        .borrow_mut()
+        .offline_queue
+        .as_mut()
+        .and_then(|queue| queue.on_conflict.take());
+
+    if let Some(mut on_conflict) = on_conflict {
+        (&mut *on_conflict)(mutation, error);
+        if let Some(ref mut queue) = inner.borrow_mut().offline_queue {
+            queue.on_conflict = Some(on_conflict);
+        }
+    }
+}
+
+fn send(client: &impl Client, mutation: QueuedMutation) -> Box<CoreFuture<()>> {
+    match mutation {
+        QueuedMutation::PutIData(data) => send_put_idata(client, data)
+            .or_else(|error| match error {
+                // Replaying a put which has made it to the network before.
+                CoreError::RoutingClientError(ClientError::DataExists) => Ok(()),
+                error => Err(error),
+            }).into_box(),
+        QueuedMutation::PutMData(data) => send_put_mdata(client, data),
+        QueuedMutation::MutateMDataEntries { name, tag, actions } => {
+            send_mutate_mdata_entries(client, name, tag, actions)
+        }
+        QueuedMutation::SetMDataUserPermissions {
+            name,
+            tag,
+            user,
+            permissions,
+            version,
+        } => send_set_mdata_user_permissions(client, name, tag, user, permissions, version),
+        QueuedMutation::DelMDataUserPermissions {
+            name,
+            tag,
+            user,
+            version,
+        } => send_del_mdata_user_permissions(client, name, tag, user, version),
+    }
+}
+
+// Errors meaning the client is disconnected, so the request hasn't been sent, or the connection
+// it was sent over has been replaced. A request which timed out may have been applied, so
+// `RequestTimeout` isn't one of them.
+fn is_offline_error(error: &CoreError) -> bool {
+    match *error {
+        CoreError::OperationAborted
+        | CoreError::RoutingInterfaceError(InterfaceError::NotConnected) => true,
+        _ => false,
+    }
+}
+
+#[cfg(all(test, feature = "use-mock-routing"))]
+mod tests {
+    use super::*;
+    use client::connection::{self, ConnectionState};
+    use rand;
+    use routing::{EntryActions, EntryError, Value};
+    use std::cell::RefCell;
+    use std::env;
+    use std::rc::Rc;
+    use std::time::Duration;
+    use tokio_core::reactor::Timeout;
+    use utils;
+    use utils::test_utils::random_client;
+
+    // Test queueing mutations while disconnected, reopening the queue and replaying it
+    // after reconnecting. The conflicting mutation must be reported instead of
+    // overwriting the entry.
+    #[test]
+    fn queue_and_replay() {
+        let dir = env::temp_dir().join(unwrap!(utils::generate_readable_string(10)));
+
+        random_client(move |client| {
+            let client2 = client.clone();
+            let client3 = client.clone();
+            let client4 = client.clone();
+            let owner = unwrap!(client.owner_key());
+
+            let name = rand::random();
+            let data = unwrap!(MutableData::new(
+                name,
+                10_000,
+                btree_map![],
+                btree_map![],
+                btree_set![owner],
+            ));
+            unwrap!(client.enable_offline_queue(&dir, |_, _| panic!("Unexpected conflict")));
+
+            connection::set_state(client, ConnectionState::Disconnected);
+
+            client
+                .put_mdata(data)
+                .then(move |res| {
+                    unwrap!(res);
+                    let actions = EntryActions::new().ins(vec![0], vec![1], 0).into();
+                    client2.mutate_mdata_entries(name, 10_000, actions)
+                }).then(move |res| {
+                    unwrap!(res);
+                    // Queued behind the others even if the client got connected again. The
+                    // state is changed directly, so the queue isn't replayed yet.
+                    client3.inner().borrow_mut().connection = ConnectionState::Connected;
+                    let actions = EntryActions::new().ins(vec![0], vec![2], 0).into();
+                    client3.mutate_mdata_entries(name, 10_000, actions)
+                }).then(move |res| {
+                    unwrap!(res);
+                    assert_eq!(unwrap!(client4.offline_mutations()).len(), 3);
+
+                    // Reopen the queue, as after a restart.
+                    let conflicts = Rc::new(RefCell::new(Vec::new()));
+                    let conflicts2 = Rc::clone(&conflicts);
+                    unwrap!(client4.enable_offline_queue(&dir, move |mutation, error| {
+                        conflicts2.borrow_mut().push((mutation, error))
+                    }));
+                    assert_eq!(unwrap!(client4.offline_mutations()).len(), 3);
+
+                    client4.replay_offline_queue().map(move |replayed| {
+                        assert_eq!(replayed, 3);
+                        assert!(unwrap!(client4.offline_mutations()).is_empty());
+
+                        let conflicts = conflicts.borrow();
+                        assert_eq!(conflicts.len(), 1);
+                        match conflicts[0] {
+                            (
+                                QueuedMutation::MutateMDataEntries { .. },
+                                CoreError::RoutingClientError(ClientError::InvalidEntryActions(
+                                    ref errors,
+                                )),
+                            ) => match errors.get(&vec![0]) {
+                                Some(&EntryError::EntryExists(0)) => (),
+                                x => panic!("Unexpected {:?}", x),
+                            },
+                            ref x => panic!("Unexpected {:?}", x),
+                        }
+                        client4
+                    })
+                }).then(move |res| {
+                    let client = unwrap!(res);
+                    client.get_mdata_value(name, 10_000, vec![0])
+                }).map(move |value| {
+                    assert_eq!(
+                        value,
+                        Value {
+                            content: vec![1],
+                            entry_version: 0,
+                        }
+                    );
+                    unwrap!(fs::remove_dir_all(&dir));
+                })
+        });
+    }
+
+    // Test the queue is replayed once the client gets connected again.
+    #[test]
+    fn replay_on_reconnect() {
+        let dir = env::temp_dir().join(unwrap!(utils::generate_readable_string(10)));
+
+        random_client(move |client| {
+            let client2 = client.clone();
+            let owner = unwrap!(client.owner_key());
+
+            let name = rand::random();
+            let data = unwrap!(MutableData::new(
+                name,
+                10_000,
+                btree_map![],
+                btree_map![],
+                btree_set![owner],
+            ));
+            unwrap!(client.enable_offline_queue(&dir, |_, _| panic!("Unexpected conflict")));
+
+            connection::set_state(client, ConnectionState::Disconnected);
+
+            client
+                .put_mdata(data)
+                .then(move |res| {
+                    unwrap!(res);
+                    assert_eq!(unwrap!(client2.offline_mutations()).len(), 1);
+
+                    unwrap!(client2.restart_routing());
+
+                    let el_handle = client2.inner().borrow().el_handle.clone();
+                    future::loop_fn(client2, move |client| {
+                        if unwrap!(client.offline_mutations()).is_empty() {
+                            return Either::A(future::ok(Loop::Break(client)));
+                        }
+                        let timeout = unwrap!(Timeout::new(Duration::from_millis(50), &el_handle));
+                        Either::B(
+                            timeout
+                                .map_err(CoreError::from)
+                                .map(|()| Loop::Continue(client)),
+                        )
+                    })
+                }).then(move |res| {
+                    let client = unwrap!(res);
+                    client.get_mdata_version(name, 10_000)
+                }).map(move |version| {
+                    assert_eq!(version, 0);
+                    unwrap!(fs::remove_dir_all(&dir));
+                })
+        });
+    }
+
+    // Test that a mutation which times out isn't queued, as it may have been applied anyway.
+    #[test]
+    fn timeout_not_queued() {
+        let dir = env::temp_dir().join(unwrap!(utils::generate_readable_string(10)));
+
+        random_client(move |client| {
+            let client2 = client.clone();
+            let owner = unwrap!(client.owner_key());
+
+            let data = unwrap!(MutableData::new(
+                rand::random(),
+                10_000,
+                btree_map![],
+                btree_map![],
+                btree_set![owner],
+            ));
+            unwrap!(client.enable_offline_queue(&dir, |_, _| panic!("Unexpected conflict")));
+
+            client.set_simulate_timeout(true);
+            client.set_timeout(Duration::from_millis(250));
+
+            client.put_mdata(data).then(move |res| {
+                match res {
+                    Err(CoreError::RequestTimeout) => (),
+                    res => panic!("Unexpected {:?}", res),
+                }
+                assert!(unwrap!(client2.offline_mutations()).is_empty());
+                unwrap!(fs::remove_dir_all(&dir));
+                Ok::<_, CoreError>(())
+            })
+        });
+    }
+}
@@ -6,18 +6,19 @@
 // KIND, either express or implied. Please review the Licences for the specific language governing
 // permissions and limitations relating to use of the SAFE Network Software.
 
-use client::Client;
+use client::{connection, Client};
 use errors::CoreError;
-use event::{CoreEvent, NetworkEvent, NetworkTx};
+use event::CoreEvent;
 use event_loop::{CoreMsg, CoreMsgTx};
 use routing::{Event, MessageId, Response};
 use std::sync::mpsc::Receiver;
 
-/// Run the routing event loop - this will receive messages from routing.
+/// Run the routing event loop - this will receive messages from routing. The `generation` of
+/// the routing is passed on when the connection is lost.
 pub fn run<C: Client, T>(
     routing_rx: &Receiver<Event>,
     mut core_tx: CoreMsgTx<C, T>,
-    net_tx: &NetworkTx,
+    generation: u64,
 ) where
     T: 'static,
 {
@@ -34,9 +35,12 @@ pub fn run<C: Client, T>(
                 }
             }
             Event::Terminate => {
-                if let Err(e) = net_tx.unbounded_send(NetworkEvent::Disconnected) {
-                    trace!("Couldn't send NetworkEvent::Disconnected: {:?}", e);
-                }
+                // The disconnection is reported only if it's of the current routing.
+                let msg = CoreMsg::new(move |client: &C, _| {
+                    connection::handle_disconnect(client, generation);
+                    None
+                });
+                let _ = core_tx.unbounded_send(msg);
                 break;
             }
             x => {
@@ -6,10 +6,12 @@
 // KIND, either express or implied. Please review the Licences for the specific language governing
 // permissions and limitations relating to use of the SAFE Network Software.
 
+use client::{Backoff, REQUEST_TIMEOUT_SECS};
 use config_file_handler;
 use std::ffi::OsString;
 #[cfg(test)]
 use std::path::PathBuf;
+use std::time::Duration;
 use CoreError;
 
 /// Configuration for safe-core.
@@ -17,6 +19,127 @@ use CoreError;
 pub struct Config {
     /// Developer options.
     pub dev: Option<DevConfig>,
+    /// Persistent cache of `ImmutableData` chunks. Disabled if not set.
+    pub chunk_cache: Option<ChunkCacheConfig>,
+    /// Timeout and retry policies of the network requests. The defaults are used if not set.
+    pub request_policies: Option<RequestPolicies>,
+    /// In-memory cache of `MutableData` reads. Disabled if not set.
+    pub mdata_cache: Option<MDataCacheConfig>,
+    /// Backoff between the attempts to reconnect automatically once the connection has been
+    /// lost. Disabled if not set.
+    pub reconnect: Option<Backoff>,
+}
+
+/// Configuration of the persistent chunk cache.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct ChunkCacheConfig {
+    /// Directory to keep the cache in. Defaults to a `chunk_cache` directory in the user app
+    /// directory.
+    pub path: Option<String>,
+    /// Maximum total size of the cached chunks, in bytes.
+    pub max_size: u64,
+}
+
+/// Configuration of the `MutableData` read cache.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct MDataCacheConfig {
+    /// Time the entries, values and versions read are kept for.
+    pub ttl: Duration,
+    /// Maximum number of `MutableData`s cached.
+    pub capacity: usize,
+}
+
+/// Timeout and retry policy of network requests.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(default)]
+pub struct RequestPolicy {
+    /// Time to wait for a response before the request times out.
+    pub timeout: Duration,
+    /// Number of times a request which timed out is sent again.
+    pub max_retries: u32,
+    /// Delay before the first retry. It doubles after every retry.
+    pub retry_delay: Duration,
+    /// Retry mutations which aren't idempotent too. As a mutation which timed out may have been
+    /// applied anyway, its retry can fail even though the mutation succeeded.
+    pub retry_mutations: bool,
+}
+
+impl Default for RequestPolicy {
+    fn default() -> Self {
+        RequestPolicy {
+            timeout: Duration::from_secs(REQUEST_TIMEOUT_SECS),
+            max_retries: 0,
+            retry_delay: Duration::from_millis(800),
+            retry_mutations: false,
+        }
+    }
+}
+
+/// Type of a network request, which its policy is picked by.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum RequestKind {
+    /// Get `ImmutableData`.
+    GetIData,
+    /// Put `ImmutableData`.
+    PutIData,
+    /// Get `MutableData`, its shell, version, entries, keys, values or permissions.
+    GetMData,
+    /// Get a single entry of `MutableData`.
+    GetMDataValue,
+    /// Any other mutation: put `MutableData`, mutate its entries or permissions, change its owner
+    /// or mutate the authorised keys.
+    Mutation,
+    /// Get the account info or the authorised keys.
+    Account,
+}
+
+impl RequestKind {
+    /// Whether sending the request more than once has the same effect as sending it once.
+    pub fn is_idempotent(self) -> bool {
+        match self {
+            RequestKind::Mutation => false,
+            RequestKind::GetIData
+            | RequestKind::PutIData
+            | RequestKind::GetMData
+            | RequestKind::GetMDataValue
+            | RequestKind::Account => true,
+        }
+    }
+}
+
+/// Policies of the network requests by their type. The requests with no policy of their own
+/// follow the `default` one.
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+pub struct RequestPolicies {
+    /// Policy of the requests with no policy of their own.
+    pub default: Option<RequestPolicy>,
+    /// Policy of `RequestKind::GetIData` requests.
+    pub get_idata: Option<RequestPolicy>,
+    /// Policy of `RequestKind::PutIData` requests.
+    pub put_idata: Option<RequestPolicy>,
+    /// Policy of `RequestKind::GetMData` requests.
+    pub get_mdata: Option<RequestPolicy>,
+    /// Policy of `RequestKind::GetMDataValue` requests.
+    pub get_mdata_value: Option<RequestPolicy>,
+    /// Policy of `RequestKind::Mutation` requests.
+    pub mutation: Option<RequestPolicy>,
+    /// Policy of `RequestKind::Account` requests.
+    pub account: Option<RequestPolicy>,
+}
+
+impl RequestPolicies {
+    /// Returns the policy of the given type of requests.
+    pub fn get(&self, kind: RequestKind) -> RequestPolicy {
+        let policy = match kind {
+            RequestKind::GetIData => self.get_idata,
+            RequestKind::PutIData => self.put_idata,
+            RequestKind::GetMData => self.get_mdata,
+            RequestKind::GetMDataValue => self.get_mdata_value,
+            RequestKind::Mutation => self.mutation,
+            RequestKind::Account => self.account,
+        };
+        policy.or(self.default).unwrap_or_default()
+    }
 }
 
 /// Extra configuration options intended for developers.
@@ -121,4 +244,52 @@ mod test {
         assert_eq!(dev_config.mock_in_memory_storage, false);
         assert_eq!(dev_config.mock_vault_path, Some(String::from("./tmp")));
     }
+
+    // Test that the policies not set fall back to the default one, and the missing fields of a
+    // policy to their defaults.
+    #[test]
+    fn parse_request_policies() {
+        let encoded_contents = r#"{
+            "dev": null,
+            "chunk_cache": null,
+            "mdata_cache": null,
+            "request_policies": {
+                "default": { "max_retries": 2 },
+                "get_mdata_value": { "timeout": { "secs": 5, "nanos": 0 } }
+            }
+        }"#;
+        let config: Config = unwrap!(serde_json::from_str(encoded_contents));
+        let policies = unwrap!(config.request_policies);
+
+        let policy = policies.get(RequestKind::GetMDataValue);
+        assert_eq!(policy.timeout, Duration::from_secs(5));
+        assert_eq!(policy.max_retries, 0);
+
+        let policy = policies.get(RequestKind::Mutation);
+        assert_eq!(policy.timeout, RequestPolicy::default().timeout);
+        assert_eq!(policy.max_retries, 2);
+
+        assert_eq!(
+            RequestPolicies::default().get(RequestKind::GetIData),
+            RequestPolicy::default()
+        );
+    }
+
+    // Test that reconnecting automatically is disabled unless it's configured, with the missing
+    // fields of the backoff falling back to their defaults.
+    #[test]
+    fn parse_reconnect() {
+        let config: Config = unwrap!(serde_json::from_str(r#"{ "dev": null }"#));
+        assert_eq!(config.reconnect, None);
+
+        let encoded_contents = r#"{
+            "dev": null,
+            "reconnect": { "max_attempts": 3 }
+        }"#;
+        let config: Config = unwrap!(serde_json::from_str(encoded_contents));
+        let backoff = unwrap!(config.reconnect);
+        assert_eq!(backoff.max_attempts, 3);
+        assert_eq!(backoff.initial_delay, Backoff::default().initial_delay);
+        assert_eq!(backoff.max_delay, Backoff::default().max_delay);
+    }
 }
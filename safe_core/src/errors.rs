@@ -54,6 +54,8 @@ pub enum CoreError {
     UnsuccessfulPwHash,
     /// Blocking operation was cancelled.
     OperationAborted,
+    /// Operation was cancelled through its `CancellationToken`.
+    OperationCancelled,
     /// MpidMessaging Error.
     MpidMessagingError(messaging::Error),
     /// Error while self-encrypting data.
@@ -180,6 +182,7 @@ impl Debug for CoreError {
             }
             CoreError::UnsuccessfulPwHash => write!(formatter, "CoreError::UnsuccessfulPwHash"),
             CoreError::OperationAborted => write!(formatter, "CoreError::OperationAborted"),
+            CoreError::OperationCancelled => write!(formatter, "CoreError::OperationCancelled"),
             CoreError::MpidMessagingError(ref error) => {
                 write!(formatter, "CoreError::MpidMessagingError -> {:?}", error)
             }
@@ -241,6 +244,7 @@ impl Display for CoreError {
                 "Unable to complete computation for password hashing"
             ),
             CoreError::OperationAborted => write!(formatter, "Blocking operation was cancelled"),
+            CoreError::OperationCancelled => write!(formatter, "Operation was cancelled"),
             CoreError::MpidMessagingError(ref error) => {
                 write!(formatter, "Mpid messaging error: {}", error)
             }
@@ -275,6 +279,7 @@ impl Error for CoreError {
             CoreError::UnsupportedSaltSizeForPwHash => "Unsupported size of salt",
             CoreError::UnsuccessfulPwHash => "Failed while password hashing",
             CoreError::OperationAborted => "Operation aborted",
+            CoreError::OperationCancelled => "Operation cancelled",
             CoreError::MpidMessagingError(_) => "Mpid messaging error",
             CoreError::SelfEncryption(ref error) => error.description(),
             CoreError::RequestTimeout => "Request has timed out",
@@ -51,7 +51,7 @@ pub enum CoreEvent {
 }
 
 /// Network Events that Client Modules need to deal with.
-#[derive(Debug)]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
 pub enum NetworkEvent {
     /// The core engine is connected to atleast one peer
     Connected,
@@ -59,6 +59,23 @@ pub enum NetworkEvent {
     /// circumstances this would indicate that client connection to proxy node
     /// has been lost)
     Disconnected,
+    /// The core engine is connecting to the network
+    Connecting,
+    /// The core engine is trying to restore the lost connection, with the
+    /// number of the attempt
+    Reconnecting(u32),
+    /// The core engine gave up connecting to the network
+    Failed,
+}
+
+impl NetworkEvent {
+    /// Number of the attempt to reconnect, or 0 if the event isn't `Reconnecting`.
+    pub fn attempt(&self) -> u32 {
+        match *self {
+            NetworkEvent::Reconnecting(attempt) => attempt,
+            _ => 0,
+        }
+    }
 }
 
 impl Into<i32> for NetworkEvent {
@@ -66,6 +83,9 @@ impl Into<i32> for NetworkEvent {
         match self {
             NetworkEvent::Connected => NETWORK_EVENT_START_RANGE,
             NetworkEvent::Disconnected => NETWORK_EVENT_START_RANGE - 1,
+            NetworkEvent::Connecting => NETWORK_EVENT_START_RANGE - 2,
+            NetworkEvent::Reconnecting(_) => NETWORK_EVENT_START_RANGE - 3,
+            NetworkEvent::Failed => NETWORK_EVENT_START_RANGE - 4,
         }
     }
 }
@@ -10,7 +10,11 @@ use client::Client;
 use errors::CoreError;
 use futures::stream::Stream;
 use futures::sync::mpsc;
-use futures::Future;
+use futures::task::{self, Task};
+use futures::{Future, Poll};
+use std::cell::RefCell;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
 use tokio_core::reactor::Core;
 
 /// Transmitter of messages to be run in the core event loop.
@@ -45,6 +49,20 @@ impl<C: Client, T> CoreMsg<C, T> {
         )))
     }
 
+    /// Construct a new message like `new` does, but belonging to the operation of `token`:
+    /// all the network requests made by the closure and by the future it returns fail with
+    /// `CoreError::OperationCancelled` once the token is cancelled.
+    pub fn cancellable<F>(token: CancellationToken, f: F) -> Self
+    where
+        F: FnOnce(&C, &T) -> Option<TailFuture> + Send + 'static,
+    {
+        CoreMsg::new(move |client, context| {
+            let future = token.enter(|| f(client, context))?;
+            let tail: TailFuture = Box::new(WithToken { future, token });
+            Some(tail)
+        })
+    }
+
     /// Construct a new message which when processed by the event loop will
     /// terminate the event loop. This will be the graceful exit condition.
     pub fn build_terminator() -> Self {
@@ -52,6 +70,122 @@ impl<C: Client, T> CoreMsg<C, T> {
     }
 }
 
+thread_local! {
+    // Token of the operation which is being run on this thread, if any.
+    static CURRENT_TOKEN: RefCell<Option<CancellationToken>> = RefCell::new(None);
+}
+
+/// Handle used to cancel an operation dispatched to the core event loop. It can be cloned and
+/// cancelled from any thread.
+#[derive(Clone, Default)]
+pub struct CancellationToken(Arc<TokenState>);
+
+#[derive(Default)]
+struct TokenState {
+    cancelled: AtomicBool,
+    // Tasks to wake up when the token is cancelled.
+    tasks: Mutex<Vec<Task>>,
+}
+
+impl CancellationToken {
+    /// Create a new token which isn't cancelled.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Cancel the operation. Its pending network requests fail with
+    /// `CoreError::OperationCancelled`, and so does every request it makes afterwards.
+    pub fn cancel(&self) {
+        self.0.cancelled.store(true, Ordering::SeqCst);
+        for task in unwrap!(self.0.tasks.lock()).drain(..) {
+            task.notify();
+        }
+    }
+
+    /// Returns `true` if the token has been cancelled.
+    pub fn is_cancelled(&self) -> bool {
+        self.0.cancelled.load(Ordering::SeqCst)
+    }
+
+    // Returns the token of the operation being run on the event loop thread, if any.
+    pub(crate) fn current() -> Option<Self> {
+        CURRENT_TOKEN.with(|current| current.borrow().clone())
+    }
+
+    // Set the token of the operation being run on the event loop thread, returning the
+    // previous one.
+    fn set_current(token: Option<Self>) -> Option<Self> {
+        CURRENT_TOKEN.with(|current| ::std::mem::replace(&mut *current.borrow_mut(), token))
+    }
+
+    // Run `f` with this token as the current one.
+    fn enter<F, R>(&self, f: F) -> R
+    where
+        F: FnOnce() -> R,
+    {
+        let prev = Self::set_current(Some(self.clone()));
+        let result = f();
+        let _ = Self::set_current(prev);
+        result
+    }
+
+    fn register_current_task(&self) {
+        let mut tasks = unwrap!(self.0.tasks.lock());
+        if !tasks.iter().any(|task| task.will_notify_current()) {
+            tasks.push(task::current());
+        }
+    }
+}
+
+/// Future which fails with `CoreError::OperationCancelled` as soon as the token is cancelled,
+/// dropping the wrapped future.
+pub struct Cancellable<F> {
+    future: F,
+    token: CancellationToken,
+}
+
+impl<F> Cancellable<F> {
+    /// Wrap `future` so it's cancelled by `token`.
+    pub fn new(future: F, token: CancellationToken) -> Self {
+        Cancellable { future, token }
+    }
+}
+
+impl<F> Future for Cancellable<F>
+where
+    F: Future,
+    F::Error: From<CoreError>,
+{
+    type Item = F::Item;
+    type Error = F::Error;
+
+    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+        // Register before checking, so a concurrent `cancel` can't be missed.
+        self.token.register_current_task();
+        if self.token.is_cancelled() {
+            return Err(From::from(CoreError::OperationCancelled));
+        }
+        self.future.poll()
+    }
+}
+
+// Polls the tail future of an operation with its token as the current one, so the requests
+// made by its continuations belong to the operation too.
+struct WithToken {
+    future: TailFuture,
+    token: CancellationToken,
+}
+
+impl Future for WithToken {
+    type Item = ();
+    type Error = ();
+
+    fn poll(&mut self) -> Poll<(), ()> {
+        let future = &mut self.future;
+        self.token.enter(|| future.poll())
+    }
+}
+
 /// Run the core event loop. This will block until the event loop is alive.
 /// Hence must typically be called inside a spawned thread.
 pub fn run<C: Client, T>(mut el: Core, client: &C, context: &T, el_rx: CoreMsgRx<C, T>) {
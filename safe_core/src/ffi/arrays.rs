@@ -14,6 +14,7 @@ use rust_sodium::crypto::box_::{
 use rust_sodium::crypto::secretbox::{KEYBYTES as SYM_KEY_LEN, NONCEBYTES as SYM_NONCE_LEN};
 use rust_sodium::crypto::sign::{
     PUBLICKEYBYTES as SIGN_PUBLIC_KEY_LEN, SECRETKEYBYTES as SIGN_SECRET_KEY_LEN,
+    SIGNATUREBYTES as SIGNATURE_LEN,
 };
 
 /// Array containing public key bytes.
@@ -32,6 +33,10 @@ pub type SymNonce = [u8; SYM_NONCE_LEN];
 pub type SignPublicKey = [u8; SIGN_PUBLIC_KEY_LEN];
 /// Array containing sign private key bytes.
 pub type SignSecretKey = [u8; SIGN_SECRET_KEY_LEN];
+/// Array containing signature bytes.
+pub type SignatureArray = [u8; SIGNATURE_LEN];
 
 /// Array containing `XorName` bytes.
 pub type XorNameArray = [u8; XOR_NAME_LEN];
+/// Array containing SHA3-256 hash bytes.
+pub type Sha3Hash = [u8; 32];
@@ -6,7 +6,7 @@
 // KIND, either express or implied. Please review the Licences for the specific language governing
 // permissions and limitations relating to use of the SAFE Network Software.
 
-use ffi::arrays::XorNameArray;
+use ffi::arrays::{AsymPublicKey, Sha3Hash, SignPublicKey, SignatureArray, XorNameArray};
 use ffi_utils::callback::CallbackArgs;
 use ffi_utils::ReprC;
 use ipc::req::permission_set_into_repr_c;
@@ -117,6 +117,16 @@ pub struct AuthReq {
     /// Capacity of container permissions array. Internal field
     /// required for the Rust allocator.
     pub containers_cap: usize,
+
+    /// Developer-signed app manifest
+    ///
+    /// null if not present
+    pub manifest: *const AppManifest,
+
+    /// `true` if the response is to be sealed to `resp_key`
+    pub has_resp_key: bool,
+    /// Ephemeral public key the response is to be sealed to
+    pub resp_key: AsymPublicKey,
 }
 
 impl Drop for AuthReq {
@@ -128,6 +138,71 @@ impl Drop for AuthReq {
                 self.containers_len,
                 self.containers_cap,
             );
+            if !self.manifest.is_null() {
+                let _ = Box::from_raw(self.manifest as *mut AppManifest);
+            }
+        }
+    }
+}
+
+/// Reason an app gives for requesting access to a container
+#[repr(C)]
+pub struct PermissionReason {
+    /// UTF-8 encoded container name
+    pub cont_name: *const c_char,
+    /// UTF-8 encoded human-readable reason
+    pub reason: *const c_char,
+}
+
+impl Drop for PermissionReason {
+    #[allow(unsafe_code)]
+    fn drop(&mut self) {
+        unsafe {
+            let _ = CString::from_raw(self.cont_name as *mut _);
+            let _ = CString::from_raw(self.reason as *mut _);
+        }
+    }
+}
+
+/// App information signed by the app developer
+#[repr(C)]
+pub struct AppManifest {
+    /// `true` if `icon_hash` is set
+    pub has_icon_hash: bool,
+    /// SHA3-256 hash of the app icon
+    pub icon_hash: Sha3Hash,
+
+    /// UTF-8 encoded homepage of the app
+    ///
+    /// null if not present
+    pub homepage: *const c_char,
+
+    /// Array of `PermissionReason`
+    pub permission_reasons: *const PermissionReason,
+    /// Size of permission reasons array
+    pub permission_reasons_len: usize,
+    /// Capacity of permission reasons array. Internal field
+    /// required for the Rust allocator.
+    pub permission_reasons_cap: usize,
+
+    /// Public signing key of the app developer
+    pub developer_key: SignPublicKey,
+    /// Signature of the manifest and the app ID
+    pub signature: SignatureArray,
+}
+
+impl Drop for AppManifest {
+    #[allow(unsafe_code)]
+    fn drop(&mut self) {
+        unsafe {
+            if !self.homepage.is_null() {
+                let _ = CString::from_raw(self.homepage as *mut _);
+            }
+            let _ = Vec::from_raw_parts(
+                self.permission_reasons as *mut PermissionReason,
+                self.permission_reasons_len,
+                self.permission_reasons_cap,
+            );
         }
     }
 }
@@ -195,3 +270,99 @@ impl Drop for ShareMDataReq {
         }
     }
 }
+
+/// Request which can be a part of a batch. Exactly one of the requests is set,
+/// the others are null.
+#[repr(C)]
+pub struct BatchItem {
+    /// Authorisation request
+    pub auth: *const AuthReq,
+    /// Containers request
+    pub containers: *const ContainersReq,
+    /// Share mutable data request
+    pub share_mdata: *const ShareMDataReq,
+}
+
+impl Drop for BatchItem {
+    #[allow(unsafe_code)]
+    fn drop(&mut self) {
+        unsafe {
+            if !self.auth.is_null() {
+                let _ = Box::from_raw(self.auth as *mut AuthReq);
+            }
+            if !self.containers.is_null() {
+                let _ = Box::from_raw(self.containers as *mut ContainersReq);
+            }
+            if !self.share_mdata.is_null() {
+                let _ = Box::from_raw(self.share_mdata as *mut ShareMDataReq);
+            }
+        }
+    }
+}
+
+/// Batch of requests of a single app, presented to the user at once
+#[repr(C)]
+pub struct BatchReq {
+    /// Requests, processed in order
+    pub items: *const BatchItem,
+    /// Length of the items array
+    pub items_len: usize,
+    /// Capacity of the items array. Internal field
+    /// required for the Rust allocator.
+    pub items_cap: usize,
+    /// `true` if the user approves or denies the batch as a whole with a single
+    /// decision, `false` if each request is approved separately. Either way the
+    /// requests are granted one by one: a request failing to be granted doesn't
+    /// undo the requests granted before it.
+    pub single_decision: bool,
+}
+
+impl Drop for BatchReq {
+    #[allow(unsafe_code)]
+    fn drop(&mut self) {
+        unsafe {
+            let _ = Vec::from_raw_parts(
+                self.items as *mut BatchItem,
+                self.items_len,
+                self.items_cap,
+            );
+        }
+    }
+}
+
+/// Represents a request to accept an offer of access to a mutable data made by
+/// another user
+#[repr(C)]
+pub struct ShareOfferReq {
+    /// Info about the app the offer is to be accepted for
+    pub app: AppExchangeInfo,
+    /// Share offer, sealed to the public encryption key of the app
+    pub offer: *const u8,
+    /// Length of the offer
+    pub offer_len: usize,
+    /// Capacity of the offer vec. Internal field
+    /// required for the Rust allocator.
+    pub offer_cap: usize,
+}
+
+impl Drop for ShareOfferReq {
+    #[allow(unsafe_code)]
+    fn drop(&mut self) {
+        unsafe {
+            let _ = Vec::from_raw_parts(self.offer as *mut u8, self.offer_len, self.offer_cap);
+        }
+    }
+}
+
+/// Terms of a share offer, as presented to the user
+#[repr(C)]
+pub struct ShareOfferTerms {
+    /// Name of the shared mutable data
+    pub name: XorNameArray,
+    /// Type tag of the shared mutable data
+    pub type_tag: u64,
+    /// Permissions being offered
+    pub perms: PermissionSet,
+    /// Public signing key of the owner of the mutable data
+    pub owner: SignPublicKey,
+}
@@ -144,6 +144,33 @@ impl Drop for AuthGranted {
     }
 }
 
+/// Represents the response to an accepted share offer.
+#[repr(C)]
+pub struct ShareOfferGranted {
+    /// The shared mutable data, along with the keys to decrypt its entries.
+    pub mdata: MDataInfo,
+    /// Permissions the app is going to be granted.
+    pub perms: PermissionSet,
+    /// Acceptance of the offer, to be delivered to the app which made it.
+    pub acceptance: *const u8,
+    /// `acceptance`'s length
+    pub acceptance_len: usize,
+    /// Used by Rust memory allocator
+    pub acceptance_cap: usize,
+}
+
+impl Drop for ShareOfferGranted {
+    fn drop(&mut self) {
+        unsafe {
+            let _ = Vec::from_raw_parts(
+                self.acceptance as *mut u8,
+                self.acceptance_len,
+                self.acceptance_cap,
+            );
+        }
+    }
+}
+
 /// Information about an application that has access to an MD through `sign_key`
 #[repr(C)]
 pub struct AppAccess {
@@ -43,3 +43,12 @@ impl Drop for File {
         };
     }
 }
+
+/// File fetched from a directory, along with its version.
+#[repr(C)]
+pub struct FetchedFile {
+    /// Pointer to the file, or null if there's no file with the name.
+    pub file: *const File,
+    /// Version of the file.
+    pub version: u64,
+}
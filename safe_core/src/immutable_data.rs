@@ -13,7 +13,7 @@ use futures::Future;
 use maidsafe_utilities::serialisation::{deserialise, serialise};
 use routing::{ImmutableData, XorName};
 use self_encryption::{DataMap, SelfEncryptor};
-use self_encryption_storage::SelfEncryptionStorage;
+use self_encryption_storage::{SelfEncryptionStorage, UploadPipeline, DEFAULT_MAX_CONCURRENT_PUTS};
 use utils::{self, FutureExt};
 
 #[derive(Serialize, Deserialize)]
@@ -28,11 +28,27 @@ pub fn create(
     client: &impl Client,
     value: &[u8],
     encryption_key: Option<shared_secretbox::Key>,
+) -> Box<CoreFuture<ImmutableData>> {
+    let pipeline = UploadPipeline::new(DEFAULT_MAX_CONCURRENT_PUTS);
+    create_with_pipeline(client, value, encryption_key, &pipeline)
+}
+
+/// Same as `create`, but uploads the chunks through the given pipeline, which can be used
+/// to follow the progress. The size of the pipeline is set to the size of the value, while
+/// the chunks of its data map don't count towards the progress.
+pub fn create_with_pipeline(
+    client: &impl Client,
+    value: &[u8],
+    encryption_key: Option<shared_secretbox::Key>,
+    pipeline: &UploadPipeline,
 ) -> Box<CoreFuture<ImmutableData>> {
     trace!("Creating conformant ImmutableData.");
 
     let client = client.clone();
-    let storage = SelfEncryptionStorage::new(client.clone());
+    let pipeline = pipeline.clone();
+    pipeline.set_size(value.len() as u64);
+
+    let storage = SelfEncryptionStorage::with_pipeline(client.clone(), pipeline.clone());
     let self_encryptor = fry!(SelfEncryptor::new(storage, DataMap::None));
 
     self_encryptor
@@ -51,7 +67,7 @@ pub fn create(
                 )))
             };
 
-            pack(client, value)
+            pack(client, value, pipeline.untracked())
         }).into_box()
 }
 
@@ -97,12 +113,16 @@ pub fn get_value(
 
 // TODO: consider rewriting these two function to not use recursion.
 
-fn pack(client: impl Client, value: Vec<u8>) -> Box<CoreFuture<ImmutableData>> {
+fn pack(
+    client: impl Client,
+    value: Vec<u8>,
+    pipeline: UploadPipeline,
+) -> Box<CoreFuture<ImmutableData>> {
     let data = ImmutableData::new(value);
     let serialised_data = fry!(serialise(&data));
 
     if !data.validate_size() {
-        let storage = SelfEncryptionStorage::new(client.clone());
+        let storage = SelfEncryptionStorage::with_pipeline(client.clone(), pipeline.clone());
         let self_encryptor = fry!(SelfEncryptor::new(storage, DataMap::None));
         self_encryptor
             .write(&serialised_data, 0)
@@ -110,7 +130,7 @@ fn pack(client: impl Client, value: Vec<u8>) -> Box<CoreFuture<ImmutableData>> {
             .map_err(From::from)
             .and_then(move |(data_map, _)| {
                 let value = fry!(serialise(&DataTypeEncoding::DataMap(data_map)));
-                pack(client, value)
+                pack(client, value, pipeline)
             }).into_box()
     } else {
         ok!(data)
@@ -167,6 +187,66 @@ mod tests {
         create_and_retrieve(10 * 1024 * 1024)
     }
 
+    // Test that the progress of uploading the chunks is reported with the number of chunks
+    // and bytes known up front, and that the uploaded data can be retrieved.
+    #[test]
+    fn create_with_progress() {
+        use std::cell::RefCell;
+        use std::rc::Rc;
+
+        let size = 2 * 1024 * 1024;
+        let value = unwrap!(utils::generate_random_vector(size));
+
+        random_client(move |client| {
+            let client2 = client.clone();
+            let client3 = client.clone();
+
+            let reports = Rc::new(RefCell::new(Vec::new()));
+            let reports2 = Rc::clone(&reports);
+
+            let pipeline = UploadPipeline::new(2);
+            pipeline.set_progress_callback(move |progress| {
+                reports2.borrow_mut().push(progress);
+                true
+            });
+
+            create_with_pipeline(client, &value, None, &pipeline)
+                .then(move |res| {
+                    let data = unwrap!(res);
+
+                    // 2 MiB are split into 3 chunks.
+                    let progress = pipeline.progress();
+                    assert_eq!(progress.chunks_done, 3);
+                    assert_eq!(progress.chunks_total, 3);
+                    assert_eq!(reports.borrow().len(), 3);
+                    assert!(reports
+                        .borrow()
+                        .iter()
+                        .all(|report| report.chunks_total == 3));
+                    assert_eq!(reports.borrow().last(), Some(&progress));
+
+                    // The bytes done grow with each chunk, up to the size of the value.
+                    assert_eq!(progress.bytes_total, size as u64);
+                    assert!(progress.bytes_done > 0);
+                    assert!(reports
+                        .borrow()
+                        .windows(2)
+                        .all(|pair| pair[0].bytes_done < pair[1].bytes_done
+                            && pair[1].bytes_done <= pair[1].bytes_total));
+
+                    let data_name = *data.name();
+                    client2.put_idata(data).map(move |_| (data_name, value))
+                }).then(move |res| {
+                    let (data_name, value) = unwrap!(res);
+                    get_value(&client3, &data_name, None)
+                        .map(move |value_after| assert_eq!(value_after, value))
+                }).then(|res| {
+                    unwrap!(res);
+                    finish()
+                })
+        })
+    }
+
     fn create_and_retrieve(size: usize) {
         let value = unwrap!(utils::generate_random_vector(size));
 
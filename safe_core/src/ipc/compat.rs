@@ -0,0 +1,305 @@
+// Copyright 2018 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+//! Encodings of IPC messages used by the earlier versions of the IPC protocol.
+//!
+//! Version 0 is the unversioned encoding used before the version envelope has been
+//! introduced. It only differs from version 1 by the lack of the app manifest in
+//! `AuthReq` and of the errors introduced along with the envelope. Version 1 only
+//! differs from version 2 by the lack of the response key in `AuthReq` and of sealed
+//! responses. Messages added by later versions are appended to the enums, so they
+//! don't change the encoding of the earlier ones.
+
+use super::req::{
+    AppExchangeInfo, AppManifest, BatchItem, ContainerPermissions, ContainersReq, ShareMDataReq,
+};
+use super::{AuthReq, IpcError, IpcMsg, IpcReq, IpcResp};
+use maidsafe_utilities::serialisation::{deserialise, serialise};
+use std::cmp;
+use std::collections::HashMap;
+
+/// Returns the lowest protocol version able to represent the message.
+pub fn min_version(msg: &IpcMsg) -> u32 {
+    match *msg {
+        IpcMsg::Req { ref req, .. } => req_version(req),
+        IpcMsg::Resp { ref resp, .. } => resp_version(resp),
+        IpcMsg::Revoked { .. } => 0,
+        IpcMsg::Err(ref error) => error_version(error),
+        IpcMsg::SealedResp { .. } => 2,
+    }
+}
+
+/// Serialises the message using the version 0 encoding.
+pub fn encode_v0(msg: &IpcMsg, mock: bool) -> Result<Vec<u8>, IpcError> {
+    if min_version(msg) > 0 {
+        return Err(IpcError::from("Message can't be encoded with IPC version 0"));
+    }
+
+    let msg = match *msg {
+        IpcMsg::Req { req_id, ref req } => IpcMsgV0Ref::Req {
+            req_id,
+            req: match *req {
+                IpcReq::Auth(ref req) => IpcReqV0Ref::Auth(AuthReqV0Ref {
+                    app: &req.app,
+                    app_container: req.app_container,
+                    containers: &req.containers,
+                }),
+                IpcReq::Containers(ref req) => IpcReqV0Ref::Containers(req),
+                IpcReq::Unregistered(ref extra_data) => IpcReqV0Ref::Unregistered(extra_data),
+                IpcReq::ShareMData(ref req) => IpcReqV0Ref::ShareMData(req),
+                IpcReq::Batch(_) | IpcReq::ShareOffer(_) => unreachable!(),
+            },
+        },
+        IpcMsg::Resp { req_id, ref resp } => IpcMsgV0Ref::Resp { req_id, resp },
+        IpcMsg::Revoked { ref app_id } => IpcMsgV0Ref::Revoked { app_id },
+        IpcMsg::Err(ref error) => IpcMsgV0Ref::Err(error),
+        IpcMsg::SealedResp { .. } => unreachable!(),
+    };
+
+    Ok(serialise(&(msg, mock))?)
+}
+
+/// Deserialises a message encoded using the version 0 encoding, along with its mock flag.
+pub fn decode_v0(encoded: &[u8]) -> Result<(IpcMsg, bool), IpcError> {
+    let (msg, mock): (IpcMsgV0, bool) = deserialise(encoded)?;
+    Ok((msg.into(), mock))
+}
+
+/// Serialises the message using the version 1 encoding of the envelope payload.
+pub fn encode_v1(msg: &IpcMsg) -> Result<Vec<u8>, IpcError> {
+    if min_version(msg) > 1 {
+        return Err(IpcError::from("Message can't be encoded with IPC version 1"));
+    }
+
+    let msg = match *msg {
+        IpcMsg::Req { req_id, ref req } => IpcMsgV1Ref::Req {
+            req_id,
+            req: match *req {
+                IpcReq::Auth(ref req) => IpcReqV1Ref::Auth(AuthReqV1Ref {
+                    app: &req.app,
+                    app_container: req.app_container,
+                    containers: &req.containers,
+                    manifest: &req.manifest,
+                }),
+                IpcReq::Containers(ref req) => IpcReqV1Ref::Containers(req),
+                IpcReq::Unregistered(ref extra_data) => IpcReqV1Ref::Unregistered(extra_data),
+                IpcReq::ShareMData(ref req) => IpcReqV1Ref::ShareMData(req),
+                IpcReq::Batch(_) | IpcReq::ShareOffer(_) => unreachable!(),
+            },
+        },
+        IpcMsg::Resp { req_id, ref resp } => IpcMsgV1Ref::Resp { req_id, resp },
+        IpcMsg::Revoked { ref app_id } => IpcMsgV1Ref::Revoked { app_id },
+        IpcMsg::Err(ref error) => IpcMsgV1Ref::Err(error),
+        IpcMsg::SealedResp { .. } => unreachable!(),
+    };
+
+    Ok(serialise(&msg)?)
+}
+
+/// Deserialises an envelope payload encoded using the version 1 encoding.
+pub fn decode_v1(encoded: &[u8]) -> Result<IpcMsg, IpcError> {
+    let msg: IpcMsgV1 = deserialise(encoded)?;
+    Ok(msg.into())
+}
+
+fn req_version(req: &IpcReq) -> u32 {
+    match *req {
+        IpcReq::Auth(ref req) => auth_req_version(req),
+        IpcReq::Containers(_) | IpcReq::Unregistered(_) | IpcReq::ShareMData(_) => 0,
+        IpcReq::Batch(ref req) => req
+            .items
+            .iter()
+            .map(|item| match *item {
+                BatchItem::Auth(ref req) => auth_req_version(req),
+                BatchItem::Containers(_) | BatchItem::ShareMData(_) => 0,
+            }).fold(3, cmp::max),
+        IpcReq::ShareOffer(_) => 4,
+    }
+}
+
+fn auth_req_version(req: &AuthReq) -> u32 {
+    if req.resp_key.is_some() {
+        2
+    } else if req.manifest.is_some() {
+        1
+    } else {
+        0
+    }
+}
+
+fn resp_version(resp: &IpcResp) -> u32 {
+    match *resp {
+        IpcResp::Auth(Err(ref error))
+        | IpcResp::Containers(Err(ref error))
+        | IpcResp::Unregistered(Err(ref error))
+        | IpcResp::ShareMData(Err(ref error)) => error_version(error),
+        IpcResp::Auth(Ok(_))
+        | IpcResp::Containers(Ok(_))
+        | IpcResp::Unregistered(Ok(_))
+        | IpcResp::ShareMData(Ok(_)) => 0,
+        IpcResp::Batch(Ok(ref resps)) => resps.iter().map(resp_version).fold(3, cmp::max),
+        IpcResp::Batch(Err(ref error)) => cmp::max(3, error_version(error)),
+        IpcResp::ShareOffer(Err(ref error)) => cmp::max(4, error_version(error)),
+        IpcResp::ShareOffer(Ok(_)) => 4,
+    }
+}
+
+fn error_version(error: &IpcError) -> u32 {
+    match *error {
+        IpcError::InvalidManifest
+        | IpcError::ManifestKeyMismatch
+        | IpcError::UpgradeRequired(_) => 1,
+        IpcError::InvalidSealedResp => 2,
+        IpcError::InvalidShareOffer => 4,
+        _ => 0,
+    }
+}
+
+#[derive(Serialize)]
+enum IpcMsgV0Ref<'a> {
+    Req { req_id: u32, req: IpcReqV0Ref<'a> },
+    Resp { req_id: u32, resp: &'a IpcResp },
+    Revoked { app_id: &'a str },
+    Err(&'a IpcError),
+}
+
+#[derive(Serialize)]
+enum IpcReqV0Ref<'a> {
+    Auth(AuthReqV0Ref<'a>),
+    Containers(&'a ContainersReq),
+    Unregistered(&'a [u8]),
+    ShareMData(&'a ShareMDataReq),
+}
+
+#[derive(Serialize)]
+struct AuthReqV0Ref<'a> {
+    app: &'a AppExchangeInfo,
+    app_container: bool,
+    containers: &'a HashMap<String, ContainerPermissions>,
+}
+
+#[derive(Deserialize)]
+enum IpcMsgV0 {
+    Req { req_id: u32, req: IpcReqV0 },
+    Resp { req_id: u32, resp: IpcResp },
+    Revoked { app_id: String },
+    Err(IpcError),
+}
+
+#[derive(Deserialize)]
+enum IpcReqV0 {
+    Auth(AuthReqV0),
+    Containers(ContainersReq),
+    Unregistered(Vec<u8>),
+    ShareMData(ShareMDataReq),
+}
+
+#[derive(Deserialize)]
+struct AuthReqV0 {
+    app: AppExchangeInfo,
+    app_container: bool,
+    containers: HashMap<String, ContainerPermissions>,
+}
+
+impl From<IpcMsgV0> for IpcMsg {
+    fn from(msg: IpcMsgV0) -> Self {
+        match msg {
+            IpcMsgV0::Req { req_id, req } => IpcMsg::Req {
+                req_id,
+                req: match req {
+                    IpcReqV0::Auth(req) => IpcReq::Auth(AuthReq {
+                        app: req.app,
+                        app_container: req.app_container,
+                        containers: req.containers,
+                        manifest: None,
+                        resp_key: None,
+                    }),
+                    IpcReqV0::Containers(req) => IpcReq::Containers(req),
+                    IpcReqV0::Unregistered(extra_data) => IpcReq::Unregistered(extra_data),
+                    IpcReqV0::ShareMData(req) => IpcReq::ShareMData(req),
+                },
+            },
+            IpcMsgV0::Resp { req_id, resp } => IpcMsg::Resp { req_id, resp },
+            IpcMsgV0::Revoked { app_id } => IpcMsg::Revoked { app_id },
+            IpcMsgV0::Err(error) => IpcMsg::Err(error),
+        }
+    }
+}
+
+#[derive(Serialize)]
+enum IpcMsgV1Ref<'a> {
+    Req { req_id: u32, req: IpcReqV1Ref<'a> },
+    Resp { req_id: u32, resp: &'a IpcResp },
+    Revoked { app_id: &'a str },
+    Err(&'a IpcError),
+}
+
+#[derive(Serialize)]
+enum IpcReqV1Ref<'a> {
+    Auth(AuthReqV1Ref<'a>),
+    Containers(&'a ContainersReq),
+    Unregistered(&'a [u8]),
+    ShareMData(&'a ShareMDataReq),
+}
+
+#[derive(Serialize)]
+struct AuthReqV1Ref<'a> {
+    app: &'a AppExchangeInfo,
+    app_container: bool,
+    containers: &'a HashMap<String, ContainerPermissions>,
+    manifest: &'a Option<AppManifest>,
+}
+
+#[derive(Deserialize)]
+enum IpcMsgV1 {
+    Req { req_id: u32, req: IpcReqV1 },
+    Resp { req_id: u32, resp: IpcResp },
+    Revoked { app_id: String },
+    Err(IpcError),
+}
+
+#[derive(Deserialize)]
+enum IpcReqV1 {
+    Auth(AuthReqV1),
+    Containers(ContainersReq),
+    Unregistered(Vec<u8>),
+    ShareMData(ShareMDataReq),
+}
+
+#[derive(Deserialize)]
+struct AuthReqV1 {
+    app: AppExchangeInfo,
+    app_container: bool,
+    containers: HashMap<String, ContainerPermissions>,
+    manifest: Option<AppManifest>,
+}
+
+impl From<IpcMsgV1> for IpcMsg {
+    fn from(msg: IpcMsgV1) -> Self {
+        match msg {
+            IpcMsgV1::Req { req_id, req } => IpcMsg::Req {
+                req_id,
+                req: match req {
+                    IpcReqV1::Auth(req) => IpcReq::Auth(AuthReq {
+                        app: req.app,
+                        app_container: req.app_container,
+                        containers: req.containers,
+                        manifest: req.manifest,
+                        resp_key: None,
+                    }),
+                    IpcReqV1::Containers(req) => IpcReq::Containers(req),
+                    IpcReqV1::Unregistered(extra_data) => IpcReq::Unregistered(extra_data),
+                    IpcReqV1::ShareMData(req) => IpcReq::ShareMData(req),
+                },
+            },
+            IpcMsgV1::Resp { req_id, resp } => IpcMsg::Resp { req_id, resp },
+            IpcMsgV1::Revoked { app_id } => IpcMsg::Revoked { app_id },
+            IpcMsgV1::Err(error) => IpcMsg::Err(error),
+        }
+    }
+}
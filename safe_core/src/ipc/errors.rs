@@ -11,6 +11,7 @@ use ffi_utils::StringError;
 use futures::sync::mpsc::SendError;
 use maidsafe_utilities::serialisation::SerialisationError;
 use routing::XorName;
+use serde_json;
 use std::error::Error;
 use std::ffi::NulError;
 use std::str::Utf8Error;
@@ -41,6 +42,23 @@ pub enum IpcError {
 
     /// Unexpected error.
     Unexpected(String),
+
+    // Variants below have been added along with the version envelope. New variants must
+    // be appended, as the version 0 encoding depends on the variant indices.
+    /// App manifest signature doesn't match its contents or the app ID.
+    InvalidManifest,
+    /// App manifest is signed by a different developer than the one the app was
+    /// authorised with.
+    ManifestKeyMismatch,
+    /// Message uses a newer version of the IPC protocol than supported.
+    /// Contains the version of the message.
+    UpgradeRequired(u32),
+    /// Sealed response can't be opened with the key of the request, or isn't signed
+    /// by the authenticator.
+    InvalidSealedResp,
+    /// Share offer can't be opened with the key of the recipient, or its signature
+    /// doesn't verify.
+    InvalidShareOffer,
 }
 
 impl<T: 'static> From<SendError<T>> for IpcError {
@@ -67,6 +85,12 @@ impl From<SerialisationError> for IpcError {
     }
 }
 
+impl From<serde_json::Error> for IpcError {
+    fn from(_err: serde_json::Error) -> Self {
+        IpcError::EncodeDecodeError
+    }
+}
+
 impl From<StringError> for IpcError {
     fn from(err: StringError) -> Self {
         IpcError::StringError(err)
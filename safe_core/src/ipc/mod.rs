@@ -11,24 +11,74 @@ pub mod req;
 /// Response module.
 pub mod resp;
 
+mod compat;
 mod errors;
+mod sealed;
+mod share_offer;
 
 pub use self::errors::IpcError;
 pub use self::req::{
-    AppExchangeInfo, AuthReq, ContainersReq, IpcReq, Permission, ShareMData, ShareMDataReq,
+    AppExchangeInfo, AppManifest, AuthReq, BatchItem, BatchReq, ContainersReq, IpcReq, Permission,
+    ShareMData, ShareMDataReq, ShareOfferReq,
 };
 pub use self::resp::{
     access_container_enc_key, AccessContInfo, AccessContainerEntry, AppKeys, AuthGranted, IpcResp,
+    ShareOfferGranted,
+};
+pub use self::sealed::{open_resp, seal_resp};
+pub use self::share_offer::{
+    open_share_acceptance, open_share_offer, seal_share_acceptance, seal_share_offer,
+    ShareAcceptance, ShareOffer, SignedShareOffer,
 };
 
-use data_encoding::BASE32_NOPAD;
+use data_encoding::{BASE32_NOPAD, BASE64URL_NOPAD};
 #[cfg(any(test, feature = "testing"))]
 use ffi_utils;
 use maidsafe_utilities::serialisation::{deserialise, serialise};
 use rand::{self, Rng};
 pub use routing::BootstrapConfig;
+use serde_json::{self, Value};
 use std::u32;
 
+/// Current version of the IPC protocol.
+///
+/// Version 0 is the unversioned encoding used before the version envelope has been
+/// introduced. Version 1 added the app manifest to `AuthReq`. Version 2 added the
+/// response key to `AuthReq` and sealed responses. Version 3 added batches of requests.
+/// Version 4 added share offers.
+pub const IPC_VERSION: u32 = 4;
+
+// Tag the versioned envelope starts with. Unversioned messages start with the index
+// of the `IpcMsg` variant instead, which can never have all bits set.
+const ENVELOPE_TAG: [u8; 4] = [0xff; 4];
+
+// Versioned envelope of an encoded `IpcMsg`. Its layout must never change, so that
+// the version can be read even from messages of newer protocol versions.
+#[derive(Serialize, Deserialize)]
+struct Envelope {
+    tag: [u8; 4],
+    version: u32,
+    mock: bool,
+    payload: Vec<u8>,
+}
+
+/// Prefix of the messages encoded with `encode_msg_json`.
+pub const JSON_PREFIX: &str = "json:";
+
+#[derive(Serialize)]
+struct JsonEnvelopeRef<'a> {
+    version: u32,
+    mock: bool,
+    msg: &'a IpcMsg,
+}
+
+#[derive(Deserialize)]
+struct JsonEnvelope {
+    version: u32,
+    mock: bool,
+    msg: Value,
+}
+
 /// IPC message.
 #[cfg_attr(feature = "cargo-clippy", allow(large_enum_variant))]
 #[derive(Serialize, Deserialize, Debug, PartialEq)]
@@ -54,14 +104,109 @@ pub enum IpcMsg {
     },
     /// Generic error like couldn't parse IpcMsg etc.
     Err(IpcError),
+    /// Response sealed to the ephemeral key of the request. See `seal_resp`.
+    SealedResp {
+        /// Request ID.
+        req_id: u32,
+        /// Serialised response, signed by the authenticator and sealed to the key.
+        payload: Vec<u8>,
+    },
 }
 
 /// Encode `IpcMsg` into string, using base32 encoding.
+///
+/// The message is encoded with the lowest protocol version able to represent it,
+/// so that peers released before a change of the message format can still read it.
 pub fn encode_msg(msg: &IpcMsg) -> Result<String, IpcError> {
+    encode_msg_with_version(msg, compat::min_version(msg))
+}
+
+/// Encode `IpcMsg` into string with the given protocol version, using base32 encoding.
+/// Fails if the message can't be represented in that version.
+pub fn encode_msg_with_version(msg: &IpcMsg, version: u32) -> Result<String, IpcError> {
+    let mock = cfg!(feature = "use-mock-routing");
+
+    let payload = match version {
+        0 => None,
+        1 => Some(compat::encode_v1(msg)?),
+        version if version <= IPC_VERSION && compat::min_version(msg) <= version => {
+            Some(serialise(msg)?)
+        }
+        _ => return Err(IpcError::from(format!("Unsupported IPC version {}", version))),
+    };
+    let encoded = match payload {
+        Some(payload) => serialise(&Envelope {
+            tag: ENVELOPE_TAG,
+            version,
+            mock,
+            payload,
+        })?,
+        None => compat::encode_v0(msg, mock)?,
+    };
+
     // We also add a multicodec compatible prefix. For more details please follow
     // https://github.com/multiformats/multicodec/blob/master/table.csv
-    let msg = (msg, cfg!(feature = "use-mock-routing"));
-    Ok(format!("b{}", BASE32_NOPAD.encode(&serialise(&msg)?)))
+    Ok(format!("b{}", BASE32_NOPAD.encode(&encoded)))
+}
+
+/// Encode `IpcMsg` into a self-describing JSON document, for front-ends which
+/// can't link the native library.
+///
+/// The result is `json:` followed by the unpadded URL-safe base64 encoding of
+/// a JSON object of the following form:
+///
+/// ```json
+/// {
+///     "version": 1,
+///     "mock": false,
+///     "msg": { "Req": { "req_id": 1, "req": { "Auth": { ... } } } }
+/// }
+/// ```
+///
+/// `version` is the IPC protocol version (see `IPC_VERSION`) and `mock` tells
+/// whether the sender has been built against mock routing. `msg` is the `IpcMsg`
+/// in the default serde representation: enum variants are objects with the variant
+/// name as the only key (unit variants are plain strings), structs are objects keyed
+/// by the field names, byte arrays and keys are arrays of numbers and `None` is `null`.
+/// Optional fields may be left out. The schema only changes along with `IPC_VERSION`.
+pub fn encode_msg_json(msg: &IpcMsg) -> Result<String, IpcError> {
+    let envelope = JsonEnvelopeRef {
+        version: IPC_VERSION,
+        mock: cfg!(feature = "use-mock-routing"),
+        msg,
+    };
+    let json = serde_json::to_vec(&envelope)?;
+
+    Ok(format!("{}{}", JSON_PREFIX, BASE64URL_NOPAD.encode(&json)))
+}
+
+/// Encoding of an IPC message.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum IpcEncoding {
+    /// Bincode encoding in base32, see `encode_msg`.
+    Base32,
+    /// Self-describing JSON encoding, see `encode_msg_json`.
+    Json,
+}
+
+impl IpcEncoding {
+    /// Returns the encoding of the given encoded message.
+    pub fn of(encoded: &str) -> Self {
+        if encoded.starts_with(JSON_PREFIX) {
+            IpcEncoding::Json
+        } else {
+            IpcEncoding::Base32
+        }
+    }
+}
+
+/// Encode `IpcMsg` into string with the given encoding. Replies should be encoded with
+/// the encoding of the message they reply to.
+pub fn encode_msg_as(msg: &IpcMsg, encoding: IpcEncoding) -> Result<String, IpcError> {
+    match encoding {
+        IpcEncoding::Base32 => encode_msg(msg),
+        IpcEncoding::Json => encode_msg_json(msg),
+    }
 }
 
 /// Encode `IpcMsg` into string, using base64 encoding.
@@ -72,8 +217,22 @@ pub fn encode_msg_64(msg: &IpcMsg) -> Result<String, IpcError> {
     Ok(ffi_utils::base64_encode(&serialise(msg)?))
 }
 
-/// Decode `IpcMsg` encoded with base32 encoding.
+/// Decode `IpcMsg` encoded with base32 encoding, or with `encode_msg_json` if it
+/// starts with `JSON_PREFIX`.
 pub fn decode_msg(encoded: &str) -> Result<IpcMsg, IpcError> {
+    decode_msg_with_version(encoded).map(|(msg, _)| msg)
+}
+
+/// Decode `IpcMsg` encoded with base32 encoding, returning also the protocol version
+/// it's been encoded with. Replies should be encoded with the same version, so that
+/// the peer can read them.
+///
+/// Fails with `IpcError::UpgradeRequired` if the message uses a newer protocol version.
+pub fn decode_msg_with_version(encoded: &str) -> Result<(IpcMsg, u32), IpcError> {
+    if encoded.starts_with(JSON_PREFIX) {
+        return decode_msg_json(&encoded[JSON_PREFIX.len()..]);
+    }
+
     let mut chars = encoded.chars();
     let decoded = match chars.next().ok_or(IpcError::InvalidMsg)? {
         // Encoded as base32
@@ -82,12 +241,42 @@ pub fn decode_msg(encoded: &str) -> Result<IpcMsg, IpcError> {
         _ => return Err(IpcError::EncodeDecodeError),
     };
 
-    let (msg, mock): (IpcMsg, bool) = deserialise(&decoded)?;
+    let (msg, mock, version) = if decoded.starts_with(&ENVELOPE_TAG) {
+        let envelope: Envelope = deserialise(&decoded)?;
+        if envelope.version > IPC_VERSION {
+            return Err(IpcError::UpgradeRequired(envelope.version));
+        }
+
+        let msg = if envelope.version == 1 {
+            compat::decode_v1(&envelope.payload)?
+        } else {
+            deserialise(&envelope.payload)?
+        };
+        (msg, envelope.mock, envelope.version)
+    } else {
+        let (msg, mock) = compat::decode_v0(&decoded)?;
+        (msg, mock, 0)
+    };
+
     if mock != cfg!(feature = "use-mock-routing") {
         return Err(IpcError::IncompatibleMockStatus);
     }
 
-    Ok(msg)
+    Ok((msg, version))
+}
+
+fn decode_msg_json(encoded: &str) -> Result<(IpcMsg, u32), IpcError> {
+    let json = BASE64URL_NOPAD.decode(encoded.as_bytes())?;
+    let envelope: JsonEnvelope = serde_json::from_slice(&json)?;
+
+    if envelope.version > IPC_VERSION {
+        return Err(IpcError::UpgradeRequired(envelope.version));
+    }
+    if envelope.mock != cfg!(feature = "use-mock-routing") {
+        return Err(IpcError::IncompatibleMockStatus);
+    }
+
+    Ok((serde_json::from_value(envelope.msg)?, envelope.version))
 }
 
 /// Decode `IpcMsg` encoded with base64 encoding.
@@ -105,3 +294,221 @@ pub fn gen_req_id() -> u32 {
     // Generate the number in range 1..MAX inclusive.
     rand::thread_rng().gen_range(0, u32::MAX) + 1
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_sodium::crypto::{box_, sign};
+    use std::collections::{BTreeMap, HashMap};
+
+    fn auth_req(manifest: bool) -> IpcMsg {
+        let app = AppExchangeInfo {
+            id: "app".to_string(),
+            scope: None,
+            name: "App".to_string(),
+            vendor: "Vendor".to_string(),
+        };
+        let manifest = if manifest {
+            let (pk, sk) = sign::gen_keypair();
+            Some(unwrap!(AppManifest::new(
+                &app.id,
+                None,
+                None,
+                BTreeMap::new(),
+                pk,
+                &sk
+            )))
+        } else {
+            None
+        };
+
+        IpcMsg::Req {
+            req_id: gen_req_id(),
+            req: IpcReq::Auth(AuthReq {
+                app,
+                app_container: false,
+                containers: HashMap::new(),
+                manifest,
+                resp_key: None,
+            }),
+        }
+    }
+
+    // Test that messages are encoded with the lowest version able to represent them.
+    #[test]
+    fn encodes_with_lowest_version() {
+        let msg = auth_req(false);
+        let (decoded, version) = unwrap!(decode_msg_with_version(&unwrap!(encode_msg(&msg))));
+        assert_eq!(decoded, msg);
+        assert_eq!(version, 0);
+
+        let msg = auth_req(true);
+        let (decoded, version) = unwrap!(decode_msg_with_version(&unwrap!(encode_msg(&msg))));
+        assert_eq!(decoded, msg);
+        assert_eq!(version, 1);
+
+        // Can't be downgraded.
+        assert!(encode_msg_with_version(&msg, 0).is_err());
+
+        let mut msg = auth_req(true);
+        if let IpcMsg::Req {
+            req: IpcReq::Auth(ref mut req),
+            ..
+        } = msg
+        {
+            req.resp_key = Some(box_::gen_keypair().0);
+        }
+        let (decoded, version) = unwrap!(decode_msg_with_version(&unwrap!(encode_msg(&msg))));
+        assert_eq!(decoded, msg);
+        assert_eq!(version, 2);
+
+        assert!(encode_msg_with_version(&msg, 1).is_err());
+
+        let msg = IpcMsg::Req {
+            req_id: gen_req_id(),
+            req: IpcReq::Batch(BatchReq {
+                items: vec![],
+                single_decision: false,
+            }),
+        };
+        let (decoded, version) = unwrap!(decode_msg_with_version(&unwrap!(encode_msg(&msg))));
+        assert_eq!(decoded, msg);
+        assert_eq!(version, 3);
+
+        assert!(encode_msg_with_version(&msg, 2).is_err());
+    }
+
+    // Test that a message can be read at any version able to represent it.
+    #[test]
+    fn decodes_all_supported_versions() {
+        for (msg, min_version) in vec![(auth_req(false), 0), (auth_req(true), 1)] {
+            for version in min_version..=IPC_VERSION {
+                let encoded = unwrap!(encode_msg_with_version(&msg, version));
+                let (decoded, decoded_version) = unwrap!(decode_msg_with_version(&encoded));
+                assert_eq!(decoded, msg);
+                assert_eq!(decoded_version, version);
+            }
+        }
+    }
+
+    // Test encoding a message as JSON and decoding it back, including a message
+    // built by hand without the optional fields.
+    #[test]
+    fn json_encoding() {
+        for msg in vec![auth_req(false), auth_req(true)] {
+            let encoded = unwrap!(encode_msg_json(&msg));
+            assert!(encoded.starts_with(JSON_PREFIX));
+
+            let (decoded, version) = unwrap!(decode_msg_with_version(&encoded));
+            assert_eq!(decoded, msg);
+            assert_eq!(version, IPC_VERSION);
+        }
+
+        let json = format!(
+            r#"{{
+                "version": 1,
+                "mock": {},
+                "msg": {{ "Req": {{ "req_id": 7, "req": {{ "Auth": {{
+                    "app": {{ "id": "app", "scope": null, "name": "App", "vendor": "Vendor" }},
+                    "app_container": true,
+                    "containers": {{ "_public": ["Read", "Insert"] }}
+                }} }} }} }}
+            }}"#,
+            cfg!(feature = "use-mock-routing")
+        );
+        let encoded = format!("{}{}", JSON_PREFIX, BASE64URL_NOPAD.encode(json.as_bytes()));
+
+        match unwrap!(decode_msg(&encoded)) {
+            IpcMsg::Req {
+                req_id: 7,
+                req: IpcReq::Auth(req),
+            } => {
+                assert_eq!(req.app.id, "app");
+                assert!(req.app_container);
+                assert_eq!(
+                    unwrap!(req.containers.get("_public")),
+                    &btree_set![Permission::Read, Permission::Insert]
+                );
+                assert_eq!(req.manifest, None);
+            }
+            x => panic!("Unexpected {:?}", x),
+        }
+    }
+
+    // Test that errors keep their version 0 encoding, by round-tripping them through
+    // a copy of the version 0 `IpcError` taken before the version envelope.
+    #[test]
+    fn v0_error_compatibility() {
+        #[derive(Serialize, Deserialize, Debug, PartialEq)]
+        enum IpcMsgV0 {
+            Req,
+            Resp,
+            Revoked,
+            Err(IpcErrorV0),
+        }
+
+        #[derive(Serialize, Deserialize, Debug, PartialEq)]
+        enum IpcErrorV0 {
+            AuthDenied,
+            ContainersDenied,
+            InvalidMsg,
+            EncodeDecodeError,
+            StringError,
+            AlreadyAuthorised,
+            UnknownApp,
+            ShareMDataDenied,
+            InvalidOwner,
+            IncompatibleMockStatus,
+            Unexpected(String),
+        }
+
+        let mock = cfg!(feature = "use-mock-routing");
+
+        // Decode a message encoded by a version 0 peer.
+        let encoded = unwrap!(serialise(&(
+            IpcMsgV0::Err(IpcErrorV0::Unexpected("v0".to_string())),
+            mock
+        )));
+        let encoded = format!("b{}", BASE32_NOPAD.encode(&encoded));
+        let (decoded, version) = unwrap!(decode_msg_with_version(&encoded));
+        assert_eq!(decoded, IpcMsg::Err(IpcError::Unexpected("v0".to_string())));
+        assert_eq!(version, 0);
+
+        // Encode messages for a version 0 peer.
+        let encoded = unwrap!(encode_msg_with_version(
+            &IpcMsg::Err(IpcError::Unexpected("v1".to_string())),
+            0
+        ));
+        let encoded = unwrap!(BASE32_NOPAD.decode(encoded[1..].as_bytes()));
+        let (decoded, _): (IpcMsgV0, bool) = unwrap!(deserialise(&encoded));
+        assert_eq!(
+            decoded,
+            IpcMsgV0::Err(IpcErrorV0::Unexpected("v1".to_string()))
+        );
+
+        let encoded = unwrap!(encode_msg_with_version(
+            &IpcMsg::Err(IpcError::IncompatibleMockStatus),
+            0
+        ));
+        let encoded = unwrap!(BASE32_NOPAD.decode(encoded[1..].as_bytes()));
+        let (decoded, _): (IpcMsgV0, bool) = unwrap!(deserialise(&encoded));
+        assert_eq!(decoded, IpcMsgV0::Err(IpcErrorV0::IncompatibleMockStatus));
+    }
+
+    // Test that a message of a newer version is rejected with a clear error.
+    #[test]
+    fn newer_version_requires_upgrade() {
+        let envelope = Envelope {
+            tag: ENVELOPE_TAG,
+            version: IPC_VERSION + 1,
+            mock: cfg!(feature = "use-mock-routing"),
+            payload: b"from the future".to_vec(),
+        };
+        let encoded = format!("b{}", BASE32_NOPAD.encode(&unwrap!(serialise(&envelope))));
+
+        match decode_msg(&encoded) {
+            Err(IpcError::UpgradeRequired(version)) => assert_eq!(version, IPC_VERSION + 1),
+            x => panic!("Unexpected {:?}", x),
+        }
+    }
+}
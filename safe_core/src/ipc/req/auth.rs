@@ -6,11 +6,16 @@
 // KIND, either express or implied. Please review the Licences for the specific language governing
 // permissions and limitations relating to use of the SAFE Network Software.
 
-use super::{containers_from_repr_c, containers_into_vec, AppExchangeInfo, ContainerPermissions};
+use super::{
+    containers_from_repr_c, containers_into_vec, AppExchangeInfo, AppManifest,
+    ContainerPermissions,
+};
 use ffi::ipc::req as ffi;
 use ffi_utils::{vec_into_raw_parts, ReprC, StringError};
 use ipc::errors::IpcError;
+use rust_sodium::crypto::box_;
 use std::collections::HashMap;
+use std::ptr;
 
 /// Represents an authorisation request
 #[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
@@ -22,6 +27,12 @@ pub struct AuthReq {
     pub app_container: bool,
     /// The list of containers it wishes to access (and desired permissions).
     pub containers: HashMap<String, ContainerPermissions>,
+    /// Developer-signed app manifest, if the app provides one.
+    #[serde(default)]
+    pub manifest: Option<AppManifest>,
+    /// Ephemeral public key the response is to be sealed to, if the app wants it sealed.
+    #[serde(default)]
+    pub resp_key: Option<box_::PublicKey>,
 }
 
 impl AuthReq {
@@ -34,17 +45,28 @@ impl AuthReq {
             app,
             app_container,
             containers,
+            manifest,
+            resp_key,
         } = self;
 
         let containers = containers_into_vec(containers).map_err(StringError::from)?;
         let (containers_ptr, containers_len, containers_cap) = vec_into_raw_parts(containers);
 
+        let manifest: *const ffi::AppManifest = if let Some(manifest) = manifest {
+            Box::into_raw(Box::new(manifest.into_repr_c()?))
+        } else {
+            ptr::null()
+        };
+
         Ok(ffi::AuthReq {
             app: app.into_repr_c()?,
             app_container,
             containers: containers_ptr,
             containers_len,
             containers_cap,
+            manifest,
+            has_resp_key: resp_key.is_some(),
+            resp_key: resp_key.map(|key| key.0).unwrap_or([0; box_::PUBLICKEYBYTES]),
         })
     }
 }
@@ -62,6 +84,16 @@ impl ReprC for AuthReq {
             app: AppExchangeInfo::clone_from_repr_c(&(*repr_c).app)?,
             app_container: (*repr_c).app_container,
             containers: containers_from_repr_c((*repr_c).containers, (*repr_c).containers_len)?,
+            manifest: if (*repr_c).manifest.is_null() {
+                None
+            } else {
+                Some(AppManifest::clone_from_repr_c((*repr_c).manifest)?)
+            },
+            resp_key: if (*repr_c).has_resp_key {
+                Some(box_::PublicKey((*repr_c).resp_key))
+            } else {
+                None
+            },
         })
     }
 }
@@ -0,0 +1,150 @@
+// Copyright 2018 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use super::{AppExchangeInfo, AuthReq, ContainersReq, ShareMDataReq};
+use ffi::ipc::req as ffi;
+use ffi_utils::{vec_into_raw_parts, ReprC};
+use ipc::errors::IpcError;
+use rust_sodium::crypto::box_;
+use std::{ptr, slice};
+
+/// Request which can be a part of a batch
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum BatchItem {
+    /// Authorisation request
+    Auth(AuthReq),
+    /// Containers request
+    Containers(ContainersReq),
+    /// Share mutable data request
+    ShareMData(ShareMDataReq),
+}
+
+impl BatchItem {
+    /// Returns the info of the app the request comes from.
+    pub fn app(&self) -> &AppExchangeInfo {
+        match *self {
+            BatchItem::Auth(ref req) => &req.app,
+            BatchItem::Containers(ref req) => &req.app,
+            BatchItem::ShareMData(ref req) => &req.app,
+        }
+    }
+
+    /// Consumes the object and returns the FFI counterpart.
+    ///
+    /// You're now responsible for freeing the subobjects memory once you're
+    /// done.
+    pub fn into_repr_c(self) -> Result<ffi::BatchItem, IpcError> {
+        let mut repr_c = ffi::BatchItem {
+            auth: ptr::null(),
+            containers: ptr::null(),
+            share_mdata: ptr::null(),
+        };
+
+        match self {
+            BatchItem::Auth(req) => repr_c.auth = Box::into_raw(Box::new(req.into_repr_c()?)),
+            BatchItem::Containers(req) => {
+                repr_c.containers = Box::into_raw(Box::new(req.into_repr_c()?))
+            }
+            BatchItem::ShareMData(req) => {
+                repr_c.share_mdata = Box::into_raw(Box::new(req.into_repr_c()?))
+            }
+        }
+
+        Ok(repr_c)
+    }
+}
+
+impl ReprC for BatchItem {
+    type C = *const ffi::BatchItem;
+    type Error = IpcError;
+
+    /// Constructs the object from the FFI counterpart. Exactly one of the
+    /// requests must be set.
+    unsafe fn clone_from_repr_c(repr_c: *const ffi::BatchItem) -> Result<Self, IpcError> {
+        let item = &*repr_c;
+
+        match (
+            item.auth.is_null(),
+            item.containers.is_null(),
+            item.share_mdata.is_null(),
+        ) {
+            (false, true, true) => Ok(BatchItem::Auth(AuthReq::clone_from_repr_c(item.auth)?)),
+            (true, false, true) => Ok(BatchItem::Containers(ContainersReq::clone_from_repr_c(
+                item.containers,
+            )?)),
+            (true, true, false) => Ok(BatchItem::ShareMData(ShareMDataReq::clone_from_repr_c(
+                item.share_mdata,
+            )?)),
+            _ => Err(IpcError::InvalidMsg),
+        }
+    }
+}
+
+/// Batch of requests of a single app, presented to the user at once
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct BatchReq {
+    /// Requests, processed in order
+    pub items: Vec<BatchItem>,
+    /// `true` if the user approves or denies the batch as a whole with a single
+    /// decision, `false` if each request is approved separately. Either way the
+    /// requests are granted one by one: a request failing to be granted doesn't
+    /// undo the requests granted before it.
+    pub single_decision: bool,
+}
+
+impl BatchReq {
+    /// Returns the response key of the authorisation request in the batch, if any.
+    /// The response to the whole batch is sealed to it.
+    pub fn resp_key(&self) -> Option<box_::PublicKey> {
+        self.items
+            .iter()
+            .filter_map(|item| match *item {
+                BatchItem::Auth(ref req) => req.resp_key,
+                _ => None,
+            }).next()
+    }
+
+    /// Consumes the object and returns the FFI counterpart.
+    ///
+    /// You're now responsible for freeing the subobjects memory once you're
+    /// done.
+    pub fn into_repr_c(self) -> Result<ffi::BatchReq, IpcError> {
+        let items = self
+            .items
+            .into_iter()
+            .map(BatchItem::into_repr_c)
+            .collect::<Result<Vec<_>, _>>()?;
+        let (items, items_len, items_cap) = vec_into_raw_parts(items);
+
+        Ok(ffi::BatchReq {
+            items,
+            items_len,
+            items_cap,
+            single_decision: self.single_decision,
+        })
+    }
+}
+
+impl ReprC for BatchReq {
+    type C = *const ffi::BatchReq;
+    type Error = IpcError;
+
+    /// Constructs the object from the FFI counterpart.
+    ///
+    /// After calling this function, the subobjects memory is owned by the
+    /// resulting object.
+    unsafe fn clone_from_repr_c(repr_c: *const ffi::BatchReq) -> Result<Self, IpcError> {
+        Ok(BatchReq {
+            items: slice::from_raw_parts((*repr_c).items, (*repr_c).items_len)
+                .iter()
+                .map(|item| BatchItem::clone_from_repr_c(item))
+                .collect::<Result<_, _>>()?,
+            single_decision: (*repr_c).single_decision,
+        })
+    }
+}
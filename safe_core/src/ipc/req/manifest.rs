@@ -0,0 +1,223 @@
+// Copyright 2018 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use ffi::ipc::req::{AppManifest as FfiAppManifest, PermissionReason as FfiPermissionReason};
+use ffi_utils::{from_c_str, vec_into_raw_parts, ReprC, StringError};
+use ipc::errors::IpcError;
+use maidsafe_utilities::serialisation::serialise;
+use rust_sodium::crypto::sign;
+use std::collections::BTreeMap;
+use std::ffi::CString;
+use std::{ptr, slice};
+
+/// Information about an app, signed by its developer. Optionally attached to
+/// an authorisation request, so that the authenticator can explain the request
+/// to the user and recognise an app claiming someone else's ID.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct AppManifest {
+    /// SHA3-256 hash of the app icon.
+    pub icon_hash: Option<[u8; 32]>,
+    /// Homepage of the app.
+    pub homepage: Option<String>,
+    /// Human-readable reason for each requested container, by container name.
+    pub permission_reasons: BTreeMap<String, String>,
+    /// Public signing key of the app developer.
+    pub developer_key: sign::PublicKey,
+    /// Signature of the manifest and the app ID, made with the developer key.
+    pub signature: sign::Signature,
+}
+
+impl AppManifest {
+    /// Creates a manifest for the given app ID, signed with the developer's secret key.
+    pub fn new(
+        app_id: &str,
+        icon_hash: Option<[u8; 32]>,
+        homepage: Option<String>,
+        permission_reasons: BTreeMap<String, String>,
+        developer_key: sign::PublicKey,
+        developer_sk: &sign::SecretKey,
+    ) -> Result<Self, IpcError> {
+        let payload = signed_payload(
+            app_id,
+            &icon_hash,
+            &homepage,
+            &permission_reasons,
+            &developer_key,
+        )?;
+
+        Ok(AppManifest {
+            icon_hash,
+            homepage,
+            permission_reasons,
+            developer_key,
+            signature: sign::sign_detached(&payload, developer_sk),
+        })
+    }
+
+    /// Checks that the manifest has been signed by its developer key for the given app ID.
+    pub fn verify(&self, app_id: &str) -> Result<(), IpcError> {
+        let payload = signed_payload(
+            app_id,
+            &self.icon_hash,
+            &self.homepage,
+            &self.permission_reasons,
+            &self.developer_key,
+        )?;
+
+        if sign::verify_detached(&self.signature, &payload, &self.developer_key) {
+            Ok(())
+        } else {
+            Err(IpcError::InvalidManifest)
+        }
+    }
+
+    /// Consumes the object and returns the FFI counterpart.
+    ///
+    /// You're now responsible for freeing the subobjects memory once you're
+    /// done.
+    pub fn into_repr_c(self) -> Result<FfiAppManifest, IpcError> {
+        let AppManifest {
+            icon_hash,
+            homepage,
+            permission_reasons,
+            developer_key,
+            signature,
+        } = self;
+
+        let permission_reasons = permission_reasons
+            .into_iter()
+            .map(|(cont_name, reason)| {
+                Ok(FfiPermissionReason {
+                    cont_name: CString::new(cont_name).map_err(StringError::from)?.into_raw(),
+                    reason: CString::new(reason).map_err(StringError::from)?.into_raw(),
+                })
+            }).collect::<Result<Vec<_>, IpcError>>()?;
+        let (reasons_ptr, reasons_len, reasons_cap) = vec_into_raw_parts(permission_reasons);
+
+        Ok(FfiAppManifest {
+            has_icon_hash: icon_hash.is_some(),
+            icon_hash: icon_hash.unwrap_or([0; 32]),
+            homepage: if let Some(homepage) = homepage {
+                CString::new(homepage)
+                    .map_err(StringError::from)?
+                    .into_raw()
+            } else {
+                ptr::null()
+            },
+            permission_reasons: reasons_ptr,
+            permission_reasons_len: reasons_len,
+            permission_reasons_cap: reasons_cap,
+            developer_key: developer_key.0,
+            signature: signature.0,
+        })
+    }
+}
+
+impl ReprC for AppManifest {
+    type C = *const FfiAppManifest;
+    type Error = IpcError;
+
+    /// Constructs the object from the FFI counterpart.
+    ///
+    /// After calling this function, the subobjects memory is owned by the
+    /// resulting object.
+    unsafe fn clone_from_repr_c(repr_c: *const FfiAppManifest) -> Result<Self, IpcError> {
+        let permission_reasons = slice::from_raw_parts(
+            (*repr_c).permission_reasons,
+            (*repr_c).permission_reasons_len,
+        ).iter()
+        .map(|raw| Ok((from_c_str(raw.cont_name)?, from_c_str(raw.reason)?)))
+        .collect::<Result<_, StringError>>()?;
+
+        Ok(AppManifest {
+            icon_hash: if (*repr_c).has_icon_hash {
+                Some((*repr_c).icon_hash)
+            } else {
+                None
+            },
+            homepage: if (*repr_c).homepage.is_null() {
+                None
+            } else {
+                Some(from_c_str((*repr_c).homepage).map_err(StringError::from)?)
+            },
+            permission_reasons,
+            developer_key: sign::PublicKey((*repr_c).developer_key),
+            signature: sign::Signature((*repr_c).signature),
+        })
+    }
+}
+
+// Serialises the signed parts of the manifest together with the app ID, so that the
+// signature can't be reused for a different app.
+fn signed_payload(
+    app_id: &str,
+    icon_hash: &Option<[u8; 32]>,
+    homepage: &Option<String>,
+    permission_reasons: &BTreeMap<String, String>,
+    developer_key: &sign::PublicKey,
+) -> Result<Vec<u8>, IpcError> {
+    Ok(serialise(&(
+        app_id,
+        icon_hash,
+        homepage,
+        permission_reasons,
+        developer_key,
+    ))?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn manifest(app_id: &str) -> (AppManifest, sign::SecretKey) {
+        let (pk, sk) = sign::gen_keypair();
+        let reasons = btree_map!["_pictures".to_string() => "To set your avatar".to_string()];
+        let manifest = unwrap!(AppManifest::new(
+            app_id,
+            Some([7; 32]),
+            Some("safe://example".to_string()),
+            reasons,
+            pk,
+            &sk,
+        ));
+        (manifest, sk)
+    }
+
+    // Test that a manifest verifies only for the app ID and contents it's been signed for.
+    #[test]
+    fn verification() {
+        let (manifest, _) = manifest("app");
+        unwrap!(manifest.verify("app"));
+        assert_eq!(manifest.verify("other-app"), Err(IpcError::InvalidManifest));
+
+        let mut tampered = manifest.clone();
+        let _ = tampered
+            .permission_reasons
+            .insert("_documents".to_string(), "Nothing to see here".to_string());
+        assert_eq!(tampered.verify("app"), Err(IpcError::InvalidManifest));
+
+        let mut tampered = manifest;
+        let (other_pk, _) = sign::gen_keypair();
+        tampered.developer_key = other_pk;
+        assert_eq!(tampered.verify("app"), Err(IpcError::InvalidManifest));
+    }
+
+    // Test converting an `AppManifest` to its FFI representation and back again.
+    #[test]
+    fn app_manifest() {
+        let (manifest, _) = manifest("app");
+
+        let ffi = unwrap!(manifest.clone().into_repr_c());
+        assert!(ffi.has_icon_hash);
+        assert_eq!(ffi.permission_reasons_len, 1);
+
+        let manifest2 = unsafe { unwrap!(AppManifest::clone_from_repr_c(&ffi)) };
+        assert_eq!(manifest, manifest2);
+        unwrap!(manifest2.verify("app"));
+    }
+}
@@ -9,12 +9,18 @@
 #![allow(unsafe_code)]
 
 mod auth;
+mod batch;
 mod containers;
+mod manifest;
 mod share_mdata;
+mod share_offer;
 
 pub use self::auth::AuthReq;
+pub use self::batch::{BatchItem, BatchReq};
 pub use self::containers::ContainersReq;
+pub use self::manifest::AppManifest;
 pub use self::share_mdata::{ShareMData, ShareMDataReq};
+pub use self::share_offer::ShareOfferReq;
 
 use ffi::ipc::req::{
     AppExchangeInfo as FfiAppExchangeInfo, ContainerPermissions as FfiContainerPermissions,
@@ -60,6 +66,10 @@ pub enum IpcReq {
     Unregistered(Vec<u8>),
     /// Share mutable data.
     ShareMData(ShareMDataReq),
+    /// Batch of requests of a single app.
+    Batch(BatchReq),
+    /// Acceptance of an offer of access to a mutable data made by another user.
+    ShareOffer(ShareOfferReq),
 }
 
 /// Consumes the object and returns the wrapped raw pointer.
@@ -403,12 +413,15 @@ mod tests {
             app,
             app_container: false,
             containers: HashMap::new(),
+            manifest: None,
+            resp_key: None,
         };
 
         let ffi = unwrap!(a.into_repr_c());
 
         assert_eq!(ffi.app_container, false);
         assert_eq!(ffi.containers_len, 0);
+        assert!(ffi.manifest.is_null());
 
         let a = unsafe { unwrap!(AuthReq::clone_from_repr_c(&ffi)) };
 
@@ -418,6 +431,7 @@ mod tests {
         assert_eq!(a.app.vendor, "4");
         assert_eq!(a.app_container, false);
         assert_eq!(a.containers.len(), 0);
+        assert_eq!(a.manifest, None);
     }
 
     // Test converting a `ContainersReq` object to its FFI representation and back again.
@@ -447,4 +461,41 @@ mod tests {
         assert_eq!(a.app.vendor, "4");
         assert_eq!(a.containers.len(), 0);
     }
+
+    // Test converting a `BatchReq` object to its FFI representation and back again.
+    #[test]
+    fn batch_req() {
+        let app = AppExchangeInfo {
+            id: "1".to_string(),
+            scope: None,
+            name: "3".to_string(),
+            vendor: "4".to_string(),
+        };
+
+        let a = BatchReq {
+            items: vec![
+                BatchItem::Auth(AuthReq {
+                    app: app.clone(),
+                    app_container: true,
+                    containers: HashMap::new(),
+                    manifest: None,
+                    resp_key: None,
+                }),
+                BatchItem::Containers(ContainersReq {
+                    app,
+                    containers: HashMap::new(),
+                }),
+            ],
+            single_decision: true,
+        };
+
+        let ffi = unwrap!(a.clone().into_repr_c());
+
+        assert_eq!(ffi.items_len, 2);
+        assert_eq!(ffi.single_decision, true);
+
+        let b = unsafe { unwrap!(BatchReq::clone_from_repr_c(&ffi)) };
+
+        assert_eq!(a, b);
+    }
 }
@@ -0,0 +1,53 @@
+// Copyright 2018 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use super::AppExchangeInfo;
+use ffi::ipc::req as ffi;
+use ffi_utils::{vec_into_raw_parts, ReprC};
+use ipc::errors::IpcError;
+use std::slice;
+
+/// Represents a request to accept an offer of access to a mutable data made by
+/// another user
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ShareOfferReq {
+    /// Info about the app the offer is to be accepted for
+    pub app: AppExchangeInfo,
+    /// Share offer, sealed to the public encryption key of the app
+    pub offer: Vec<u8>,
+}
+
+impl ShareOfferReq {
+    /// Consumes the object and returns the FFI counterpart.
+    ///
+    /// You're now responsible for freeing the subobjects memory once you're
+    /// done.
+    pub fn into_repr_c(self) -> Result<ffi::ShareOfferReq, IpcError> {
+        let (offer, offer_len, offer_cap) = vec_into_raw_parts(self.offer);
+
+        Ok(ffi::ShareOfferReq {
+            app: self.app.into_repr_c()?,
+            offer,
+            offer_len,
+            offer_cap,
+        })
+    }
+}
+
+impl ReprC for ShareOfferReq {
+    type C = *const ffi::ShareOfferReq;
+    type Error = IpcError;
+
+    /// Constructs the object from the FFI counterpart.
+    unsafe fn clone_from_repr_c(repr_c: *const ffi::ShareOfferReq) -> Result<Self, IpcError> {
+        Ok(ShareOfferReq {
+            app: AppExchangeInfo::clone_from_repr_c(&(*repr_c).app)?,
+            offer: slice::from_raw_parts((*repr_c).offer, (*repr_c).offer_len).to_vec(),
+        })
+    }
+}
@@ -50,6 +50,11 @@ pub enum IpcResp {
     Unregistered(Result<BootstrapConfig, IpcError>),
     /// Share mutable data.
     ShareMData(Result<(), IpcError>),
+    /// Batch of requests. Contains the response to each request, in order, or
+    /// an error if the batch has been denied or rejected as a whole.
+    Batch(Result<Vec<IpcResp>, IpcError>),
+    /// Accepted share offer.
+    ShareOffer(Result<ShareOfferGranted, IpcError>),
 }
 
 /// It represents the authentication response.
@@ -120,6 +125,50 @@ impl ReprC for AuthGranted {
     }
 }
 
+/// Response to an accepted offer of access to a mutable data made by another user.
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+pub struct ShareOfferGranted {
+    /// The shared data, along with the keys to decrypt its entries.
+    pub mdata: MDataInfo,
+    /// Permissions the app is going to be granted.
+    pub perms: PermissionSet,
+    /// Acceptance of the offer, sealed to the app which made it. The app has to
+    /// receive it to grant the permissions.
+    pub acceptance: Vec<u8>,
+}
+
+impl ShareOfferGranted {
+    /// Consumes the object and returns the FFI counterpart.
+    ///
+    /// You're now responsible for freeing the subobjects memory once you're
+    /// done.
+    pub fn into_repr_c(self) -> ffi::ShareOfferGranted {
+        let (acceptance, acceptance_len, acceptance_cap) = vec_into_raw_parts(self.acceptance);
+
+        ffi::ShareOfferGranted {
+            mdata: self.mdata.into_repr_c(),
+            perms: permission_set_into_repr_c(self.perms),
+            acceptance,
+            acceptance_len,
+            acceptance_cap,
+        }
+    }
+}
+
+impl ReprC for ShareOfferGranted {
+    type C = *const ffi::ShareOfferGranted;
+    type Error = IpcError;
+
+    unsafe fn clone_from_repr_c(repr_c: Self::C) -> Result<Self, Self::Error> {
+        Ok(ShareOfferGranted {
+            mdata: MDataInfo::clone_from_repr_c(&(*repr_c).mdata)?,
+            perms: permission_set_clone_from_repr_c((*repr_c).perms)?,
+            acceptance: slice::from_raw_parts((*repr_c).acceptance, (*repr_c).acceptance_len)
+                .to_vec(),
+        })
+    }
+}
+
 /// Represents the needed keys to work with the data.
 #[derive(Clone, Serialize, Deserialize, Debug, Eq, PartialEq)]
 pub struct AppKeys {
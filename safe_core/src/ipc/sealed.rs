@@ -0,0 +1,119 @@
+// Copyright 2018 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+//! Responses sealed to an ephemeral key of the request, so that only the requesting
+//! app can read them, and signed with the account key of the authenticator.
+//!
+//! The key of the request travels in the clear, so anyone who sees the request can seal
+//! a response to it. The signature tells the app who sealed the response: it has to check
+//! the signer is the authenticator it's been registered with, i.e. the owner key it's
+//! been given in the keys of its first authorisation.
+
+use super::{IpcError, IpcMsg, IpcResp};
+use maidsafe_utilities::serialisation::{deserialise, serialise};
+use rust_sodium::crypto::{box_, sealedbox, sign};
+
+/// Seals the response to the ephemeral public key of the request and signs it with the
+/// account key of the authenticator, binding the signature to the request ID and the
+/// ephemeral key.
+pub fn seal_resp(
+    req_id: u32,
+    resp: &IpcResp,
+    resp_key: &box_::PublicKey,
+    sign_pk: sign::PublicKey,
+    sign_sk: &sign::SecretKey,
+) -> Result<IpcMsg, IpcError> {
+    let resp = serialise(resp)?;
+    let signature = sign::sign_detached(&signed_payload(req_id, resp_key, &resp)?, sign_sk);
+    let payload = sealedbox::seal(&serialise(&(resp, sign_pk, signature))?, resp_key);
+
+    Ok(IpcMsg::SealedResp { req_id, payload })
+}
+
+/// Opens a response sealed with `seal_resp` for the given request and verifies its
+/// signature. Returns the response along with the public key it's been signed with,
+/// which the caller has to check against the key of its authenticator.
+pub fn open_resp(
+    req_id: u32,
+    payload: &[u8],
+    resp_pk: &box_::PublicKey,
+    resp_sk: &box_::SecretKey,
+) -> Result<(IpcResp, sign::PublicKey), IpcError> {
+    let plaintext =
+        sealedbox::open(payload, resp_pk, resp_sk).map_err(|()| IpcError::InvalidSealedResp)?;
+    let (resp, sign_pk, signature): (Vec<u8>, sign::PublicKey, sign::Signature) =
+        deserialise(&plaintext)?;
+
+    let signed = signed_payload(req_id, resp_pk, &resp)?;
+    if !sign::verify_detached(&signature, &signed, &sign_pk) {
+        return Err(IpcError::InvalidSealedResp);
+    }
+
+    Ok((deserialise(&resp)?, sign_pk))
+}
+
+fn signed_payload(
+    req_id: u32,
+    resp_key: &box_::PublicKey,
+    resp: &[u8],
+) -> Result<Vec<u8>, IpcError> {
+    Ok(serialise(&(req_id, resp_key, resp))?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Test that a sealed response can only be opened with the ephemeral key of the
+    // request it's been sealed for and only for the same request ID, and that it
+    // reports the key it's been signed with.
+    #[test]
+    fn seal_and_open() {
+        let (resp_pk, resp_sk) = box_::gen_keypair();
+        let (sign_pk, sign_sk) = sign::gen_keypair();
+        let resp = IpcResp::Containers(Ok(()));
+
+        let payload = match unwrap!(seal_resp(1, &resp, &resp_pk, sign_pk, &sign_sk)) {
+            IpcMsg::SealedResp { req_id: 1, payload } => payload,
+            x => panic!("Unexpected {:?}", x),
+        };
+
+        let (opened, signer) = unwrap!(open_resp(1, &payload, &resp_pk, &resp_sk));
+        assert_eq!(opened, resp);
+        assert_eq!(signer, sign_pk);
+
+        assert_eq!(
+            open_resp(2, &payload, &resp_pk, &resp_sk),
+            Err(IpcError::InvalidSealedResp)
+        );
+
+        let (other_pk, other_sk) = box_::gen_keypair();
+        assert_eq!(
+            open_resp(1, &payload, &other_pk, &other_sk),
+            Err(IpcError::InvalidSealedResp)
+        );
+    }
+
+    // Test that a response whose signature doesn't match the signer it carries is rejected.
+    #[test]
+    fn open_forged() {
+        let (resp_pk, resp_sk) = box_::gen_keypair();
+        let (sign_pk, _) = sign::gen_keypair();
+        let (_, forger_sk) = sign::gen_keypair();
+        let resp = unwrap!(serialise(&IpcResp::Containers(Ok(()))));
+
+        let signature =
+            sign::sign_detached(&unwrap!(signed_payload(1, &resp_pk, &resp)), &forger_sk);
+        let payload = sealedbox::seal(&unwrap!(serialise(&(resp, sign_pk, signature))), &resp_pk);
+
+        assert_eq!(
+            open_resp(1, &payload, &resp_pk, &resp_sk),
+            Err(IpcError::InvalidSealedResp)
+        );
+    }
+}
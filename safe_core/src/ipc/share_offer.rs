@@ -0,0 +1,214 @@
+// Copyright 2018 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+//! Offers of access to a `MutableData` made to another user.
+//!
+//! An app of the owner creates an offer, signs its terms and seals it to the public
+//! encryption key of one of the recipient's apps, as published under a public ID.
+//! The recipient's authenticator opens it with the keys of that app and, once the
+//! user accepts it, hands the `MDataInfo` to the app and seals an acceptance back to
+//! the offering app. The offering app then grants
+//! the offered permissions to the keys listed in the acceptance.
+
+use super::IpcError;
+use client::MDataInfo;
+use maidsafe_utilities::serialisation::{deserialise, serialise};
+use routing::{PermissionSet, XorName};
+use rust_sodium::crypto::{box_, sealedbox, sign};
+
+/// Offer of access to a `MutableData`, made by an app of its owner to another user
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ShareOffer {
+    /// The shared data, along with the keys to decrypt its entries
+    pub mdata: MDataInfo,
+    /// Permissions being offered
+    pub perms: PermissionSet,
+    /// Signing key of the app making the offer
+    pub sign_pk: sign::PublicKey,
+    /// Key the acceptance of the offer is sealed to
+    pub reply_key: box_::PublicKey,
+}
+
+/// Opened share offer, along with the signature of its terms
+#[derive(Clone, Debug, PartialEq)]
+pub struct SignedShareOffer {
+    /// The offer
+    pub offer: ShareOffer,
+    /// Signature of the terms of the offer by the offering app
+    pub signature: sign::Signature,
+}
+
+/// Acceptance of a share offer, as opened by the app which made the offer
+#[derive(Clone, Debug, PartialEq)]
+pub struct ShareAcceptance {
+    /// Name of the shared data
+    pub name: XorName,
+    /// Type tag of the shared data
+    pub type_tag: u64,
+    /// Permissions to grant, as offered
+    pub perms: PermissionSet,
+    /// Keys of the recipient's apps the permissions are to be granted to
+    pub keys: Vec<sign::PublicKey>,
+}
+
+/// Signs the terms of the offer with the key of the offering app and seals the
+/// offer to the public encryption key of the recipient app.
+pub fn seal_share_offer(
+    offer: &ShareOffer,
+    sign_sk: &sign::SecretKey,
+    recipient: &box_::PublicKey,
+) -> Result<Vec<u8>, IpcError> {
+    let terms = signed_terms(
+        &offer.mdata.name,
+        offer.mdata.type_tag,
+        &offer.perms,
+        &offer.reply_key,
+    )?;
+    let signature = sign::sign_detached(&terms, sign_sk);
+
+    Ok(sealedbox::seal(&serialise(&(offer, signature))?, recipient))
+}
+
+/// Opens an offer sealed with `seal_share_offer` and verifies its signature.
+pub fn open_share_offer(
+    sealed: &[u8],
+    pk: &box_::PublicKey,
+    sk: &box_::SecretKey,
+) -> Result<SignedShareOffer, IpcError> {
+    let plaintext = sealedbox::open(sealed, pk, sk).map_err(|()| IpcError::InvalidShareOffer)?;
+    let (offer, signature): (ShareOffer, sign::Signature) = deserialise(&plaintext)?;
+
+    let terms = signed_terms(
+        &offer.mdata.name,
+        offer.mdata.type_tag,
+        &offer.perms,
+        &offer.reply_key,
+    )?;
+    if !sign::verify_detached(&signature, &terms, &offer.sign_pk) {
+        return Err(IpcError::InvalidShareOffer);
+    }
+
+    Ok(SignedShareOffer { offer, signature })
+}
+
+/// Seals the acceptance of the offer for the given app keys to the reply key of
+/// the offer. The signature of the offer is returned to the offering app, so that
+/// it can check the terms haven't been changed.
+pub fn seal_share_acceptance(
+    offer: &SignedShareOffer,
+    keys: Vec<sign::PublicKey>,
+) -> Result<Vec<u8>, IpcError> {
+    let acceptance = (
+        &offer.offer.mdata.name,
+        offer.offer.mdata.type_tag,
+        &offer.offer.perms,
+        keys,
+        &offer.signature,
+    );
+
+    Ok(sealedbox::seal(
+        &serialise(&acceptance)?,
+        &offer.offer.reply_key,
+    ))
+}
+
+/// Opens an acceptance sealed with `seal_share_acceptance` and verifies it's been
+/// made for an offer signed with `sign_pk` and sealed to `reply_pk`.
+pub fn open_share_acceptance(
+    sealed: &[u8],
+    sign_pk: &sign::PublicKey,
+    reply_pk: &box_::PublicKey,
+    reply_sk: &box_::SecretKey,
+) -> Result<ShareAcceptance, IpcError> {
+    let plaintext =
+        sealedbox::open(sealed, reply_pk, reply_sk).map_err(|()| IpcError::InvalidShareOffer)?;
+    let (name, type_tag, perms, keys, signature): (
+        XorName,
+        u64,
+        PermissionSet,
+        Vec<sign::PublicKey>,
+        sign::Signature,
+    ) = deserialise(&plaintext)?;
+
+    let terms = signed_terms(&name, type_tag, &perms, reply_pk)?;
+    if !sign::verify_detached(&signature, &terms, sign_pk) {
+        return Err(IpcError::InvalidShareOffer);
+    }
+
+    Ok(ShareAcceptance {
+        name,
+        type_tag,
+        perms,
+        keys,
+    })
+}
+
+fn signed_terms(
+    name: &XorName,
+    type_tag: u64,
+    perms: &PermissionSet,
+    reply_key: &box_::PublicKey,
+) -> Result<Vec<u8>, IpcError> {
+    Ok(serialise(&(name, type_tag, perms, reply_key))?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use routing::Action;
+
+    // Test that an offer can only be opened by its recipient and that the acceptance
+    // is only valid for the app which made the offer, with unchanged terms.
+    #[test]
+    fn offer_and_accept() {
+        let (sign_pk, sign_sk) = sign::gen_keypair();
+        let (reply_pk, reply_sk) = box_::gen_keypair();
+        let (recipient_pk, recipient_sk) = box_::gen_keypair();
+
+        let offer = ShareOffer {
+            mdata: unwrap!(MDataInfo::random_private(15_000)),
+            perms: PermissionSet::new().allow(Action::Insert),
+            sign_pk,
+            reply_key: reply_pk,
+        };
+
+        let sealed = unwrap!(seal_share_offer(&offer, &sign_sk, &recipient_pk));
+
+        let (other_pk, other_sk) = box_::gen_keypair();
+        assert_eq!(
+            open_share_offer(&sealed, &other_pk, &other_sk),
+            Err(IpcError::InvalidShareOffer)
+        );
+
+        let opened = unwrap!(open_share_offer(&sealed, &recipient_pk, &recipient_sk));
+        assert_eq!(opened.offer, offer);
+
+        let (app_pk, _) = sign::gen_keypair();
+        let acceptance = unwrap!(seal_share_acceptance(&opened, vec![app_pk]));
+        let accepted = unwrap!(open_share_acceptance(
+            &acceptance,
+            &sign_pk,
+            &reply_pk,
+            &reply_sk
+        ));
+        assert_eq!(accepted.name, offer.mdata.name);
+        assert_eq!(accepted.type_tag, offer.mdata.type_tag);
+        assert_eq!(accepted.perms, offer.perms);
+        assert_eq!(accepted.keys, vec![app_pk]);
+
+        let mut tampered = opened.clone();
+        tampered.offer.perms = PermissionSet::new()
+            .allow(Action::Insert)
+            .allow(Action::ManagePermissions);
+        let acceptance = unwrap!(seal_share_acceptance(&tampered, vec![app_pk]));
+        assert_eq!(
+            open_share_acceptance(&acceptance, &sign_pk, &reply_pk, &reply_sk),
+            Err(IpcError::InvalidShareOffer)
+        );
+    }
+}
@@ -98,7 +98,6 @@ extern crate serde;
 extern crate serde_derive;
 extern crate rust_sodium;
 extern crate self_encryption;
-#[cfg(test)]
 extern crate serde_json;
 extern crate tiny_keccak;
 extern crate tokio_core;
@@ -131,6 +130,12 @@ pub mod immutable_data;
 pub mod ipc;
 /// NFS utilities.
 pub mod nfs;
+/// Registry of public names.
+pub mod public_id;
+/// Typed key-value store over `MutableData`.
+pub mod secure_map;
+/// Collection of entries spread over several `MutableData`.
+pub mod sharded_map;
 /// Implements the Self Encryption storage trait.
 pub mod self_encryption_storage;
 
@@ -142,14 +147,21 @@ pub use self::client::{mdata_info, recovery, Client, ClientKeys, MDataInfo};
 pub use self::client::{mock_vault_path, MockRouting};
 pub use self::errors::CoreError;
 pub use self::event::{CoreEvent, NetworkEvent, NetworkRx, NetworkTx};
-pub use self::event_loop::{CoreFuture, CoreMsg, CoreMsgRx, CoreMsgTx};
-pub use self::self_encryption_storage::{SelfEncryptionStorage, SelfEncryptionStorageError};
+pub use self::event_loop::{
+    Cancellable, CancellationToken, CoreFuture, CoreMsg, CoreMsgRx, CoreMsgTx,
+};
+pub use self::self_encryption_storage::{
+    DownloadProgress, DownloadTracker, SelfEncryptionStorage, SelfEncryptionStorageError,
+    UploadPipeline, UploadProgress,
+};
 pub use self::utils::FutureExt;
 
 /// All Maidsafe tagging should positive-offset from this.
 pub const MAIDSAFE_TAG: u64 = 5_483_000;
 /// `MutableData` type tag for a directory.
 pub const DIR_TAG: u64 = 15_000;
+/// `MutableData` type tag for a public ID.
+pub const PUBLIC_ID_TAG: u64 = 15_001;
 
 /// Gets name of the dedicated container of the given app.
 pub fn app_container_name(app_id: &str) -> String {
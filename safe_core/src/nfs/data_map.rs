@@ -16,6 +16,7 @@ use maidsafe_utilities::serialisation::{deserialise, serialise};
 use nfs::NfsFuture;
 use routing::XorName;
 use self_encryption::DataMap;
+use self_encryption_storage::UploadPipeline;
 use utils::FutureExt;
 
 // Get `DataMap` from the network.
@@ -31,22 +32,27 @@ pub fn get(
         .into_box()
 }
 
-// Put `DataMap` on the network.
+// Put `DataMap` on the network, uploading the chunks through `pipeline` without counting
+// them towards its progress.
 // If `encryption_key` is passed in, the `DataMap` will be encrypted.
 pub fn put(
     client: &impl Client,
     data_map: &DataMap,
     encryption_key: Option<shared_secretbox::Key>,
+    pipeline: &UploadPipeline,
 ) -> Box<NfsFuture<XorName>> {
     let client = client.clone();
     let client2 = client.clone();
+    let pipeline = pipeline.untracked();
+    let pipeline2 = pipeline.clone();
 
     future::result(serialise(&data_map))
         .map_err(From::from)
-        .and_then(move |encoded| immutable_data::create(&client, &encoded, encryption_key))
-        .and_then(move |data| {
+        .and_then(move |encoded| {
+            immutable_data::create_with_pipeline(&client, &encoded, encryption_key, &pipeline)
+        }).and_then(move |data| {
             let name = *data.name();
-            client2.put_idata(data).map(move |_| name)
+            pipeline2.put_idata(&client2, data).map(move |_| name)
         }).map_err(From::from)
         .into_box()
 }
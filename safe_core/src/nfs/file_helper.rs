@@ -6,14 +6,23 @@
 // KIND, either express or implied. Please review the Licences for the specific language governing
 // permissions and limitations relating to use of the SAFE Network Software.
 
+//! Once a directory hits the limits of a single `MutableData`, further files are
+//! inserted into its overflow: a `ShardedMap` named after the directory and sharing
+//! its encryption info and permissions, which is created on demand. Files missing
+//! from the directory itself are looked up in its overflow, if there is one.
+
 use client::{Client, MDataInfo};
 use crypto::shared_secretbox;
 use errors::CoreError;
+use event_loop::CoreFuture;
+use futures::future::{self, Either};
 use futures::{Future, IntoFuture};
 use maidsafe_utilities::serialisation::{deserialise, serialise};
 use nfs::{File, Mode, NfsError, NfsFuture, Reader, Writer};
-use routing::{ClientError, EntryActions};
+use routing::{ClientError, EntryActions, EntryError, Value, XorName};
 use self_encryption_storage::SelfEncryptionStorage;
+use sharded_map::ShardedMap;
+use tiny_keccak::sha3_256;
 use utils::FutureExt;
 
 /// Enum specifying which version should be used in places where a version is required.
@@ -25,7 +34,7 @@ pub enum Version {
     Custom(u64),
 }
 
-/// Insert the file into the directory.
+/// Insert the file into the directory, or into its overflow if the directory is full.
 pub fn insert<S>(client: impl Client, parent: MDataInfo, name: S, file: &File) -> Box<NfsFuture<()>>
 where
     S: AsRef<str>,
@@ -33,20 +42,29 @@ where
     let name = name.as_ref();
     trace!("Inserting file with name '{}'", name);
 
+    let name = name.as_bytes().to_vec();
+
     serialise(&file)
         .map_err(From::from)
         .and_then(|encoded| {
-            let key = parent.enc_entry_key(name.as_bytes())?;
+            let key = parent.enc_entry_key(&name)?;
             let value = parent.enc_entry_value(&encoded)?;
 
-            Ok((key, value))
+            Ok((key, value, encoded))
         }).into_future()
-        .and_then(move |(key, value)| {
-            client.mutate_mdata_entries(
-                parent.name,
-                parent.type_tag,
-                EntryActions::new().ins(key, value, 0).into(),
-            )
+        .and_then(move |(key, value, encoded)| {
+            client
+                .mutate_mdata_entries(
+                    parent.name,
+                    parent.type_tag,
+                    EntryActions::new().ins(key.clone(), value, 0).into(),
+                ).or_else(move |error| match error {
+                    CoreError::RoutingClientError(ClientError::TooManyEntries)
+                    | CoreError::RoutingClientError(ClientError::DataTooLarge) => {
+                        Either::A(insert_overflow(client, parent, key, name, encoded, error))
+                    }
+                    error => Either::B(future::err(error)),
+                })
         }).map_err(From::from)
         .into_box()
 }
@@ -56,17 +74,75 @@ pub fn fetch<S>(client: impl Client, parent: MDataInfo, name: S) -> Box<NfsFutur
 where
     S: AsRef<str>,
 {
-    parent
-        .enc_entry_key(name.as_ref().as_bytes())
+    get_value(&client, &parent, name.as_ref().as_bytes())
+        .and_then(|value| {
+            let file = deserialise(&value.content)?;
+            Ok((value.entry_version, file))
+        }).map_err(convert_error)
+        .into_box()
+}
+
+/// Get many files from the directory at once, in the same order as their names, with `None` for
+/// the ones which don't exist or have been deleted. See `Client::get_many` for how they're read.
+pub fn fetch_many<S>(
+    client: impl Client,
+    parent: MDataInfo,
+    names: &[S],
+) -> Box<NfsFuture<Vec<Option<(u64, File)>>>>
+where
+    S: AsRef<str>,
+{
+    let names: Vec<_> = names
+        .iter()
+        .map(|name| name.as_ref().as_bytes().to_vec())
+        .collect();
+    let overflow = overflow(&client, &parent);
+
+    names
+        .iter()
+        .map(|name| parent.enc_entry_key(name))
+        .collect::<Result<Vec<_>, _>>()
         .into_future()
-        .and_then(move |key| {
+        .and_then(move |keys| {
             client
-                .get_mdata_value(parent.name, parent.type_tag, key)
-                .map(move |value| (value, parent))
-        }).and_then(move |(value, parent)| {
-            let plaintext = parent.decrypt(&value.content)?;
-            let file = deserialise(&plaintext)?;
-            Ok((value.entry_version, file))
+                .get_mdata_values(parent.name, parent.type_tag, keys)
+                .map(move |values| (values, parent))
+        }).and_then(move |(values, parent)| {
+            let values = values.into_iter().zip(names).map(move |(value, name)| {
+                match value {
+                    // Deleted files are left with empty content.
+                    Some(ref value) if value.content.is_empty() => future::ok(None).into_box(),
+                    Some(value) => parent
+                        .decrypt(&value.content)
+                        .map(|content| {
+                            Some(Value {
+                                content,
+                                entry_version: value.entry_version,
+                            })
+                        }).into_future()
+                        .into_box(),
+                    // Files missing from the directory may be in its overflow.
+                    None => overflow
+                        .get(&name)
+                        .map(Some)
+                        .or_else(|error| match overflow_error(error) {
+                            CoreError::RoutingClientError(ClientError::NoSuchEntry) => Ok(None),
+                            error => Err(error),
+                        }).into_box(),
+                }
+            });
+
+            future::join_all(values)
+        }).and_then(|values| {
+            values
+                .into_iter()
+                .map(|value| match value {
+                    Some(value) => {
+                        let file = deserialise(&value.content)?;
+                        Ok(Some((value.entry_version, file)))
+                    }
+                    None => Ok(None),
+                }).collect::<Result<Vec<_>, CoreError>>()
         }).map_err(convert_error)
         .into_box()
 }
@@ -105,25 +181,30 @@ where
     let name = name.as_ref();
     trace!("Deleting file with name {}.", name);
 
-    let key = fry!(parent.enc_entry_key(name.as_bytes()));
-
-    let version_fut = match version {
-        Version::GetNext => client
-            .get_mdata_value(parent.name, parent.type_tag, key.clone())
-            .map(move |value| (value.entry_version + 1))
-            .into_box(),
-        Version::Custom(version) => ok!(version),
-    }.map_err(NfsError::from);
+    let name = name.as_bytes().to_vec();
+    let key = fry!(parent.enc_entry_key(&name));
+    let overflow = overflow(&client, &parent);
 
-    version_fut.and_then(move |version| {
-        client
-            .mutate_mdata_entries(
-                parent.name,
-                parent.type_tag,
-                EntryActions::new().del(key, version).into(),
-            ).map(move |()| version)
-            .map_err(convert_error)
-    }).into_box()
+    next_version(&client, &parent, &name, version)
+        .and_then(move |version| {
+            client
+                .mutate_mdata_entries(
+                    parent.name,
+                    parent.type_tag,
+                    EntryActions::new().del(key, version).into(),
+                ).or_else(move |error| {
+                    if is_missing_entry(&error) {
+                        Either::A(
+                            overflow
+                                .delete(&name, version)
+                                .map_err(|err| missing_overflow_error(err, error)),
+                        )
+                    } else {
+                        Either::B(future::err(error))
+                    }
+                }).map(move |()| version)
+        }).map_err(convert_error)
+        .into_box()
 }
 
 /// Update the file.
@@ -143,29 +224,30 @@ where
     let name = name.as_ref();
     trace!("Updating file with name '{}'", name);
 
-    let client2 = client.clone();
-
-    serialise(&file)
-        .map_err(From::from)
-        .and_then(|encoded| {
-            let key = parent.enc_entry_key(name.as_bytes())?;
-            let content = parent.enc_entry_value(&encoded)?;
+    let name = name.as_bytes().to_vec();
+    let encoded = fry!(serialise(&file));
+    let key = fry!(parent.enc_entry_key(&name));
+    let content = fry!(parent.enc_entry_value(&encoded));
+    let overflow = overflow(&client, &parent);
 
-            Ok((key, content))
-        }).into_future()
-        .and_then(move |(key, content)| match version {
-            Version::GetNext => client
-                .get_mdata_value(parent.name, parent.type_tag, key.clone())
-                .map(move |value| (key, content, value.entry_version + 1, parent))
-                .into_box(),
-            Version::Custom(version) => ok!((key, content, version, parent)),
-        }).and_then(move |(key, content, version, parent)| {
-            client2
+    next_version(&client, &parent, &name, version)
+        .and_then(move |version| {
+            client
                 .mutate_mdata_entries(
                     parent.name,
                     parent.type_tag,
                     EntryActions::new().update(key, content, version).into(),
-                ).map(move |()| version)
+                ).or_else(move |error| {
+                    if is_missing_entry(&error) {
+                        Either::A(
+                            overflow
+                                .update(&name, &encoded, version)
+                                .map_err(|err| missing_overflow_error(err, error)),
+                        )
+                    } else {
+                        Either::B(future::err(error))
+                    }
+                }).map(move |()| version)
         }).map_err(convert_error)
         .into_box()
 }
@@ -200,3 +282,127 @@ fn convert_error(err: CoreError) -> NfsError {
         _ => NfsError::from(err),
     }
 }
+
+// Returns the overflow of the directory, which holds the files that don't fit into it.
+fn overflow<C: Client>(client: &C, parent: &MDataInfo) -> ShardedMap<C> {
+    let mut name = parent.name.0.to_vec();
+    name.extend_from_slice(b"overflow");
+
+    ShardedMap::new(
+        client,
+        MDataInfo {
+            name: XorName(sha3_256(&name)),
+            ..parent.clone()
+        },
+    )
+}
+
+// Inserts the file into the overflow of the directory, creating the overflow if it doesn't exist
+// yet. Fails with `error` if the directory itself already has an entry for the file.
+fn insert_overflow<C: Client>(
+    client: C,
+    parent: MDataInfo,
+    key: Vec<u8>,
+    name: Vec<u8>,
+    encoded: Vec<u8>,
+    error: CoreError,
+) -> Box<CoreFuture<()>> {
+    let overflow = overflow(&client, &parent);
+    let client2 = client.clone();
+
+    client
+        .get_mdata_value(parent.name, parent.type_tag, key)
+        .then(move |res| match res {
+            Ok(_) => Err(error),
+            Err(CoreError::RoutingClientError(ClientError::NoSuchEntry)) => Ok(()),
+            Err(error) => Err(error),
+        }).and_then(move |()| {
+            overflow
+                .insert(&name, &encoded)
+                .or_else(move |error| match error {
+                    CoreError::RoutingClientError(ClientError::NoSuchData) => {
+                        let root = overflow.root().clone();
+                        let client3 = client2.clone();
+
+                        Either::A(
+                            client2
+                                .list_mdata_permissions(parent.name, parent.type_tag)
+                                .and_then(move |permissions| {
+                                    ShardedMap::create(&client3, root, permissions)
+                                }).and_then(move |overflow| overflow.insert(&name, &encoded)),
+                        )
+                    }
+                    error => Either::B(future::err(error)),
+                })
+        }).into_box()
+}
+
+// Gets the decrypted value of the entry for the file, from the directory or its overflow.
+fn get_value<C: Client>(client: &C, parent: &MDataInfo, name: &[u8]) -> Box<CoreFuture<Value>> {
+    let key = fry!(parent.enc_entry_key(name));
+    let overflow = overflow(client, parent);
+    let parent = parent.clone();
+    let name = name.to_vec();
+
+    client
+        .get_mdata_value(parent.name, parent.type_tag, key)
+        .then(move |res| match res {
+            Ok(value) => {
+                let content = fry!(parent.decrypt(&value.content));
+                ok!(Value {
+                    content,
+                    entry_version: value.entry_version,
+                })
+            }
+            Err(CoreError::RoutingClientError(ClientError::NoSuchEntry)) => {
+                overflow.get(&name).map_err(overflow_error).into_box()
+            }
+            Err(error) => err!(error),
+        }).into_box()
+}
+
+// Resolves `version` to the actual version of the next mutation of the entry.
+fn next_version<C: Client>(
+    client: &C,
+    parent: &MDataInfo,
+    name: &[u8],
+    version: Version,
+) -> Box<CoreFuture<u64>> {
+    match version {
+        Version::GetNext => get_value(client, parent, name)
+            .map(|value| value.entry_version + 1)
+            .into_box(),
+        Version::Custom(version) => ok!(version),
+    }
+}
+
+// Returns whether the mutation failed only because the entry doesn't exist.
+fn is_missing_entry(error: &CoreError) -> bool {
+    match *error {
+        CoreError::RoutingClientError(ClientError::InvalidEntryActions(ref errors)) => {
+            errors.values().all(|error| match *error {
+                EntryError::NoSuchEntry => true,
+                _ => false,
+            })
+        }
+        _ => false,
+    }
+}
+
+// A directory which doesn't have an overflow yet doesn't have the entry either.
+fn overflow_error(error: CoreError) -> CoreError {
+    match error {
+        CoreError::RoutingClientError(ClientError::NoSuchData) => {
+            CoreError::RoutingClientError(ClientError::NoSuchEntry)
+        }
+        error => error,
+    }
+}
+
+// Keeps the error of mutating the directory itself if it doesn't have an overflow.
+fn missing_overflow_error(err: CoreError, error: CoreError) -> CoreError {
+    match err {
+        CoreError::RoutingClientError(ClientError::NoSuchData) => error,
+        err => err,
+    }
+}
@@ -11,7 +11,7 @@ use crypto::shared_secretbox;
 use futures::Future;
 use nfs::{data_map, File, NfsError, NfsFuture};
 use self_encryption::SelfEncryptor;
-use self_encryption_storage::SelfEncryptionStorage;
+use self_encryption_storage::{DownloadTracker, SelfEncryptionStorage};
 use utils::FutureExt;
 
 /// `Reader` is used to read contents of a `File`. It can read in chunks if the `File` happens to be
@@ -20,6 +20,7 @@ use utils::FutureExt;
 pub struct Reader<C: Client> {
     client: C,
     self_encryptor: SelfEncryptor<SelfEncryptionStorage<C>>,
+    downloads: DownloadTracker,
 }
 
 impl<C: Client> Reader<C> {
@@ -30,13 +31,17 @@ impl<C: Client> Reader<C> {
         file: &File,
         encryption_key: Option<shared_secretbox::Key>,
     ) -> Box<NfsFuture<Self>> {
+        let downloads = storage.download_tracker().clone();
+
         data_map::get(&client, file.data_map_name(), encryption_key)
             .and_then(move |data_map| {
+                downloads.set_data_map(&data_map);
                 let self_encryptor = SelfEncryptor::new(storage, data_map)?;
 
                 Ok(Self {
                     client,
                     self_encryptor,
+                    downloads,
                 })
             }).into_box()
     }
@@ -46,6 +51,12 @@ impl<C: Client> Reader<C> {
         self.self_encryptor.len()
     }
 
+    /// Tracker of the chunks fetched while reading, which can be used to follow the
+    /// progress of reads against the chunks and size of the file.
+    pub fn download_tracker(&self) -> &DownloadTracker {
+        &self.downloads
+    }
+
     /// Read data from file/blob.
     pub fn read(&self, position: u64, length: u64) -> Box<NfsFuture<Vec<u8>>> {
         trace!(
@@ -7,16 +7,18 @@
 // permissions and limitations relating to use of the SAFE Network Software.
 
 use client::core_client::CoreClient;
-use client::MDataInfo;
+use client::{Client, MDataInfo};
 use crypto::shared_secretbox;
 use errors::CoreError;
 use futures::future::{self, Loop};
 use futures::Future;
+use maidsafe_utilities::serialisation::serialise;
 use nfs::file_helper::{self, Version};
 use nfs::reader::Reader;
 use nfs::writer::Writer;
 use nfs::{create_dir, File, Mode, NfsError, NfsFuture};
 use rand::{self, Rng};
+use routing::{Value, MAX_MUTABLE_DATA_ENTRIES};
 use rust_sodium::crypto::secretbox;
 use self_encryption::MIN_CHUNK_SIZE;
 use std;
@@ -185,6 +187,49 @@ fn file_read() {
     });
 }
 
+// Insert two files and fetch them at once, along with a file which doesn't exist and a file
+// which has been deleted.
+#[test]
+fn file_fetch_many() {
+    random_client(|client| {
+        let c2 = client.clone();
+        let c3 = client.clone();
+        let c4 = client.clone();
+        let c5 = client.clone();
+
+        create_test_file(client)
+            .then(move |res| {
+                let (dir, file) = unwrap!(res);
+                let mut file2 = file.clone();
+                file2.set_user_metadata(vec![1, 2, 3]);
+
+                file_helper::insert(c2, dir.clone(), "world.txt", &file2)
+                    .map(move |_| (dir, file, file2))
+            })
+            .then(move |res| {
+                let (dir, file, file2) = unwrap!(res);
+
+                file_helper::insert(c3, dir.clone(), "deleted.txt", &file)
+                    .map(move |_| (dir, file, file2))
+            })
+            .then(move |res| {
+                let (dir, file, file2) = unwrap!(res);
+
+                file_helper::delete(c4, dir.clone(), "deleted.txt", Version::Custom(1))
+                    .map(move |_| (dir, file, file2))
+            })
+            .then(move |res| {
+                let (dir, file, file2) = unwrap!(res);
+                let names = ["hello.txt", "missing.txt", "world.txt", "deleted.txt"];
+
+                file_helper::fetch_many(c5, dir, &names).map(move |files| (files, file, file2))
+            })
+            .map(move |(files, file, file2)| {
+                assert_eq!(files, vec![Some((0, file)), None, Some((0, file2)), None]);
+            })
+    });
+}
+
 // Test reading file in chunks.
 #[test]
 fn file_read_chunks() {
@@ -819,3 +864,77 @@ fn encryption() {
         })
     })
 }
+
+// Test inserting, fetching, updating and deleting files in a full directory, which
+// go into its overflow.
+#[test]
+fn full_dir_overflow() {
+    random_client(|client| {
+        let c2 = client.clone();
+        let c3 = client.clone();
+        let c4 = client.clone();
+        let c5 = client.clone();
+        let c6 = client.clone();
+        let c7 = client.clone();
+        let c8 = client.clone();
+
+        let dir = unwrap!(MDataInfo::random_private(DIR_TAG));
+        let dir2 = dir.clone();
+        let dir3 = dir.clone();
+        let dir4 = dir.clone();
+        let dir5 = dir.clone();
+        let dir6 = dir.clone();
+        let dir7 = dir.clone();
+        let dir8 = dir.clone();
+
+        let encoded = unwrap!(serialise(&File::new(Vec::new())));
+        let contents = (0..MAX_MUTABLE_DATA_ENTRIES)
+            .map(|i| {
+                let key = unwrap!(dir.enc_entry_key(format!("file{}", i).as_bytes()));
+                let content = unwrap!(dir.enc_entry_value(&encoded));
+                (
+                    key,
+                    Value {
+                        content,
+                        entry_version: 0,
+                    },
+                )
+            }).collect();
+
+        create_dir(client, &dir, contents, btree_map![])
+            .and_then(move |()| {
+                let file = File::new(vec![1u8; 10]);
+                file_helper::insert(c2, dir2, "hello.txt", &file)
+            }).and_then(move |()| file_helper::fetch(c3, dir3, "hello.txt"))
+            .and_then(move |(version, file)| {
+                assert_eq!(version, 0);
+                assert_eq!(*file.user_metadata(), [1u8; 10][..]);
+
+                let file = File::new(vec![2u8; 10]);
+                file_helper::update(c4, dir4, "hello.txt", &file, Version::GetNext)
+            }).and_then(move |version| {
+                assert_eq!(version, 1);
+                file_helper::fetch_many(c5, dir5, &["file0", "hello.txt", "missing.txt"])
+            }).and_then(move |files| {
+                assert_eq!(files.len(), 3);
+                assert!(files[0].is_some());
+                let (version, ref file) = *unwrap!(files[1].as_ref());
+                assert_eq!(version, 1);
+                assert_eq!(*file.user_metadata(), [2u8; 10][..]);
+                assert!(files[2].is_none());
+
+                file_helper::delete(c6, dir6, "hello.txt", Version::Custom(2))
+            }).and_then(move |_| {
+                // The directory itself is left untouched.
+                c7.list_mdata_entries(dir7.name, dir7.type_tag)
+                    .map_err(NfsError::from)
+            }).and_then(move |entries| {
+                assert_eq!(entries.len() as u64, MAX_MUTABLE_DATA_ENTRIES);
+
+                file_helper::fetch(c8, dir8, "hello.txt").then(|res| match res {
+                    Err(_) => Ok(()),
+                    Ok(_) => panic!("Fetched deleted file successfully"),
+                })
+            })
+    })
+}
@@ -12,7 +12,7 @@ use crypto::shared_secretbox;
 use futures::Future;
 use nfs::{data_map, File, NfsFuture};
 use self_encryption::SequentialEncryptor;
-use self_encryption_storage::SelfEncryptionStorage;
+use self_encryption_storage::{SelfEncryptionStorage, UploadPipeline};
 use utils::FutureExt;
 
 /// Mode of the writer.
@@ -30,6 +30,7 @@ pub struct Writer<C: Client> {
     client: C,
     file: File,
     self_encryptor: SequentialEncryptor<SelfEncryptionStorage<C>>,
+    pipeline: UploadPipeline,
     encryption_key: Option<shared_secretbox::Key>,
 }
 
@@ -49,12 +50,14 @@ impl<C: Client> Writer<C> {
             Mode::Overwrite => ok!(None),
         };
         let client = client.clone();
+        let pipeline = storage.pipeline().clone();
         fut.and_then(move |data_map| {
             SequentialEncryptor::new(storage, data_map).map_err(From::from)
         }).map(move |self_encryptor| Writer {
             client,
             file,
             self_encryptor,
+            pipeline,
             encryption_key,
         }).map_err(From::from)
         .into_box()
@@ -72,6 +75,13 @@ impl<C: Client> Writer<C> {
             .into_box()
     }
 
+    /// Pipeline the chunks of the file are uploaded through, which can be used to follow the
+    /// progress of the upload. Its size can be set to the size the file will have once
+    /// written, so the total number of chunks is known up front.
+    pub fn upload_pipeline(&self) -> &UploadPipeline {
+        &self.pipeline
+    }
+
     /// close() should be invoked only after all the data is completely written. The file/blob is
     /// saved only when close() is invoked. Returns the final `File` with the data_map stored on the
     /// network.
@@ -82,12 +92,17 @@ impl<C: Client> Writer<C> {
         let size = self.self_encryptor.len();
         let client = self.client;
         let encryption_key = self.encryption_key;
+        let pipeline = self.pipeline;
+        let pipeline2 = pipeline.clone();
 
         self.self_encryptor
             .close()
             .map_err(From::from)
-            .and_then(move |(data_map, _)| data_map::put(&client, &data_map, encryption_key))
+            .and_then(move |(data_map, _)| {
+                data_map::put(&client, &data_map, encryption_key, &pipeline)
+            })
             .map(move |data_map_name| {
+                pipeline2.finish();
                 file.set_data_map_name(data_map_name);
                 file.set_modified_time(Utc::now());
                 file.set_size(size);
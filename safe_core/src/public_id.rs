@@ -0,0 +1,320 @@
+// Copyright 2018 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+//! Registry of human-readable public names.
+//!
+//! A public ID is an unencrypted `MutableData` stored at the SHA3 hash of its name,
+//! with the type tag `PUBLIC_ID_TAG`. It publishes the public signing and encryption
+//! keys of the app which registered it, so that share offers sealed to the encryption
+//! key can be accepted for that app. It also maps service names (e.g. `www`) to the
+//! name and type tag of the public data backing them. The names registered by a user
+//! are also recorded in their `_publicNames` container, so that they can be listed
+//! later.
+
+use client::{Client, MDataInfo};
+use errors::CoreError;
+use event_loop::CoreFuture;
+use futures::Future;
+use maidsafe_utilities::serialisation::{deserialise, serialise};
+use routing::{ClientError, EntryActions, MutableData, Value, XorName};
+use rust_sodium::crypto::{box_, sign};
+use std::collections::BTreeMap;
+use tiny_keccak::sha3_256;
+use utils::FutureExt;
+use PUBLIC_ID_TAG;
+
+/// Entry holding the public signing key of the app which registered the ID.
+pub const SIGN_PK_KEY: &[u8] = b"_signPk";
+/// Entry holding the public encryption key of the app which registered the ID.
+pub const ENC_PK_KEY: &[u8] = b"_encPk";
+
+/// Keys and services published under a public name.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PublicId {
+    /// Public signing key of the app which registered the ID
+    pub sign_pk: sign::PublicKey,
+    /// Public encryption key of the app which registered the ID
+    pub enc_pk: box_::PublicKey,
+    /// Services, by name. Only public data can back a service.
+    pub services: BTreeMap<String, MDataInfo>,
+}
+
+/// Returns the name of the `MutableData` a public ID is stored in.
+pub fn public_id_address(name: &str) -> XorName {
+    XorName(sha3_256(name.as_bytes()))
+}
+
+/// Registers a new public name and records it in the `_publicNames` container.
+/// Fails with `ClientError::DataExists` if the name is already taken, and if any of
+/// the services is backed by encrypted data.
+pub fn register(
+    client: &impl Client,
+    public_names: &MDataInfo,
+    name: &str,
+    id: &PublicId,
+) -> Box<CoreFuture<()>> {
+    let owner = fry!(
+        client
+            .owner_key()
+            .ok_or_else(|| CoreError::Unexpected("Owner key not found".to_string()))
+    );
+    let address = public_id_address(name);
+
+    let mut entries = btree_map![
+        SIGN_PK_KEY.to_vec() => Value {
+            content: fry!(serialise(&id.sign_pk)),
+            entry_version: 0,
+        },
+        ENC_PK_KEY.to_vec() => Value {
+            content: fry!(serialise(&id.enc_pk)),
+            entry_version: 0,
+        }
+    ];
+    for (service, info) in &id.services {
+        fry!(validate_service_name(service));
+        let _ = entries.insert(
+            service.as_bytes().to_vec(),
+            Value {
+                content: fry!(service_content(info)),
+                entry_version: 0,
+            },
+        );
+    }
+
+    let data = fry!(MutableData::new(
+        address,
+        PUBLIC_ID_TAG,
+        btree_map![],
+        entries,
+        btree_set![owner],
+    ));
+
+    let key = fry!(public_names.enc_entry_key(name.as_bytes()));
+    let value = fry!(public_names.enc_entry_value(&fry!(serialise(&address))));
+    let actions = EntryActions::new().ins(key, value, 0).into();
+
+    let c2 = client.clone();
+    let public_names = public_names.clone();
+
+    client
+        .put_mdata(data)
+        .and_then(move |()| {
+            c2.mutate_mdata_entries(public_names.name, public_names.type_tag, actions)
+        }).into_box()
+}
+
+/// Resolves a public name to the keys and services published under it.
+pub fn resolve(client: &impl Client, name: &str) -> Box<CoreFuture<PublicId>> {
+    client
+        .list_mdata_entries(public_id_address(name), PUBLIC_ID_TAG)
+        .and_then(|mut entries| {
+            let sign_pk = entries
+                .remove(SIGN_PK_KEY)
+                .ok_or(CoreError::ReceivedUnexpectedData)?;
+            let enc_pk = entries
+                .remove(ENC_PK_KEY)
+                .ok_or(CoreError::ReceivedUnexpectedData)?;
+
+            let mut services = BTreeMap::new();
+            for (key, value) in entries {
+                // Deleted services are left behind as empty entries.
+                if value.content.is_empty() {
+                    continue;
+                }
+                let service =
+                    String::from_utf8(key).map_err(|_| CoreError::ReceivedUnexpectedData)?;
+                let (name, type_tag) = deserialise(&value.content)?;
+                let _ = services.insert(service, MDataInfo::new_public(name, type_tag));
+            }
+
+            Ok(PublicId {
+                sign_pk: deserialise(&sign_pk.content)?,
+                enc_pk: deserialise(&enc_pk.content)?,
+                services,
+            })
+        }).into_box()
+}
+
+/// Publishes a service under a public name owned by the user, replacing any
+/// service with the same name. Only the name and the type tag of the data are
+/// published, so the data has to be public: encrypted data is rejected.
+pub fn add_service(
+    client: &impl Client,
+    name: &str,
+    service: &str,
+    info: &MDataInfo,
+) -> Box<CoreFuture<()>> {
+    fry!(validate_service_name(service));
+
+    let address = public_id_address(name);
+    let key = service.as_bytes().to_vec();
+    let content = fry!(service_content(info));
+    let c2 = client.clone();
+
+    client
+        .get_mdata_value(address, PUBLIC_ID_TAG, key.clone())
+        .then(move |res| {
+            let actions = match res {
+                Ok(value) => EntryActions::new().update(key, content, value.entry_version + 1),
+                Err(CoreError::RoutingClientError(ClientError::NoSuchEntry)) => {
+                    EntryActions::new().ins(key, content, 0)
+                }
+                Err(error) => return Err(error),
+            };
+            Ok(actions.into())
+        }).and_then(move |actions| c2.mutate_mdata_entries(address, PUBLIC_ID_TAG, actions))
+        .into_box()
+}
+
+/// Removes a service from a public name owned by the user.
+pub fn remove_service(client: &impl Client, name: &str, service: &str) -> Box<CoreFuture<()>> {
+    fry!(validate_service_name(service));
+
+    let address = public_id_address(name);
+    let key = service.as_bytes().to_vec();
+    let c2 = client.clone();
+
+    client
+        .get_mdata_value(address, PUBLIC_ID_TAG, key.clone())
+        .and_then(move |value| {
+            if value.content.is_empty() {
+                return err!(CoreError::RoutingClientError(ClientError::NoSuchEntry));
+            }
+            let actions = EntryActions::new()
+                .del(key, value.entry_version + 1)
+                .into();
+            c2.mutate_mdata_entries(address, PUBLIC_ID_TAG, actions)
+        }).into_box()
+}
+
+/// Lists the public names registered by the user.
+pub fn list_registered(
+    client: &impl Client,
+    public_names: &MDataInfo,
+) -> Box<CoreFuture<Vec<String>>> {
+    let public_names = public_names.clone();
+
+    client
+        .list_mdata_keys(public_names.name, public_names.type_tag)
+        .and_then(move |keys| {
+            keys.into_iter()
+                .map(|key| {
+                    let name = public_names.decrypt(&key)?;
+                    String::from_utf8(name).map_err(|_| CoreError::ReceivedUnexpectedData)
+                }).collect()
+        }).into_box()
+}
+
+// Serialises the address of the public data backing a service. The `MDataInfo` itself
+// isn't published, as it would publish the keys of encrypted data.
+fn service_content(info: &MDataInfo) -> Result<Vec<u8>, CoreError> {
+    if info.enc_info.is_some() {
+        return Err(CoreError::Unexpected(
+            "Services can only be backed by public data".to_string(),
+        ));
+    }
+
+    Ok(serialise(&(info.name, info.type_tag))?)
+}
+
+// Names starting with an underscore are reserved for the keys of the owner.
+fn validate_service_name(service: &str) -> Result<(), CoreError> {
+    if service.is_empty() || service.starts_with('_') {
+        Err(CoreError::Unexpected(format!(
+            "Invalid service name: {:?}",
+            service
+        )))
+    } else {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use utils::test_utils::random_client;
+    use DIR_TAG;
+
+    // Test registering a public name, publishing services under it and resolving it.
+    #[test]
+    fn register_and_resolve() {
+        random_client(|client| {
+            let c2 = client.clone();
+            let c3 = client.clone();
+            let c4 = client.clone();
+            let c5 = client.clone();
+            let c6 = client.clone();
+            let c7 = client.clone();
+            let c8 = client.clone();
+            let c9 = client.clone();
+
+            let public_names = unwrap!(MDataInfo::random_private(DIR_TAG));
+            let public_names2 = public_names.clone();
+            let public_names3 = public_names.clone();
+            let names_data = unwrap!(MutableData::new(
+                public_names.name,
+                public_names.type_tag,
+                btree_map![],
+                btree_map![],
+                btree_set![unwrap!(client.owner_key())],
+            ));
+
+            let www = unwrap!(MDataInfo::random_public(DIR_TAG));
+            let blog = unwrap!(MDataInfo::random_public(DIR_TAG));
+            let blog2 = blog.clone();
+            let blog3 = blog.clone();
+            let private = unwrap!(MDataInfo::random_private(DIR_TAG));
+
+            let id = PublicId {
+                sign_pk: unwrap!(client.public_signing_key()),
+                enc_pk: unwrap!(client.public_encryption_key()),
+                services: btree_map!["www".to_string() => www],
+            };
+            let id2 = id.clone();
+            let id3 = id.clone();
+
+            client
+                .put_mdata(names_data)
+                .then(move |res| {
+                    unwrap!(res);
+                    register(&c2, &public_names, "alice", &id)
+                }).then(move |res| {
+                    unwrap!(res);
+                    register(&c3, &public_names2, "alice", &id2)
+                }).then(move |res| {
+                    match res {
+                        Err(CoreError::RoutingClientError(ClientError::DataExists)) => (),
+                        x => panic!("Unexpected {:?}", x),
+                    }
+                    list_registered(&c4, &public_names3)
+                }).then(move |res| {
+                    assert_eq!(unwrap!(res), vec!["alice".to_string()]);
+                    add_service(&c5, "alice", "blog", &blog)
+                }).then(move |res| {
+                    unwrap!(res);
+                    remove_service(&c6, "alice", "www")
+                }).then(move |res| {
+                    unwrap!(res);
+                    add_service(&c7, "alice", "_signPk", &blog2)
+                }).then(move |res| {
+                    assert!(res.is_err());
+                    // The keys of encrypted data must not be published.
+                    add_service(&c8, "alice", "private", &private)
+                }).then(move |res| {
+                    assert!(res.is_err());
+                    resolve(&c9, "alice")
+                }).then(move |res| {
+                    let resolved = unwrap!(res);
+                    assert_eq!(resolved.sign_pk, id3.sign_pk);
+                    assert_eq!(resolved.enc_pk, id3.enc_pk);
+                    assert_eq!(resolved.services, btree_map!["blog".to_string() => blog3]);
+                    Ok::<_, CoreError>(())
+                })
+        })
+    }
+}
@@ -0,0 +1,413 @@
+// Copyright 2018 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+//! Typed key-value store over a (usually private) `MutableData`.
+//!
+//! Keys and values are encrypted with the `MDataInfo` of the data. Values are serialised,
+//! and those larger than `MAX_INLINE_VALUE_SIZE` are self-encrypted into `ImmutableData`,
+//! with the entry only pointing to them. Note that `ImmutableData` can't be deleted, so
+//! overwriting or deleting a large value leaves its chunks behind.
+//!
+//! The map remembers the entry version of every key it has read or written. `put` and
+//! `delete` recover from stale versions, while `compare_and_swap` only succeeds if the
+//! entry hasn't changed since it was last seen by this map.
+
+use client::{recovery, Client, MDataInfo};
+use errors::CoreError;
+use event_loop::CoreFuture;
+use futures::future::{self, Either};
+use futures::Future;
+use immutable_data;
+use maidsafe_utilities::serialisation::{deserialise, serialise};
+use routing::{ClientError, EntryAction, EntryActions, MutableData, Value, XorName};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap};
+use std::marker::PhantomData;
+use std::rc::Rc;
+use utils::FutureExt;
+
+/// Serialised values larger than this are stored in `ImmutableData`.
+pub const MAX_INLINE_VALUE_SIZE: usize = 10 * 1024;
+
+#[derive(Serialize, Deserialize)]
+enum StoredValue {
+    Inline(Vec<u8>),
+    IData(XorName),
+}
+
+/// Typed key-value store over `MutableData`.
+pub struct SecureMap<C: Client, V> {
+    client: C,
+    info: MDataInfo,
+    // Last seen entry version of each key, `None` if there was no entry.
+    versions: Rc<RefCell<HashMap<Vec<u8>, Option<u64>>>>,
+    _value: PhantomData<V>,
+}
+
+impl<C: Client, V> Clone for SecureMap<C, V> {
+    fn clone(&self) -> Self {
+        SecureMap {
+            client: self.client.clone(),
+            info: self.info.clone(),
+            versions: Rc::clone(&self.versions),
+            _value: PhantomData,
+        }
+    }
+}
+
+impl<C: Client, V: Serialize + DeserializeOwned + 'static> SecureMap<C, V> {
+    /// Creates a map over an existing `MutableData`.
+    pub fn new(client: &C, info: MDataInfo) -> Self {
+        SecureMap {
+            client: client.clone(),
+            info,
+            versions: Rc::new(RefCell::new(HashMap::new())),
+            _value: PhantomData,
+        }
+    }
+
+    /// Puts a new, empty `MutableData` owned by the user on the network and returns
+    /// a map over it.
+    pub fn create(client: &C, info: MDataInfo) -> Box<CoreFuture<Self>> {
+        let owner = fry!(
+            client
+                .owner_key()
+                .ok_or_else(|| CoreError::Unexpected("Owner key not found".to_string()))
+        );
+        let data = fry!(MutableData::new(
+            info.name,
+            info.type_tag,
+            btree_map![],
+            btree_map![],
+            btree_set![owner],
+        ));
+        let map = Self::new(client, info);
+
+        client.put_mdata(data).map(move |()| map).into_box()
+    }
+
+    /// `MDataInfo` of the underlying data.
+    pub fn info(&self) -> &MDataInfo {
+        &self.info
+    }
+
+    /// Gets the value for the key, if there's one.
+    pub fn get(&self, key: &[u8]) -> Box<CoreFuture<Option<V>>> {
+        let enc_key = fry!(self.info.enc_entry_key(key));
+        let key = key.to_vec();
+        let versions = Rc::clone(&self.versions);
+        let client = self.client.clone();
+        let info = self.info.clone();
+
+        self.client
+            .get_mdata_value(self.info.name, self.info.type_tag, enc_key)
+            .then(move |res| match res {
+                Ok(value) => {
+                    let _ = versions
+                        .borrow_mut()
+                        .insert(key, Some(value.entry_version));
+                    if value.content.is_empty() {
+                        // The entry has been deleted.
+                        Either::A(future::ok(None))
+                    } else {
+                        Either::B(load_value(&client, &info, &value.content).map(Some))
+                    }
+                }
+                Err(CoreError::RoutingClientError(ClientError::NoSuchEntry)) => {
+                    let _ = versions.borrow_mut().insert(key, None);
+                    Either::A(future::ok(None))
+                }
+                Err(error) => Either::A(future::err(error)),
+            }).into_box()
+    }
+
+    /// Sets the value for the key, overwriting any existing value.
+    pub fn put(&self, key: &[u8], value: &V) -> Box<CoreFuture<()>> {
+        let enc_key = fry!(self.info.enc_entry_key(key));
+        let key = key.to_vec();
+        let versions = Rc::clone(&self.versions);
+        let client = self.client.clone();
+        let info = self.info.clone();
+        let version = self.last_version(&key);
+
+        store_value(&self.client, &self.info, value)
+            .and_then(move |content| {
+                let action = match version {
+                    Some(version) => EntryAction::Update(Value {
+                        content,
+                        entry_version: version + 1,
+                    }),
+                    None => EntryAction::Ins(Value {
+                        content,
+                        entry_version: 0,
+                    }),
+                };
+                let actions = btree_map![enc_key => action];
+
+                recovery::mutate_mdata_entries(&client, info.name, info.type_tag, actions)
+            }).map(move |()| {
+                // The version is only a hint here, as it could have been corrected
+                // during the recovery. A wrong one gets corrected again next time.
+                let _ = versions
+                    .borrow_mut()
+                    .insert(key, Some(version.map_or(0, |version| version + 1)));
+            }).into_box()
+    }
+
+    /// Deletes the value for the key. Succeeds even if there's no such value.
+    pub fn delete(&self, key: &[u8]) -> Box<CoreFuture<()>> {
+        let enc_key = fry!(self.info.enc_entry_key(key));
+        let key = key.to_vec();
+        let versions = Rc::clone(&self.versions);
+        let version = self.last_version(&key).map_or(1, |version| version + 1);
+
+        let actions = EntryActions::new().del(enc_key, version).into();
+
+        recovery::mutate_mdata_entries(&self.client, self.info.name, self.info.type_tag, actions)
+            .map(move |()| {
+                // The deleted entry is left behind with empty content, so it's got a version
+                // too. As in `put`, it's only a hint.
+                let _ = versions.borrow_mut().insert(key, Some(version));
+            }).into_box()
+    }
+
+    /// Sets the value for the key, but only if the entry hasn't changed since
+    /// it was last read or written by this map. If the key hasn't been seen yet,
+    /// the value is only set if there's no entry for it. Returns whether the value
+    /// has been set. Call `get` to see the current value after a failed swap.
+    pub fn compare_and_swap(&self, key: &[u8], value: &V) -> Box<CoreFuture<bool>> {
+        let enc_key = fry!(self.info.enc_entry_key(key));
+        let key = key.to_vec();
+        let versions = Rc::clone(&self.versions);
+        let client = self.client.clone();
+        let info = self.info.clone();
+        let version = self.last_version(&key);
+
+        store_value(&self.client, &self.info, value)
+            .and_then(move |content| {
+                let actions: BTreeMap<_, _> = match version {
+                    Some(version) => EntryActions::new().update(enc_key, content, version + 1),
+                    None => EntryActions::new().ins(enc_key, content, 0),
+                }.into();
+
+                client
+                    .mutate_mdata_entries(info.name, info.type_tag, actions)
+                    .then(move |res| match res {
+                        Ok(()) => {
+                            let new_version = version.map_or(0, |version| version + 1);
+                            let _ = versions.borrow_mut().insert(key, Some(new_version));
+                            Ok(true)
+                        }
+                        Err(CoreError::RoutingClientError(ClientError::InvalidEntryActions(
+                            _,
+                        ))) => {
+                            let _ = versions.borrow_mut().remove(&key);
+                            Ok(false)
+                        }
+                        Err(error) => Err(error),
+                    })
+            }).into_box()
+    }
+
+    fn last_version(&self, key: &[u8]) -> Option<u64> {
+        self.versions.borrow().get(key).cloned().and_then(|version| version)
+    }
+}
+
+// Serialises the value and returns the encrypted content of its entry, putting the
+// value to the network first if it's too large to be stored inline.
+fn store_value<V: Serialize>(
+    client: &impl Client,
+    info: &MDataInfo,
+    value: &V,
+) -> Box<CoreFuture<Vec<u8>>> {
+    let serialised = fry!(serialise(value));
+
+    if serialised.len() <= MAX_INLINE_VALUE_SIZE {
+        let stored = fry!(serialise(&StoredValue::Inline(serialised)));
+        return ok!(fry!(info.enc_entry_value(&stored)));
+    }
+
+    let client2 = client.clone();
+    let info = info.clone();
+
+    immutable_data::create(client, &serialised, info.enc_key().cloned())
+        .and_then(move |data| {
+            let name = *data.name();
+            client2.put_idata(data).map(move |()| name)
+        }).and_then(move |name| {
+            let stored = serialise(&StoredValue::IData(name))?;
+            info.enc_entry_value(&stored)
+        }).into_box()
+}
+
+// Decrypts the content of an entry and deserialises the value it holds or points to.
+fn load_value<V: DeserializeOwned + 'static>(
+    client: &impl Client,
+    info: &MDataInfo,
+    content: &[u8],
+) -> Box<CoreFuture<V>> {
+    let stored = fry!(info.decrypt(content));
+
+    match fry!(deserialise(&stored)) {
+        StoredValue::Inline(serialised) => ok!(fry!(deserialise(&serialised))),
+        StoredValue::IData(name) => {
+            immutable_data::get_value(client, &name, info.enc_key().cloned())
+                .and_then(|serialised| Ok(deserialise(&serialised)?))
+                .into_box()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use utils::test_utils::random_client;
+    use DIR_TAG;
+
+    #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+    struct Record {
+        name: String,
+        data: Vec<u8>,
+    }
+
+    // Test getting, putting and deleting values, including ones too large to be
+    // stored inline.
+    #[test]
+    fn put_get_delete() {
+        random_client(|client| {
+            let info = unwrap!(MDataInfo::random_private(DIR_TAG));
+
+            let small = Record {
+                name: "small".to_string(),
+                data: vec![1; 10],
+            };
+            let large = Record {
+                name: "large".to_string(),
+                data: vec![2; 3 * MAX_INLINE_VALUE_SIZE],
+            };
+            let small2 = small.clone();
+            let large2 = large.clone();
+
+            SecureMap::<_, Record>::create(client, info)
+                .then(move |res| {
+                    let map = unwrap!(res);
+                    let map2 = map.clone();
+                    map.get(b"key").map(move |value| (map2, value))
+                }).then(move |res| {
+                    let (map, value) = unwrap!(res);
+                    assert_eq!(value, None);
+                    let map2 = map.clone();
+                    map.put(b"key", &small).map(move |()| map2)
+                }).then(move |res| {
+                    let map = unwrap!(res);
+                    let map2 = map.clone();
+                    map.get(b"key").map(move |value| (map2, value))
+                }).then(move |res| {
+                    let (map, value) = unwrap!(res);
+                    assert_eq!(value, Some(small2));
+                    let map2 = map.clone();
+                    map.put(b"key", &large).map(move |()| map2)
+                }).then(move |res| {
+                    let map = unwrap!(res);
+                    // Read through a fresh map, without any cached versions.
+                    let map2 = SecureMap::<_, Record>::new(&map.client, map.info().clone());
+                    map2.get(b"key").map(move |value| (map, value))
+                }).then(move |res| {
+                    let (map, value) = unwrap!(res);
+                    assert_eq!(value, Some(large2));
+                    let map2 = map.clone();
+                    map.delete(b"key").map(move |()| map2)
+                }).then(move |res| {
+                    let map = unwrap!(res);
+                    map.get(b"key")
+                }).then(|res| {
+                    assert_eq!(unwrap!(res), None);
+                    Ok::<_, CoreError>(())
+                })
+        })
+    }
+
+    // Test that a swap fails if the entry has been changed since it was last seen.
+    #[test]
+    fn compare_and_swap() {
+        random_client(|client| {
+            let info = unwrap!(MDataInfo::random_private(DIR_TAG));
+            let c2 = client.clone();
+
+            SecureMap::<_, u64>::create(client, info)
+                .then(move |res| {
+                    let map0 = unwrap!(res);
+                    let map1 = SecureMap::new(&c2, map0.info().clone());
+                    map0.compare_and_swap(b"counter", &0)
+                        .map(move |swapped| (map0, map1, swapped))
+                }).then(|res| {
+                    let (map0, map1, swapped) = unwrap!(res);
+                    assert!(swapped);
+                    // `map1` hasn't seen the entry yet, so expects it not to exist.
+                    map1.compare_and_swap(b"counter", &1)
+                        .map(move |swapped| (map0, map1, swapped))
+                }).then(|res| {
+                    let (map0, map1, swapped) = unwrap!(res);
+                    assert!(!swapped);
+                    map1.get(b"counter").map(move |value| (map0, map1, value))
+                }).then(|res| {
+                    let (map0, map1, value) = unwrap!(res);
+                    assert_eq!(value, Some(0));
+                    map1.compare_and_swap(b"counter", &1)
+                        .map(move |swapped| (map0, map1, swapped))
+                }).then(|res| {
+                    let (map0, map1, swapped) = unwrap!(res);
+                    assert!(swapped);
+                    // `map0` is now behind.
+                    map0.compare_and_swap(b"counter", &2)
+                        .map(move |swapped| (map1, swapped))
+                }).then(|res| {
+                    let (map1, swapped) = unwrap!(res);
+                    assert!(!swapped);
+                    map1.get(b"counter")
+                }).then(|res| {
+                    assert_eq!(unwrap!(res), Some(1));
+                    Ok::<_, CoreError>(())
+                })
+        })
+    }
+
+    // Test that a swap succeeds after the map has deleted the entry itself.
+    #[test]
+    fn delete_and_compare_and_swap() {
+        random_client(|client| {
+            let info = unwrap!(MDataInfo::random_private(DIR_TAG));
+
+            SecureMap::<_, u64>::create(client, info)
+                .then(|res| {
+                    let map = unwrap!(res);
+                    let map2 = map.clone();
+                    map.put(b"counter", &0).map(move |()| map2)
+                }).then(|res| {
+                    let map = unwrap!(res);
+                    let map2 = map.clone();
+                    map.delete(b"counter").map(move |()| map2)
+                }).then(|res| {
+                    let map = unwrap!(res);
+                    let map2 = map.clone();
+                    map.compare_and_swap(b"counter", &1)
+                        .map(move |swapped| (map2, swapped))
+                }).then(|res| {
+                    let (map, swapped) = unwrap!(res);
+                    assert!(swapped);
+                    map.get(b"counter")
+                }).then(|res| {
+                    assert_eq!(unwrap!(res), Some(1));
+                    Ok::<_, CoreError>(())
+                })
+        })
+    }
+}
@@ -7,22 +7,53 @@
 // permissions and limitations relating to use of the SAFE Network Software.
 
 use super::{Client, CoreError, FutureExt};
+use event_loop::CoreFuture;
+use futures::future::Either;
+use futures::sync::oneshot;
 use futures::{self, Future};
 use routing::{ImmutableData, XorName, XOR_NAME_LEN};
-use self_encryption::{Storage, StorageError};
+use self_encryption::{DataMap, Storage, StorageError, MAX_CHUNK_SIZE, MIN_CHUNK_SIZE};
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::error::Error;
 use std::fmt::{self, Display, Formatter};
+use std::rc::Rc;
+
+/// Default maximum number of chunks uploaded at the same time.
+pub const DEFAULT_MAX_CONCURRENT_PUTS: usize = 8;
 
 /// Network storage is the concrete type which self-encryption crate will use
 /// to put or get data from the network.
 pub struct SelfEncryptionStorage<C: Client> {
     client: C,
+    pipeline: UploadPipeline,
+    downloads: DownloadTracker,
 }
 
 impl<C: Client> SelfEncryptionStorage<C> {
     /// Create a new SelfEncryptionStorage instance.
     pub fn new(client: C) -> Self {
-        SelfEncryptionStorage { client }
+        Self::with_pipeline(client, UploadPipeline::new(DEFAULT_MAX_CONCURRENT_PUTS))
+    }
+
+    /// Create a new SelfEncryptionStorage instance which uploads chunks through the
+    /// given pipeline.
+    pub fn with_pipeline(client: C, pipeline: UploadPipeline) -> Self {
+        SelfEncryptionStorage {
+            client,
+            pipeline,
+            downloads: DownloadTracker::new(),
+        }
+    }
+
+    /// Pipeline the chunks are uploaded through.
+    pub fn pipeline(&self) -> &UploadPipeline {
+        &self.pipeline
+    }
+
+    /// Tracker of the chunks fetched from the network.
+    pub fn download_tracker(&self) -> &DownloadTracker {
+        &self.downloads
     }
 }
 
@@ -44,17 +75,381 @@ impl<C: Client> Storage for SelfEncryptionStorage<C> {
             XorName(temp)
         };
 
+        if self.downloads.is_cancelled() {
+            let err = SelfEncryptionStorageError::from(CoreError::OperationCancelled);
+            return Box::new(futures::failed(err));
+        }
+
+        let downloads = self.downloads.clone();
+
         self.client
             .get_idata(name)
-            .map(|data| data.value().clone())
-            .map_err(From::from)
+            .map(move |data| {
+                downloads.chunk_done(name);
+                data.value().clone()
+            }).map_err(From::from)
             .into_box()
     }
 
     fn put(&mut self, _: Vec<u8>, data: Vec<u8>) -> Box<Future<Item = (), Error = Self::Error>> {
         trace!("Self encrypt invoked PutIData.");
         let data = ImmutableData::new(data);
-        self.client.put_idata(data).map_err(From::from).into_box()
+        self.pipeline
+            .put_idata(&self.client, data)
+            .map_err(From::from)
+            .into_box()
+    }
+}
+
+/// Progress of uploading chunks through an `UploadPipeline`.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct UploadProgress {
+    /// Number of chunks uploaded so far.
+    pub chunks_done: u64,
+    /// Number of chunks of the data, if its size has been set. Otherwise, number of chunks
+    /// submitted for upload so far.
+    pub chunks_total: u64,
+    /// Length of the chunks uploaded so far, at most `bytes_total`.
+    pub bytes_done: u64,
+    /// Size of the data, if it has been set. Otherwise, length of the chunks submitted for
+    /// upload so far.
+    pub bytes_total: u64,
+}
+
+/// Uploads `ImmutableData` chunks with a bounded number of them in flight at the same time,
+/// keeping track of the progress. Clones share the same limit and progress.
+#[derive(Clone)]
+pub struct UploadPipeline {
+    inner: Rc<RefCell<PipelineInner>>,
+    // Whether the chunks put through this clone count towards the progress.
+    tracked: bool,
+}
+
+struct PipelineInner {
+    max_concurrent: usize,
+    in_flight: usize,
+    waiting: VecDeque<oneshot::Sender<Slot>>,
+    progress: UploadProgress,
+    sized: bool,
+    on_progress: Option<Box<FnMut(UploadProgress) -> bool>>,
+    cancelled: bool,
+}
+
+impl UploadPipeline {
+    /// Create a pipeline uploading at most `max_concurrent` chunks at the same time.
+    pub fn new(max_concurrent: usize) -> Self {
+        UploadPipeline {
+            inner: Rc::new(RefCell::new(PipelineInner {
+                max_concurrent: max_concurrent.max(1),
+                in_flight: 0,
+                waiting: VecDeque::new(),
+                progress: UploadProgress::default(),
+                sized: false,
+                on_progress: None,
+                cancelled: false,
+            })),
+            tracked: true,
+        }
+    }
+
+    /// Return a clone of the pipeline whose chunks don't count towards the progress, to
+    /// upload the data map of the data through.
+    pub fn untracked(&self) -> Self {
+        UploadPipeline {
+            inner: Rc::clone(&self.inner),
+            tracked: false,
+        }
+    }
+
+    /// Set the size of the data uploaded through the pipeline, so the total number of its
+    /// chunks and bytes is known up front. Does nothing on untracked clones.
+    pub fn set_size(&self, size: u64) {
+        if self.tracked {
+            let mut inner = self.inner.borrow_mut();
+            inner.sized = true;
+            inner.progress.chunks_total = chunk_count(size);
+            inner.progress.bytes_total = size;
+        }
+    }
+
+    /// Report all the chunks as uploaded, once the data and its data map have been stored.
+    /// This accounts for chunks which weren't uploaded again, e.g. when appending to data,
+    /// and for small data kept in the data map.
+    pub fn finish(&self) {
+        self.update_progress(|progress| {
+            progress.chunks_done = progress.chunks_total;
+            progress.bytes_done = progress.bytes_total;
+        });
+    }
+
+    /// Set the function called with the progress every time a chunk has been uploaded.
+    /// Returning `false` from it cancels the pipeline.
+    pub fn set_progress_callback<F>(&self, on_progress: F)
+    where
+        F: FnMut(UploadProgress) -> bool + 'static,
+    {
+        self.inner.borrow_mut().on_progress = Some(Box::new(on_progress));
+    }
+
+    /// Return the progress so far.
+    pub fn progress(&self) -> UploadProgress {
+        self.inner.borrow().progress
+    }
+
+    /// Cancel the pipeline. Chunks already in flight are still uploaded, but all the
+    /// other ones fail with `CoreError::OperationCancelled`.
+    pub fn cancel(&self) {
+        let waiting = {
+            let mut inner = self.inner.borrow_mut();
+            inner.cancelled = true;
+            inner.waiting.drain(..).collect::<Vec<_>>()
+        };
+        // Dropping the senders fails the waiting puts.
+        drop(waiting);
+    }
+
+    /// Return `true` if the pipeline has been cancelled.
+    pub fn is_cancelled(&self) -> bool {
+        self.inner.borrow().cancelled
+    }
+
+    /// Put the data on the network as soon as fewer than the maximum number of chunks
+    /// are in flight.
+    pub fn put_idata(&self, client: &impl Client, data: ImmutableData) -> Box<CoreFuture<()>> {
+        let len = data.value().len() as u64;
+        let slot = {
+            let mut inner = self.inner.borrow_mut();
+            if inner.cancelled {
+                return err!(CoreError::OperationCancelled);
+            }
+
+            if self.tracked && !inner.sized {
+                inner.progress.chunks_total += 1;
+                inner.progress.bytes_total += len;
+            }
+
+            if inner.in_flight < inner.max_concurrent {
+                inner.in_flight += 1;
+                Either::A(futures::future::ok(Slot(self.clone())))
+            } else {
+                let (tx, rx) = oneshot::channel();
+                inner.waiting.push_back(tx);
+                Either::B(rx.map_err(|_| CoreError::OperationCancelled))
+            }
+        };
+
+        let client = client.clone();
+        let pipeline = self.clone();
+
+        slot.and_then(move |slot| {
+            client.put_idata(data).map(move |()| {
+                drop(slot);
+                if pipeline.tracked {
+                    // Identical chunks are put more than once, but count only once.
+                    pipeline.update_progress(|progress| {
+                        if progress.chunks_done < progress.chunks_total {
+                            progress.chunks_done += 1;
+                        }
+                        progress.bytes_done = (progress.bytes_done + len).min(progress.bytes_total);
+                    });
+                }
+            })
+        }).into_box()
+    }
+
+    fn update_progress<F: FnOnce(&mut UploadProgress)>(&self, f: F) {
+        let (progress, on_progress) = {
+            let mut inner = self.inner.borrow_mut();
+            f(&mut inner.progress);
+            (inner.progress, inner.on_progress.take())
+        };
+
+        // Called without holding the borrow, so the callback can use the pipeline.
+        if let Some(mut on_progress) = on_progress {
+            if !on_progress(progress) {
+                self.cancel();
+            }
+
+            let mut inner = self.inner.borrow_mut();
+            if inner.on_progress.is_none() {
+                inner.on_progress = Some(on_progress);
+            }
+        }
+    }
+
+    fn release_slot(&self) {
+        let tx = self.inner.borrow_mut().waiting.pop_front();
+        match tx {
+            // If the waiting put has been dropped in the meantime, the slot is
+            // dropped again too, and passed on to the next one.
+            Some(tx) => {
+                let _ = tx.send(Slot(self.clone()));
+            }
+            None => self.inner.borrow_mut().in_flight -= 1,
+        }
+    }
+}
+
+/// Progress of fetching chunks through a `SelfEncryptionStorage`.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct DownloadProgress {
+    /// Number of different chunks fetched so far.
+    pub chunks_done: u64,
+    /// Number of different chunks of the data map.
+    pub chunks_total: u64,
+    /// Size of the data of the chunks fetched so far.
+    pub bytes_done: u64,
+    /// Size of the data.
+    pub bytes_total: u64,
+}
+
+/// Keeps track of the chunks fetched through a `SelfEncryptionStorage`, and allows cancelling
+/// the fetching of further ones. Chunks fetched more than once count only once. Clones share
+/// the same state.
+#[derive(Clone, Default)]
+pub struct DownloadTracker {
+    inner: Rc<RefCell<TrackerInner>>,
+}
+
+#[derive(Default)]
+struct TrackerInner {
+    chunks_done: HashSet<XorName>,
+    // Size of the data of each chunk of the data map, before encryption.
+    chunk_sizes: HashMap<XorName, u64>,
+    progress: DownloadProgress,
+    // Whether the data is kept in the data map, so it's fetched along with it.
+    in_data_map: bool,
+    on_progress: Option<Box<FnMut(DownloadProgress) -> bool>>,
+    cancelled: bool,
+}
+
+impl DownloadTracker {
+    /// Create a new tracker.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Set the data map of the data being fetched, whose chunks and size are the total of
+    /// the progress.
+    pub fn set_data_map(&self, data_map: &DataMap) {
+        let mut chunk_sizes = HashMap::new();
+        if let DataMap::Chunks(ref chunks) = *data_map {
+            for chunk in chunks
+                .iter()
+                .filter(|chunk| chunk.hash.len() == XOR_NAME_LEN)
+            {
+                let mut name = [0u8; XOR_NAME_LEN];
+                name.clone_from_slice(&chunk.hash);
+                // Identical chunks are fetched only once, so their sizes add up.
+                *chunk_sizes.entry(XorName(name)).or_insert(0) += chunk.source_size;
+            }
+        }
+
+        let in_data_map = match *data_map {
+            DataMap::Content(_) => true,
+            DataMap::Chunks(_) | DataMap::None => false,
+        };
+
+        let mut inner = self.inner.borrow_mut();
+        inner.chunks_done.clear();
+        inner.progress = DownloadProgress {
+            chunks_done: 0,
+            chunks_total: chunk_sizes.len() as u64,
+            bytes_done: if in_data_map { data_map.len() } else { 0 },
+            bytes_total: data_map.len(),
+        };
+        inner.chunk_sizes = chunk_sizes;
+        inner.in_data_map = in_data_map;
+    }
+
+    /// Set the function called with the progress every time a new chunk has been fetched.
+    /// If the data is kept in the data map, it's called right away with all of it done.
+    /// Returning `false` from it cancels the tracker.
+    pub fn set_progress_callback<F>(&self, on_progress: F)
+    where
+        F: FnMut(DownloadProgress) -> bool + 'static,
+    {
+        let in_data_map = {
+            let mut inner = self.inner.borrow_mut();
+            inner.on_progress = Some(Box::new(on_progress));
+            inner.in_data_map
+        };
+
+        if in_data_map {
+            self.report_progress();
+        }
+    }
+
+    /// Return the progress so far.
+    pub fn progress(&self) -> DownloadProgress {
+        self.inner.borrow().progress
+    }
+
+    /// Cancel fetching further chunks, which then fail with `CoreError::OperationCancelled`.
+    pub fn cancel(&self) {
+        self.inner.borrow_mut().cancelled = true;
+    }
+
+    /// Return `true` if the tracker has been cancelled.
+    pub fn is_cancelled(&self) -> bool {
+        self.inner.borrow().cancelled
+    }
+
+    fn chunk_done(&self, name: XorName) {
+        {
+            let mut inner = self.inner.borrow_mut();
+            if !inner.chunks_done.insert(name) {
+                return;
+            }
+            let size = inner.chunk_sizes.get(&name).cloned().unwrap_or(0);
+            inner.progress.chunks_done += 1;
+            inner.progress.bytes_done += size;
+        }
+
+        self.report_progress();
+    }
+
+    fn report_progress(&self) {
+        let (progress, on_progress) = {
+            let mut inner = self.inner.borrow_mut();
+            (inner.progress, inner.on_progress.take())
+        };
+
+        // Called without holding the borrow, so the callback can use the tracker.
+        if let Some(mut on_progress) = on_progress {
+            if !on_progress(progress) {
+                self.cancel();
+            }
+
+            let mut inner = self.inner.borrow_mut();
+            if inner.on_progress.is_none() {
+                inner.on_progress = Some(on_progress);
+            }
+        }
+    }
+}
+
+// Number of chunks self-encryption splits data of the given size into.
+fn chunk_count(size: u64) -> u64 {
+    let (min, max) = (u64::from(MIN_CHUNK_SIZE), u64::from(MAX_CHUNK_SIZE));
+
+    if size < 3 * min {
+        // Small data is kept in the data map.
+        0
+    } else if size < 3 * max {
+        3
+    } else {
+        (size + max - 1) / max
+    }
+}
+
+// Permission to have one chunk in flight, given back when dropped. This way, a put
+// dropped before it has completed doesn't hold up the other ones.
+struct Slot(UploadPipeline);
+
+impl Drop for Slot {
+    fn drop(&mut self) {
+        self.0.release_slot();
     }
 }
 
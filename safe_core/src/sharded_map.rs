@@ -0,0 +1,496 @@
+// Copyright 2018 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+//! Collection of entries spread over several `MutableData`, to get past the limits on
+//! the number of entries and the size of a single one.
+//!
+//! A root `MutableData` holds a single entry indexing the shards by prefixes of the SHA3
+//! hash of the keys of their entries. Initially there's a single shard, with an empty
+//! prefix. When a mutation fails because its shard is full, the entries of the shard are
+//! split between two new shards by the next bit of the hash of their keys, and the index
+//! entry is updated in place, so the root never runs out of entries. Once the index
+//! entry itself gets too large, mutations which need a split fail.
+//!
+//! Shards share the encryption info of the root, so entries are moved between them as
+//! they are. Splits aren't coordinated between clients though, so a collection should
+//! only be mutated by one client at a time.
+
+use client::{Client, MDataInfo};
+use errors::CoreError;
+use event_loop::CoreFuture;
+use futures::future::{self, Either, Loop};
+use futures::Future;
+use maidsafe_utilities::serialisation::{deserialise, serialise};
+use routing::{
+    ClientError, EntryAction, EntryActions, MutableData, PermissionSet, User, Value, XorName,
+};
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::rc::Rc;
+use tiny_keccak::sha3_256;
+use utils::FutureExt;
+
+// Key of the root entry holding the index.
+const INDEX_KEY: &[u8] = b"_index";
+// Number of times a mutation is retried after splitting its shard.
+const MAX_SPLITS: usize = 8;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct Shard {
+    // Number of leading bits of the hash of a key which have to match the prefix.
+    depth: u8,
+    prefix: XorName,
+    info: MDataInfo,
+}
+
+impl Shard {
+    fn contains(&self, hash: &XorName) -> bool {
+        (0..self.depth as usize).all(|bit| get_bit(hash, bit) == get_bit(&self.prefix, bit))
+    }
+}
+
+#[derive(Clone, Debug)]
+struct Index {
+    shards: Vec<Shard>,
+    // Version of the root entry holding the index.
+    version: u64,
+}
+
+/// Collection of entries spread over several `MutableData`.
+#[derive(Clone)]
+pub struct ShardedMap<C: Client> {
+    client: C,
+    root: MDataInfo,
+    index: Rc<RefCell<Option<Index>>>,
+}
+
+impl<C: Client> ShardedMap<C> {
+    /// Creates a collection over an existing root `MutableData`.
+    pub fn new(client: &C, root: MDataInfo) -> Self {
+        ShardedMap {
+            client: client.clone(),
+            root,
+            index: Rc::new(RefCell::new(None)),
+        }
+    }
+
+    /// Puts a new root `MutableData` owned by the user, along with its first shard,
+    /// and returns a collection over it. The root and all the shards get the given
+    /// permissions.
+    pub fn create(
+        client: &C,
+        root: MDataInfo,
+        permissions: BTreeMap<User, PermissionSet>,
+    ) -> Box<CoreFuture<Self>> {
+        let owner = fry!(
+            client
+                .owner_key()
+                .ok_or_else(|| CoreError::Unexpected("Owner key not found".to_string()))
+        );
+        let index = Index {
+            shards: vec![Shard {
+                depth: 0,
+                prefix: XorName([0; 32]),
+                info: fry!(new_shard_info(&root)),
+            }],
+            version: 0,
+        };
+        let (index_key, index_value) = fry!(encode_index(&root, &index.shards));
+        let shard_data = fry!(MutableData::new(
+            index.shards[0].info.name,
+            index.shards[0].info.type_tag,
+            permissions.clone(),
+            btree_map![],
+            btree_set![owner],
+        ));
+        let root_data = fry!(MutableData::new(
+            root.name,
+            root.type_tag,
+            permissions,
+            btree_map![index_key => Value {
+                content: index_value,
+                entry_version: 0,
+            }],
+            btree_set![owner],
+        ));
+
+        let map = Self::new(client, root);
+        *map.index.borrow_mut() = Some(index);
+
+        client
+            .put_mdata(shard_data)
+            .join(client.put_mdata(root_data))
+            .map(move |_| map)
+            .into_box()
+    }
+
+    /// `MDataInfo` of the root `MutableData`.
+    pub fn root(&self) -> &MDataInfo {
+        &self.root
+    }
+
+    /// Returns the `MDataInfo`s of all the shards.
+    pub fn shards(&self) -> Box<CoreFuture<Vec<MDataInfo>>> {
+        self.index()
+            .map(|index| index.shards.into_iter().map(|shard| shard.info).collect())
+            .into_box()
+    }
+
+    /// Gets the decrypted value of an entry.
+    pub fn get(&self, key: &[u8]) -> Box<CoreFuture<Value>> {
+        let hash = XorName(sha3_256(key));
+        let enc_key = fry!(self.root.enc_entry_key(key));
+        let client = self.client.clone();
+
+        self.index()
+            .and_then(move |index| {
+                let shard = fry!(find_shard(&index.shards, &hash));
+                let info = shard.info.clone();
+
+                client
+                    .get_mdata_value(shard.info.name, shard.info.type_tag, enc_key)
+                    .and_then(move |value| decrypt_value(&info, value))
+                    .into_box()
+            }).into_box()
+    }
+
+    /// Lists all the entries, decrypted.
+    pub fn list_entries(&self) -> Box<CoreFuture<BTreeMap<Vec<u8>, Value>>> {
+        let client = self.client.clone();
+
+        self.index()
+            .and_then(move |index| {
+                let futures = index.shards.into_iter().map(move |shard| {
+                    let info = shard.info;
+                    client
+                        .list_mdata_entries(info.name, info.type_tag)
+                        .and_then(move |entries| {
+                            entries
+                                .into_iter()
+                                .map(|(key, value)| {
+                                    Ok((info.decrypt(&key)?, decrypt_value(&info, value)?))
+                                }).collect::<Result<Vec<_>, CoreError>>()
+                        })
+                });
+                future::join_all(futures)
+            }).map(|shards| shards.into_iter().flatten().collect())
+            .into_box()
+    }
+
+    /// Inserts a new entry.
+    pub fn insert(&self, key: &[u8], content: &[u8]) -> Box<CoreFuture<()>> {
+        let enc_key = fry!(self.root.enc_entry_key(key));
+        let content = fry!(self.root.enc_entry_value(content));
+
+        self.mutate(key, EntryActions::new().ins(enc_key, content, 0).into())
+    }
+
+    /// Updates an existing entry. `version` has to be one more than the current
+    /// version of the entry.
+    pub fn update(&self, key: &[u8], content: &[u8], version: u64) -> Box<CoreFuture<()>> {
+        let enc_key = fry!(self.root.enc_entry_key(key));
+        let content = fry!(self.root.enc_entry_value(content));
+
+        self.mutate(
+            key,
+            EntryActions::new().update(enc_key, content, version).into(),
+        )
+    }
+
+    /// Deletes an entry. `version` has to be one more than the current version of
+    /// the entry.
+    pub fn delete(&self, key: &[u8], version: u64) -> Box<CoreFuture<()>> {
+        let enc_key = fry!(self.root.enc_entry_key(key));
+
+        self.mutate(key, EntryActions::new().del(enc_key, version).into())
+    }
+
+    // Applies the actions to the shard of the key, splitting it if it's full.
+    fn mutate(&self, key: &[u8], actions: BTreeMap<Vec<u8>, EntryAction>) -> Box<CoreFuture<()>> {
+        let hash = XorName(sha3_256(key));
+        let map = self.clone();
+
+        future::loop_fn(0, move |splits| {
+            let map = map.clone();
+            let actions = actions.clone();
+
+            map.index().and_then(move |index| {
+                let shard = fry!(find_shard(&index.shards, &hash));
+
+                map.client
+                    .mutate_mdata_entries(shard.info.name, shard.info.type_tag, actions)
+                    .then(move |res| match res {
+                        Ok(()) => Either::A(future::ok(Loop::Break(()))),
+                        Err(CoreError::RoutingClientError(ClientError::TooManyEntries))
+                        | Err(CoreError::RoutingClientError(ClientError::DataTooLarge))
+                            if splits < MAX_SPLITS =>
+                        {
+                            Either::B(
+                                map.split(index, shard)
+                                    .map(move |()| Loop::Continue(splits + 1)),
+                            )
+                        }
+                        Err(error) => Either::A(future::err(error)),
+                    }).into_box()
+            })
+        }).into_box()
+    }
+
+    // Moves the entries of the shard into two new ones, by the next bit of the hash
+    // of their keys, and replaces the shard with them in the index.
+    fn split(&self, index: Index, shard: Shard) -> Box<CoreFuture<()>> {
+        if shard.depth == u8::max_value() {
+            return err!(CoreError::Unexpected("Shard can't be split".to_string()));
+        }
+
+        let client = self.client.clone();
+        let client2 = self.client.clone();
+        let root = self.root.clone();
+        let cache = Rc::clone(&self.index);
+
+        let halves = fry!(split_shard(&self.root, &shard));
+        let mut shards = index.shards;
+        shards.retain(|other| other.info.name != shard.info.name);
+        shards.push(halves.0.clone());
+        shards.push(halves.1.clone());
+        let (index_key, index_value) = fry!(encode_index(&self.root, &shards));
+        let new_index = Index {
+            shards,
+            version: index.version + 1,
+        };
+
+        self.client
+            .get_mdata(shard.info.name, shard.info.type_tag)
+            .and_then(move |data| {
+                let mut entries = (BTreeMap::new(), BTreeMap::new());
+                for (key, value) in data.entries() {
+                    // Deleted entries aren't moved.
+                    if value.content.is_empty() {
+                        continue;
+                    }
+                    let hash = XorName(sha3_256(&shard.info.decrypt(key)?));
+                    let half = if get_bit(&hash, shard.depth as usize) {
+                        &mut entries.1
+                    } else {
+                        &mut entries.0
+                    };
+                    let _ = half.insert(key.clone(), value.clone());
+                }
+
+                let data0 = MutableData::new(
+                    halves.0.info.name,
+                    halves.0.info.type_tag,
+                    data.permissions().clone(),
+                    entries.0,
+                    data.owners().clone(),
+                )?;
+                let data1 = MutableData::new(
+                    halves.1.info.name,
+                    halves.1.info.type_tag,
+                    data.permissions().clone(),
+                    entries.1,
+                    data.owners().clone(),
+                )?;
+
+                Ok((data0, data1))
+            }).and_then(move |(data0, data1)| {
+                client
+                    .put_mdata(data0)
+                    .join(client.put_mdata(data1))
+                    .map(|_| ())
+            }).and_then(move |()| {
+                let actions = EntryActions::new()
+                    .update(index_key, index_value, new_index.version)
+                    .into();
+
+                client2
+                    .mutate_mdata_entries(root.name, root.type_tag, actions)
+                    .map_err(|error| match error {
+                        CoreError::RoutingClientError(ClientError::DataTooLarge) => {
+                            CoreError::Unexpected("Index can't hold any more shards".to_string())
+                        }
+                        error => error,
+                    }).map(move |()| {
+                        *cache.borrow_mut() = Some(new_index);
+                    })
+            }).into_box()
+    }
+
+    fn index(&self) -> Box<CoreFuture<Index>> {
+        if let Some(ref index) = *self.index.borrow() {
+            return ok!(index.clone());
+        }
+
+        let root = self.root.clone();
+        let cache = Rc::clone(&self.index);
+        let key = fry!(self.root.enc_entry_key(INDEX_KEY));
+
+        self.client
+            .get_mdata_value(self.root.name, self.root.type_tag, key)
+            .and_then(move |value| {
+                let index = Index {
+                    shards: deserialise(&root.decrypt(&value.content)?)?,
+                    version: value.entry_version,
+                };
+                *cache.borrow_mut() = Some(index.clone());
+                Ok(index)
+            }).into_box()
+    }
+}
+
+fn find_shard(shards: &[Shard], hash: &XorName) -> Result<Shard, CoreError> {
+    shards
+        .iter()
+        .find(|shard| shard.contains(hash))
+        .cloned()
+        .ok_or_else(|| CoreError::Unexpected("No shard for the key".to_string()))
+}
+
+fn new_shard_info(root: &MDataInfo) -> Result<MDataInfo, CoreError> {
+    Ok(MDataInfo {
+        name: MDataInfo::random_public(root.type_tag)?.name,
+        ..root.clone()
+    })
+}
+
+fn split_shard(root: &MDataInfo, shard: &Shard) -> Result<(Shard, Shard), CoreError> {
+    let depth = shard.depth + 1;
+    let mut prefix = shard.prefix;
+    set_bit(&mut prefix, shard.depth as usize, false);
+    let half0 = Shard {
+        depth,
+        prefix,
+        info: new_shard_info(root)?,
+    };
+    set_bit(&mut prefix, shard.depth as usize, true);
+    let half1 = Shard {
+        depth,
+        prefix,
+        info: new_shard_info(root)?,
+    };
+
+    Ok((half0, half1))
+}
+
+fn encode_index(root: &MDataInfo, shards: &[Shard]) -> Result<(Vec<u8>, Vec<u8>), CoreError> {
+    let key = root.enc_entry_key(INDEX_KEY)?;
+    let value = root.enc_entry_value(&serialise(&shards)?)?;
+    Ok((key, value))
+}
+
+fn decrypt_value(info: &MDataInfo, value: Value) -> Result<Value, CoreError> {
+    let content = if value.content.is_empty() {
+        value.content
+    } else {
+        info.decrypt(&value.content)?
+    };
+
+    Ok(Value {
+        content,
+        entry_version: value.entry_version,
+    })
+}
+
+fn get_bit(name: &XorName, bit: usize) -> bool {
+    name.0[bit / 8] & (0x80 >> (bit % 8)) != 0
+}
+
+fn set_bit(name: &mut XorName, bit: usize, value: bool) {
+    if value {
+        name.0[bit / 8] |= 0x80 >> (bit % 8);
+    } else {
+        name.0[bit / 8] &= !(0x80 >> (bit % 8));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use routing::MAX_MUTABLE_DATA_ENTRIES;
+    use utils::test_utils::random_client;
+    use DIR_TAG;
+
+    // Test that a full shard is split and that its entries are still reachable
+    // afterwards.
+    #[test]
+    fn split_full_shard() {
+        random_client(|client| {
+            let root = unwrap!(MDataInfo::random_private(DIR_TAG));
+            let c2 = client.clone();
+            let c3 = client.clone();
+
+            ShardedMap::create(client, root, btree_map![])
+                .then(move |res| {
+                    let map = unwrap!(res);
+                    let map2 = map.clone();
+                    map.shards().map(move |shards| (map2, shards))
+                }).then(move |res| {
+                    let (map, shards) = unwrap!(res);
+                    assert_eq!(shards.len(), 1);
+
+                    // Fill the only shard up to the limit, in a single mutation.
+                    let shard = shards[0].clone();
+                    let actions = (0..MAX_MUTABLE_DATA_ENTRIES)
+                        .fold(EntryActions::new(), |actions, i| {
+                            let key = unwrap!(shard.enc_entry_key(format!("{}", i).as_bytes()));
+                            let value = unwrap!(shard.enc_entry_value(&[1]));
+                            actions.ins(key, value, 0)
+                        }).into();
+                    c2.mutate_mdata_entries(shard.name, shard.type_tag, actions)
+                        .map(move |()| map)
+                }).then(|res| {
+                    let map = unwrap!(res);
+                    let map2 = map.clone();
+                    map.insert(b"key", b"value").map(move |()| map2)
+                }).then(|res| {
+                    let map = unwrap!(res);
+                    let map2 = map.clone();
+                    map.shards().map(move |shards| (map2, shards))
+                }).then(move |res| {
+                    let (map, shards) = unwrap!(res);
+                    assert!(shards.len() >= 2);
+
+                    // The index is updated in place.
+                    let root = map.root().clone();
+                    c3.list_mdata_entries(root.name, root.type_tag)
+                        .map(move |entries| (map, entries))
+                }).then(|res| {
+                    let (map, entries) = unwrap!(res);
+                    assert_eq!(entries.len(), 1);
+
+                    // The index is loaded from the network by a fresh collection.
+                    let map = ShardedMap::new(&map.client, map.root().clone());
+                    let map2 = map.clone();
+                    map.get(b"key").map(move |value| (map2, value))
+                }).then(|res| {
+                    let (map, value) = unwrap!(res);
+                    assert_eq!(value.content, b"value".to_vec());
+                    let map2 = map.clone();
+                    map.get(b"7").map(move |value| (map2, value))
+                }).then(|res| {
+                    let (map, value) = unwrap!(res);
+                    assert_eq!(value.content, vec![1]);
+                    let map2 = map.clone();
+                    map.update(b"7", &[2], 1).map(move |()| map2)
+                }).then(|res| {
+                    let map = unwrap!(res);
+                    let map2 = map.clone();
+                    map.delete(b"key", 1).map(move |()| map2)
+                }).then(|res| {
+                    let map = unwrap!(res);
+                    map.list_entries()
+                }).then(|res| {
+                    let entries = unwrap!(res);
+                    assert_eq!(entries.len() as u64, MAX_MUTABLE_DATA_ENTRIES + 1);
+                    assert_eq!(unwrap!(entries.get(&b"7".to_vec())).content, vec![2]);
+                    assert!(unwrap!(entries.get(&b"key".to_vec())).content.is_empty());
+                    Ok::<_, CoreError>(())
+                })
+        })
+    }
+}
@@ -623,6 +623,8 @@ fn ffi_authorise_app(auth_h: *mut Authenticator, app_info: &AppExchangeInfo) ->
         app: app_info.clone(),
         app_container: false,
         containers: create_containers_req(),
+        manifest: None,
+        resp_key: None,
     };
     let ffi_auth_req = unwrap!(auth_req.clone().into_repr_c());
 